@@ -0,0 +1,81 @@
+//! `ExtentsWriter` over many small extents, and hashing an image as it is written versus
+//! afterwards.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use otaripper::cmd::crypto;
+use otaripper::cmd::simd::{CpuSimd, ExtentsWriter};
+use std::hint::black_box;
+use std::io::Write;
+
+const IMAGE_SIZE: usize = 8 << 20;
+const BLOCK_SIZE: usize = 4096;
+
+/// Destination extents of `blocks_per_extent` blocks each, as an operation would scatter them.
+fn extents(buf: &mut [u8], blocks_per_extent: usize) -> Vec<&mut [u8]> {
+    buf.chunks_mut(blocks_per_extent * BLOCK_SIZE).collect()
+}
+
+fn extents_writer(c: &mut Criterion) {
+    let data: Vec<u8> = (0..IMAGE_SIZE).map(|i| (i * 7) as u8).collect();
+    let mut image = vec![0u8; IMAGE_SIZE];
+    let simd = CpuSimd::get();
+
+    let mut group = c.benchmark_group("extents_writer");
+    group.throughput(Throughput::Bytes(IMAGE_SIZE as u64));
+    for blocks_per_extent in [1, 16, 256] {
+        let mut parts = extents(&mut image, blocks_per_extent);
+        group.bench_function(
+            BenchmarkId::new("blocks_per_extent", blocks_per_extent),
+            |b| {
+                b.iter(|| {
+                    let mut writer = ExtentsWriter::new(&mut parts, simd);
+                    writer.write_all(black_box(&data)).unwrap();
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Decoders hand over data in chunks; hashing each chunk on the way in touches it while it is
+/// still in cache, hashing the finished image reads it back from memory.
+fn hashing(c: &mut Criterion) {
+    let data: Vec<u8> = (0..IMAGE_SIZE).map(|i| (i * 7) as u8).collect();
+    let mut image = vec![0u8; IMAGE_SIZE];
+    let simd = CpuSimd::get();
+
+    let mut group = c.benchmark_group("hashing");
+    group.throughput(Throughput::Bytes(IMAGE_SIZE as u64));
+    for chunk in [64 << 10, 1 << 20] {
+        let mut parts = extents(&mut image, 16);
+        group.bench_function(BenchmarkId::new("inline", chunk), |b| {
+            b.iter(|| {
+                let mut writer = ExtentsWriter::new(&mut parts, simd);
+                let mut hasher = crypto::Sha256::new();
+                for piece in data.chunks(chunk) {
+                    hasher.update(piece);
+                    writer.write_all(piece).unwrap();
+                }
+                black_box(hasher.finish())
+            })
+        });
+        group.bench_function(BenchmarkId::new("post", chunk), |b| {
+            b.iter(|| {
+                let mut writer = ExtentsWriter::new(&mut parts, simd);
+                for piece in data.chunks(chunk) {
+                    writer.write_all(piece).unwrap();
+                }
+                // The extents are consecutive slices of one image, so this hashes it in order.
+                let mut hasher = crypto::Sha256::new();
+                for part in &parts {
+                    hasher.update(part);
+                }
+                black_box(hasher.finish())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, extents_writer, hashing);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+//! Copy and zero-check kernels at every SIMD level the CPU supports.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use otaripper::cmd::simd::{CpuSimd, is_all_zero_with_simd, simd_copy_large};
+use std::hint::black_box;
+
+/// Below, at and above the cache-friendly sizes, and past the 1 MiB streaming-store cutoff.
+const SIZES: &[usize] = &[4 << 10, 64 << 10, 512 << 10, 4 << 20];
+
+fn copy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("copy");
+    for &size in SIZES {
+        let src: Vec<u8> = (0..size).map(|i| i as u8).collect();
+        let mut dst = vec![0u8; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        for simd in CpuSimd::available() {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{simd:?}"), size),
+                &src,
+                |b, src| b.iter(|| simd_copy_large(simd, black_box(src), black_box(&mut dst))),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn zero_check(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zero_check");
+    for &size in SIZES {
+        // All zeros: the worst case, every byte has to be looked at.
+        let data = vec![0u8; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        for simd in CpuSimd::available() {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{simd:?}"), size),
+                &data,
+                |b, data| b.iter(|| is_all_zero_with_simd(simd, black_box(data))),
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, copy, zero_check);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+//! Manifest decoding on its own, full and partition-table-only, and the extent arithmetic
+//! applied to every operation.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use otaripper::payload;
+use otaripper::proto::chromeos_update_engine::DeltaArchiveManifest;
+use otaripper::proto::summary::ManifestSummary;
+use prost::Message;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ManifestSummary::decode(data);
+    let Ok(manifest) = DeltaArchiveManifest::decode(data) else {
+        return;
+    };
+    for block_size in [1, 4096, usize::MAX] {
+        for partition in &manifest.partitions {
+            for op in &partition.operations {
+                let _ = payload::extent_ranges(&op.dst_extents, block_size, usize::MAX);
+                let _ = payload::extent_ranges(&op.src_extents, block_size, 1 << 30);
+            }
+        }
+    }
+});
@@ -0,0 +1,36 @@
+//! A whole payload.bin: header, manifest, operation data, extents and signatures.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use otaripper::payload::{self, Payload};
+use otaripper::proto::chromeos_update_engine::DeltaArchiveManifest;
+use prost::Message;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(mut payload) = Payload::parse(data) else {
+        return;
+    };
+    let _ = payload.to_string();
+    let _ = payload.metadata_signatures();
+    let Ok(manifest) = DeltaArchiveManifest::decode(payload.manifest()) else {
+        return;
+    };
+    payload.trim_trailing(&manifest);
+    let _ = payload.payload_signatures(&manifest);
+
+    let block_size = manifest.block_size.unwrap_or(4096) as usize;
+    for partition in &manifest.partitions {
+        let partition_len = partition
+            .new_partition_info
+            .as_ref()
+            .and_then(|info| info.size)
+            .and_then(|size| usize::try_from(size).ok())
+            .unwrap_or(0);
+        for op in &partition.operations {
+            let _ = payload.op_data(op);
+            let _ = payload::extent_ranges(&op.dst_extents, block_size, partition_len);
+            let _ = payload::extent_ranges(&op.src_extents, block_size, usize::MAX);
+        }
+    }
+});
@@ -0,0 +1,13 @@
+//! Streaming header parsing, as used for remote and compressed zip entries.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use otaripper::payload::Payload;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(header) = Payload::parse_header(data) {
+        let _ = header.metadata_signatures();
+        let _ = header.metadata_hash();
+    }
+});
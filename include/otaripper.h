@@ -0,0 +1,53 @@
+#ifndef OTARIPPER_H
+#define OTARIPPER_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Called during extraction with `user_data`, the bytes written so far and the total.
+typedef void (*OtaripperProgressFn)(void *user_data, uint64_t done, uint64_t total);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Lists the partitions of an OTA zip or payload.bin as a JSON array of
+// `{"name": "boot", "size": 100663296}` objects, in manifest order. Returns NULL on failure.
+// Free the result with [`otaripper_free_string`].
+//
+// # Safety
+// `source` must be a NUL-terminated string.
+char *otaripper_list_partitions(const char *source);
+
+// Extracts one partition of an OTA zip or payload.bin (or an `http(s)` URL to either) into
+// `output_dir` as `<partition>.img`, verified against the manifest. `progress`, when not NULL,
+// is called a few times a second from another thread. Returns 0 on success, -1 on failure.
+//
+// # Safety
+// `source`, `partition` and `output_dir` must be NUL-terminated strings. `progress` must be
+// safe to call from another thread with `user_data` until this function returns.
+int32_t otaripper_extract_partition(const char *source,
+                                    const char *partition,
+                                    const char *output_dir,
+                                    OtaripperProgressFn progress,
+                                    void *user_data);
+
+// The message of the last failure on this thread, or NULL if nothing failed yet. Valid until
+// the next failing call on the thread; do not free it.
+const char *otaripper_last_error(void);
+
+// Frees a string returned by otaripper. NULL is ignored.
+//
+// # Safety
+// `s` must be NULL or a string otaripper returned that has not been freed yet.
+void otaripper_free_string(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* OTARIPPER_H */
@@ -0,0 +1,146 @@
+//! Writes a set of small synthetic payloads for testing otaripper, or anything else that reads
+//! payload.bin, without real multi-gigabyte OTAs.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use otaripper::builder::{Corruption, PartitionBuilder, PayloadBuilder};
+use otaripper::proto::chromeos_update_engine::install_operation::Type;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const BLOCK_SIZE: usize = 4096;
+
+#[derive(Debug, Parser)]
+#[clap(
+    about = "Generate small synthetic OTA payloads for testing",
+    after_help = "Files written to DIR:
+  full.bin                    every full operation type (REPLACE, REPLACE_BZ, REPLACE_XZ, ZERO, DISCARD)
+  full_v1.bin                 the same partitions in file format version 1
+  no_hashes.bin               no partition or operation hashes
+  delta.bin                   every incremental operation type, plus a full partition
+  corrupt_partition_hash.bin  partition hashes that don't match the images
+  corrupt_op_data.bin         operation data that doesn't match its hash
+  truncated.bin               data section cut off halfway
+  bad_magic.bin               header without the CrAU magic
+  expected/<partition>.img    images the full payloads extract to"
+)]
+struct Args {
+    /// Directory to write the payloads to
+    #[clap(default_value = "test-payloads", value_name = "DIR")]
+    out_dir: PathBuf,
+
+    /// Seed for the image contents; the same seed always gives the same files
+    #[clap(long, default_value_t = 1)]
+    seed: u64,
+}
+
+fn main() {
+    if let Err(e) = run(&Args::parse()) {
+        eprintln!("\nERROR: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &Args) -> Result<()> {
+    let expected_dir = args.out_dir.join("expected");
+    fs::create_dir_all(&expected_dir)
+        .with_context(|| format!("could not create {expected_dir:?}"))?;
+
+    // boot: one of each full operation, the last four blocks zero.
+    let mut boot = image(16, args.seed);
+    boot[12 * BLOCK_SIZE..].fill(0);
+    let boot = PartitionBuilder::new("boot", boot)
+        .op(Type::Replace, 0..4)
+        .op(Type::ReplaceXz, 4..8)
+        .op(Type::ReplaceBz, 8..12)
+        .op(Type::Zero, 12..14)
+        .op(Type::Discard, 14..16);
+    let system = PartitionBuilder::new("system", image(32, args.seed + 1))
+        .op(Type::ReplaceXz, 0..16)
+        .op(Type::ReplaceBz, 16..24)
+        .op(Type::Replace, 24..32);
+    let vendor = PartitionBuilder::new("vendor", vec![0; 8 * BLOCK_SIZE]).op(Type::Zero, 0..8);
+    let full = PayloadBuilder::new()
+        .partition(boot.clone())
+        .partition(system.clone())
+        .partition(vendor.clone());
+
+    for partition in [&boot, &system, &vendor] {
+        let name = format!("{}.img", partition.name());
+        write(&expected_dir, &name, partition.image())?;
+    }
+
+    write(&args.out_dir, "full.bin", &full.build()?)?;
+    write(
+        &args.out_dir,
+        "full_v1.bin",
+        &full.clone().version(1).build()?,
+    )?;
+    let no_hashes = PayloadBuilder::new()
+        .partition(boot.clone().without_image_hash().without_op_hashes())
+        .partition(system.clone().without_image_hash().without_op_hashes());
+    write(&args.out_dir, "no_hashes.bin", &no_hashes.build()?)?;
+
+    let incremental = [
+        Type::Move,
+        Type::Bsdiff,
+        Type::SourceCopy,
+        Type::SourceBsdiff,
+        Type::BrotliBsdiff,
+        Type::Puffdiff,
+        Type::Zucchini,
+        Type::Lz4diffBsdiff,
+        Type::Lz4diffPuffdiff,
+    ];
+    let patched = incremental.iter().enumerate().fold(
+        PartitionBuilder::new("boot", image(incremental.len(), args.seed + 2)),
+        |partition, (i, &kind)| partition.op(kind, i as u64..i as u64 + 1),
+    );
+    let delta = PayloadBuilder::new()
+        .minor_version(9)
+        .partition(patched)
+        .partition(system.clone());
+    write(&args.out_dir, "delta.bin", &delta.build()?)?;
+
+    for (name, corruption) in [
+        ("corrupt_partition_hash.bin", Corruption::PartitionHash),
+        ("corrupt_op_data.bin", Corruption::OperationData),
+        ("truncated.bin", Corruption::Truncated),
+        ("bad_magic.bin", Corruption::BadMagic),
+    ] {
+        write(
+            &args.out_dir,
+            name,
+            &full.clone().corrupt(corruption).build()?,
+        )?;
+    }
+    Ok(())
+}
+
+/// Compressible but non-trivial contents: each block repeats 64 pseudo-random bytes.
+fn image(blocks: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    let mut out = Vec::with_capacity(blocks * BLOCK_SIZE);
+    for _ in 0..blocks {
+        let word: Vec<u8> = (0..64)
+            .map(|_| {
+                // xorshift64
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        for _ in 0..BLOCK_SIZE / 64 {
+            out.extend_from_slice(&word);
+        }
+    }
+    out
+}
+
+fn write(dir: &Path, name: &str, bytes: &[u8]) -> Result<()> {
+    let path = dir.join(name);
+    fs::write(&path, bytes).with_context(|| format!("could not write {path:?}"))?;
+    println!("{}", path.display());
+    Ok(())
+}
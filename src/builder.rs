@@ -0,0 +1,307 @@
+//! Builds small `payload.bin` files from in-memory images, for tests and fixtures that would
+//! otherwise need multi-gigabyte OTAs.
+//!
+//! Full operations (`REPLACE`, `REPLACE_BZ`, `REPLACE_XZ`, `ZERO`, `DISCARD`) carry real data
+//! and extract back to the given image. Incremental operations get source extents and a
+//! placeholder blob that is not a valid patch: enough for code that inspects delta payloads,
+//! not for applying them.
+//!
+//! ```
+//! use otaripper::builder::{PartitionBuilder, PayloadBuilder};
+//! use otaripper::proto::chromeos_update_engine::install_operation::Type;
+//!
+//! let image = vec![7u8; 8 * 4096];
+//! let payload = PayloadBuilder::new()
+//!     .partition(
+//!         PartitionBuilder::new("boot", image)
+//!             .op(Type::ReplaceXz, 0..4)
+//!             .op(Type::Replace, 4..8),
+//!     )
+//!     .build()
+//!     .unwrap();
+//! assert!(otaripper::payload::Payload::parse(&payload).is_ok());
+//! ```
+
+use crate::cmd::crypto;
+use crate::proto::chromeos_update_engine::install_operation::Type;
+use crate::proto::chromeos_update_engine::{
+    DeltaArchiveManifest, Extent, InstallOperation, PartitionInfo, PartitionUpdate,
+};
+use anyhow::{Context, Result, ensure};
+use prost::Message;
+use std::io::Write;
+use std::ops::Range;
+
+const PAYLOAD_MAGIC: &[u8] = b"CrAU";
+
+/// Stands in for patch data in incremental operations.
+const PLACEHOLDER_PATCH: &[u8] = b"otaripper placeholder patch";
+
+/// Deliberate defects [`PayloadBuilder::corrupt`] can introduce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corruption {
+    /// Every partition's manifest hash is off by one byte.
+    PartitionHash,
+    /// The first data blob of every partition has a byte flipped after its hash was taken.
+    OperationData,
+    /// The payload stops halfway through its data section.
+    Truncated,
+    /// The header does not start with `CrAU`.
+    BadMagic,
+}
+
+/// Assembles a payload: header, manifest and data section, without signatures.
+#[derive(Debug, Clone)]
+pub struct PayloadBuilder {
+    version: u64,
+    block_size: u32,
+    minor_version: u32,
+    partitions: Vec<PartitionBuilder>,
+    corruptions: Vec<Corruption>,
+}
+
+/// One partition: its target image and the operations that write it.
+#[derive(Debug, Clone)]
+pub struct PartitionBuilder {
+    name: String,
+    image: Vec<u8>,
+    ops: Vec<(Type, Range<u64>)>,
+    image_hash: bool,
+    op_hashes: bool,
+}
+
+impl Default for PayloadBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PayloadBuilder {
+    /// A format version 2 payload with 4 KiB blocks and minor version 0 (a full OTA).
+    pub fn new() -> Self {
+        Self {
+            version: 2,
+            block_size: 4096,
+            minor_version: 0,
+            partitions: Vec::new(),
+            corruptions: Vec::new(),
+        }
+    }
+
+    /// File format version: 1 omits the metadata signature size from the header.
+    pub fn version(mut self, version: u64) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn block_size(mut self, block_size: u32) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Manifest minor version; non-zero marks a delta payload.
+    pub fn minor_version(mut self, minor_version: u32) -> Self {
+        self.minor_version = minor_version;
+        self
+    }
+
+    pub fn partition(mut self, partition: PartitionBuilder) -> Self {
+        self.partitions.push(partition);
+        self
+    }
+
+    pub fn corrupt(mut self, corruption: Corruption) -> Self {
+        self.corruptions.push(corruption);
+        self
+    }
+
+    /// Encodes the payload. Fails if an image isn't a whole number of blocks, an operation
+    /// reaches past its image, or a `ZERO`/`DISCARD` range of the image isn't zero.
+    pub fn build(&self) -> Result<Vec<u8>> {
+        let block_size = self.block_size as usize;
+        ensure!(block_size > 0, "block size must not be zero");
+
+        let mut data = Vec::new();
+        let mut partitions = Vec::with_capacity(self.partitions.len());
+        for partition in &self.partitions {
+            let update = partition
+                .encode(block_size, &mut data, &self.corruptions)
+                .with_context(|| format!("partition {}", partition.name))?;
+            partitions.push(update);
+        }
+
+        let manifest = DeltaArchiveManifest {
+            block_size: Some(self.block_size),
+            minor_version: Some(self.minor_version),
+            partitions,
+            ..Default::default()
+        }
+        .encode_to_vec();
+
+        let mut payload = Vec::with_capacity(24 + manifest.len() + data.len());
+        payload.extend_from_slice(PAYLOAD_MAGIC);
+        payload.extend_from_slice(&self.version.to_be_bytes());
+        payload.extend_from_slice(&(manifest.len() as u64).to_be_bytes());
+        if self.version >= 2 {
+            payload.extend_from_slice(&0u32.to_be_bytes());
+        }
+        payload.extend_from_slice(&manifest);
+        let data_start = payload.len();
+        payload.extend_from_slice(&data);
+
+        if self.corruptions.contains(&Corruption::Truncated) {
+            payload.truncate(data_start + data.len() / 2);
+        }
+        if self.corruptions.contains(&Corruption::BadMagic) {
+            payload[..PAYLOAD_MAGIC.len()].copy_from_slice(b"CrAV");
+        }
+        Ok(payload)
+    }
+}
+
+impl PartitionBuilder {
+    /// A partition written by one `REPLACE` operation unless [`op`](Self::op) adds others.
+    pub fn new(name: impl Into<String>, image: Vec<u8>) -> Self {
+        Self {
+            name: name.into(),
+            image,
+            ops: Vec::new(),
+            image_hash: true,
+            op_hashes: true,
+        }
+    }
+
+    /// Adds an operation of type `kind` writing `blocks` of the image.
+    pub fn op(mut self, kind: Type, blocks: Range<u64>) -> Self {
+        self.ops.push((kind, blocks));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The image the partition's operations write.
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    /// Leaves the partition hash out of the manifest.
+    pub fn without_image_hash(mut self) -> Self {
+        self.image_hash = false;
+        self
+    }
+
+    /// Leaves the data hashes out of every operation.
+    pub fn without_op_hashes(mut self) -> Self {
+        self.op_hashes = false;
+        self
+    }
+
+    fn encode(
+        &self,
+        block_size: usize,
+        data: &mut Vec<u8>,
+        corruptions: &[Corruption],
+    ) -> Result<PartitionUpdate> {
+        ensure!(
+            self.image.len().is_multiple_of(block_size),
+            "image size {} is not a multiple of the block size {block_size}",
+            self.image.len()
+        );
+        let num_blocks = (self.image.len() / block_size) as u64;
+        let whole = [(Type::Replace, 0..num_blocks)];
+        let ops = if self.ops.is_empty() {
+            &whole[..]
+        } else {
+            &self.ops[..]
+        };
+
+        let mut operations = Vec::with_capacity(ops.len());
+        let mut corrupted = false;
+        for (kind, blocks) in ops {
+            ensure!(
+                blocks.start < blocks.end && blocks.end <= num_blocks,
+                "{} blocks {blocks:?} are outside the image's {num_blocks} blocks",
+                kind.as_str_name()
+            );
+            let range = blocks.start as usize * block_size..blocks.end as usize * block_size;
+            let extent = Extent {
+                start_block: Some(blocks.start),
+                num_blocks: Some(blocks.end - blocks.start),
+            };
+            let mut op = InstallOperation {
+                r#type: *kind as i32,
+                dst_extents: vec![extent],
+                ..Default::default()
+            };
+
+            let blob = match kind {
+                Type::Replace => Some(self.image[range].to_vec()),
+                Type::ReplaceBz => Some(bzip2_compress(&self.image[range])?),
+                Type::ReplaceXz => Some(xz_compress(&self.image[range])?),
+                Type::Zero | Type::Discard => {
+                    ensure!(
+                        self.image[range].iter().all(|&b| b == 0),
+                        "{} blocks {blocks:?} are not zero in the image",
+                        kind.as_str_name()
+                    );
+                    None
+                }
+                Type::Move | Type::SourceCopy => {
+                    op.src_extents = vec![extent];
+                    None
+                }
+                _ => {
+                    op.src_extents = vec![extent];
+                    Some(PLACEHOLDER_PATCH.to_vec())
+                }
+            };
+            if let Some(mut blob) = blob {
+                if self.op_hashes {
+                    op.data_sha256_hash = Some(crypto::sha256(&blob).to_vec());
+                }
+                if !corrupted && corruptions.contains(&Corruption::OperationData) {
+                    blob[0] ^= 0xff;
+                    corrupted = true;
+                }
+                op.data_offset = Some(data.len() as u64);
+                op.data_length = Some(blob.len() as u64);
+                data.extend_from_slice(&blob);
+            }
+            operations.push(op);
+        }
+
+        let hash = self.image_hash.then(|| {
+            let mut hash = crypto::sha256(&self.image);
+            if corruptions.contains(&Corruption::PartitionHash) {
+                hash[0] ^= 0xff;
+            }
+            hash.to_vec()
+        });
+        let is_delta = operations.iter().any(|op| !op.src_extents.is_empty());
+        let info = |hash| PartitionInfo {
+            size: Some(self.image.len() as u64),
+            hash,
+        };
+        Ok(PartitionUpdate {
+            partition_name: self.name.clone().into_bytes(),
+            old_partition_info: is_delta.then(|| info(None)),
+            new_partition_info: Some(info(hash)),
+            operations,
+            ..Default::default()
+        })
+    }
+}
+
+fn bzip2_compress(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::best());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+fn xz_compress(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = liblzma::write::XzEncoder::new(Vec::new(), 6);
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
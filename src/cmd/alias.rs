@@ -0,0 +1,72 @@
+use anyhow::{Result, ensure};
+use std::collections::HashMap;
+
+/// Names people reach for, and the partitions they usually mean. An alias only applies when
+/// the payload has no partition by that name, and selects whichever of its targets it has.
+const BUILTIN: &[(&str, &[&str])] = &[
+    // Devices launched with Android 11+ keep the recovery ramdisk in vendor_boot.
+    ("recovery", &["vendor_boot"]),
+    ("kernel", &["boot"]),
+    // Chrome OS payloads name the ROOT-x and KERN-x GPT partitions root and kernel.
+    ("ROOT", &["root"]),
+    ("rootfs", &["root"]),
+    ("KERN", &["kernel"]),
+    // Qualcomm boot chain images that OTAs carry.
+    (
+        "bootloader",
+        &[
+            "abl",
+            "xbl",
+            "xbl_config",
+            "aop",
+            "aop_config",
+            "tz",
+            "hyp",
+            "devcfg",
+            "keymaster",
+            "uefi",
+            "uefisecapp",
+            "imagefv",
+            "qupfw",
+            "cpucp",
+            "shrm",
+        ],
+    ),
+];
+
+/// Built-in aliases plus those given with `--alias`, which take precedence.
+pub(crate) struct Aliases(HashMap<String, Vec<String>>);
+
+impl Aliases {
+    /// Parses `NAME=PART[+PART...]` definitions on top of the built-in ones.
+    pub(crate) fn new(user: &[String]) -> Result<Self> {
+        let mut map: HashMap<String, Vec<String>> = BUILTIN
+            .iter()
+            .map(|(name, targets)| {
+                let targets = targets.iter().map(|t| t.to_string()).collect();
+                (name.to_string(), targets)
+            })
+            .collect();
+        for definition in user {
+            let (name, targets) = definition.split_once('=').unzip();
+            let targets: Vec<String> = targets
+                .unwrap_or_default()
+                .split('+')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect();
+            ensure!(
+                name.is_some_and(|n| !n.trim().is_empty()) && !targets.is_empty(),
+                "invalid --alias \"{definition}\": expected NAME=PARTITION[+PARTITION...]"
+            );
+            map.insert(name.unwrap_or_default().trim().to_string(), targets);
+        }
+        Ok(Self(map))
+    }
+
+    /// Partitions `name` stands for, if it is an alias.
+    pub(crate) fn get(&self, name: &str) -> Option<&[String]> {
+        self.0.get(name).map(Vec::as_slice)
+    }
+}
@@ -3,8 +3,11 @@ use std::fs::{write, File};
 use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
+use crate::cmd::config::Config;
 use crate::cmd::extractor::Extractor;
-use crate::cmd::Cmd;
+use crate::cmd::hashalgo::HashAlgo;
+use crate::cmd::i18n::{self, Msg};
+use crate::cmd::{Cmd, IoModeChoice, ProgressMode, ProgressOrder, RenameSlot, Slot};
 use serde::Serialize;
 
 const EI_CLASS: usize = 4;
@@ -89,7 +92,7 @@ fn ask_yes_no(prompt: &str) -> bool {
     let _ = io::stdout().flush();
     let mut input = String::new();
     io::stdin().read_line(&mut input).ok();
-    matches!(input.trim().to_lowercase().as_str(), "y" | "yes")
+    i18n::is_yes(&input)
 }
 
 fn ask_string(prompt: &str) -> String {
@@ -158,19 +161,66 @@ pub fn run(no_json: bool, path: &Path) -> anyhow::Result<()> {
         subcmd: None,
         list: false,
         threads: None,
+        jobs: 1,
+        flush_every: None,
         output_dir: Some(temp_dir.path().to_path_buf()),
+        flat: false,
+        force: false,
+        resume: None,
         partitions: vec!["xbl_config".to_string()],
+        skip_partitions: Vec::new(),
+        changed_since: None,
+        slot: Slot::All,
+        rename_slot: RenameSlot::Keep,
+        aliases: Vec::new(),
         no_verify: true,
         strict: false,
+        verify_chain: false,
+        keyring: None,
+        pubkey: None,
+        verify_metadata: false,
+        cert: None,
+        audit: None,
+        dump_ops: None,
+        source_dir: None,
+        stdout: false,
+        verify_only: false,
+        extract_metadata: false,
+        output_map: None,
+        edl_xml: None,
+        make_recovery_zip: false,
+        sparse: false,
+        compress: None,
+        split_size: None,
         print_hash: false,
+        hash_file: false,
+        hash_algo: vec![HashAlgo::Sha256],
+        trust_op_hashes: false,
         sanity: false,
+        verity: false,
         stats: false,
+        progress: ProgressMode::Bars,
+        progress_order: ProgressOrder::Size,
+        quiet_flag: false,
+        io_mode: IoModeChoice::Auto,
+        low_memory: false,
+        max_memory: None,
+        temp_dir: None,
         no_open: true,
+        open_with: None,
+        allow_sleep: false,
+        config: None,
+        debug_cpu: false,
+        debug_bundle: None,
+        verbose: 0,
+        log_file: None,
+        lang: None,
         positional_payload: Some(path.to_path_buf()),
-        quiet: true,
+        more_payloads: Vec::new(),
+        machine_output: true,
     };
 
-    let extractor = Extractor { cmd: &cmd };
+    let extractor = Extractor::new(&cmd, Config::default());
     extractor.run()?;
 
     let mut xbl_path = None;
@@ -314,7 +364,7 @@ fn do_run(no_json: bool, path: &Path, original_path: &Path) -> Result<(), ArbErr
     println!("  Minor Version : {}", minor);
     println!("  ARB Index     : {}", arb);
 
-    if !no_json && ask_yes_no("\nWrite JSON output? [y/N]: ") {
+    if !no_json && ask_yes_no(&format!("\n{}", Msg::WriteJsonPrompt.text())) {
         let device_model = ask_string("Device model      : ");
         let update_label = ask_string("Update / build    : ");
 
@@ -331,7 +381,7 @@ fn do_run(no_json: bool, path: &Path, original_path: &Path) -> Result<(), ArbErr
 
         let out = json_filename(original_path);
         write(&out, serde_json::to_string_pretty(&meta)?)?;
-        println!("\n✔ JSON written: {}", out);
+        println!("\n{}", Msg::JsonWritten.fill(&[&out]));
     }
 
     Ok(())
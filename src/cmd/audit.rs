@@ -0,0 +1,195 @@
+use crate::cmd::crypto;
+use crate::payload::Payload;
+use crate::proto::chromeos_update_engine::install_operation::Type;
+use crate::proto::chromeos_update_engine::{DeltaArchiveManifest, InstallOperation};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// `--audit` log: one JSON object per line, describing the payload, then every operation as it
+/// completes (in completion order; `partition` + `index` identify it), then each finished image.
+pub(crate) struct AuditLog {
+    out: Mutex<BufWriter<File>>,
+    start: Instant,
+    /// Offset of the payload's data section within payload.bin.
+    data_start: u64,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum Record<'a> {
+    Payload {
+        tool: &'a str,
+        source: String,
+        payload_size: u64,
+        format_version: u64,
+        manifest_sha256: String,
+        data_start: u64,
+        block_size: u32,
+        minor_version: u32,
+        partitions: usize,
+        signer_sha256: Option<String>,
+    },
+    Operation {
+        partition: &'a str,
+        index: usize,
+        r#type: &'a str,
+        /// Offset of the op data within the data section, as stored in the manifest.
+        data_offset: Option<u64>,
+        data_length: u64,
+        /// Absolute offset of the op data within payload.bin.
+        payload_offset: Option<u64>,
+        data_sha256: Option<String>,
+        expected_sha256: Option<String>,
+        dst_extents: Vec<Extent>,
+        decompressed_size: Option<usize>,
+        written_bytes: Option<usize>,
+        started_us: u64,
+        duration_us: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Partition {
+        partition: &'a str,
+        file: String,
+        size: usize,
+        sha256: Option<String>,
+        verified: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+struct Extent {
+    start_block: Option<u64>,
+    num_blocks: Option<u64>,
+}
+
+/// What an operation did to the image: bytes its data decoded to, and bytes of the image it
+/// wrote (0 for ZERO/DISCARD ops skipped on a sparse or pre-zeroed file).
+#[derive(Clone, Copy)]
+pub(crate) struct OpOutput {
+    pub decoded: usize,
+    pub written: usize,
+}
+
+impl AuditLog {
+    pub(crate) fn create(path: &Path, payload: &Payload) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("unable to create audit log {}", path.display()))?;
+        Ok(Self {
+            out: Mutex::new(BufWriter::new(file)),
+            start: Instant::now(),
+            data_start: payload.data_offset() as u64,
+        })
+    }
+
+    pub(crate) fn payload(
+        &self,
+        source: &Path,
+        payload_bytes: &[u8],
+        payload: &Payload,
+        manifest: &DeltaArchiveManifest,
+        signer_sha256: Option<String>,
+    ) -> Result<()> {
+        self.write(&Record::Payload {
+            tool: concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")),
+            source: source.display().to_string(),
+            payload_size: payload_bytes.len() as u64,
+            format_version: payload.version(),
+            manifest_sha256: hex::encode(crypto::sha256(payload.manifest())),
+            data_start: self.data_start,
+            block_size: manifest.block_size.unwrap_or(4096),
+            minor_version: manifest.minor_version.unwrap_or(0),
+            partitions: manifest.partitions.len(),
+            signer_sha256,
+        })
+    }
+
+    /// Microseconds since the log was opened, for `started_us`.
+    pub(crate) fn elapsed_us(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn operation(
+        &self,
+        partition: &str,
+        index: usize,
+        op: &InstallOperation,
+        payload: &Payload,
+        started_us: u64,
+        duration: Duration,
+        result: &Result<OpOutput>,
+    ) -> Result<()> {
+        let data_length = op.data_length.unwrap_or(0);
+        // Hash what is actually in the file, which may differ from what the manifest claims.
+        let data_sha256 = payload
+            .op_data(op)
+            .ok()
+            .filter(|_| data_length > 0)
+            .map(|data| hex::encode(crypto::sha256(data)));
+
+        let output = result.as_ref().ok();
+        self.write(&Record::Operation {
+            partition,
+            index,
+            r#type: Type::try_from(op.r#type).map_or("UNKNOWN", |t| t.as_str_name()),
+            data_offset: op.data_offset,
+            data_length,
+            payload_offset: op.data_offset.map(|o| self.data_start.saturating_add(o)),
+            data_sha256,
+            expected_sha256: op.data_sha256_hash.as_deref().map(hex::encode),
+            dst_extents: op
+                .dst_extents
+                .iter()
+                .map(|e| Extent {
+                    start_block: e.start_block,
+                    num_blocks: e.num_blocks,
+                })
+                .collect(),
+            decompressed_size: output.map(|o| o.decoded),
+            written_bytes: output.map(|o| o.written),
+            started_us,
+            duration_us: duration.as_micros() as u64,
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+        })
+    }
+
+    pub(crate) fn partition(
+        &self,
+        partition: &str,
+        file: &Path,
+        size: usize,
+        sha256: Option<[u8; 32]>,
+        verified: bool,
+        error: Option<String>,
+    ) -> Result<()> {
+        self.write(&Record::Partition {
+            partition,
+            file: file.display().to_string(),
+            size,
+            sha256: sha256.map(hex::encode),
+            verified,
+            error,
+        })
+    }
+
+    /// Flushes buffered records; called once extraction has finished, successfully or not.
+    pub(crate) fn finish(&self) -> Result<()> {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        out.flush().context("unable to write audit log")
+    }
+
+    fn write(&self, record: &Record) -> Result<()> {
+        let mut line = serde_json::to_vec(record).context("unable to encode audit record")?;
+        line.push(b'\n');
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        out.write_all(&line).context("unable to write audit log")
+    }
+}
@@ -0,0 +1,135 @@
+//! Just enough of Android Verified Boot to say whether an image carries verification data that
+//! has to be regenerated after modifying it. Layouts follow libavb's `avb_footer.h`,
+//! `avb_vbmeta_image.h` and the descriptor headers; every field is big-endian.
+
+const FOOTER_MAGIC: &[u8] = b"AVBf";
+const FOOTER_SIZE: usize = 64;
+const VBMETA_MAGIC: &[u8] = b"AVB0";
+const VBMETA_HEADER_SIZE: usize = 256;
+
+const TAG_HASHTREE: u64 = 1;
+const TAG_HASH: u64 = 2;
+/// `AvbHashtreeDescriptor` and `AvbHashDescriptor` without the 16-byte tag and length.
+const HASHTREE_FIXED: usize = 164;
+const HASH_FIXED: usize = 116;
+
+/// The verification data an image carries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Verity {
+    /// No AVB footer: nothing to regenerate.
+    None,
+    /// A dm-verity hash tree appended to the filesystem (system, vendor, ...).
+    Hashtree {
+        tree_offset: u64,
+        tree_size: u64,
+        algorithm: String,
+        salt: Vec<u8>,
+        /// Forward error correction data follows the tree.
+        fec: bool,
+    },
+    /// A digest of the whole image, checked at boot (boot, dtbo, ...).
+    Hash { algorithm: String, salt: Vec<u8> },
+    /// An AVB footer whose descriptors cover other partitions only.
+    Footer,
+    /// A standalone vbmeta image, holding descriptors for other partitions.
+    Vbmeta,
+}
+
+impl Verity {
+    /// Reads the AVB footer at the end of `image`, or the vbmeta header at its start.
+    pub(crate) fn scan(image: &[u8]) -> Self {
+        if image.starts_with(VBMETA_MAGIC) {
+            return Verity::Vbmeta;
+        }
+        let Some(footer) = image
+            .len()
+            .checked_sub(FOOTER_SIZE)
+            .map(|start| &image[start..])
+            .filter(|footer| footer.starts_with(FOOTER_MAGIC))
+        else {
+            return Verity::None;
+        };
+        // magic, version major and minor, original image size, then the vbmeta blob.
+        let vbmeta = be64(footer, 20)
+            .zip(be64(footer, 28))
+            .and_then(|(offset, size)| {
+                let start = usize::try_from(offset).ok()?;
+                image.get(start..start.checked_add(usize::try_from(size).ok()?)?)
+            });
+        vbmeta.and_then(descriptor).unwrap_or(Verity::Footer)
+    }
+
+    /// The `--verity` column: yes/no and what kind.
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            Verity::None => "no",
+            Verity::Hashtree { fec: true, .. } => "yes (hashtree+fec)",
+            Verity::Hashtree { .. } => "yes (hashtree)",
+            Verity::Hash { .. } => "yes (hash)",
+            Verity::Footer => "yes (footer)",
+            Verity::Vbmeta => "vbmeta",
+        }
+    }
+}
+
+/// The first hashtree or hash descriptor in a vbmeta blob.
+fn descriptor(vbmeta: &[u8]) -> Option<Verity> {
+    if !vbmeta.starts_with(VBMETA_MAGIC) {
+        return None;
+    }
+    let auth_size = usize::try_from(be64(vbmeta, 12)?).ok()?;
+    let aux = vbmeta.get(VBMETA_HEADER_SIZE.checked_add(auth_size)?..)?;
+    let offset = usize::try_from(be64(vbmeta, 96)?).ok()?;
+    let size = usize::try_from(be64(vbmeta, 104)?).ok()?;
+    let mut descriptors = aux.get(offset..offset.checked_add(size)?)?;
+
+    while descriptors.len() >= 16 {
+        let tag = be64(descriptors, 0)?;
+        let len = usize::try_from(be64(descriptors, 8)?).ok()?;
+        let body = descriptors.get(16..16usize.checked_add(len)?)?;
+        descriptors = &descriptors[16 + len..];
+        match tag {
+            TAG_HASHTREE if body.len() >= HASHTREE_FIXED => {
+                let name_len = be32(body, 88)? as usize;
+                let salt_len = be32(body, 92)? as usize;
+                let salt_start = HASHTREE_FIXED.checked_add(name_len)?;
+                return Some(Verity::Hashtree {
+                    tree_offset: be64(body, 12)?,
+                    tree_size: be64(body, 20)?,
+                    algorithm: c_string(&body[56..88]),
+                    salt: body
+                        .get(salt_start..salt_start.checked_add(salt_len)?)?
+                        .to_vec(),
+                    fec: be64(body, 48)? > 0,
+                });
+            }
+            TAG_HASH if body.len() >= HASH_FIXED => {
+                let name_len = be32(body, 40)? as usize;
+                let salt_len = be32(body, 44)? as usize;
+                let salt_start = HASH_FIXED.checked_add(name_len)?;
+                return Some(Verity::Hash {
+                    algorithm: c_string(&body[8..40]),
+                    salt: body
+                        .get(salt_start..salt_start.checked_add(salt_len)?)?
+                        .to_vec(),
+                });
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn be64(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn be32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// A NUL-padded fixed-size string field.
+fn c_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
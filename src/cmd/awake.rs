@@ -0,0 +1,162 @@
+/// Keeps the machine from suspending while held. Acquisition is best-effort: when the platform
+/// mechanism is unavailable the guard simply does nothing.
+pub(crate) struct KeepAwake {
+    #[cfg(target_os = "linux")]
+    inhibitor: Option<std::process::Child>,
+    #[cfg(target_os = "macos")]
+    assertion: Option<u32>,
+    #[cfg(windows)]
+    active: bool,
+}
+
+cfg_select! {
+    target_os = "linux" => {
+        const REASON: &str = "Extracting partition images";
+
+        impl KeepAwake {
+            /// Holds a logind sleep/idle inhibitor through `systemd-inhibit`.
+            ///
+            /// The inhibitor runs `cat` on a pipe we own, so it is released even if otaripper
+            /// dies without running `Drop`: the pipe closes and `cat` exits.
+            pub(crate) fn acquire() -> Self {
+                use std::process::{Command, Stdio};
+
+                let inhibitor = Command::new("systemd-inhibit")
+                    .args([
+                        "--what=sleep:idle",
+                        concat!("--who=", env!("CARGO_PKG_NAME")),
+                        &format!("--why={REASON}"),
+                        "--mode=block",
+                        "cat",
+                    ])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .ok();
+                Self { inhibitor }
+            }
+        }
+
+        impl Drop for KeepAwake {
+            fn drop(&mut self) {
+                if let Some(mut child) = self.inhibitor.take() {
+                    drop(child.stdin.take());
+                    let _ = child.wait();
+                }
+            }
+        }
+    }
+    target_os = "macos" => {
+        use std::ffi::{c_char, c_void};
+
+        type CFStringRef = *const c_void;
+
+        const K_CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+        const K_IOPM_ASSERTION_LEVEL_ON: u32 = 255;
+
+        #[link(name = "CoreFoundation", kind = "framework")]
+        unsafe extern "C" {
+            fn CFStringCreateWithCString(
+                alloc: *const c_void,
+                c_str: *const c_char,
+                encoding: u32,
+            ) -> CFStringRef;
+            fn CFRelease(cf: *const c_void);
+        }
+
+        #[link(name = "IOKit", kind = "framework")]
+        unsafe extern "C" {
+            fn IOPMAssertionCreateWithName(
+                assertion_type: CFStringRef,
+                level: u32,
+                name: CFStringRef,
+                id: *mut u32,
+            ) -> i32;
+            fn IOPMAssertionRelease(id: u32) -> i32;
+        }
+
+        impl KeepAwake {
+            /// Takes a `PreventUserIdleSystemSleep` power assertion (what `caffeinate -i` uses).
+            pub(crate) fn acquire() -> Self {
+                let assertion = unsafe {
+                    let kind = CFStringCreateWithCString(
+                        std::ptr::null(),
+                        c"PreventUserIdleSystemSleep".as_ptr(),
+                        K_CF_STRING_ENCODING_UTF8,
+                    );
+                    let name = CFStringCreateWithCString(
+                        std::ptr::null(),
+                        c"Extracting partition images".as_ptr(),
+                        K_CF_STRING_ENCODING_UTF8,
+                    );
+
+                    let mut id = 0u32;
+                    let ok = !kind.is_null()
+                        && !name.is_null()
+                        && IOPMAssertionCreateWithName(
+                            kind,
+                            K_IOPM_ASSERTION_LEVEL_ON,
+                            name,
+                            &mut id,
+                        ) == 0;
+
+                    if !kind.is_null() {
+                        CFRelease(kind);
+                    }
+                    if !name.is_null() {
+                        CFRelease(name);
+                    }
+                    ok.then_some(id)
+                };
+                Self { assertion }
+            }
+        }
+
+        impl Drop for KeepAwake {
+            fn drop(&mut self) {
+                if let Some(id) = self.assertion.take() {
+                    unsafe {
+                        IOPMAssertionRelease(id);
+                    }
+                }
+            }
+        }
+    }
+    windows => {
+        const ES_CONTINUOUS: u32 = 0x8000_0000;
+        const ES_SYSTEM_REQUIRED: u32 = 0x0000_0001;
+
+        #[link(name = "kernel32")]
+        unsafe extern "system" {
+            fn SetThreadExecutionState(flags: u32) -> u32;
+        }
+
+        impl KeepAwake {
+            /// Marks the calling thread as requiring the system. The state is per-thread, so the
+            /// guard must be dropped on the thread that acquired it.
+            pub(crate) fn acquire() -> Self {
+                let active =
+                    unsafe { SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED) } != 0;
+                Self { active }
+            }
+        }
+
+        impl Drop for KeepAwake {
+            fn drop(&mut self) {
+                if self.active {
+                    unsafe {
+                        SetThreadExecutionState(ES_CONTINUOUS);
+                    }
+                }
+            }
+        }
+    }
+    _ => {
+        impl KeepAwake {
+            pub(crate) fn acquire() -> Self {
+                Self {}
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crate::cmd::Cmd;
+use crate::cmd::config::Config;
+use crate::cmd::exitcode::FailureKind;
+use crate::cmd::extractor::Extractor;
+use crate::cmd::i18n::Msg;
+use anyhow::{Context, Result, anyhow};
+use chrono::Local;
+use console::Style;
+use indicatif::HumanDuration;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use std::{env, thread};
+
+/// How one payload of a batch went.
+struct Outcome {
+    result: Result<()>,
+    elapsed: Duration,
+}
+
+/// Batch mode: extracts every payload given on the command line as if it were run on its own,
+/// each into its own folder, `--jobs` at a time, then prints one summary for all of them.
+pub(super) fn run(cmd: &Cmd, cancel: Arc<AtomicBool>) -> Result<()> {
+    let payloads: Vec<PathBuf> = cmd
+        .positional_payload
+        .iter()
+        .chain(&cmd.more_payloads)
+        .cloned()
+        .collect();
+    let base = match &cmd.output_dir {
+        Some(dir) => dir.clone(),
+        None => env::current_dir().context(
+            "Failed to determine current directory. Please specify --output-dir explicitly.",
+        )?,
+    };
+    // One timestamp for the whole batch, so its folders sort together.
+    let stamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+    let folders: Vec<PathBuf> = labels(&payloads)
+        .into_iter()
+        .map(|label| {
+            if cmd.flat {
+                base.join(label)
+            } else {
+                base.join(format!("extracted_{label}_{stamp}"))
+            }
+        })
+        .collect();
+
+    let workers = usize::from(cmd.jobs).min(payloads.len());
+    let concurrent = workers > 1;
+    let started = Instant::now();
+    let next = AtomicUsize::new(0);
+    let outcomes: Vec<Mutex<Option<Outcome>>> = payloads.iter().map(|_| Mutex::default()).collect();
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(payload) = payloads.get(index) else {
+                        break;
+                    };
+                    if cancel.load(Ordering::Acquire) {
+                        break;
+                    }
+                    if cmd.chatty() {
+                        eprintln!(
+                            "\n[{}/{}] {} → {}",
+                            index + 1,
+                            payloads.len(),
+                            payload.display(),
+                            folders[index].display()
+                        );
+                    }
+                    let start = Instant::now();
+                    let result = extract(cmd, payload, &folders[index], concurrent, &cancel);
+                    if let Err(e) = &result {
+                        eprintln!("\n{}: {}: {:#}", Msg::Error.text(), payload.display(), e);
+                    }
+                    *outcomes[index]
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner) = Some(Outcome {
+                        result,
+                        elapsed: start.elapsed(),
+                    });
+                }
+            });
+        }
+    });
+    let outcomes: Vec<Option<Outcome>> = outcomes
+        .into_iter()
+        .map(|slot| slot.into_inner().unwrap_or_else(PoisonError::into_inner))
+        .collect();
+
+    let extracted = outcomes
+        .iter()
+        .filter(|o| o.as_ref().is_some_and(|o| o.result.is_ok()))
+        .count();
+    if cmd.chatty() {
+        let ok = Style::new().bold().green();
+        let bad = Style::new().bold().red();
+        let note = Style::new().yellow();
+        println!(
+            "\nBatch: {extracted} of {} payloads extracted in {}.",
+            payloads.len(),
+            HumanDuration(started.elapsed())
+        );
+        for ((payload, folder), outcome) in payloads.iter().zip(&folders).zip(&outcomes) {
+            match outcome {
+                Some(Outcome {
+                    result: Ok(()),
+                    elapsed,
+                }) => println!(
+                    "  {} {} → {} ({})",
+                    ok.apply_to("✓"),
+                    payload.display(),
+                    folder.display(),
+                    HumanDuration(*elapsed)
+                ),
+                Some(Outcome { result: Err(e), .. }) => {
+                    println!("  {} {}: {e:#}", bad.apply_to("✗"), payload.display())
+                }
+                None => println!(
+                    "  {} {}: not started",
+                    note.apply_to("-"),
+                    payload.display()
+                ),
+            }
+        }
+    }
+
+    if cancel.load(Ordering::Acquire) {
+        return Err(FailureKind::Interrupted.tag(anyhow!("{}", Msg::Cancelled.text())));
+    }
+    let kinds: Vec<FailureKind> = outcomes
+        .iter()
+        .flatten()
+        .filter_map(|o| o.result.as_ref().err().map(FailureKind::of))
+        .collect();
+    match kinds.first() {
+        None => Ok(()),
+        Some(&first) => {
+            // Scripts get a specific code only when every payload failed the same way.
+            let kind = if kinds.iter().all(|&k| k == first) {
+                first
+            } else {
+                FailureKind::Other
+            };
+            Err(kind.tag(anyhow!(
+                "{} of {} payloads failed (see above).",
+                kinds.len(),
+                payloads.len()
+            )))
+        }
+    }
+}
+
+/// Extracts `payload` into `folder` with the rest of `cmd` as given. Folders are not opened, and
+/// payloads extracted side by side draw no progress bars over each other.
+fn extract(
+    cmd: &Cmd,
+    payload: &Path,
+    folder: &Path,
+    concurrent: bool,
+    cancel: &Arc<AtomicBool>,
+) -> Result<()> {
+    let mut job = cmd.clone();
+    job.positional_payload = Some(payload.to_path_buf());
+    job.more_payloads.clear();
+    job.output_dir = Some(folder.to_path_buf());
+    job.flat = true;
+    job.no_open = true;
+    job.quiet_flag |= concurrent;
+    Extractor::new(&job, Config::resolve(&job)?)
+        .interruptible(Arc::clone(cancel))
+        .run()
+}
+
+/// Output folder names for `payloads`: the file name without its extension, or for a bare
+/// payload.bin the name of the folder it is in. Repeated names get `_2`, `_3`, ...
+fn labels(payloads: &[PathBuf]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    payloads
+        .iter()
+        .map(|path| {
+            let stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let name = if stem.eq_ignore_ascii_case("payload") {
+                path.parent()
+                    .and_then(Path::file_name)
+                    .map_or(stem, |dir| dir.to_string_lossy().into_owned())
+            } else {
+                stem
+            };
+            let name = if name.is_empty() {
+                "payload".to_string()
+            } else {
+                name
+            };
+            let mut label = name.clone();
+            let mut n = 1;
+            while !seen.insert(label.clone()) {
+                n += 1;
+                label = format!("{name}_{n}");
+            }
+            label
+        })
+        .collect()
+}
@@ -0,0 +1,127 @@
+use crate::proto::chromeos_update_engine::{
+    DeltaArchiveManifest, InstallOperation, PartitionInfo, PartitionUpdate,
+};
+use crate::proto::summary::{ManifestSummary, OperationSummary, PartitionSummary};
+
+/// Names update_engine gives the Chrome OS root filesystem and kernel (the ROOT-x and KERN-x GPT
+/// partitions of the slot being updated).
+const ROOT: &str = "root";
+const KERNEL: &str = "kernel";
+/// Carried by Chrome OS payloads for devices with a MiniOS recovery image.
+const MINIOS: &str = "minios";
+
+/// `start_block` of a hole. Major version 1 payloads end the kernel's operations with a dummy
+/// `REPLACE` into a hole that covers the signature blob, for the sake of old clients.
+const SPARSE_HOLE: u64 = u64::MAX;
+
+/// Major version 1 (Chrome OS) manifests have no `partitions`; the root filesystem and kernel
+/// each get fields of their own. Moves those into `root` and `kernel` entries so everything past
+/// decoding sees one layout, dropping the dummy signature operation.
+#[allow(deprecated)]
+pub(crate) fn upgrade_manifest(manifest: &mut DeltaArchiveManifest) {
+    if !manifest.partitions.is_empty() {
+        return;
+    }
+    let legacy = [
+        (
+            ROOT,
+            std::mem::take(&mut manifest.install_operations),
+            manifest.old_rootfs_info.take(),
+            manifest.new_rootfs_info.take(),
+        ),
+        (
+            KERNEL,
+            std::mem::take(&mut manifest.kernel_install_operations),
+            manifest.old_kernel_info.take(),
+            manifest.new_kernel_info.take(),
+        ),
+    ];
+    for (name, operations, old_info, new_info) in legacy {
+        if operations.is_empty() && new_info.is_none() {
+            continue;
+        }
+        manifest.partitions.push(PartitionUpdate {
+            partition_name: name.into(),
+            operations: operations
+                .into_iter()
+                .filter(|op| !is_signature_op(op))
+                .collect(),
+            old_partition_info: old_info,
+            new_partition_info: new_info,
+            ..Default::default()
+        });
+    }
+}
+
+/// [`upgrade_manifest`] for listing. The summary has no extents to spot the signature operation
+/// by, so the kernel's count includes it.
+pub(crate) fn upgrade_summary(manifest: &mut ManifestSummary) {
+    if !manifest.partitions.is_empty() {
+        return;
+    }
+    let legacy: [(_, Vec<OperationSummary>, Option<PartitionInfo>); 2] = [
+        (
+            ROOT,
+            std::mem::take(&mut manifest.install_operations),
+            manifest.new_rootfs_info.take(),
+        ),
+        (
+            KERNEL,
+            std::mem::take(&mut manifest.kernel_install_operations),
+            manifest.new_kernel_info.take(),
+        ),
+    ];
+    for (name, operations, new_info) in legacy {
+        if operations.is_empty() && new_info.is_none() {
+            continue;
+        }
+        manifest.partitions.push(PartitionSummary {
+            partition_name: name.into(),
+            new_partition_info: new_info,
+            operations,
+        });
+    }
+}
+
+fn is_signature_op(op: &InstallOperation) -> bool {
+    op.dst_extents
+        .iter()
+        .any(|e| e.start_block == Some(SPARSE_HOLE))
+}
+
+/// Whether the payload updates a Chrome OS device: it carries a root filesystem and nothing but
+/// Chrome OS partitions. Android payloads name theirs `system`, `boot` and so on.
+pub(crate) fn is_chromeos(manifest: &DeltaArchiveManifest) -> bool {
+    let is =
+        |p: &PartitionUpdate, name: &str| p.partition_name.eq_ignore_ascii_case(name.as_bytes());
+    manifest.partitions.iter().any(|p| is(p, ROOT))
+        && manifest
+            .partitions
+            .iter()
+            .all(|p| [ROOT, KERNEL, MINIOS].iter().any(|name| is(p, name)))
+}
+
+/// What a payload's major (header format) version means.
+pub(crate) fn major_version_name(version: u64) -> &'static str {
+    match version {
+        1 => "Chrome OS, root and kernel fields",
+        _ => "partition list",
+    }
+}
+
+/// The delta features a minor version adds, from update_engine's `payload_constants.h`.
+pub(crate) fn minor_version_name(minor: u32) -> &'static str {
+    match minor {
+        0 => "full",
+        1 => "in-place delta",
+        2 => "source delta",
+        3 => "source delta, source hashes",
+        4 => "source delta, brotli bsdiff",
+        5 => "source delta, puffdiff",
+        6 => "source delta, verity",
+        7 => "source delta, partial update",
+        8 => "source delta, zucchini",
+        9 => "source delta, lz4diff",
+        _ => "delta, newer than otaripper knows",
+    }
+}
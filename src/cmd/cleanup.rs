@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// What an extraction has written so far.
+struct Created {
+    files: Vec<PathBuf>,
+    dir: PathBuf,
+    /// The extraction created `dir`, so it may be removed along with the files.
+    dir_is_new: bool,
+}
+
+/// Deletes partially extracted images when dropped, whether extraction returned an error or a
+/// panic is unwinding, unless [`keep`](Self::keep) was called first. Being scoped to one
+/// extraction, it leaves the process's panic hook alone for programs embedding otaripper.
+pub(crate) struct CleanupGuard(Mutex<Created>);
+
+impl CleanupGuard {
+    pub(crate) fn new(dir: PathBuf, dir_is_new: bool) -> Self {
+        Self(Mutex::new(Created {
+            files: Vec::new(),
+            dir,
+            dir_is_new,
+        }))
+    }
+
+    /// Records an output file to remove if extraction doesn't finish.
+    pub(crate) fn track(&self, file: PathBuf) {
+        self.lock().files.push(file);
+    }
+
+    /// Keeps `file` even if extraction doesn't finish.
+    pub(crate) fn untrack(&self, file: &Path) {
+        self.lock().files.retain(|tracked| tracked != file);
+    }
+
+    /// Extraction finished: keep everything it wrote.
+    pub(crate) fn keep(&self) {
+        let mut created = self.lock();
+        created.files.clear();
+        created.dir_is_new = false;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Created> {
+        // A worker that panicked while holding the lock left the list intact.
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        let mut created = self.lock();
+        if created.files.is_empty() && !created.dir_is_new {
+            return;
+        }
+        for file in created.files.drain(..) {
+            let _ = fs::remove_file(file);
+        }
+        if created.dir_is_new {
+            let _ = fs::remove_dir_all(&created.dir);
+            created.dir_is_new = false;
+        }
+        if std::thread::panicking() {
+            eprintln!(
+                "Extraction aborted due to an error. Any partially extracted partition images have been deleted to prevent misuse."
+            );
+        }
+    }
+}
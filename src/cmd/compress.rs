@@ -0,0 +1,128 @@
+use flate2::write::GzEncoder;
+use liblzma::write::XzEncoder;
+use std::io::{self, Write};
+
+/// Compressor and level for `--compress`, written as `zstd`, `gzip` or `xz`, optionally
+/// followed by `:LEVEL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Compression {
+    codec: Codec,
+    level: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Zstd,
+    Gzip,
+    Xz,
+}
+
+impl Codec {
+    /// Default level and the highest one accepted.
+    fn levels(self) -> (u32, u32) {
+        match self {
+            Codec::Zstd => (3, 22),
+            Codec::Gzip => (6, 9),
+            Codec::Xz => (6, 9),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Codec::Zstd => "zst",
+            Codec::Gzip => "gz",
+            Codec::Xz => "xz",
+        }
+    }
+}
+
+impl Compression {
+    /// Parses a `--compress` value.
+    pub(crate) fn parse(value: &str) -> Result<Self, String> {
+        let (name, level) = match value.split_once(':') {
+            Some((name, level)) => (name, Some(level)),
+            None => (value, None),
+        };
+        let codec = match name.to_ascii_lowercase().as_str() {
+            "zstd" | "zst" => Codec::Zstd,
+            "gzip" | "gz" => Codec::Gzip,
+            "xz" => Codec::Xz,
+            _ => return Err(format!("unknown compressor '{name}'; use zstd, gzip or xz")),
+        };
+        let (default, max) = codec.levels();
+        let level = match level {
+            None => default,
+            Some(level) => match level.parse::<u32>() {
+                Ok(level) if (1..=max).contains(&level) => level,
+                _ => return Err(format!("{name} levels go from 1 to {max}, not '{level}'")),
+            },
+        };
+        Ok(Self { codec, level })
+    }
+
+    /// File name extension of the compressed images, without the dot.
+    pub(crate) fn extension(self) -> &'static str {
+        self.codec.extension()
+    }
+
+    fn encoder<W: Write>(self, out: W) -> io::Result<Encoder<W>> {
+        Ok(match self.codec {
+            Codec::Zstd => {
+                Encoder::Zstd(zstd::stream::write::Encoder::new(out, self.level as i32)?)
+            }
+            Codec::Gzip => Encoder::Gzip(GzEncoder::new(out, flate2::Compression::new(self.level))),
+            Codec::Xz => Encoder::Xz(XzEncoder::new(out, self.level)),
+        })
+    }
+}
+
+/// Wraps `out` in the encoder for `compression`, or passes data through when it is `None`.
+pub(crate) fn encoder<W: Write>(
+    compression: Option<Compression>,
+    out: W,
+) -> io::Result<Encoder<W>> {
+    match compression {
+        Some(compression) => compression.encoder(out),
+        None => Ok(Encoder::Plain(out)),
+    }
+}
+
+/// A compressing writer for one of the [`Codec`]s, or none.
+pub(crate) enum Encoder<W: Write> {
+    Plain(W),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Gzip(GzEncoder<W>),
+    Xz(XzEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Writes the end of the stream and returns the underlying writer.
+    pub(crate) fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Plain(w) => Ok(w),
+            Encoder::Zstd(e) => e.finish(),
+            Encoder::Gzip(e) => e.finish(),
+            Encoder::Xz(e) => e.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Plain(w) => w.write(buf),
+            Encoder::Zstd(e) => e.write(buf),
+            Encoder::Gzip(e) => e.write(buf),
+            Encoder::Xz(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Plain(w) => w.flush(),
+            Encoder::Zstd(e) => e.flush(),
+            Encoder::Gzip(e) => e.flush(),
+            Encoder::Xz(e) => e.flush(),
+        }
+    }
+}
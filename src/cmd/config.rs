@@ -0,0 +1,91 @@
+use crate::cmd::Lang;
+use crate::cmd::i18n;
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The config file as written, every key optional:
+///
+/// ```toml
+/// debug-cpu = true
+/// open-with = "code {dir}"
+/// lang = "pt-br"
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+    debug_cpu: Option<bool>,
+    open_with: Option<String>,
+    lang: Option<String>,
+}
+
+/// Runtime knobs that can come from the command line, the environment or the config file, in
+/// that order of precedence. Resolved once before extraction starts so nothing further in has to
+/// look at the environment.
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    /// `--debug-cpu` / `OTARIPPER_DEBUG_CPU`: print the detected SIMD features.
+    pub(crate) debug_cpu: bool,
+    /// `--open-with` / `OTARIPPER_OPEN_WITH`: command run on the output directory.
+    pub(crate) open_with: Option<String>,
+}
+
+impl Config {
+    pub(crate) fn resolve(cmd: &super::Cmd) -> Result<Self> {
+        let file = match &cmd.config {
+            Some(path) => load(path)?,
+            None => match default_path().filter(|path| path.is_file()) {
+                Some(path) => load(&path)?,
+                None => ConfigFile::default(),
+            },
+        };
+
+        // The config file's choice beats the system locale, which covers every program.
+        let lang = match (cmd.lang, &file.lang) {
+            (Some(lang), _) => lang,
+            (None, Some(name)) => Lang::from_str(name, true).map_err(|_| {
+                anyhow!("invalid config file: unknown lang {name:?} (expected en, es or pt-br)")
+            })?,
+            (None, None) => i18n::system_lang().unwrap_or(Lang::En),
+        };
+        i18n::init(lang);
+
+        let debug_cpu = if cmd.debug_cpu {
+            true
+        } else if let Some(value) = env::var_os("OTARIPPER_DEBUG_CPU") {
+            !matches!(value.to_str(), Some("0" | "false"))
+        } else {
+            file.debug_cpu.unwrap_or(false)
+        };
+
+        Ok(Self {
+            debug_cpu,
+            // clap has already taken the flag over OTARIPPER_OPEN_WITH.
+            open_with: cmd.open_with.clone().or(file.open_with),
+        })
+    }
+}
+
+fn load(path: &Path) -> Result<ConfigFile> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("could not read config file {path:?}"))?;
+    toml::from_str(&text).with_context(|| format!("invalid config file {path:?}"))
+}
+
+/// `otaripper/config.toml` in the user's config directory: `%APPDATA%` on Windows,
+/// `$XDG_CONFIG_HOME` or `~/.config` elsewhere.
+fn default_path() -> Option<PathBuf> {
+    let non_empty = |var| env::var_os(var).filter(|value| !value.is_empty());
+    let dir = cfg_select! {
+        windows => { non_empty("APPDATA").map(PathBuf::from) }
+        _ => {
+            non_empty("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| non_empty("HOME").map(|home| Path::new(&home).join(".config")))
+        }
+    };
+    Some(dir?.join("otaripper").join("config.toml"))
+}
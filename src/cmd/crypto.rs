@@ -0,0 +1,153 @@
+//! SHA-256 and signature verification, backed by `ring` (default) or the system OpenSSL.
+//!
+//! The backend is picked at build time: `--features crypto-openssl` links OpenSSL, and `fips`
+//! additionally restricts it to the OpenSSL FIPS provider. When several are enabled, OpenSSL wins.
+
+use crate::cmd::sigverify::KeyAlgorithm;
+use anyhow::Result;
+
+#[cfg(not(any(feature = "crypto-ring", feature = "crypto-openssl")))]
+compile_error!("enable one crypto backend: `crypto-ring` (default) or `crypto-openssl`");
+
+cfg_select! {
+    feature = "crypto-openssl" => {
+        use openssl::ec::{EcGroup, EcKey, EcPoint};
+        use openssl::hash::MessageDigest;
+        use openssl::nid::Nid;
+        use openssl::pkey::PKey;
+        use openssl::rsa::Rsa;
+        use openssl::sign::Verifier;
+
+        /// Backend name, as shown by `info`.
+        pub(crate) const BACKEND: &str = if cfg!(feature = "fips") {
+            "OpenSSL (FIPS provider)"
+        } else {
+            "OpenSSL"
+        };
+
+        /// Prepares the backend. With `fips`, loads the FIPS provider before any algorithm is
+        /// fetched; OpenSSL then never falls back to the default provider.
+        pub(crate) fn init() -> Result<()> {
+            #[cfg(feature = "fips")]
+            {
+                use anyhow::Context;
+                use openssl::provider::Provider;
+                use std::sync::OnceLock;
+
+                static PROVIDERS: OnceLock<Result<(Provider, Provider), String>> = OnceLock::new();
+                PROVIDERS
+                    .get_or_init(|| {
+                        let fips = Provider::load(None, "fips").map_err(|e| e.to_string())?;
+                        // Key decoders live in the base provider, which FIPS mode permits.
+                        let base = Provider::load(None, "base").map_err(|e| e.to_string())?;
+                        Ok((fips, base))
+                    })
+                    .as_ref()
+                    .map_err(|e| anyhow::anyhow!("{e}"))
+                    .context(
+                        "unable to load the OpenSSL FIPS provider (is it installed and configured?)",
+                    )?;
+            }
+            Ok(())
+        }
+
+        pub fn sha256(data: &[u8]) -> [u8; 32] {
+            openssl::sha::sha256(data)
+        }
+
+        /// Incremental SHA-256, for data that is never in memory all at once.
+        pub struct Sha256(openssl::sha::Sha256);
+
+        impl Sha256 {
+            pub fn new() -> Self {
+                Self(openssl::sha::Sha256::new())
+            }
+
+            pub fn update(&mut self, data: &[u8]) {
+                self.0.update(data);
+            }
+
+            pub fn finish(self) -> [u8; 32] {
+                self.0.finish()
+            }
+        }
+
+        /// Checks a SHA-256 signature. `key` is a PKCS#1 RSAPublicKey for RSA or an uncompressed
+        /// SEC1 point for P-256, i.e. the bit string of a SubjectPublicKeyInfo.
+        pub(crate) fn verify(algorithm: KeyAlgorithm, key: &[u8], message: &[u8], sig: &[u8]) -> bool {
+            let pkey = match algorithm {
+                KeyAlgorithm::Rsa => Rsa::public_key_from_der_pkcs1(key)
+                    .ok()
+                    // Same modulus range ring accepts, so both backends agree on every key.
+                    .filter(|rsa| (2048..=8192).contains(&rsa.n().num_bits()))
+                    .and_then(|rsa| PKey::from_rsa(rsa).ok()),
+                KeyAlgorithm::EcP256 => {
+                    let point = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).and_then(|group| {
+                        let mut ctx = openssl::bn::BigNumContext::new()?;
+                        let point = EcPoint::from_bytes(&group, key, &mut ctx)?;
+                        EcKey::from_public_key(&group, &point)
+                    });
+                    point.ok().and_then(|ec| PKey::from_ec_key(ec).ok())
+                }
+            };
+            let Some(pkey) = pkey else {
+                return false;
+            };
+            Verifier::new(MessageDigest::sha256(), &pkey)
+                .and_then(|mut v| v.verify_oneshot(sig, message))
+                .unwrap_or(false)
+        }
+    }
+    _ => {
+        use ring::signature::{self, UnparsedPublicKey};
+
+        /// Backend name, as shown by `info`.
+        pub(crate) const BACKEND: &str = "ring";
+
+        pub(crate) fn init() -> Result<()> {
+            Ok(())
+        }
+
+        pub fn sha256(data: &[u8]) -> [u8; 32] {
+            let d = ring::digest::digest(&ring::digest::SHA256, data);
+            let mut out = [0u8; 32];
+            out.copy_from_slice(d.as_ref());
+            out
+        }
+
+        /// Incremental SHA-256, for data that is never in memory all at once.
+        pub struct Sha256(ring::digest::Context);
+
+        impl Sha256 {
+            pub fn new() -> Self {
+                Self(ring::digest::Context::new(&ring::digest::SHA256))
+            }
+
+            pub fn update(&mut self, data: &[u8]) {
+                self.0.update(data);
+            }
+
+            pub fn finish(self) -> [u8; 32] {
+                let mut out = [0u8; 32];
+                out.copy_from_slice(self.0.finish().as_ref());
+                out
+            }
+        }
+
+        /// Checks a SHA-256 signature. `key` is a PKCS#1 RSAPublicKey for RSA or an uncompressed
+        /// SEC1 point for P-256, i.e. the bit string of a SubjectPublicKeyInfo.
+        pub(crate) fn verify(algorithm: KeyAlgorithm, key: &[u8], message: &[u8], sig: &[u8]) -> bool {
+            let alg: &dyn signature::VerificationAlgorithm = match algorithm {
+                KeyAlgorithm::Rsa => &signature::RSA_PKCS1_2048_8192_SHA256,
+                KeyAlgorithm::EcP256 => &signature::ECDSA_P256_SHA256_ASN1,
+            };
+            UnparsedPublicKey::new(alg, key).verify(message, sig).is_ok()
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,215 @@
+use crate::cmd::crypto;
+use crate::cmd::exitcode::FailureKind;
+use crate::cmd::extractor::Extractor;
+use crate::cmd::i18n::Msg;
+use crate::cmd::simd::CpuSimd;
+use crate::proto::chromeos_update_engine::DeltaArchiveManifest;
+use crate::proto::chromeos_update_engine::install_operation::Type;
+use anyhow::Result;
+use chrono::Local;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use sysinfo::{CpuRefreshKind, MemoryRefreshKind, RefreshKind, System};
+
+impl Extractor<'_> {
+    /// `--debug-bundle`: writes what is needed to triage a bug report to the given file. Without
+    /// the flag, an unexpected failure ([`FailureKind::Other`]) writes one to the temp directory
+    /// anyway. A bad payload or command line, cancelling and the library, which leaves reporting
+    /// to its caller, don't.
+    /// Only metadata goes in: header fields, the manifest's partitions and operation types, the
+    /// system and the error. Never fails; a bundle that can't be written is a warning.
+    pub(super) fn write_debug_bundle(&self, error: Option<&anyhow::Error>) {
+        let path = match (&self.cmd.debug_bundle, error) {
+            (Some(path), _) => path.clone(),
+            (None, Some(error))
+                if FailureKind::of(error) == FailureKind::Other
+                    && !self.interrupted()
+                    && !self.cmd.machine_output
+                    && self.cmd.positional_payload.is_some() =>
+            {
+                env::temp_dir().join(format!(
+                    "otaripper-debug-{}.txt",
+                    Local::now().format("%Y-%m-%d_%H-%M-%S")
+                ))
+            }
+            _ => return,
+        };
+        match fs::write(&path, self.debug_report(error)) {
+            Ok(()) if error.is_some() || !self.cmd.machine_output => {
+                eprintln!("{}", Msg::DebugBundleSaved.fill(&[&path.display()]));
+            }
+            Ok(()) => {}
+            Err(e) => eprintln!("Warning: could not write debug bundle {path:?}: {e}"),
+        }
+    }
+
+    fn debug_report(&self, error: Option<&anyhow::Error>) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# otaripper debug bundle\n");
+        let _ = writeln!(out, "Version:   otaripper {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(
+            out,
+            "Generated: {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S %:z")
+        );
+        let args: Vec<_> = env::args_os()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let _ = writeln!(out, "Command:   {}", args.join(" "));
+        let _ = writeln!(
+            out,
+            "Result:    {}",
+            if error.is_some() {
+                "failed"
+            } else {
+                "succeeded"
+            }
+        );
+
+        let _ = writeln!(out, "\n## System\n");
+        let sys = System::new_with_specifics(
+            RefreshKind::nothing()
+                .with_memory(MemoryRefreshKind::nothing().with_ram())
+                .with_cpu(CpuRefreshKind::nothing()),
+        );
+        let _ = writeln!(
+            out,
+            "OS:        {} ({}, {})",
+            System::long_os_version().unwrap_or_else(|| env::consts::OS.to_string()),
+            env::consts::ARCH,
+            System::kernel_version().unwrap_or_default()
+        );
+        let _ = writeln!(
+            out,
+            "CPU:       {} ({} logical cores)",
+            sys.cpus()
+                .first()
+                .map_or("unknown", |cpu| cpu.brand())
+                .trim(),
+            sys.cpus().len()
+        );
+        let _ = writeln!(
+            out,
+            "Memory:    {} available of {}",
+            indicatif::HumanBytes(sys.available_memory()),
+            indicatif::HumanBytes(sys.total_memory())
+        );
+        let _ = writeln!(out, "Crypto:    {}", crypto::BACKEND);
+        out.push_str(&CpuSimd::detection());
+
+        if let Some(path) = &self.cmd.positional_payload {
+            let _ = writeln!(out, "\n## Payload\n");
+            if let Err(e) = payload_summary(&mut out, path) {
+                let _ = writeln!(out, "unavailable: {e:#}");
+            }
+        }
+
+        if let Some(error) = error {
+            let _ = writeln!(out, "\n## Error\n");
+            let _ = writeln!(out, "{error:?}");
+        }
+        let failures = self.failures();
+        if !failures.is_empty() {
+            let _ = writeln!(out, "\n## Worker errors\n");
+            for failure in failures {
+                let _ = writeln!(out, "{failure}");
+            }
+        }
+        out
+    }
+}
+
+/// Header fields, manifest fields and per-partition operation counts; no payload data.
+fn payload_summary(out: &mut String, path: &Path) -> Result<()> {
+    let _ = writeln!(out, "File:           {}", path.display());
+    if let Ok(meta) = fs::metadata(path) {
+        let _ = writeln!(out, "File size:      {} bytes", meta.len());
+    }
+    let header = Extractor::read_payload_header(path)?;
+    let _ = writeln!(out, "Format version: {}", header.version());
+    let _ = writeln!(out, "Header size:    {} bytes", header.header_size());
+    let _ = writeln!(out, "Manifest size:  {} bytes", header.manifest_size());
+    let _ = writeln!(
+        out,
+        "Metadata sig:   {}",
+        header
+            .metadata_signature()
+            .map_or("none".to_string(), |sig| format!("{} bytes", sig.len()))
+    );
+    let _ = writeln!(out, "Data offset:    {} bytes", header.data_offset());
+    let _ = writeln!(
+        out,
+        "Metadata hash:  {}",
+        hex::encode(header.metadata_hash())
+    );
+
+    let manifest = Extractor::decode_manifest(header.manifest())?;
+    manifest_summary(out, &manifest);
+    Ok(())
+}
+
+fn manifest_summary(out: &mut String, manifest: &DeltaArchiveManifest) {
+    let _ = writeln!(
+        out,
+        "Block size:     {}",
+        manifest.block_size.unwrap_or(4096)
+    );
+    let _ = writeln!(
+        out,
+        "Minor version:  {}",
+        manifest.minor_version.unwrap_or(0)
+    );
+    if let Some(ts) = manifest.max_timestamp {
+        let _ = writeln!(out, "Max timestamp:  {ts}");
+    }
+    if let Some(spl) = &manifest.security_patch_level {
+        let _ = writeln!(out, "Patch level:    {spl}");
+    }
+    if manifest.partial_update == Some(true) {
+        let _ = writeln!(out, "Partial update: yes");
+    }
+
+    let _ = writeln!(out, "\n## Partitions\n");
+    let mut totals: BTreeMap<&str, usize> = BTreeMap::new();
+    for update in &manifest.partitions {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for op in &update.operations {
+            let name = Type::try_from(op.r#type).map_or("UNKNOWN", |t| t.as_str_name());
+            *counts.entry(name).or_default() += 1;
+            *totals.entry(name).or_default() += 1;
+        }
+        let size = update
+            .new_partition_info
+            .as_ref()
+            .and_then(|info| info.size)
+            .unwrap_or(0);
+        let _ = writeln!(
+            out,
+            "{}: {size} bytes, {} ops ({}){}",
+            update.name(),
+            update.operations.len(),
+            render_counts(&counts),
+            if update.old_partition_info.is_some() {
+                ", delta"
+            } else {
+                ""
+            }
+        );
+    }
+
+    let _ = writeln!(out, "\n## Operation types\n");
+    for (name, count) in &totals {
+        let _ = writeln!(out, "{name}: {count}");
+    }
+}
+
+fn render_counts(counts: &BTreeMap<&str, usize>) -> String {
+    counts
+        .iter()
+        .map(|(name, count)| format!("{name} {count}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,340 @@
+use crate::cmd::ManifestFormat;
+use crate::cmd::extractor::Extractor;
+use crate::proto::chromeos_update_engine::{
+    ApexInfo, CowMergeOperation, DeltaArchiveManifest, DynamicPartitionMetadata, Extent,
+    InstallOperation, PartitionInfo, PartitionUpdate, cow_merge_operation, install_operation,
+    signatures,
+};
+use anyhow::{Context, Result};
+use prost::Message;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt::Write;
+use std::io::{self, Write as _};
+use std::path::Path;
+
+/// A manifest field's value, kept apart from JSON so textproto can tell enums from strings.
+enum Value {
+    Message(Fields),
+    Repeated(Vec<Value>),
+    Str(String),
+    Uint(u64),
+    Int(i64),
+    Bool(bool),
+    Enum(&'static str),
+}
+
+/// A message's set fields, in field number order, under their `.proto` names.
+#[derive(Default)]
+struct Fields(Vec<(&'static str, Value)>);
+
+impl Fields {
+    fn set(&mut self, name: &'static str, value: impl Into<Option<Value>>) -> &mut Self {
+        if let Some(value) = value.into() {
+            self.0.push((name, value));
+        }
+        self
+    }
+
+    fn repeated<T>(
+        &mut self,
+        name: &'static str,
+        items: &[T],
+        f: impl Fn(&T) -> Value,
+    ) -> &mut Self {
+        if !items.is_empty() {
+            self.0
+                .push((name, Value::Repeated(items.iter().map(f).collect())));
+        }
+        self
+    }
+
+    fn done(&mut self) -> Value {
+        Value::Message(std::mem::take(self))
+    }
+}
+
+impl Extractor<'_> {
+    /// `dump-manifest` subcommand: prints the payload's manifest as stored, every field it sets
+    /// included, as JSON or textproto. Bytes fields are written in hex, except partition names.
+    pub(super) fn run_dump_manifest(&self, path: &Path, format: ManifestFormat) -> Result<()> {
+        let header = Self::read_payload_header(path)?;
+        // Decoded without the Chrome OS upgrade, so major version 1 fields show as they are.
+        let manifest =
+            DeltaArchiveManifest::decode(header.manifest()).context("unable to parse manifest")?;
+        let value = manifest_value(&manifest);
+
+        let out = match (format, &value) {
+            (ManifestFormat::Json, value) => serde_json::to_string_pretty(value)? + "\n",
+            (ManifestFormat::Textproto, Value::Message(fields)) => {
+                let mut out = String::new();
+                write_text(&mut out, fields, 0);
+                out
+            }
+            (ManifestFormat::Textproto, _) => unreachable!("a manifest is a message"),
+        };
+        let mut stdout = io::stdout().lock();
+        match stdout.write_all(out.as_bytes()) {
+            // Piped into `head`; the rest isn't wanted.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result.context("failed to write the manifest"),
+        }
+    }
+}
+
+/// The deprecated fields are those of Chrome OS major version 1 payloads.
+#[allow(deprecated)]
+fn manifest_value(m: &DeltaArchiveManifest) -> Value {
+    Fields::default()
+        .repeated("install_operations", &m.install_operations, operation)
+        .repeated(
+            "kernel_install_operations",
+            &m.kernel_install_operations,
+            operation,
+        )
+        .set("block_size", m.block_size.map(u64::from).map(Value::Uint))
+        .set("signatures_offset", m.signatures_offset.map(Value::Uint))
+        .set("signatures_size", m.signatures_size.map(Value::Uint))
+        .set("old_kernel_info", m.old_kernel_info.as_ref().map(info))
+        .set("new_kernel_info", m.new_kernel_info.as_ref().map(info))
+        .set("old_rootfs_info", m.old_rootfs_info.as_ref().map(info))
+        .set("new_rootfs_info", m.new_rootfs_info.as_ref().map(info))
+        .set(
+            "minor_version",
+            m.minor_version.map(u64::from).map(Value::Uint),
+        )
+        .repeated("partitions", &m.partitions, partition)
+        .set("max_timestamp", m.max_timestamp.map(Value::Int))
+        .set(
+            "dynamic_partition_metadata",
+            m.dynamic_partition_metadata.as_ref().map(dynamic_metadata),
+        )
+        .set("partial_update", m.partial_update.map(Value::Bool))
+        .repeated("apex_info", &m.apex_info, apex)
+        .set(
+            "security_patch_level",
+            m.security_patch_level.clone().map(Value::Str),
+        )
+        .done()
+}
+
+fn partition(p: &PartitionUpdate) -> Value {
+    Fields::default()
+        .set("partition_name", Value::Str(p.name().into_owned()))
+        .set("run_postinstall", p.run_postinstall.map(Value::Bool))
+        .set(
+            "postinstall_path",
+            p.postinstall_path.clone().map(Value::Str),
+        )
+        .set("filesystem_type", p.filesystem_type.clone().map(Value::Str))
+        .repeated(
+            "new_partition_signature",
+            &p.new_partition_signature,
+            signature,
+        )
+        .set(
+            "old_partition_info",
+            p.old_partition_info.as_ref().map(info),
+        )
+        .set(
+            "new_partition_info",
+            p.new_partition_info.as_ref().map(info),
+        )
+        .repeated("operations", &p.operations, operation)
+        .set(
+            "postinstall_optional",
+            p.postinstall_optional.map(Value::Bool),
+        )
+        .set(
+            "hash_tree_data_extent",
+            p.hash_tree_data_extent.as_ref().map(extent),
+        )
+        .set("hash_tree_extent", p.hash_tree_extent.as_ref().map(extent))
+        .set(
+            "hash_tree_algorithm",
+            p.hash_tree_algorithm.clone().map(Value::Str),
+        )
+        .set("hash_tree_salt", p.hash_tree_salt.as_deref().map(bytes))
+        .set("fec_data_extent", p.fec_data_extent.as_ref().map(extent))
+        .set("fec_extent", p.fec_extent.as_ref().map(extent))
+        .set("fec_roots", p.fec_roots.map(u64::from).map(Value::Uint))
+        .set("version", p.version.clone().map(Value::Str))
+        .repeated("merge_operations", &p.merge_operations, merge_operation)
+        .set("estimate_cow_size", p.estimate_cow_size.map(Value::Uint))
+        .set(
+            "estimate_op_count_max",
+            p.estimate_op_count_max.map(Value::Uint),
+        )
+        .done()
+}
+
+fn operation(op: &InstallOperation) -> Value {
+    let op_type = install_operation::Type::try_from(op.r#type)
+        .map_or(Value::Int(op.r#type.into()), |t| {
+            Value::Enum(t.as_str_name())
+        });
+    Fields::default()
+        .set("type", op_type)
+        .set("data_offset", op.data_offset.map(Value::Uint))
+        .set("data_length", op.data_length.map(Value::Uint))
+        .repeated("src_extents", &op.src_extents, extent)
+        .set("src_length", op.src_length.map(Value::Uint))
+        .repeated("dst_extents", &op.dst_extents, extent)
+        .set("dst_length", op.dst_length.map(Value::Uint))
+        .set(
+            "data_sha256_hash",
+            op.data_sha256_hash.as_deref().map(bytes),
+        )
+        .set("src_sha256_hash", op.src_sha256_hash.as_deref().map(bytes))
+        .done()
+}
+
+fn merge_operation(op: &CowMergeOperation) -> Value {
+    let op_type = op.r#type.map(|t| {
+        cow_merge_operation::Type::try_from(t)
+            .map_or(Value::Int(t.into()), |t| Value::Enum(t.as_str_name()))
+    });
+    Fields::default()
+        .set("type", op_type)
+        .set("src_extent", op.src_extent.as_ref().map(extent))
+        .set("dst_extent", op.dst_extent.as_ref().map(extent))
+        .set("src_offset", op.src_offset.map(u64::from).map(Value::Uint))
+        .done()
+}
+
+fn extent(e: &Extent) -> Value {
+    Fields::default()
+        .set("start_block", e.start_block.map(Value::Uint))
+        .set("num_blocks", e.num_blocks.map(Value::Uint))
+        .done()
+}
+
+fn info(i: &PartitionInfo) -> Value {
+    Fields::default()
+        .set("size", i.size.map(Value::Uint))
+        .set("hash", i.hash.as_deref().map(bytes))
+        .done()
+}
+
+#[allow(deprecated)]
+fn signature(s: &signatures::Signature) -> Value {
+    Fields::default()
+        .set("version", s.version.map(u64::from).map(Value::Uint))
+        .set("data", s.data.as_deref().map(bytes))
+        .set(
+            "unpadded_signature_size",
+            s.unpadded_signature_size.map(u64::from).map(Value::Uint),
+        )
+        .done()
+}
+
+fn dynamic_metadata(d: &DynamicPartitionMetadata) -> Value {
+    Fields::default()
+        .repeated("groups", &d.groups, |g| {
+            Fields::default()
+                .set("name", Value::Str(g.name.clone()))
+                .set("size", g.size.map(Value::Uint))
+                .repeated("partition_names", &g.partition_names, |name| {
+                    Value::Str(String::from_utf8_lossy(name).into_owned())
+                })
+                .done()
+        })
+        .set("snapshot_enabled", d.snapshot_enabled.map(Value::Bool))
+        .set("vabc_enabled", d.vabc_enabled.map(Value::Bool))
+        .set(
+            "vabc_compression_param",
+            d.vabc_compression_param.clone().map(Value::Str),
+        )
+        .set("cow_version", d.cow_version.map(u64::from).map(Value::Uint))
+        .set(
+            "vabc_feature_set",
+            d.vabc_feature_set.as_ref().map(|f| {
+                Fields::default()
+                    .set("threaded", f.threaded.map(Value::Bool))
+                    .set("batch_writes", f.batch_writes.map(Value::Bool))
+                    .done()
+            }),
+        )
+        .set("compression_factor", d.compression_factor.map(Value::Uint))
+        .done()
+}
+
+fn apex(a: &ApexInfo) -> Value {
+    Fields::default()
+        .set("package_name", a.package_name.clone().map(Value::Str))
+        .set("version", a.version.map(Value::Int))
+        .set("is_compressed", a.is_compressed.map(Value::Bool))
+        .set("decompressed_size", a.decompressed_size.map(Value::Int))
+        .done()
+}
+
+fn bytes(b: &[u8]) -> Value {
+    Value::Str(hex::encode(b))
+}
+
+/// JSON with fields in the same order as textproto; `serde_json::Value` would sort them.
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Message(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.0.len()))?;
+                for (name, value) in &fields.0 {
+                    map.serialize_entry(name, value)?;
+                }
+                map.end()
+            }
+            Value::Repeated(items) => serializer.collect_seq(items),
+            Value::Str(s) => serializer.serialize_str(s),
+            Value::Uint(n) => serializer.serialize_u64(*n),
+            Value::Int(n) => serializer.serialize_i64(*n),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Enum(name) => serializer.serialize_str(name),
+        }
+    }
+}
+
+fn write_text(out: &mut String, fields: &Fields, depth: usize) {
+    for (name, value) in &fields.0 {
+        match value {
+            Value::Repeated(items) => {
+                for item in items {
+                    write_field(out, name, item, depth);
+                }
+            }
+            value => write_field(out, name, value, depth),
+        }
+    }
+}
+
+fn write_field(out: &mut String, name: &str, value: &Value, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let _ = match value {
+        Value::Message(fields) => {
+            let _ = writeln!(out, "{indent}{name} {{");
+            write_text(out, fields, depth + 1);
+            writeln!(out, "{indent}}}")
+        }
+        Value::Repeated(_) => unreachable!("repeated fields are flattened by write_text"),
+        Value::Str(s) => writeln!(out, "{indent}{name}: \"{}\"", escape(s)),
+        Value::Uint(n) => writeln!(out, "{indent}{name}: {n}"),
+        Value::Int(n) => writeln!(out, "{indent}{name}: {n}"),
+        Value::Bool(b) => writeln!(out, "{indent}{name}: {b}"),
+        Value::Enum(e) => writeln!(out, "{indent}{name}: {e}"),
+    };
+}
+
+/// A textproto string literal's contents: quotes and backslashes escaped, other bytes outside
+/// printable ASCII as octal escapes.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            0x20..=0x7e => out.push(b as char),
+            _ => {
+                let _ = write!(out, "\\{b:03o}");
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,176 @@
+use crate::cmd::crypto;
+use crate::cmd::exitcode::FailureKind;
+use crate::cmd::extractor::Extractor;
+use crate::cmd::fsprobe::{self, FsTraits};
+use crate::payload::Payload;
+use crate::proto::chromeos_update_engine::install_operation::Type;
+use crate::proto::chromeos_update_engine::{DeltaArchiveManifest, Extent};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the index written next to the blobs.
+const INDEX_FILE: &str = "index.json";
+
+#[derive(Serialize)]
+struct Index {
+    tool: &'static str,
+    source: String,
+    format_version: u64,
+    block_size: u32,
+    minor_version: u32,
+    /// Offset of the data section within payload.bin; `data_offset` values are relative to it.
+    data_start: u64,
+    partitions: Vec<PartitionIndex>,
+}
+
+#[derive(Serialize)]
+struct PartitionIndex {
+    name: String,
+    dir: String,
+    operations: Vec<OpIndex>,
+}
+
+#[derive(Serialize)]
+struct OpIndex {
+    index: usize,
+    r#type: &'static str,
+    /// Blob path relative to the dump directory; `None` for ops that carry no data.
+    file: Option<String>,
+    data_offset: Option<u64>,
+    data_length: u64,
+    sha256: Option<String>,
+    expected_sha256: Option<String>,
+    src_extents: Vec<[u64; 2]>,
+    dst_extents: Vec<[u64; 2]>,
+}
+
+impl Extractor<'_> {
+    /// `--dump-ops`: writes every selected operation's data blob, exactly as stored in the payload
+    /// (still compressed or diffed), to `<dir>/<partition>/<index>.<ext>`, plus an `index.json`
+    /// describing each op. Hashes are recorded, not enforced, so damaged payloads can be dumped too.
+    pub(super) fn dump_ops(
+        &self,
+        dir: &Path,
+        source: &Path,
+        payload: &Payload,
+        manifest: &DeltaArchiveManifest,
+    ) -> Result<()> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create dump directory: {dir:?}"))?;
+
+        let selected: Vec<_> = manifest
+            .partitions
+            .iter()
+            .filter(|u| self.is_selected(u))
+            .collect();
+        let dir_names = fsprobe::output_file_names(
+            selected.iter().map(|u| u.partition_name.as_slice()),
+            FsTraits::probe(dir),
+        );
+
+        let mut partitions = Vec::with_capacity(selected.len());
+        let mut blobs = 0usize;
+        let mut bytes = 0u64;
+        for update in selected {
+            let file_name = &dir_names[update.partition_name.as_slice()];
+            let sub = PathBuf::from(file_name).with_extension("");
+            let part_dir = dir.join(&sub);
+            fs::create_dir_all(&part_dir)
+                .with_context(|| format!("could not create directory: {part_dir:?}"))?;
+
+            let mut operations = Vec::with_capacity(update.operations.len());
+            for (index, op) in update.operations.iter().enumerate() {
+                let op_type = Type::try_from(op.r#type).ok();
+                let data_length = op.data_length.unwrap_or(0);
+                let data = match data_length {
+                    0 => None,
+                    _ => Some(
+                        payload
+                            .op_data(op)
+                            .map_err(|e| FailureKind::Parse.tag(e))
+                            .with_context(|| {
+                                format!("'{}' op #{index}: unable to read its data", update.name())
+                            })?,
+                    ),
+                };
+
+                let file = match data {
+                    Some(data) => {
+                        let name = format!("{index:06}.{}", blob_extension(op_type));
+                        let path = part_dir.join(&name);
+                        fs::write(&path, data)
+                            .with_context(|| format!("could not write {path:?}"))?;
+                        blobs += 1;
+                        bytes += data.len() as u64;
+                        Some(format!("{}/{name}", sub.display()))
+                    }
+                    None => None,
+                };
+
+                operations.push(OpIndex {
+                    index,
+                    r#type: op_type.map_or("UNKNOWN", |t| t.as_str_name()),
+                    file,
+                    data_offset: op.data_offset,
+                    data_length,
+                    sha256: data.map(|d| hex::encode(crypto::sha256(d))),
+                    expected_sha256: op.data_sha256_hash.as_deref().map(hex::encode),
+                    src_extents: extents(&op.src_extents),
+                    dst_extents: extents(&op.dst_extents),
+                });
+            }
+
+            partitions.push(PartitionIndex {
+                name: update.name().into_owned(),
+                dir: sub.display().to_string(),
+                operations,
+            });
+        }
+
+        let index = Index {
+            tool: concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")),
+            source: source.display().to_string(),
+            format_version: payload.version(),
+            block_size: manifest.block_size.unwrap_or(4096),
+            minor_version: manifest.minor_version.unwrap_or(0),
+            data_start: payload.data_offset() as u64,
+            partitions,
+        };
+        let index_path = dir.join(INDEX_FILE);
+        fs::write(&index_path, serde_json::to_vec_pretty(&index)?)
+            .with_context(|| format!("could not write {index_path:?}"))?;
+
+        if self.cmd.chatty() {
+            println!(
+                "Dumped {blobs} operation blobs ({}) from {} partitions to {}",
+                indicatif::HumanBytes(bytes),
+                index.partitions.len(),
+                fsprobe::display_path(dir).display()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// File extension hinting at how a blob is encoded.
+fn blob_extension(op_type: Option<Type>) -> &'static str {
+    match op_type {
+        Some(Type::ReplaceBz) => "bz2",
+        Some(Type::ReplaceXz) => "xz",
+        Some(Type::Bsdiff | Type::SourceBsdiff | Type::BrotliBsdiff) => "bsdiff",
+        Some(Type::Puffdiff) => "puffdiff",
+        Some(Type::Zucchini) => "zucchini",
+        Some(Type::Lz4diffBsdiff | Type::Lz4diffPuffdiff) => "lz4diff",
+        _ => "bin",
+    }
+}
+
+/// Extents as `[start_block, num_blocks]` pairs.
+fn extents(extents: &[Extent]) -> Vec<[u64; 2]> {
+    extents
+        .iter()
+        .map(|e| [e.start_block.unwrap_or(0), e.num_blocks.unwrap_or(0)])
+        .collect()
+}
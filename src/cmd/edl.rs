@@ -0,0 +1,259 @@
+use crate::cmd::extractor::Extractor;
+use crate::cmd::i18n::Msg;
+use crate::proto::chromeos_update_engine::PartitionUpdate;
+use anyhow::{Context, Result, ensure};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// The `--edl-xml` map file as written:
+///
+/// ```toml
+/// sector-size = 4096                   # UFS; eMMC uses 512 (default 4096)
+/// default-lun = 0                      # LUN for partitions without an entry (optional)
+/// first-sector = 6                     # where laying out starts on each LUN (default 6)
+///
+/// [partitions]
+/// boot = { lun = 4, start = 131078 }   # fixed start sector
+/// xbl = { lun = 1 }                    # placed after what is already on LUN 1
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct MapFile {
+    #[serde(default = "default_sector_size")]
+    sector_size: u64,
+    default_lun: Option<u32>,
+    #[serde(default = "default_first_sector")]
+    first_sector: u64,
+    #[serde(default)]
+    partitions: BTreeMap<String, Entry>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    lun: u32,
+    start: Option<u64>,
+}
+
+fn default_sector_size() -> u64 {
+    4096
+}
+
+/// The primary GPT takes sectors 0-5 at 4096 bytes a sector (MBR, header, 16 KiB of entries).
+fn default_first_sector() -> u64 {
+    6
+}
+
+/// Where `--edl-xml` places partitions on the device, for QFIL/edl `rawprogram<LUN>.xml` files.
+pub(crate) struct EdlMap {
+    sector_size: u64,
+    default_lun: Option<u32>,
+    first_sector: u64,
+    entries: Vec<(String, Entry)>,
+}
+
+/// Where the selected partitions go on which LUN, worked out before anything is extracted.
+pub(crate) struct EdlLayout<'m> {
+    sector_size: u64,
+    luns: BTreeMap<u32, Vec<Program<'m>>>,
+    /// Partitions the map gives no LUN.
+    skipped: Vec<Cow<'m, str>>,
+}
+
+/// One `<program>` element.
+struct Program<'m> {
+    update: &'m PartitionUpdate,
+    start: u64,
+    sectors: u64,
+    /// First sector after the partition.
+    end: u64,
+    size: u64,
+}
+
+impl EdlMap {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("could not read EDL map {path:?}"))?;
+        let map: MapFile =
+            toml::from_str(&text).with_context(|| format!("invalid EDL map {path:?}"))?;
+        ensure!(
+            map.sector_size.is_power_of_two() && map.sector_size >= 512,
+            "invalid EDL map {path:?}: sector-size must be a power of two of at least 512"
+        );
+        Ok(Self {
+            sector_size: map.sector_size,
+            default_lun: map.default_lun,
+            first_sector: map.first_sector,
+            entries: map.partitions.into_iter().collect(),
+        })
+    }
+}
+
+impl Extractor<'_> {
+    /// Places the selected partitions for `--edl-xml`. Partitions with a fixed start come first
+    /// on their LUN, the rest follow in the order given, each taking its manifest size rounded up
+    /// to whole sectors. Fails if two partitions overlap or one ends past the last sector a
+    /// 64-bit byte offset can address.
+    pub(super) fn edl_layout<'m>(
+        &self,
+        map: &EdlMap,
+        selected: &[&'m PartitionUpdate],
+    ) -> Result<EdlLayout<'m>> {
+        let mut placed: Vec<(u32, Option<u64>, &PartitionUpdate)> = Vec::new();
+        let mut skipped = Vec::new();
+        for &update in selected {
+            let entry = map
+                .entries
+                .iter()
+                .find(|(entry, _)| *entry == update.name())
+                .or_else(|| {
+                    map.entries
+                        .iter()
+                        .find(|(entry, _)| self.name_matches(update, entry))
+                })
+                .map(|(_, entry)| *entry);
+            match (entry, map.default_lun) {
+                (Some(entry), _) => placed.push((entry.lun, entry.start, update)),
+                (None, Some(lun)) => placed.push((lun, None, update)),
+                (None, None) => skipped.push(update.name()),
+            }
+        }
+
+        // Fixed partitions first, so the others go after everything already on the LUN.
+        placed.sort_by_key(|(_, start, _)| start.is_none());
+        let mut luns: BTreeMap<u32, Vec<Program>> = BTreeMap::new();
+        let mut next: HashMap<u32, u64> = HashMap::new();
+        for (lun, start, update) in placed {
+            let size = update
+                .new_partition_info
+                .as_ref()
+                .and_then(|info| info.size)
+                .unwrap_or(0);
+            let sectors = size.div_ceil(map.sector_size);
+            let free = next.entry(lun).or_insert(map.first_sector);
+            let start = start.unwrap_or(*free);
+            // Crafted starts or sizes must not wrap around; byte offsets are written too.
+            let end = start
+                .checked_add(sectors)
+                .filter(|end| end.checked_mul(map.sector_size).is_some())
+                .with_context(|| {
+                    format!(
+                        "The EDL map puts {} past the last addressable sector on LUN {lun}.",
+                        update.name()
+                    )
+                })?;
+            *free = (*free).max(end);
+            luns.entry(lun).or_default().push(Program {
+                update,
+                start,
+                sectors,
+                end,
+                size,
+            });
+        }
+
+        for (lun, programs) in &mut luns {
+            programs.sort_by_key(|program| program.start);
+            for pair in programs.windows(2) {
+                ensure!(
+                    pair[0].end <= pair[1].start,
+                    "The EDL map puts {} and {} at overlapping sectors on LUN {lun}.\n\
+                     Hint: give them separate entries, or leave out `start` to lay them out \
+                     one after another.",
+                    pair[0].update.name(),
+                    pair[1].update.name(),
+                );
+            }
+        }
+        Ok(EdlLayout {
+            sector_size: map.sector_size,
+            luns,
+            skipped,
+        })
+    }
+
+    /// `--edl-xml`: writes `rawprogram<LUN>.xml` for the extracted images, plus an empty
+    /// `patch<LUN>.xml` next to each, as QFIL expects one per LUN; the partition table itself is
+    /// left alone, so there is nothing to patch.
+    pub(super) fn write_edl_xml(
+        &self,
+        layout: &EdlLayout,
+        file_names: &HashMap<&[u8], OsString>,
+        dir: &Path,
+    ) -> Result<()> {
+        let mut written = Vec::new();
+        for (lun, programs) in &layout.luns {
+            let mut xml = String::from("<?xml version=\"1.0\" ?>\n<data>\n");
+            for program in programs {
+                let file = &file_names[program.update.partition_name.as_slice()];
+                let filename = Path::new(file)
+                    .file_name()
+                    .unwrap_or(file)
+                    .to_string_lossy();
+                let _ = writeln!(
+                    xml,
+                    "  <program SECTOR_SIZE_IN_BYTES=\"{}\" file_sector_offset=\"0\" \
+                     filename=\"{}\" label=\"{}\" num_partition_sectors=\"{}\" \
+                     partofsingleimage=\"false\" physical_partition_number=\"{lun}\" \
+                     readbackverify=\"false\" size_in_KB=\"{:.1}\" sparse=\"false\" \
+                     start_byte_hex=\"{:#x}\" start_sector=\"{}\" />",
+                    layout.sector_size,
+                    escape(&filename),
+                    escape(&program.update.name()),
+                    program.sectors,
+                    program.size as f64 / 1024.0,
+                    program.start * layout.sector_size,
+                    program.start,
+                );
+            }
+            xml.push_str("</data>\n");
+
+            for (name, contents) in [
+                (format!("rawprogram{lun}.xml"), xml),
+                (
+                    format!("patch{lun}.xml"),
+                    "<?xml version=\"1.0\" ?>\n<patches>\n</patches>\n".to_string(),
+                ),
+            ] {
+                let path = dir.join(&name);
+                fs::write(&path, contents).with_context(|| format!("could not write {path:?}"))?;
+                written.push(name);
+            }
+        }
+
+        if self.cmd.chatty() && !layout.skipped.is_empty() {
+            eprintln!(
+                "Note: the EDL map has no LUN for {}; left out of the rawprogram files.",
+                layout.skipped.join(", ")
+            );
+        }
+        if self.cmd.chatty() && !written.is_empty() {
+            println!("{}", Msg::SavedEdl.fill(&[&written.join(", ")]));
+        }
+        Ok(())
+    }
+}
+
+/// Escapes text for an XML attribute value.
+fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
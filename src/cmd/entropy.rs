@@ -0,0 +1,123 @@
+use super::simd::{CpuSimd, is_all_zero_with_simd};
+
+/// Regions an image is split into for its entropy profile.
+const REGIONS: usize = 16;
+/// Each region is estimated from up to this many evenly spaced windows of `WINDOW` bytes, so the
+/// scan reads at most a few MiB per image however large it is.
+const SAMPLES_PER_REGION: usize = 64;
+const WINDOW: usize = 4096;
+/// Smaller images (vbmeta, misc) are mostly padding by design and are not profiled.
+const MIN_IMAGE_SIZE: usize = 1024 * 1024;
+
+/// Trailing zeros covering at least this share of the image look like a truncated write.
+const ZERO_TAIL_PERCENT: usize = 50;
+/// Bits per byte below which a region is considered a constant fill.
+const UNIFORM_BELOW: f64 = 0.05;
+/// Bits per byte above which a region is indistinguishable from random data.
+const RANDOM_ABOVE: f64 = 7.99;
+
+/// Sampled Shannon entropy of an image, region by region, plus the length of its zero tail.
+pub(crate) struct Profile {
+    regions: Vec<f64>,
+    zero_tail: usize,
+    len: usize,
+}
+
+impl Profile {
+    /// Profiles `image`, or returns `None` when it is too small for the profile to mean anything.
+    pub(crate) fn scan(simd: CpuSimd, image: &[u8]) -> Option<Self> {
+        if image.len() < MIN_IMAGE_SIZE {
+            return None;
+        }
+        let region_len = image.len().div_ceil(REGIONS);
+        let regions = image
+            .chunks(region_len)
+            .map(|region| {
+                let mut counts = [0u64; 256];
+                let stride = (region.len() / SAMPLES_PER_REGION).max(WINDOW);
+                for start in (0..region.len()).step_by(stride) {
+                    for &b in &region[start..region.len().min(start + WINDOW)] {
+                        counts[b as usize] += 1;
+                    }
+                }
+                shannon(&counts)
+            })
+            .collect();
+
+        Some(Self {
+            regions,
+            zero_tail: zero_tail(simd, image),
+            len: image.len(),
+        })
+    }
+
+    /// Reasons the image looks implausible, worded for a warning line.
+    pub(crate) fn findings(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.zero_tail * 100 >= self.len * ZERO_TAIL_PERCENT && self.zero_tail < self.len {
+            out.push(format!(
+                "the last {}% ({}) is all zeros; the image may be truncated",
+                self.zero_tail * 100 / self.len,
+                indicatif::HumanBytes(self.zero_tail as u64)
+            ));
+        }
+        if self.regions.iter().all(|&e| e < UNIFORM_BELOW) && self.zero_tail < self.len {
+            out.push(format!(
+                "content is a near-constant fill (below {UNIFORM_BELOW} bits/byte everywhere)"
+            ));
+        }
+        if self.regions.iter().all(|&e| e > RANDOM_ABOVE) {
+            out.push(format!(
+                "content is indistinguishable from random data (above {RANDOM_ABOVE} bits/byte everywhere)"
+            ));
+        }
+        out
+    }
+
+    /// Per-region entropy in bits per byte, first region first, e.g. `6.1 5.8 … 0.0`.
+    pub(crate) fn render(&self) -> String {
+        self.regions
+            .iter()
+            .map(|e| format!("{e:.1}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn shannon(counts: &[u64; 256]) -> f64 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let total = total as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / total;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+/// Length of the all-zero run at the end of `image`, to `WINDOW` granularity.
+fn zero_tail(simd: CpuSimd, image: &[u8]) -> usize {
+    // Coarse pass over 1 MiB steps, then refine inside the first step that has data.
+    const STEP: usize = 1024 * 1024;
+    let mut end = image.len();
+    while end > 0 {
+        let start = end.saturating_sub(STEP);
+        if !is_all_zero_with_simd(simd, &image[start..end]) {
+            break;
+        }
+        end = start;
+    }
+    while end > 0 {
+        let start = end.saturating_sub(WINDOW);
+        if !is_all_zero_with_simd(simd, &image[start..end]) {
+            break;
+        }
+        end = start;
+    }
+    image.len() - end
+}
@@ -0,0 +1,81 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Why a run failed, as the exit code scripts can branch on. The codes are listed in the README;
+/// don't renumber them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// Anything not covered below.
+    Other,
+    /// The payload, zip or manifest is malformed or truncated.
+    Parse,
+    /// The command line asks for something that can't be done, e.g. `--stdout` with two
+    /// partitions. Exits with 2, as clap does for arguments it rejects.
+    Usage,
+    /// An image, operation or signature did not match what the payload promises.
+    Verification,
+    /// The payload needs an operation or format otaripper does not implement, e.g. a patch.
+    Unsupported,
+    /// Reading the input or writing the images failed: a missing file, permissions, a full disk.
+    Io,
+    /// Stopped with Ctrl+C or by the caller.
+    Interrupted,
+}
+
+impl FailureKind {
+    pub fn code(self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::Parse | Self::Usage => 2,
+            Self::Verification => 3,
+            Self::Unsupported => 4,
+            Self::Io => 5,
+            Self::Interrupted => 130,
+        }
+    }
+
+    /// The kind of `err`: the outermost one it was `tag`ged with, else what its
+    /// causes say about it.
+    pub fn of(err: &anyhow::Error) -> Self {
+        let mut kind = Self::Other;
+        for cause in err.chain() {
+            if let Some(tagged) = cause.downcast_ref::<Tagged>() {
+                return tagged.kind;
+            }
+            if kind == Self::Other {
+                if cause.is::<io::Error>() {
+                    kind = Self::Io;
+                } else if cause.is::<prost::DecodeError>() {
+                    kind = Self::Parse;
+                }
+            }
+        }
+        kind
+    }
+
+    /// Marks `err` as this kind of failure; its message and causes read as before.
+    pub(crate) fn tag(self, err: anyhow::Error) -> anyhow::Error {
+        anyhow::Error::new(Tagged { kind: self, err })
+    }
+}
+
+/// An error [`FailureKind::tag`] has classified. Displays as the error it wraps, whose causes
+/// become its own.
+#[derive(Debug)]
+struct Tagged {
+    kind: FailureKind,
+    err: anyhow::Error,
+}
+
+impl fmt::Display for Tagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.err)
+    }
+}
+
+impl Error for Tagged {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.err.source()
+    }
+}
@@ -1,34 +1,68 @@
-use crate::payload::Payload;
+use crate::payload::{self, Payload, PayloadHeader};
 use crate::proto::chromeos_update_engine::install_operation::Type;
 use crate::proto::chromeos_update_engine::{
-    DeltaArchiveManifest, InstallOperation, PartitionUpdate,
+    DeltaArchiveManifest, InstallOperation, PartitionInfo, PartitionUpdate,
 };
-use anyhow::{Context, Result, bail, ensure};
-
-use crate::cmd::SubCmd;
+use crate::proto::summary::ManifestSummary;
+use anyhow::{Context, Result, anyhow, bail, ensure};
+
+use crate::cmd::alias::Aliases;
+use crate::cmd::audit::{AuditLog, OpOutput};
+use crate::cmd::avb::Verity;
+use crate::cmd::awake::KeepAwake;
+use crate::cmd::chromeos;
+use crate::cmd::cleanup::CleanupGuard;
+use crate::cmd::config::Config;
+use crate::cmd::crypto;
+use crate::cmd::edl::EdlMap;
+use crate::cmd::entropy;
+use crate::cmd::exitcode::FailureKind;
+use crate::cmd::fastcopy;
+use crate::cmd::flush::Flusher;
+use crate::cmd::fsprobe::{self, Backing, FsTraits};
+use crate::cmd::hashalgo::HashAlgo;
+use crate::cmd::holes;
+use crate::cmd::i18n::Msg;
+use crate::cmd::inflate::PayloadBytes;
+use crate::cmd::lineprogress::{self, LineProgress};
+use crate::cmd::logging;
+use crate::cmd::magic;
+use crate::cmd::membudget::{MemoryBudget, Shares};
+use crate::cmd::outmap::OutputMap;
+use crate::cmd::pagecache;
+use crate::cmd::readahead::Readahead;
+use crate::cmd::remote;
+use crate::cmd::resume::Journal;
+use crate::cmd::sidecar;
+use crate::cmd::sigverify::{self, PublicKey};
+use crate::cmd::sink::{self, IoMode};
+use crate::cmd::source::SourceImage;
+use crate::cmd::suggest;
+use crate::cmd::writebehind::{self, Target, WriteBehind};
+use crate::cmd::{IoModeChoice, ProgressMode, ProgressOrder, Slot, SubCmd};
 use bzip2::read::BzDecoder;
 use chrono::Local;
 
 use console::Style;
 use crossbeam_channel::unbounded;
-use ctrlc;
-use indicatif::{MultiProgress, ProgressBar, ProgressFinish, ProgressStyle};
-use memmap2::{Mmap, MmapMut};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressFinish, ProgressStyle};
+use memmap2::{Mmap, MmapMut, MmapOptions};
 use prost::Message;
+use rayon::prelude::*;
 use rayon::{ThreadPool, ThreadPoolBuilder};
-use ring::digest::{SHA256, digest};
 use std::cell::RefCell;
 use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::{OsStr, OsString};
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, Read, Seek, Write};
 use std::ops::Deref;
 use std::path::Component;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
-use std::{env, slice};
-use sysinfo::{MemoryRefreshKind, RefreshKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
 use tempfile::NamedTempFile;
 use zip::ZipArchive;
 
@@ -38,6 +72,13 @@ use super::simd::*;
 const MIN_BLOCK_SIZE: usize = 512;
 const MAX_BLOCK_SIZE: usize = 16 * 1024 * 1024;
 
+/// REPLACE_XZ ops that decode to at least this much use liblzma's multithreaded decoder.
+/// Smaller ones rarely span more than one xz block, the unit it parallelizes over.
+const XZ_MT_MIN_SIZE: usize = 16 * 1024 * 1024;
+/// Memory the multithreaded xz decoder may use before it cuts back on threads. It never
+/// refuses to decode because of it.
+const XZ_MT_MEMLIMIT: u64 = 1024 * 1024 * 1024;
+
 // ===== Thread-local Buffers =====
 thread_local! {
     /// 1MB buffer utilized by `run_op_replace` to amortize Rayon allocation costs
@@ -46,24 +87,19 @@ thread_local! {
 }
 
 pub enum PayloadSource {
-    Mapped(Mmap),
+    /// Mapping of `File` starting at the given byte offset (raw payload.bin, or a payload.bin
+    /// stored uncompressed inside a zip).
+    Mapped(Mmap, File, u64),
+    /// A deflated payload.bin inflated into memory.
     Owned(Vec<u8>),
-    Temp(Mmap, NamedTempFile),
+    /// A deflated payload.bin inflated into a temp file and mapped, for payloads too large for
+    /// RAM or `--low-memory`. The file is deleted when this is dropped.
+    TempFile(Mmap, NamedTempFile),
 }
 
-#[repr(transparent)]
-#[derive(Clone, Copy)]
-struct PartitionPtr(*mut u8);
-
-// SAFETY:
-// - Pointer comes from Arc<MmapMut>
-// - validate_non_overlapping_extents guarantees no aliasing
-// - rayon::scope prevents threads from outliving the mmap
-unsafe impl Send for PartitionPtr {}
-unsafe impl Sync for PartitionPtr {}
-
 #[derive(Clone)]
 struct Stat {
+    order: usize,
     name: String,
     bytes: u64,
     ms: u128,
@@ -74,38 +110,198 @@ struct Stat {
 struct HashRec {
     order: usize,
     name: String,
-    hex: String,
+    /// Hex digests, one per --hash-algo.
+    digests: Vec<(HashAlgo, String)>,
+    /// Where the image was written, for --hash-file.
+    file: PathBuf,
+}
+
+// Magic and entropy warnings from --sanity, printed after extraction like hashes
+struct SanityRec {
+    order: usize,
+    name: String,
+    findings: Vec<String>,
+    profile: Option<String>,
+}
+
+// AVB verification data from --verity, listed after extraction
+struct VerityRec {
+    order: usize,
+    name: String,
+    verity: Verity,
 }
 
-// Shared per-partition worker state to reduce Arc clones per operation
-struct WorkerContext {
-    partition_file: Arc<MmapMut>,
+// Per-partition state shared by the workers writing its operations
+struct WorkerContext<'p> {
+    out_file: File,
+    /// Payload's backing file and the file offset of `Payload::data`, when it has one.
+    input_file: Option<(&'p File, u64)>,
     part_name: Arc<str>,
     cancellation_token: Arc<AtomicBool>,
+    interrupt: &'p AtomicBool,
     stats_sender: Option<crossbeam_channel::Sender<Stat>>,
     hash_sender: Option<crossbeam_channel::Sender<HashRec>>,
+    sanity_sender: Option<crossbeam_channel::Sender<SanityRec>>,
+    verity_sender: Option<crossbeam_channel::Sender<VerityRec>>,
     first_error: Arc<Mutex<Option<anyhow::Error>>>,
-    remaining_ops: Arc<AtomicUsize>,
     partition_len: usize,
     zero_ops_are_noops: bool,
+    /// The output filesystem has holes: ZERO/DISCARD regions are deallocated, not just skipped.
+    punch_holes: bool,
+    /// `--trust-op-hashes` applies: the operation hashes already vouch for every byte.
+    skip_image_hash: bool,
+    flusher: Option<Flusher>,
+    /// In [`IoMode::Pwrite`] mode, the queue decoded operations are handed to and this image's
+    /// place in it.
+    write_behind: Option<(&'p WriteBehind, Arc<Target>)>,
+    audit: Option<&'p AuditLog>,
+    readahead: Option<&'p Readahead>,
+    /// `--max-memory`: what the operations running at once may allocate.
+    memory: Option<&'p MemoryBudget>,
+    /// `--source-dir`: the base build's image SOURCE_COPY operations read from.
+    source: Option<&'p [u8]>,
+    out_path: PathBuf,
+    /// `--resume`: where the image is recorded once it is complete.
+    journal: Option<&'p Journal>,
+}
+
+impl WorkerContext<'_> {
+    /// Extraction failed elsewhere or the caller asked it to stop.
+    fn is_cancelled(&self) -> bool {
+        self.cancellation_token.load(Ordering::Acquire) || self.interrupt.load(Ordering::Acquire)
+    }
+
+    /// Cancels the extraction, keeping `e` if it is the first error.
+    fn fail(&self, e: anyhow::Error) {
+        self.cancellation_token.store(true, Ordering::Release);
+        if let Ok(mut slot) = self.first_error.lock()
+            && slot.is_none()
+        {
+            *slot = Some(e.context(format!("Error in partition '{}'", self.part_name)));
+        }
+    }
+
+    /// `op` has written its extents; `--flush-every` may start writing them back.
+    fn written(&self, op: &InstallOperation, block_size: usize) {
+        if let Some(flusher) = &self.flusher {
+            let ranges = op.dst_extents.iter().map(|e| {
+                let start = e.start_block.unwrap_or(0) as usize;
+                let num_blocks = e.num_blocks.unwrap_or(0) as usize;
+                (start * block_size, num_blocks * block_size)
+            });
+            flusher.written(&self.out_file, ranges);
+        }
+    }
 }
 
 impl Deref for PayloadSource {
     type Target = [u8];
     fn deref(&self) -> &Self::Target {
         match self {
-            PayloadSource::Mapped(mmap) => mmap,
+            PayloadSource::Mapped(mmap, _, _) => mmap,
             PayloadSource::Owned(vec) => vec,
-            PayloadSource::Temp(mmap, _) => mmap,
+            PayloadSource::TempFile(mmap, _) => mmap,
         }
     }
 }
 
+impl PayloadSource {
+    /// The file backing this source and the file offset of its first byte.
+    fn backing_file(&self) -> Option<(&File, u64)> {
+        match self {
+            PayloadSource::Mapped(_, file, offset) => Some((file, *offset)),
+            PayloadSource::Owned(_) => None,
+            PayloadSource::TempFile(_, temp) => Some((temp.as_file(), 0)),
+        }
+    }
+}
+
+/// Receives overall progress, as bytes written so far and in total, for the library's caller.
+pub(crate) type ProgressHook = Arc<dyn Fn(u64, u64) + Send + Sync>;
+
+/// How often a [`ProgressHook`] is called while extraction runs.
+const PROGRESS_HOOK_INTERVAL: Duration = Duration::from_millis(250);
+
 pub(super) struct Extractor<'a> {
     pub cmd: &'a super::Cmd,
+    /// Settings from the command line, environment and config file.
+    config: Config,
+    /// `--partitions` with aliases expanded, once the manifest has been read.
+    selection: OnceLock<Vec<String>>,
+    /// `--skip-partitions`, likewise.
+    skipped: OnceLock<Vec<String>>,
+    /// Partitions an earlier `--resume` run already extracted.
+    finished: OnceLock<HashSet<String>>,
+    /// Partitions whose image is the same in the `--changed-since` payload.
+    unchanged: OnceLock<HashSet<String>>,
+    /// Set by the caller to stop extraction early.
+    interrupt: Arc<AtomicBool>,
+    /// Errors workers reported while extracting, kept for the debug bundle.
+    failures: Mutex<Vec<String>>,
+    /// What kind of failure the first of them was, for the exit code.
+    failure_kind: OnceLock<FailureKind>,
+    /// Called with the progress summed over `bars`, every bar made when one is set.
+    progress_hook: Option<ProgressHook>,
+    bars: Mutex<Vec<ProgressBar>>,
 }
 
 impl<'a> Extractor<'a> {
+    pub(super) fn new(cmd: &'a super::Cmd, config: Config) -> Self {
+        Self {
+            cmd,
+            config,
+            selection: OnceLock::new(),
+            skipped: OnceLock::new(),
+            finished: OnceLock::new(),
+            unchanged: OnceLock::new(),
+            interrupt: Arc::default(),
+            failures: Mutex::default(),
+            failure_kind: OnceLock::new(),
+            progress_hook: None,
+            bars: Mutex::default(),
+        }
+    }
+
+    /// Reports overall progress to `hook` while running, whether or not bars are drawn.
+    pub(super) fn reporting(mut self, hook: Option<ProgressHook>) -> Self {
+        self.progress_hook = hook;
+        self
+    }
+
+    /// Stops extraction between operations once `interrupt` is set, removing partial files.
+    pub(super) fn interruptible(mut self, interrupt: Arc<AtomicBool>) -> Self {
+        self.interrupt = interrupt;
+        self
+    }
+
+    pub(super) fn interrupted(&self) -> bool {
+        self.interrupt.load(Ordering::Acquire)
+    }
+
+    /// Stops extraction over a finished image that can't be kept, reporting `message` now.
+    fn critical(&self, ctx: &WorkerContext, kind: FailureKind, message: String) {
+        ctx.cancellation_token.store(true, Ordering::Release);
+        eprintln!("\nCritical error: {message}");
+        log::error!("{message}");
+        self.record_failure(kind, message);
+    }
+
+    fn record_failure(&self, kind: FailureKind, message: String) {
+        let _ = self.failure_kind.set(kind);
+        self.failures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(message);
+    }
+
+    /// What [`critical`](Self::critical) and failed operations reported, in order.
+    pub(super) fn failures(&self) -> Vec<String> {
+        self.failures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
     fn run_clean(&self, base_dir: Option<&Path>) -> Result<()> {
         let base_dir = match base_dir {
             Some(p) => p.to_path_buf(),
@@ -118,7 +314,7 @@ impl<'a> Extractor<'a> {
             "Refusing to clean filesystem root."
         );
 
-        println!("Scanning for extracted folders in:");
+        println!("{}", Msg::ScanningFolders.text());
         println!("  {}", base_dir.display());
 
         let mut targets = Vec::<PathBuf>::new();
@@ -142,32 +338,32 @@ impl<'a> Extractor<'a> {
         }
 
         if targets.is_empty() {
-            println!("No extracted folders found.");
+            println!("{}", Msg::NoExtractedFolders.text());
             return Ok(());
         }
 
-        println!("\nThe following folders will be removed:");
+        println!("\n{}", Msg::FoldersToRemove.text());
         for dir in &targets {
             println!("  {}", dir.display());
         }
 
-        println!("\nProceed? [Y/n]");
+        println!("\n{}", Msg::ProceedPrompt.text());
         let mut input = String::new();
         std::io::stdin().read_line(&mut input)?;
         let input = input.trim().to_lowercase();
 
         if input == "n" {
-            println!("Aborted.");
+            println!("{}", Msg::Aborted.text());
             return Ok(());
         }
 
         for dir in targets {
             fs::remove_dir_all(&dir)
                 .with_context(|| format!("failed to remove {}", dir.display()))?;
-            println!("Removed {}", dir.display());
+            println!("{}", Msg::Removed.fill(&[&dir.display()]));
         }
 
-        println!("\nCleanup complete.");
+        println!("\n{}", Msg::CleanupComplete.text());
         Ok(())
     }
     // High-level extraction flow:
@@ -177,6 +373,52 @@ impl<'a> Extractor<'a> {
     // 4. Extract partitions in size-descending order
     // 5. Verify, sanity-check, and finalize output
     pub fn run(&self) -> Result<()> {
+        let result = match &self.progress_hook {
+            Some(hook) => std::thread::scope(|threads| {
+                let (stop, stopped) = crossbeam_channel::bounded::<()>(0);
+                threads.spawn(move || {
+                    while stopped
+                        .recv_timeout(PROGRESS_HOOK_INTERVAL)
+                        .is_err_and(|e| e.is_timeout())
+                    {
+                        self.report_progress(hook);
+                    }
+                    self.report_progress(hook);
+                });
+                let result = self.run_command();
+                drop(stop);
+                result
+            }),
+            None => self.run_command(),
+        };
+        if self.cmd.subcmd.is_none() {
+            self.write_debug_bundle(result.as_ref().err());
+        }
+        match &result {
+            Ok(()) => log::info!("finished"),
+            Err(e) => log::error!("{e:#}"),
+        }
+        log::logger().flush();
+        if self.cmd.progress == ProgressMode::Json
+            && let Err(e) = &result
+        {
+            lineprogress::event(
+                serde_json::json!({ "event": "error", "message": format!("{e:#}") }),
+            );
+        }
+        result
+    }
+
+    fn run_command(&self) -> Result<()> {
+        crypto::init()?;
+        logging::init(self.cmd.verbose, self.cmd.log_file.as_deref())?;
+
+        // Plain progress goes to logs and screen readers: no escape sequences anywhere.
+        if self.cmd.progress != ProgressMode::Bars {
+            console::set_colors_enabled(false);
+            console::set_colors_enabled_stderr(false);
+        }
+
         // Handle subcommands early (before extraction logic)
         if let Some(subcmd) = &self.cmd.subcmd {
             match subcmd {
@@ -186,12 +428,31 @@ impl<'a> Extractor<'a> {
                 SubCmd::Arbscan { no_json, image } => {
                     return crate::cmd::arbscan::run(*no_json, image);
                 }
+                SubCmd::Info { payload } => {
+                    return self.run_info(payload);
+                }
+                SubCmd::DumpManifest { format, payload } => {
+                    return self.run_dump_manifest(payload, *format);
+                }
+                SubCmd::Verify { payload } => {
+                    return self.run_verify(payload);
+                }
+                SubCmd::Repack {
+                    output,
+                    level,
+                    images,
+                } => {
+                    return self.run_repack(images, output, *level);
+                }
             }
         }
 
         // Initialize SIMD detection early - this ensures SIMD capabilities are
         // detected and available for all operations throughout the extraction
         let simd = CpuSimd::get();
+        if self.config.debug_cpu {
+            CpuSimd::print_detection();
+        }
         if let Some(t) = self.cmd.threads {
             match t {
                 0 => { /* Use default - valid */ }
@@ -233,95 +494,137 @@ impl<'a> Extractor<'a> {
             ))?
             .clone();
 
-        // Proceed with the rest of the method using payload_path
-        let payload = self.open_payload_file(&payload_path)?;
-        // Because PayloadSource implements Deref, this call works seamlessly.
-        let payload = &Payload::parse(&payload)?;
-
-        let mut manifest =
-            DeltaArchiveManifest::decode(payload.manifest).context("unable to parse manifest")?;
-
-        // 1. Identify if the payload contains any incremental operations
-        let has_incremental_ops = manifest
-            .partitions
-            .iter()
-            .any(Self::is_incremental_partition);
-
-        let block_size = manifest.block_size.context(
-            "The update file is missing critical metadata (block_size). It is likely corrupted.",
-        )? as usize;
-        ensure!(
-            (MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size),
-            "The update file has an invalid internal structure (block size {} is unsupported). It may be corrupted.",
-            block_size,
-        );
-        ensure!(
-            block_size.is_power_of_two(),
-            "The update file is malformed: block size {} is not a power of two.",
-            block_size,
-        );
+        // A URL stands for a sparse local copy holding just what this run reads.
+        let remote = match payload_path.to_str() {
+            Some(url) if remote::is_url(&payload_path) => {
+                Some(self.fetch_remote(url, self.cmd.list)?)
+            }
+            _ => None,
+        };
+        let payload_path = remote
+            .as_ref()
+            .map_or(payload_path, |local| local.path().to_path_buf());
 
-        // 2. LIST MODE: Shows partition details and identifies Incremental vs Full updates.
+        // LIST MODE: only the header and manifest are needed, so don't map or unpack the payload.
         if self.cmd.list {
-            manifest
-                .partitions
-                .sort_unstable_by(|p1, p2| p1.partition_name.cmp(&p2.partition_name));
-
-            println!("{:<20} {:<16} {:<10}", "Partition", "Size", "Type");
-            println!("{:-<46}", "");
-
-            let partition_count = manifest.partitions.len();
-
-            for partition in &manifest.partitions {
-                // Distinguish between explicit 0 size and missing metadata
-                let size_str = if let Some(size) = partition
-                    .new_partition_info
-                    .as_ref()
-                    .and_then(|info| info.size)
-                {
-                    indicatif::HumanBytes(size).to_string()
-                } else {
-                    "???".to_string()
-                };
-
-                // Check for operations that rely on source data (meaning it's a patch/delta)
-                let is_patch = Self::is_incremental_partition(partition);
-
-                let type_label = if is_patch {
-                    Style::new().bold().red().apply_to("Incremental")
-                } else {
-                    Style::new().bold().green().apply_to("Full")
-                };
+            let header = Self::read_payload_header(&payload_path)?;
+            let mut manifest =
+                ManifestSummary::decode(header.manifest()).context("unable to parse manifest")?;
+            chromeos::upgrade_summary(&mut manifest);
+            return Self::list_partitions(manifest);
+        }
 
-                let name_style = Style::new().bold().green();
-                println!(
-                    "{:<20} {:<16} {:<10}",
-                    name_style.apply_to(&partition.partition_name),
-                    size_str,
-                    type_label
-                );
-            }
+        // Proceed with the rest of the method using payload_path. A deflated payload.bin keeps
+        // inflating while its manifest, streamed from the start of the entry, gets the output
+        // files ready; steps that need the payload's data wait for it.
+        let payload_bytes = self.start_payload(&payload_path)?;
+        let mut manifest = match payload_bytes.get() {
+            Some(source) => Self::decode_manifest(
+                Payload::parse(source)
+                    .map_err(|e| FailureKind::Parse.tag(e))?
+                    .manifest(),
+            )?,
+            None => Self::decode_manifest(Self::read_payload_header(&payload_path)?.manifest())?,
+        };
 
-            // Simplified footer focusing only on the partition count
-            println!("{:-<46}", "");
-            println!(
-                "Total Partitions: {}",
-                Style::new().bold().cyan().apply_to(partition_count)
-            );
+        let block_size = Self::block_size(&manifest).map_err(|e| FailureKind::Parse.tag(e))?;
+        log::info!(
+            "payload {}: {} partitions, block size {block_size}, minor version {}",
+            payload_path.display(),
+            manifest.partitions.len(),
+            manifest.minor_version.unwrap_or(0)
+        );
 
+        if !self.select_partitions(&manifest)? {
             return Ok(());
         }
 
-        // 3. EXTRACTION GUARD: Bail if incremental
-        if has_incremental_ops {
+        // Raw op blobs are dumped as stored, so incremental payloads can be dumped too.
+        if let Some(dir) = &self.cmd.dump_ops {
+            let (_, payload) = self.parse_payload(&payload_bytes, &manifest)?;
+            return self.dump_ops(dir, &payload_path, &payload, &manifest);
+        }
+
+        // 3. EXTRACTION GUARD: Bail if a selected partition is incremental, before anything
+        // is created. Partitions stored in full can still be extracted from a delta payload, and
+        // with --source-dir so can those that only copy blocks from the base build.
+        let (patched, full): (Vec<_>, Vec<_>) = manifest
+            .partitions
+            .iter()
+            .filter(|u| self.is_selected(u))
+            .partition(|u| Self::is_incremental_partition(u));
+        let (copied, patched): (Vec<_>, Vec<_>) = patched
+            .into_iter()
+            .partition(|u| self.cmd.source_dir.is_some() && Self::is_source_copy_only(u));
+        if !patched.is_empty() {
             let bold_cyan = Style::new().bold().cyan();
             let bold_yellow = Style::new().bold().yellow();
 
-            bail!(
+            let names = |parts: &[&PartitionUpdate]| {
+                parts.iter().map(|u| u.name()).collect::<Vec<_>>().join(",")
+            };
+            let mut details = format!(
+                "{} {}\n",
+                Style::new().bold().apply_to("Patched partitions:"),
+                names(&patched).replace(',', ", ")
+            );
+            let diff_ops = Self::diff_op_names(&patched);
+            if !diff_ops.is_empty() {
+                details += &format!(
+                    "{} {}\n",
+                    Style::new().bold().apply_to("Unsupported operations:"),
+                    diff_ops.join(", ")
+                );
+            }
+            let metadata = sidecar::package_metadata(&payload_path)?;
+            if let Some(base) = metadata.get("pre-build") {
+                details += &format!(
+                    "{} {}\n",
+                    Style::new().bold().apply_to("Base build needed:"),
+                    bold_yellow.apply_to(base)
+                );
+            } else if let Some(base) = metadata.get("pre-build-incremental") {
+                details += &format!(
+                    "{} incremental {}\n",
+                    Style::new().bold().apply_to("Base build needed:"),
+                    bold_yellow.apply_to(base)
+                );
+            }
+            if let Some(target) = metadata.get("post-build") {
+                details += &format!("{} {target}\n", Style::new().bold().apply_to("Updates to:"));
+            }
+            if !full.is_empty() {
+                details += &format!(
+                    "\n{} These partitions are stored in full and can still be extracted:\n  \
+                     otaripper -p {} {}\n",
+                    Style::new().bold().green().apply_to("📌 Tip:"),
+                    names(&full),
+                    fsprobe::display_path(&payload_path).display()
+                );
+            }
+            let copy_only: Vec<_> = patched
+                .iter()
+                .copied()
+                .filter(|u| Self::is_source_copy_only(u))
+                .collect();
+            if self.cmd.source_dir.is_none() && !copy_only.is_empty() {
+                details += &format!(
+                    "\n{} These partitions only copy blocks from the base build and can be \
+                     rebuilt from its images:\n  otaripper -p {} --source-dir <DIR> {}\n",
+                    Style::new().bold().green().apply_to("📌 Tip:"),
+                    names(&copy_only),
+                    fsprobe::display_path(&payload_path).display()
+                );
+            }
+
+            return Err(FailureKind::Unsupported.tag(anyhow!(
                 "\n{header}\n\n\
                 This file is an {incremental} update (patch). It only contains the {changes} \
                 made between two versions, not the full system images.\n\n\
-                {stop} {tool_name} only supports {full_ota} images.\n\n\
+                {details}\n\
+                {stop} {tool_name} only supports {full_ota} images; applying a patch needs the \
+                base build's partition images, and of the patch operations it can only apply \
+                SOURCE_COPY (with --source-dir).\n\n\
                 {tip} Look for a larger zip (usually 2GB+) often labeled {factory} or {sideload} on OEM websites.\n",
                 header = Style::new()
                     .bold()
@@ -335,19 +638,150 @@ impl<'a> Extractor<'a> {
                 tip = Style::new().bold().green().apply_to("📌 Tip:"),
                 factory = bold_yellow.apply_to("\"Full OTA\""),
                 sideload = bold_yellow.apply_to("\"Recovery Flashable\"")
-            );
+            )));
         }
 
+        let sources = match &self.cmd.source_dir {
+            Some(dir) => self.map_source_images(dir, &copied)?,
+            None => HashMap::new(),
+        };
+
         // 4. Continue with extraction setup...
-        for partition in &self.cmd.partitions {
-            if !manifest
-                .partitions
-                .iter()
-                .any(|p| &p.partition_name == partition)
+        // Signatures are checked before anything is written. With --verify-chain, operation and
+        // partition hashes are then required (as in --strict) and verified during extraction.
+        // A payload that doesn't match --pubkey is only refused in strict mode. The chain needs
+        // keys from outside the zip: its own certificate is replaced by whoever re-signs it.
+        let strict = self.cmd.strict || self.cmd.verify_chain;
+        let keyring = match (&self.cmd.keyring, &self.cmd.pubkey) {
+            (Some(path), _) => {
+                Some(sigverify::load_keyring(path).context("unable to load keyring")?)
+            }
+            (None, Some(path)) => {
+                Some(sigverify::load_pubkey(path).context("unable to load --pubkey")?)
+            }
+            (None, None) => None,
+        };
+        if self.cmd.verify_chain && keyring.is_none() {
+            return Err(FailureKind::Usage.tag(anyhow!(
+                "--verify-chain needs keys you trust: pass --keyring or --pubkey.\n\
+                 The certificate inside the zip can't vouch for the zip it comes with; anyone \
+                 who re-signs the payload replaces it too."
+            )));
+        }
+        let signer = if let Some(keys) = keyring {
+            let anchor = if self.cmd.pubkey.is_some() {
+                "the key given with --pubkey"
+            } else {
+                "any key in the keyring"
+            };
+            let (payload_source, payload) = self.parse_payload(&payload_bytes, &manifest)?;
+            let verified = if self.cmd.verify_chain {
+                self.verify_signature_chain(&keys, anchor, payload_source, &payload, &manifest)
+            } else {
+                self.verify_payload_signature(
+                    &keys,
+                    anchor,
+                    payload_source,
+                    &payload,
+                    &manifest,
+                    "Payload signature verification failed",
+                )
+            };
+            let signer = match verified {
+                Err(e) if self.cmd.pubkey.is_some() && !strict => {
+                    eprintln!("Warning: {e:#}. Extracting anyway; use --strict to refuse.");
+                    None
+                }
+                verified => Some(verified.map_err(|e| FailureKind::Verification.tag(e))?),
+            };
+            if let Some(signer) = &signer
+                && self.cmd.chatty()
             {
-                bail!("partition \"{}\" not found in manifest", partition);
+                let verified = format!(
+                    "Payload signature verified with key '{}' (SHA-256 {}).",
+                    signer.label,
+                    signer.fingerprint()
+                );
+                // Standard output carries the image with --stdout.
+                if self.cmd.stdout {
+                    eprintln!("{verified}");
+                } else {
+                    println!("{verified}");
+                }
+            }
+            signer
+        } else {
+            None
+        };
+
+        // --verify-chain already checked the metadata signature along with the payload's.
+        // Without --cert, the zip's own certificate only shows the zip is self-consistent.
+        if self.cmd.verify_metadata && !self.cmd.verify_chain {
+            let failed = "Metadata signature verification failed";
+            let (keys, anchor) = match &self.cmd.cert {
+                Some(path) => (
+                    sigverify::load_pubkey(path).context("unable to load --cert")?,
+                    "the certificate given with --cert",
+                ),
+                None => (
+                    self.package_keys(&payload_path, failed, "--cert")?,
+                    "the certificate shipped in the zip",
+                ),
+            };
+            let (_, payload) = self.parse_payload(&payload_bytes, &manifest)?;
+            let signer = self
+                .verify_metadata_signature(&keys, anchor, &payload, failed)
+                .map_err(|e| FailureKind::Verification.tag(e))?;
+            if self.cmd.chatty() {
+                let verified = if self.cmd.cert.is_some() {
+                    format!(
+                        "Metadata signature verified with key '{}' (SHA-256 {}).",
+                        signer.label,
+                        signer.fingerprint()
+                    )
+                } else {
+                    format!(
+                        "Metadata signature self-consistent: signed by the certificate shipped in \
+                         the zip, '{}' (SHA-256 {}). Pass --cert with a key you trust to verify it.",
+                        signer.label,
+                        signer.fingerprint()
+                    )
+                };
+                if self.cmd.stdout {
+                    eprintln!("{verified}");
+                } else {
+                    println!("{verified}");
+                }
             }
         }
+
+        // Keep laptops from suspending halfway through; released when run() returns.
+        let _keep_awake = (!self.cmd.allow_sleep).then(KeepAwake::acquire);
+
+        let audit = match &self.cmd.audit {
+            Some(path) => {
+                let (payload_source, payload) = self.parse_payload(&payload_bytes, &manifest)?;
+                let log = AuditLog::create(path, &payload)?;
+                log.payload(
+                    &payload_path,
+                    payload_source,
+                    &payload,
+                    &manifest,
+                    signer.as_ref().map(PublicKey::fingerprint),
+                )?;
+                Some(log)
+            }
+            None => None,
+        };
+
+        // The payload's own order, for `--progress-order selection`.
+        let payload_order: HashMap<Vec<u8>, usize> = manifest
+            .partitions
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.partition_name.clone(), i))
+            .collect();
+
         // Sort partitions by size (descending).
         // Processing larger partitions first improves threadpool utilization and
         // ensures the most time-consuming progress bars start immediately.
@@ -377,28 +811,33 @@ impl<'a> Extractor<'a> {
         };
 
         // Channel for hash records
-        let (hash_sender, hash_receiver) = if self.cmd.print_hash {
+        let (hash_sender, hash_receiver) = if self.cmd.print_hash || self.cmd.hash_file {
             let (s, r) = unbounded::<HashRec>();
             (Some(s), Some(r))
         } else {
             (None, None)
         };
 
-        // Count selected partitions for progress redraw heuristic
-        let selected_count: usize = manifest
-            .partitions
-            .iter()
-            .filter(|u| {
-                self.cmd.partitions.is_empty() || self.cmd.partitions.contains(&u.partition_name)
-            })
-            .count();
+        // Channel for entropy warnings
+        let (sanity_sender, sanity_receiver) = if self.cmd.sanity {
+            let (s, r) = unbounded::<SanityRec>();
+            (Some(s), Some(r))
+        } else {
+            (None, None)
+        };
+
+        // Channel for AVB footers
+        let (verity_sender, verity_receiver) = if self.cmd.verity {
+            let (s, r) = unbounded::<VerityRec>();
+            (Some(s), Some(r))
+        } else {
+            (None, None)
+        };
 
         // Strict mode sanity: ensure hashes exist when required
-        if self.cmd.strict {
+        if strict {
             for update in &manifest.partitions {
-                if self.cmd.partitions.is_empty()
-                    || self.cmd.partitions.contains(&update.partition_name)
-                {
+                if self.is_selected(update) {
                     // Partition-level hash must exist
                     ensure!(
                         update
@@ -407,7 +846,7 @@ impl<'a> Extractor<'a> {
                             .and_then(|i| i.hash.as_ref())
                             .is_some(),
                         "strict mode: missing partition hash for '{}'",
-                        update.partition_name
+                        update.name()
                     );
                     // Operation-level hashes must exist when data is present
                     for op in &update.operations {
@@ -415,7 +854,7 @@ impl<'a> Extractor<'a> {
                             ensure!(
                                 op.data_sha256_hash.is_some(),
                                 "strict mode: missing data_sha256_hash for an operation in '{}'",
-                                update.partition_name
+                                update.name()
                             );
                         }
                     }
@@ -423,365 +862,1014 @@ impl<'a> Extractor<'a> {
             }
         }
 
-        // Create/ensure output directory and detect if it was newly created
-        let (partition_dir, created_new_dir) = self.create_partition_dir()?;
-
-        let cleanup_state = Arc::new(Mutex::new((
-            Vec::<PathBuf>::new(),
-            partition_dir.to_path_buf(),
-            created_new_dir,
-        )));
-
-        let cancellation_token = Arc::new(AtomicBool::new(false));
-
-        // Channel to store the first error message
-        let first_error: Arc<Mutex<Option<anyhow::Error>>> = Arc::new(Mutex::new(None));
-
-        let cleanup_state_ctrlc = Arc::clone(&cleanup_state);
-        let cancellation_token_ctrlc = Arc::clone(&cancellation_token);
-
-        ctrlc::set_handler(move || {
-            eprintln!("\n\nReceived interrupt signal (Ctrl+C). Cleaning up and exiting...");
-
-            // Signal all workers to abort
-            cancellation_token_ctrlc.store(true, Ordering::Release);
-
-            // Best-effort cleanup — avoid blocking in signal handler
-            if let Ok(state) = cleanup_state_ctrlc.try_lock() {
-                let (files, dir, dir_is_new) = &*state;
-
-                if !files.is_empty() {
-                    eprintln!("Removing {} partially extracted file(s)...", files.len());
-                    let mut removed = 0;
-                    for file in files.iter() {
-                        if file.exists() {
-                            if fs::remove_file(file).is_ok() {
-                                removed += 1;
-                            } else {
-                                eprintln!("  ⚠️ Failed to remove: {}", file.display());
-                            }
-                        }
-                    }
-                    if removed > 0 {
-                        eprintln!("Cleaned up {} partial file(s).", removed);
-                    }
-                }
+        // Workers write disjoint regions without locks, so overlapping destination extents would be
+        // a data race. Check every selected partition before anything is written.
+        for update in manifest.partitions.iter().filter(|u| self.is_selected(u)) {
+            self.validate_non_overlapping_extents(&update.operations)
+                .with_context(|| format!("Invalid extents in partition '{}'", update.name()))?;
+        }
 
-                if *dir_is_new && dir.exists() {
-                    if fs::remove_dir_all(dir).is_ok() {
-                        eprintln!("Removed temporary extraction directory: {}", dir.display());
-                    } else {
-                        eprintln!(
-                            "⚠️ Failed to remove extraction directory: {}",
-                            dir.display()
-                        );
-                    }
-                }
-            } else {
-                eprintln!(
-                    "⚠️ Warning: Could not acquire cleanup lock (likely due to a thread panic)."
+        if self.cmd.verify_only {
+            let (_, payload) = self.parse_payload(&payload_bytes, &manifest)?;
+            self.verify_only(&payload, &manifest, block_size, simd)?;
+            if self.cmd.verify_chain && signer.is_some() && self.cmd.chatty() {
+                println!(
+                    "Chain of trust verified: payload signature → metadata signature → operation hashes → partition hashes."
                 );
-                eprintln!("   Please manually check your output directory for partial files.");
             }
+            return Ok(());
+        }
+        if self.cmd.stdout {
+            let (_, payload) = self.parse_payload(&payload_bytes, &manifest)?;
+            return self.extract_to_stdout(&payload, &manifest, block_size, simd);
+        }
+        if self.cmd.compress.is_some() || self.cmd.split_size.is_some() {
+            let (_, payload) = self.parse_payload(&payload_bytes, &manifest)?;
+            return self.extract_streamed(&payload, &manifest, block_size, simd);
+        }
 
-            eprintln!("✨ Goodbye!");
-            std::process::exit(130);
-        })
-        .context("Failed to set up Ctrl+C handler")?;
-
-        // Cleanup state: tracks files to delete and directory info for error cleanup
-        let threadpool = self.get_threadpool()?;
+        let output_map = self
+            .cmd
+            .output_map
+            .as_deref()
+            .map(OutputMap::load)
+            .transpose()?;
+        let edl_map = self.cmd.edl_xml.as_deref().map(EdlMap::load).transpose()?;
+        let default_dir = output_map.as_ref().and_then(|map| map.default.as_deref());
+        if default_dir.is_some() && self.cmd.output_dir.is_some() {
+            return Err(FailureKind::Usage.tag(anyhow!(
+                "The output map's `default` and --output-dir both say where images go; use only one."
+            )));
+        }
+        // Resolved before anything is created, so a bad map or clashing names leave no empty
+        // folders behind.
+        let mapped_paths = match &output_map {
+            Some(map) => {
+                let selected: Vec<_> = manifest
+                    .partitions
+                    .iter()
+                    .filter(|u| self.is_selected(u))
+                    .collect();
+                self.mapped_output_paths(map, &selected)?
+            }
+            None => Vec::new(),
+        };
+        let stems = self.output_stems(&manifest)?;
+        let edl_layout = match &edl_map {
+            Some(map) => {
+                let selected: Vec<_> = manifest
+                    .partitions
+                    .iter()
+                    .filter(|u| self.is_selected(u))
+                    .collect();
+                let layout = self
+                    .edl_layout(map, &selected)
+                    .map_err(|e| FailureKind::Usage.tag(e))?;
+                Some(layout)
+            }
+            None => None,
+        };
 
-        // Set up panic hook to trigger cleanup on any thread panic
-        let cleanup_state_clone = Arc::clone(&cleanup_state);
-        std::panic::set_hook(Box::new(move |_panic_info| {
-            if let Ok(state) = cleanup_state_clone.lock() {
-                let (files, dir, dir_is_new) = &*state;
-                // Try to remove created files
-                for f in files {
-                    let _ = fs::remove_file(f);
-                }
-                // If we created the directory, try to remove it as well
-                if *dir_is_new {
-                    let _ = fs::remove_dir_all(dir);
-                }
+        // Create/ensure output directory and detect if it was newly created
+        let (partition_dir, created_new_dir) = self.create_partition_dir(default_dir)?;
+
+        // Resolve output file names up front so case-insensitive or restrictive filesystems
+        // (exFAT, NTFS, FAT) never fail halfway through on a clashing or invalid name.
+        let fs_traits = FsTraits::probe(&partition_dir);
+        let stem_files =
+            fsprobe::output_file_names(stems.iter().map(|(_, stem)| stem.as_slice()), fs_traits);
+        if self.cmd.chatty() {
+            let mut renamed: Vec<(&[u8], &OsString)> = stem_files
+                .iter()
+                .map(|(stem, file)| (*stem, file))
+                .filter(|(stem, file)| file.as_encoded_bytes().strip_suffix(b".img") != Some(*stem))
+                .collect();
+            renamed.sort();
+            for (name, file) in renamed {
                 eprintln!(
-                    "Extraction aborted due to an error. Any partially extracted partition images have been deleted to prevent misuse."
+                    "Note: '{}' cannot be stored as-is on {}; writing it as '{}'",
+                    String::from_utf8_lossy(name).escape_debug(),
+                    fs_traits.kind.label(),
+                    file.display()
                 );
             }
-        }));
+        }
 
-        if !self.cmd.quiet {
-            // Inform the user about effective concurrency when -t/--threads is provided
-            if let Some(t) = self.cmd.threads
-                && t > 0
-            {
+        let mut file_names: HashMap<&[u8], OsString> = stems
+            .iter()
+            .map(|(name, stem)| (*name, stem_files[stem.as_slice()].clone()))
+            .collect();
+        for (name, path) in mapped_paths {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("could not create directory: {parent:?}"))?;
+            }
+            if self.cmd.chatty() {
                 eprintln!(
-                    "Using {} worker thread(s)",
-                    threadpool.current_num_threads()
+                    "Note: writing '{}' to {}",
+                    String::from_utf8_lossy(name).escape_debug(),
+                    path.display()
                 );
             }
-
-            let bold_bright_red = Style::new().bold().red();
-            let bold_yellow = Style::new().bold().yellow();
-            let bold_bright_green = Style::new().bold().green();
-            eprintln!(
-                "\n{}: do {} close this window! Use {} to cancel safely.",
-                bold_yellow.apply_to("Extraction in progress"),
-                bold_bright_red.apply_to("NOT"),
-                bold_bright_green.apply_to("Ctrl+C")
-            );
-            eprintln!(
-                "Processing {} partitions using {} threads...",
-                selected_count,
-                threadpool.current_num_threads()
-            );
-            eprintln!();
+            // Mapped paths are absolute, so joining them onto partition_dir leaves them as is.
+            file_names.insert(name, path.into_os_string());
         }
-        threadpool.scope(|scope| -> Result<()> {
-            let multiprogress = MultiProgress::new();
 
-            // Maintain the manifest/extraction order for neatly printing hashes later
-            for (hash_index_counter, update) in manifest
+        // --resume: images an earlier run finished are left as they are.
+        let journal = match &self.cmd.resume {
+            Some(_) => {
+                let payload_id =
+                    hex::encode(Self::read_payload_header(&payload_path)?.metadata_hash());
+                Some(Journal::open(&partition_dir, &payload_id)?)
+            }
+            None => None,
+        };
+        if let Some(journal) = &journal {
+            let finished: HashSet<String> = manifest
                 .partitions
                 .iter()
-                .filter(|update| {
-                    self.cmd.partitions.is_empty()
-                        || self.cmd.partitions.contains(&update.partition_name)
+                .filter(|u| self.is_selected(u))
+                .filter(|u| {
+                    journal.is_done(
+                        &u.name(),
+                        Path::new(&file_names[u.partition_name.as_slice()]),
+                    )
                 })
-                .enumerate()
-            {
-                self.validate_non_overlapping_extents(&update.operations)
-                    .with_context(|| {
-                        format!("Invalid extents in partition '{}'", update.partition_name)
-                    })?;
-                if cancellation_token.load(Ordering::Acquire) {
-                    eprintln!(
-                        "Extraction cancelled before processing '{}'",
-                        update.partition_name
+                .map(|u| u.name().into_owned())
+                .collect();
+            if self.cmd.chatty() && !finished.is_empty() {
+                let mut names: Vec<&str> = finished.iter().map(String::as_str).collect();
+                names.sort_unstable();
+                eprintln!("Resuming: already extracted {}", names.join(", "));
+            }
+            let _ = self.finished.set(finished);
+        }
+        let selected_count = manifest
+            .partitions
+            .iter()
+            .filter(|u| self.is_pending(u))
+            .count();
+
+        // Without hole support every ZERO/DISCARD region costs real disk space; say so up front.
+        if !fs_traits.sparse_files && self.cmd.chatty() {
+            let zero_bytes: u64 = manifest
+                .partitions
+                .iter()
+                .filter(|u| self.is_pending(u))
+                .map(|u| Self::zero_extent_bytes(u, block_size))
+                .fold(0, u64::saturating_add);
+            if zero_bytes > 0 {
+                eprintln!(
+                    "Note: {} does not support sparse files. Empty regions will be fully allocated, \
+                     needing an extra {} of disk space.",
+                    fs_traits.kind.label(),
+                    indicatif::HumanBytes(zero_bytes)
+                );
+            }
+        }
+
+        // Dirty mmap pages on network and FUSE filesystems are flushed whenever the OS sees fit,
+        // which is slow and surfaces write errors as SIGBUS. Positional writes report errors
+        // where they happen.
+        let io_mode = match (self.cmd.io_mode, fs_traits.backing) {
+            (IoModeChoice::Mmap, _) => IoMode::Mmap,
+            (IoModeChoice::Pwrite, _) => IoMode::Pwrite,
+            (IoModeChoice::Auto, Backing::Local) => {
+                let probed = sink::probe(&partition_dir).unwrap_or(IoMode::Mmap);
+                if probed == IoMode::Pwrite && self.cmd.chatty() {
+                    eprintln!(
+                        "Note: memory-mapped writes are slow in the output directory; using buffered writes instead."
                     );
-                    break;
                 }
-                let zero_bytes: u64 = update
-                    .operations
-                    .iter()
-                    .filter(|op| {
-                        matches!(Type::try_from(op.r#type), Ok(Type::Zero | Type::Discard))
-                    })
-                    .flat_map(|op| &op.dst_extents)
-                    .map(|e| {
-                        let blocks = e.num_blocks.unwrap_or(0);
-                        blocks * block_size as u64
-                    })
-                    .sum();
-
-                let total_bytes = update
-                    .new_partition_info
-                    .as_ref()
-                    .and_then(|i| i.size)
-                    .unwrap_or(0);
+                probed
+            }
+            (IoModeChoice::Auto, backing) => {
+                if self.cmd.chatty() {
+                    eprintln!(
+                        "Note: output directory is on {}; using buffered writes instead of memory mapping.",
+                        backing.label()
+                    );
+                }
+                IoMode::Pwrite
+            }
+        };
+        log::info!(
+            "writing to {} with {io_mode:?} ({fs_traits:?})",
+            partition_dir.display()
+        );
 
-                let zero_heavy = total_bytes > 0 && zero_bytes * 100 / total_bytes >= 50;
+        // Existing images are only replaced with --force; say so before anything is written
+        // rather than partway through. Stale images of a resumed run are replaced, though.
+        if journal.is_none() && !self.cmd.force {
+            let mut existing: Vec<String> = manifest
+                .partitions
+                .iter()
+                .filter(|u| self.is_selected(u))
+                .map(|u| partition_dir.join(&file_names[u.partition_name.as_slice()]))
+                .filter(|path| path.exists())
+                .map(|path| path.display().to_string())
+                .collect();
+            existing.sort_unstable();
+            if !existing.is_empty() {
+                return Err(FailureKind::Usage.tag(anyhow!(
+                    "{} already exist: {}\nRemove them, extract somewhere else, or pass --force \
+                     to replace them.",
+                    existing.len(),
+                    existing.join(", ")
+                )));
+            }
+        }
 
-                let progress_bar = self.create_progress_bar(update)?;
-                let progress_bar = multiprogress.add(progress_bar);
-                let (mut partition_file, partition_len, out_path) =
-                    self.open_partition_file(update, &partition_dir)?;
+        // Removes partial images and a directory we created unless extraction finishes. A folder
+        // being resumed stays, journal and all.
+        let cleanup = CleanupGuard::new(
+            partition_dir.to_path_buf(),
+            created_new_dir && journal.is_none(),
+        );
 
-                if zero_heavy {
-                    let mmap = Arc::get_mut(&mut partition_file)
-                        .expect("partition_file Arc unexpectedly shared");
-                    mmap.fill(0);
+        // Create and size every image now, which overlaps with a payload still inflating.
+        let mut outputs = HashMap::new();
+        for update in manifest.partitions.iter().filter(|u| self.is_pending(u)) {
+            if journal.is_some() || self.cmd.force {
+                // Left unfinished by the interrupted run, or replaced with --force.
+                let stale = partition_dir.join(&file_names[update.partition_name.as_slice()]);
+                match fs::remove_file(&stale) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                        return Err(e).with_context(|| format!("could not remove {stale:?}"));
+                    }
+                    _ => {}
                 }
+            }
+            let opened = self.open_partition_file(
+                update,
+                &partition_dir,
+                &file_names[update.partition_name.as_slice()],
+                io_mode,
+            )?;
+            // Track the file we just created for cleanup in case of errors
+            cleanup.track(opened.3.clone());
+            outputs.insert(update.partition_name.as_slice(), opened);
+        }
 
-                // Track the file we just created for cleanup in case of errors
-                if let Ok(mut state) = cleanup_state.lock() {
-                    state.0.push(out_path);
-                }
+        let (payload_source, payload) = self.parse_payload(&payload_bytes, &manifest)?;
+        let payload = &payload;
+        // File offset of the data section, for kernel-side copies of uncompressed ops.
+        let input_file = payload_source
+            .backing_file()
+            .map(|(file, base)| (file, base + payload.data_offset() as u64));
 
-                let part_start = if self.cmd.stats {
-                    Some(Instant::now())
-                } else {
-                    None
-                };
-                let stats_sender = stats_sender.clone();
-
-                // Assign an order index for hash printing
-                let part_index = hash_index_counter;
-                let ctx = Arc::new(WorkerContext {
-                    partition_file: partition_file.clone(),
-                    part_name: Arc::from(update.partition_name.as_str()),
-                    cancellation_token: cancellation_token.clone(),
-                    stats_sender: stats_sender.clone(),
-                    hash_sender: hash_sender.clone(),
-                    first_error: first_error.clone(),
-                    remaining_ops: Arc::new(AtomicUsize::new(update.operations.len())),
-                    partition_len,
-                    zero_ops_are_noops: zero_heavy,
-                });
-                let ops = &update.operations;
-                // Use smaller chunks for small partitions to reduce tail latency,
-                // larger chunks for big partitions to amortize Rayon scheduling cost.
-                let chunk_size = if ops.len() < 64 { 8 } else { 16 };
-
-                let base_ptr = PartitionPtr(partition_file.as_ptr() as *mut u8);
-                // Progress invariant:
-                // Each InstallOperation MUST increment the progress bar exactly once,
-                // regardless of execution path (serial or parallel).
-                if ops.len() <= 2 {
-                    // SERIAL FAST PATH
-                    for op in ops {
-                        if ctx.cancellation_token.load(Ordering::Acquire) {
+        let cancellation_token = Arc::new(AtomicBool::new(false));
+
+        // Channel to store the first error message
+        let first_error: Arc<Mutex<Option<anyhow::Error>>> = Arc::new(Mutex::new(None));
+
+        let threadpool = self.get_threadpool()?;
+        let verify_pool = self.get_verify_pool(&threadpool)?;
+
+        if self.cmd.chatty() {
+            // Inform the user about effective concurrency when -t/--threads is provided
+            if let Some(t) = self.cmd.threads
+                && t > 0
+            {
+                eprintln!(
+                    "Using {} worker thread(s)",
+                    threadpool.current_num_threads()
+                );
+            }
+
+            let bold_bright_red = Style::new().bold().red();
+            let bold_yellow = Style::new().bold().yellow();
+            let bold_bright_green = Style::new().bold().green();
+            eprintln!(
+                "\n{}: do {} close this window! Use {} to cancel safely.",
+                bold_yellow.apply_to("Extraction in progress"),
+                bold_bright_red.apply_to("NOT"),
+                bold_bright_green.apply_to("Ctrl+C")
+            );
+            eprintln!(
+                "Processing {} partitions using {} threads...",
+                selected_count,
+                threadpool.current_num_threads()
+            );
+            eprintln!();
+        }
+        // Bars are laid out up front in display order; partitions still start largest first.
+        let multiprogress = match self.cmd.progress {
+            ProgressMode::Bars => MultiProgress::new(),
+            ProgressMode::Plain | ProgressMode::Json => {
+                MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+            }
+        };
+        let mut progress_bars = HashMap::new();
+        let mut progress_lines = Vec::new();
+        for update in self.progress_display_order(&manifest, &payload_order) {
+            let bar = multiprogress.add(self.create_progress_bar(update)?);
+            progress_lines.push((update.name().into_owned(), bar.clone()));
+            progress_bars.insert(update.partition_name.as_slice(), bar);
+        }
+        let line_progress = (self.cmd.progress != ProgressMode::Bars && self.cmd.chatty())
+            .then(|| LineProgress::new(progress_lines, self.cmd.progress));
+
+        // Pages in payload data ahead of the workers; a payload read into memory is already in.
+        let readahead = match payload_source {
+            PayloadSource::Owned(_) => None,
+            _ => Some(Readahead::new()),
+        };
+
+        let shares = self.cmd.max_memory.map(Shares::of);
+        let memory = shares.map(|shares| MemoryBudget::new(shares.operations));
+        let write_behind = (io_mode == IoMode::Pwrite).then(|| {
+            let budget = shares.map_or(writebehind::BUDGET, |shares| {
+                writebehind::BUDGET.min(shares.write_behind as usize)
+            });
+            WriteBehind::new(budget)
+        });
+
+        std::thread::scope(|threads| -> Result<()> {
+            if let Some(readahead) = &readahead {
+                // Partitions are spawned in this order and their operations queued front to back.
+                let upcoming: Vec<&[u8]> = manifest
+                    .partitions
+                    .iter()
+                    .filter(|update| self.is_pending(update))
+                    .flat_map(|update| &update.operations)
+                    .filter_map(|op| payload.op_data(op).ok())
+                    .collect();
+                threads.spawn(move || readahead.run(upcoming));
+            }
+            let _stop_readahead = readahead.as_ref().map(Readahead::stop_on_drop);
+            if let Some(write_behind) = &write_behind {
+                for _ in 0..writebehind::WRITERS {
+                    threads.spawn(move || write_behind.run());
+                }
+            }
+            let _close_writes = write_behind.as_ref().map(WriteBehind::close_on_drop);
+            if let Some(line_progress) = &line_progress {
+                threads.spawn(move || line_progress.run());
+            }
+            let _stop_lines = line_progress.as_ref().map(LineProgress::stop_on_drop);
+
+            // Images are verified on their own pool as they finish; the scope waits for both.
+            verify_pool.in_place_scope(|verify| {
+                threadpool.scope(|scope| -> Result<()> {
+                    // Maintain the manifest/extraction order for neatly printing hashes later
+                    for (hash_index_counter, update) in manifest
+                        .partitions
+                        .iter()
+                        .filter(|update| self.is_pending(update))
+                        .enumerate()
+                    {
+                        if cancellation_token.load(Ordering::Acquire) || self.interrupted() {
+                            eprintln!("{}", Msg::CancelledBefore.fill(&[&update.name()]));
                             break;
                         }
+                        let zero_bytes = Self::zero_extent_bytes(update, block_size);
 
-                        let result = self.run_op_raw(
-                            &ctx,
-                            op,
-                            payload,
-                            base_ptr,
-                            ctx.partition_len,
-                            block_size,
-                            &ctx.part_name,
-                            simd,
+                        let total_bytes = update
+                            .new_partition_info
+                            .as_ref()
+                            .and_then(|i| i.size)
+                            .unwrap_or(0);
+
+                        let zero_heavy =
+                            total_bytes > 0 && zero_bytes.saturating_mul(100) / total_bytes >= 50;
+                        // A freshly sized file already reads as zeros. On filesystems with
+                        // hole support, leaving ZERO/DISCARD regions untouched keeps them
+                        // sparse; elsewhere zero-heavy images are filled in one sequential pass
+                        // instead of per-op.
+                        let zero_ops_are_noops =
+                            fs_traits.sparse_files || zero_heavy || io_mode == IoMode::Pwrite;
+
+                        // Nothing needs the image's own digest, so its hash pass can go.
+                        let skip_image_hash = self.cmd.trust_op_hashes
+                            && (hash_sender.is_none()
+                                || !self.cmd.hash_algo.contains(&HashAlgo::Sha256))
+                            && audit.is_none()
+                            && Self::op_hashes_cover_image(update, block_size);
+
+                        let progress_bar = progress_bars
+                            .remove(update.partition_name.as_slice())
+                            .context("no progress bar for partition")?;
+                        let (mut partition_file, out_file, partition_len, out_path) = outputs
+                            .remove(update.partition_name.as_slice())
+                            .context("no output file for partition")?;
+
+                        if zero_heavy
+                            && !fs_traits.sparse_files
+                            && let Some(mmap) = partition_file.as_mut()
+                        {
+                            mmap.fill(0);
+                        }
+
+                        log::info!(
+                            "extracting {}: {total_bytes} bytes, {} operations{}",
+                            update.name(),
+                            update.operations.len(),
+                            if zero_ops_are_noops {
+                                ", zero operations skipped"
+                            } else {
+                                ""
+                            }
                         );
 
-                        match result {
-                            Ok(bytes) => {
-                                progress_bar.inc(bytes as u64);
+                        let part_start = if self.cmd.stats {
+                            Some(Instant::now())
+                        } else {
+                            None
+                        };
+
+                        // Assign an order index for hash printing
+                        let part_index = hash_index_counter;
+                        let queued_writes = match &write_behind {
+                            Some(queue) => {
+                                let target = Target::new(out_file.try_clone()?);
+                                Some((queue, Arc::new(target)))
                             }
-                            Err(e) if let Ok(mut slot) = ctx.first_error.lock() => {
-                                ctx.cancellation_token.store(true, Ordering::Release);
-                                if slot.is_none() {
-                                    *slot = Some(e.context(format!(
-                                        "Error in partition '{}'",
-                                        ctx.part_name
-                                    )));
-                                }
+                            None => None,
+                        };
+                        let ctx = WorkerContext {
+                            out_file,
+                            input_file,
+                            part_name: Arc::from(update.name()),
+                            cancellation_token: cancellation_token.clone(),
+                            interrupt: &self.interrupt,
+                            stats_sender: stats_sender.clone(),
+                            hash_sender: hash_sender.clone(),
+                            sanity_sender: sanity_sender.clone(),
+                            verity_sender: verity_sender.clone(),
+                            first_error: first_error.clone(),
+                            partition_len,
+                            zero_ops_are_noops,
+                            punch_holes: fs_traits.sparse_files,
+                            skip_image_hash,
+                            flusher: self.cmd.flush_every.map(|mib| Flusher::new(mib << 20)),
+                            write_behind: queued_writes,
+                            audit: audit.as_ref(),
+                            readahead: readahead.as_ref(),
+                            memory: memory.as_ref(),
+                            source: sources.get(&*update.name()).map(SourceImage::bytes),
+                            out_path,
+                            journal: journal.as_ref(),
+                        };
+
+                        if update.operations.len() <= 2 {
+                            // SERIAL FAST PATH: not worth a task of its own
+                            self.write_partition(
+                                &ctx,
+                                update,
+                                payload,
+                                partition_file.as_deref_mut(),
+                                &progress_bar,
+                                block_size,
+                                simd,
+                            );
+                            if ctx.first_error.lock().is_ok_and(|slot| slot.is_some()) {
                                 return Ok(());
                             }
-                            Err(_) => return Ok(()),
-                        }
-                    }
-
-                    if !ctx.cancellation_token.load(Ordering::Acquire) {
-                        self.post_process_partition(&ctx, update, simd, part_index, part_start);
-                    }
-                } else {
-                    // PARALLEL CHUNKED PATH
-                    for chunk in ops.chunks(chunk_size) {
-                        let progress_bar = progress_bar.clone();
-                        let ctx = ctx.clone();
-
-
-                        scope.spawn(move |_| {
-                            let mut chunk_bytes_processed = 0usize; // Buffer for this thread's chunk
-
-                            for op in chunk {
-                                if ctx.cancellation_token.load(Ordering::Acquire) {
-                                    return;
-                                }
-
-                                let result = self.run_op_raw(
+                            self.verify_later(
+                                verify,
+                                ctx,
+                                update,
+                                partition_file,
+                                progress_bar,
+                                simd,
+                                part_index,
+                                part_start,
+                            );
+                        } else {
+                            // The task owns the mapping until it goes to the verification pool;
+                            // its operations each borrow their own part of it.
+                            scope.spawn(move |_| {
+                                let mut partition_file = partition_file;
+                                self.write_partition(
                                     &ctx,
-                                    op,
+                                    update,
                                     payload,
-                                    base_ptr,
-                                    ctx.partition_len,
+                                    partition_file.as_deref_mut(),
+                                    &progress_bar,
                                     block_size,
-                                    &ctx.part_name,
                                     simd,
                                 );
-
-                                match result {
-                                    Ok(bytes) => {
-                                        chunk_bytes_processed += bytes;
-                                    }
-                                    Err(e) if let Ok(mut slot) = ctx.first_error.lock() => {
-                                        ctx.cancellation_token.store(true, Ordering::Release);
-                                        if slot.is_none() {
-                                            *slot = Some(e.context(format!(
-                                                "Error in partition '{}'",
-                                                ctx.part_name
-                                            )));
-                                        }
-                                        return;
-                                    }
-                                    Err(_) => return,
-                                }
-                            }
-
-                            // Batch update: Call inc() once per chunk instead of once per operation
-                            if chunk_bytes_processed > 0 {
-                                progress_bar.inc(chunk_bytes_processed as u64);
-                            }
-
-                            if ctx.remaining_ops.fetch_sub(chunk.len(), Ordering::Release)
-                                == chunk.len()
-                            {
-                                self.post_process_partition(
-                                    &ctx, update, simd, part_index, part_start,
+                                self.verify_later(
+                                    verify,
+                                    ctx,
+                                    update,
+                                    partition_file,
+                                    progress_bar,
+                                    simd,
+                                    part_index,
+                                    part_start,
                                 );
-                            }
-                        });
+                            });
+                        }
                     }
-                }
-            }
-            Ok(())
+                    Ok(())
+                })
+            })
         })?;
 
-        // Check if extraction was cancelled due to critical errors
-        if cancellation_token.load(Ordering::Acquire) {
-            // Clean up any partially extracted files
-            if let Ok(state) = cleanup_state.lock() {
-                let (files, dir, dir_is_new) = &*state;
-                // Try to remove created files
-                for f in files {
-                    let _ = fs::remove_file(f);
-                }
-                // If we created the directory, try to remove it as well
-                if *dir_is_new {
-                    let _ = fs::remove_dir_all(dir);
+        // Keep the log even when extraction fails; that is when it matters most.
+        if let Some(audit) = &audit {
+            audit.finish()?;
+        }
+
+        // Check if extraction was cancelled due to critical errors or by the caller
+        if self.interrupted() || cancellation_token.load(Ordering::Acquire) {
+            // Report what did finish before the files go away.
+            self.print_summary(
+                &drain_hashes(hash_receiver.as_ref()),
+                sanity_receiver.as_ref(),
+                verity_receiver.as_ref(),
+                stats_receiver.as_ref(),
+                total_start,
+                false,
+            );
+            // With --resume, images that finished stay for the next run.
+            if let Some(journal) = &journal {
+                for file in journal.finished() {
+                    cleanup.untrack(&file);
                 }
             }
+            // Clean up any partially extracted files
+            drop(cleanup);
+            if self.interrupted() {
+                return Err(FailureKind::Interrupted.tag(anyhow!("{}", Msg::Cancelled.text())));
+            }
             // Print the stored error message
             if let Some(err) = first_error.lock().unwrap().take() {
                 eprintln!("\n{}", err);
+                self.record_failure(FailureKind::of(&err), format!("{err:#}"));
             }
 
-            bail!(
-                "❌ Extraction failed due to errors (see above). All partial files have been cleaned up."
+            let kind = self
+                .failure_kind
+                .get()
+                .copied()
+                .unwrap_or(FailureKind::Other);
+            return Err(kind.tag(anyhow!("{}", Msg::Failed.text())));
+        }
+
+        // Converted before the guard lets go, so a failure leaves no half-done output behind.
+        if self.cmd.sparse {
+            self.write_sparse_images(&manifest, &file_names, &partition_dir, block_size)?;
+        }
+
+        cleanup.keep();
+        if let Some(journal) = journal {
+            journal.finish()?;
+        }
+        let hashes = drain_hashes(hash_receiver.as_ref());
+        self.print_summary(
+            &hashes,
+            sanity_receiver.as_ref(),
+            verity_receiver.as_ref(),
+            stats_receiver.as_ref(),
+            total_start,
+            true,
+        );
+        if self.cmd.hash_file {
+            write_hash_files(&partition_dir, &self.cmd.hash_algo, &hashes)?;
+        }
+
+        if self.cmd.extract_metadata {
+            self.extract_metadata(&payload_path, payload_source, payload, &partition_dir)?;
+        }
+        if let Some(layout) = &edl_layout {
+            self.write_edl_xml(layout, &file_names, &partition_dir)?;
+        }
+        if self.cmd.make_recovery_zip {
+            self.make_recovery_zip(&manifest, &file_names, &partition_dir)?;
+        }
+
+        // Calculate and display extracted folder size
+        if self.cmd.chatty() {
+            if self.cmd.verify_chain && signer.is_some() {
+                println!(
+                    "\nChain of trust verified: payload signature → metadata signature → operation hashes → partition hashes."
+                );
+            }
+            self.display_extracted_folder_size(&partition_dir)?;
+        }
+
+        // Automatically open the extracted folder (unless disabled)
+        if !self.cmd.no_open && self.cmd.chatty() {
+            self.open_extracted_folder(&partition_dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// `--list`: partition names, sizes and whether each is a full image or a delta.
+    fn list_partitions(mut manifest: ManifestSummary) -> Result<()> {
+        manifest
+            .partitions
+            .sort_unstable_by(|p1, p2| p1.partition_name.cmp(&p2.partition_name));
+
+        println!("{:<20} {:<16} {:<10}", "Partition", "Size", "Type");
+        println!("{:-<46}", "");
+
+        let partition_count = manifest.partitions.len();
+
+        for partition in &manifest.partitions {
+            // Distinguish between explicit 0 size and missing metadata
+            let size_str = if let Some(size) = partition
+                .new_partition_info
+                .as_ref()
+                .and_then(|info| info.size)
+            {
+                indicatif::HumanBytes(size).to_string()
+            } else {
+                "???".to_string()
+            };
+
+            // Check for operations that rely on source data (meaning it's a patch/delta)
+            let is_patch = partition
+                .operations
+                .iter()
+                .any(|op| Self::is_source_op(op.r#type));
+
+            let type_label = if is_patch {
+                Style::new().bold().red().apply_to("Incremental")
+            } else {
+                Style::new().bold().green().apply_to("Full")
+            };
+
+            let name_style = Style::new().bold().green();
+            println!(
+                "{:<20} {:<16} {:<10}",
+                name_style.apply_to(partition.name()),
+                size_str,
+                type_label
             );
         }
 
-        if let Ok(mut state) = cleanup_state.lock() {
-            state.0.clear();
+        // Simplified footer focusing only on the partition count
+        println!("{:-<46}", "");
+        println!(
+            "Total Partitions: {}",
+            Style::new().bold().cyan().apply_to(partition_count)
+        );
+
+        Ok(())
+    }
+
+    /// Selected partitions in the order `--progress-order` asks their bars to be shown in.
+    /// `manifest` is already sorted largest first.
+    fn progress_display_order<'m>(
+        &self,
+        manifest: &'m DeltaArchiveManifest,
+        payload_order: &HashMap<Vec<u8>, usize>,
+    ) -> Vec<&'m PartitionUpdate> {
+        let mut selected: Vec<_> = manifest
+            .partitions
+            .iter()
+            .filter(|u| self.is_pending(u))
+            .collect();
+        match self.cmd.progress_order {
+            ProgressOrder::Size => {}
+            ProgressOrder::Name => selected.sort_by(|a, b| a.partition_name.cmp(&b.partition_name)),
+            ProgressOrder::Selection => selected.sort_by_key(|u| {
+                let picked = self
+                    .selection()
+                    .iter()
+                    .position(|p| self.name_matches(u, p));
+                (picked, payload_order.get(&u.partition_name).copied())
+            }),
+        }
+        selected
+    }
+
+    pub(super) fn create_progress_bar(&self, update: &PartitionUpdate) -> Result<ProgressBar> {
+        let bar = self.new_progress_bar(update)?;
+        if self.progress_hook.is_some() {
+            self.bars
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(bar.clone());
         }
+        Ok(bar)
+    }
+
+    /// Calls `hook` with the position and length summed over every bar so far.
+    fn report_progress(&self, hook: &ProgressHook) {
+        let (done, total) = self
+            .bars
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .fold((0, 0), |(done, total), bar| {
+                (done + bar.position(), total + bar.length().unwrap_or(0))
+            });
+        hook(done, total);
+    }
+
+    fn new_progress_bar(&self, update: &PartitionUpdate) -> Result<ProgressBar> {
+        let total_bytes = update
+            .new_partition_info
+            .as_ref()
+            .and_then(|i| i.size)
+            .unwrap_or(0);
+        // Hidden bars still count, for a progress hook.
+        if !self.cmd.chatty() {
+            return Ok(ProgressBar::with_draw_target(
+                Some(total_bytes),
+                ProgressDrawTarget::hidden(),
+            ));
+        }
+        if self.cmd.progress != ProgressMode::Bars {
+            // Counted for LineProgress, never drawn.
+            return Ok(ProgressBar::with_draw_target(
+                Some(total_bytes),
+                ProgressDrawTarget::hidden(),
+            ));
+        }
+
+        let style = ProgressStyle::with_template(
+            "{prefix:>24!.green.bold} [{wide_bar:.white.dim}] {percent:>3}% {msg:.dim}",
+        )
+        .context("unable to build progress bar template")?
+        .progress_chars("=> ");
+
+        Ok(ProgressBar::new(total_bytes)
+            .with_finish(ProgressFinish::AndLeave)
+            .with_prefix(update.name().into_owned())
+            .with_style(style))
+    }
+
+    /// Runs a partition's operations, in parallel chunks unless there are only a couple, and
+    /// waits for their writes. `image` is the output mapping, or `None` in [`IoMode::Pwrite`]
+    /// mode. The image is checked afterwards by [`finish_partition`](Self::finish_partition).
+    #[allow(clippy::too_many_arguments)]
+    fn write_partition(
+        &self,
+        ctx: &WorkerContext,
+        update: &PartitionUpdate,
+        payload: &Payload,
+        image: Option<&mut [u8]>,
+        progress_bar: &ProgressBar,
+        block_size: usize,
+        simd: CpuSimd,
+    ) {
+        let ops = &update.operations;
+        let regions = match image {
+            Some(image) => self
+                .op_regions(ops, image, block_size)
+                .map(|regions| regions.into_iter().map(Some).collect()),
+            None => Ok(ops.iter().map(|_| None).collect()),
+        };
+        let mut regions: Vec<Option<Vec<&mut [u8]>>> = match regions {
+            Ok(regions) => regions,
+            Err(e) => return ctx.fail(e),
+        };
+
+        // Progress invariant:
+        // Each InstallOperation MUST increment the progress bar exactly once,
+        // regardless of execution path (serial or parallel).
+        if ops.len() <= 2 {
+            // SERIAL FAST PATH
+            for (index, (op, region)) in ops.iter().zip(&mut regions).enumerate() {
+                if ctx.is_cancelled() {
+                    break;
+                }
+                let result = self.run_op(
+                    ctx,
+                    index,
+                    op,
+                    payload,
+                    region.as_deref_mut(),
+                    ctx.partition_len,
+                    block_size,
+                    &ctx.part_name,
+                    simd,
+                );
+                match result {
+                    Ok(bytes) => {
+                        ctx.written(op, block_size);
+                        progress_bar.inc(bytes as u64);
+                    }
+                    Err(e) => return ctx.fail(e),
+                }
+            }
+        } else {
+            // PARALLEL CHUNKED PATH
+            // Use smaller chunks for small partitions to reduce tail latency,
+            // larger chunks for big partitions to amortize Rayon scheduling cost.
+            let chunk_size = if ops.len() < 64 { 8 } else { 16 };
+            rayon::scope(|scope| {
+                let chunks = ops.chunks(chunk_size).zip(regions.chunks_mut(chunk_size));
+                for (chunk_index, (chunk, regions)) in chunks.enumerate() {
+                    scope.spawn(move |_| {
+                        let mut chunk_bytes_processed = 0usize; // Buffer for this thread's chunk
+
+                        for (i, (op, region)) in chunk.iter().zip(regions).enumerate() {
+                            if ctx.is_cancelled() {
+                                return;
+                            }
+                            let result = self.run_op(
+                                ctx,
+                                chunk_index * chunk_size + i,
+                                op,
+                                payload,
+                                region.as_deref_mut(),
+                                ctx.partition_len,
+                                block_size,
+                                &ctx.part_name,
+                                simd,
+                            );
+                            match result {
+                                Ok(bytes) => {
+                                    ctx.written(op, block_size);
+                                    chunk_bytes_processed += bytes;
+                                }
+                                Err(e) => return ctx.fail(e),
+                            }
+                        }
+
+                        // Batch update: Call inc() once per chunk instead of once per operation
+                        if chunk_bytes_processed > 0 {
+                            progress_bar.inc(chunk_bytes_processed as u64);
+                        }
+                    });
+                }
+            });
+        }
+        drop(regions);
+
+        if let Some((_, target)) = &ctx.write_behind
+            && let Err(e) = target.wait()
+        {
+            ctx.fail(anyhow::Error::new(e).context("failed to write to output file"));
+        }
+    }
+
+    /// Hands a written image, and the mapping it was written through, to the verification
+    /// pool, so this pool's workers go on decoding other partitions while it is hashed.
+    #[allow(clippy::too_many_arguments)]
+    fn verify_later<'s>(
+        &'s self,
+        verify: &rayon::Scope<'s>,
+        ctx: WorkerContext<'s>,
+        update: &'s PartitionUpdate,
+        image: Option<MmapMut>,
+        progress_bar: ProgressBar,
+        simd: CpuSimd,
+        part_index: usize,
+        part_start: Option<Instant>,
+    ) {
+        if ctx.is_cancelled() {
+            return;
+        }
+        progress_bar.set_message("verifying");
+        verify.spawn(move |_| {
+            self.finish_partition(
+                &ctx,
+                update,
+                image.as_deref(),
+                &progress_bar,
+                simd,
+                part_index,
+                part_start,
+            );
+        });
+    }
+
+    /// Verifies a written image and reports it.
+    #[allow(clippy::too_many_arguments)]
+    fn finish_partition(
+        &self,
+        ctx: &WorkerContext,
+        update: &PartitionUpdate,
+        image: Option<&[u8]>,
+        progress_bar: &ProgressBar,
+        simd: CpuSimd,
+        part_index: usize,
+        part_start: Option<Instant>,
+    ) {
+        if ctx.is_cancelled() {
+            return;
+        }
+        if let Some(flusher) = &ctx.flusher {
+            flusher.finish(&ctx.out_file);
+        }
+        self.post_process_partition(ctx, update, image, simd, part_index, part_start);
+        if ctx.is_cancelled() {
+            return;
+        }
+        // Skipped zero operations never move the bar; a verified image is complete.
+        progress_bar.set_message("");
+        progress_bar.finish();
+        if let Some(journal) = ctx.journal {
+            // Only an image that is on disk may be skipped next time.
+            let recorded = ctx
+                .out_file
+                .sync_data()
+                .map_err(anyhow::Error::from)
+                .and_then(|()| {
+                    journal.record(&ctx.part_name, &ctx.out_path, ctx.partition_len as u64)
+                });
+            if let Err(e) = recorded {
+                ctx.fail(e.context(format!("could not record '{}' as extracted", ctx.part_name)));
+            }
+        }
+        pagecache::release(image, &ctx.out_file);
+    }
+
+    /// Prints the hashes, sanity warnings, AVB verity data and statistics gathered during
+    /// extraction, in partition order. When extraction did not `complete`, they cover the
+    /// partitions that finished before it stopped.
+    fn print_summary(
+        &self,
+        hashes: &[HashRec],
+        sanity: Option<&crossbeam_channel::Receiver<SanityRec>>,
+        verity: Option<&crossbeam_channel::Receiver<VerityRec>>,
+        stats: Option<&crossbeam_channel::Receiver<Stat>>,
+        total_start: Option<Instant>,
+        complete: bool,
+    ) {
         // Print partition hashes (cleanly) if requested
-        if let Some(receiver) = hash_receiver.as_ref() {
-            let mut v: Vec<HashRec> = Vec::new();
-            while let Ok(r) = receiver.try_recv() {
-                v.push(r);
+        if self.cmd.print_hash && !hashes.is_empty() {
+            if complete {
+                println!("{}", Msg::HashesHeading.text());
+            } else {
+                println!("{}", Msg::HashesPartialHeading.text());
+            }
+            for r in hashes {
+                println!("{}: {}", r.name, format_digests(&r.digests));
+            }
+        }
+
+        // Print magic and entropy warnings, if any. They don't fail extraction: padded images can trip them.
+        if let Some(receiver) = sanity {
+            let mut v: Vec<SanityRec> = receiver.try_iter().collect();
+            if !v.is_empty() {
+                v.sort_by_key(|r| r.order);
+                let warn = Style::new().bold().yellow();
+                eprintln!("\n{}", Msg::SanityHeading.text());
+                for r in v.iter() {
+                    for finding in &r.findings {
+                        eprintln!("  {} {}: {}", warn.apply_to("⚠"), r.name, finding);
+                    }
+                    if let Some(profile) = &r.profile {
+                        eprintln!("      entropy per 1/16 (bits/byte): {profile}");
+                    }
+                }
             }
+        }
+
+        // AVB data per image, so users modifying images know what needs regenerating.
+        if let Some(receiver) = verity {
+            let mut v: Vec<VerityRec> = receiver.try_iter().collect();
             if !v.is_empty() {
                 v.sort_by_key(|r| r.order);
-                println!("Partition hashes (SHA-256):");
+                let width = v.iter().map(|r| r.name.len()).max().unwrap_or(0).max(9);
+                println!("\n{}", Msg::VerityHeading.text());
+                println!(
+                    "  {:<width$}  {:<18}  {:<12}  Salt",
+                    "Partition", "Verity", "Tree offset"
+                );
                 for r in v.iter() {
-                    println!("{}: sha256={}", r.name, r.hex);
+                    let (offset, salt) = match &r.verity {
+                        Verity::Hashtree {
+                            tree_offset,
+                            tree_size,
+                            algorithm,
+                            salt,
+                            ..
+                        } => (
+                            format!("{tree_offset:#x}"),
+                            format!(
+                                "{} ({algorithm}, tree {})",
+                                hex::encode(salt),
+                                indicatif::HumanBytes(*tree_size)
+                            ),
+                        ),
+                        Verity::Hash { algorithm, salt } => (
+                            "-".to_string(),
+                            format!("{} ({algorithm})", hex::encode(salt)),
+                        ),
+                        _ => ("-".to_string(), "-".to_string()),
+                    };
+                    println!(
+                        "  {:<width$}  {:<18}  {offset:<12}  {salt}",
+                        r.name,
+                        r.verity.label()
+                    );
                 }
             }
         }
 
         // Print stats summary if requested
-        if let Some(receiver) = stats_receiver.as_ref() {
+        if let Some(receiver) = stats {
             let mut v: Vec<Stat> = Vec::new();
             while let Ok(s) = receiver.try_recv() {
                 v.push(s);
             }
             if !v.is_empty() {
+                // Partitions finish in whatever order the workers get to them.
+                v.sort_by_key(|s| s.order);
                 let total_bytes: u64 = v.iter().map(|s| s.bytes).sum();
                 let wall_ms = total_start.map(|t| t.elapsed().as_millis()).unwrap_or(0);
-                eprintln!("\nExtraction statistics:");
+                eprintln!("\n{}", Msg::StatsHeading.text());
                 for s in v.iter() {
                     let gbps = if s.ms > 0 {
                         (s.bytes as f64) / (s.ms as f64) / 1_000_000.0
@@ -809,46 +1897,6 @@ impl<'a> Extractor<'a> {
                 }
             }
         }
-
-        // If we got here, everything succeeded; clear cleanup state
-        if let Ok(mut state) = cleanup_state.lock() {
-            state.0.clear(); // Clear the file list so no cleanup happens
-        }
-
-        // Calculate and display extracted folder size
-        if !self.cmd.quiet {
-            self.display_extracted_folder_size(&partition_dir)?;
-        }
-
-        // Automatically open the extracted folder (unless disabled)
-        if !self.cmd.no_open && !self.cmd.quiet {
-            self.open_extracted_folder(&partition_dir)?;
-        }
-
-        Ok(())
-    }
-
-    fn create_progress_bar(&self, update: &PartitionUpdate) -> Result<ProgressBar> {
-        if self.cmd.quiet {
-            return Ok(ProgressBar::hidden());
-        }
-
-        let total_bytes = update
-            .new_partition_info
-            .as_ref()
-            .and_then(|i| i.size)
-            .unwrap_or(0);
-
-        let style = ProgressStyle::with_template(
-            "{prefix:>24!.green.bold} [{wide_bar:.white.dim}] {percent:>3}%",
-        )
-        .context("unable to build progress bar template")?
-        .progress_chars("=> ");
-
-        Ok(ProgressBar::new(total_bytes)
-            .with_finish(ProgressFinish::AndLeave)
-            .with_prefix(update.partition_name.to_string())
-            .with_style(style))
     }
 
     #[inline]
@@ -856,17 +1904,36 @@ impl<'a> Extractor<'a> {
         &self,
         ctx: &WorkerContext,
         update: &PartitionUpdate,
+        image: Option<&[u8]>,
         simd: CpuSimd,
         part_index: usize,
         part_start: Option<Instant>,
     ) {
-        let is_cancelled = || ctx.cancellation_token.load(Ordering::Acquire);
-
-        let final_slice: &[u8] = &ctx.partition_file;
+        // In pwrite mode the finished image is mapped read-only just for hashing.
+        let readback;
+        let final_slice: &[u8] = match image {
+            Some(image) => image,
+            None => match unsafe { Mmap::map(&ctx.out_file) } {
+                Ok(mmap) => {
+                    readback = mmap;
+                    &readback
+                }
+                Err(e) => {
+                    return self.critical(
+                        ctx,
+                        FailureKind::Io,
+                        format!(
+                            "Failed to read back '{}' for verification: {}",
+                            ctx.part_name, e
+                        ),
+                    );
+                }
+            },
+        };
 
         let mut computed_digest_opt: Option<[u8; 32]> = None;
 
-        if !self.cmd.no_verify {
+        if !self.cmd.no_verify && !ctx.skip_image_hash {
             if let Some(hash) = update
                 .new_partition_info
                 .as_ref()
@@ -875,62 +1942,123 @@ impl<'a> Extractor<'a> {
                 match self.verify_sha256_returning(final_slice, hash) {
                     Ok(d) => computed_digest_opt = Some(d),
                     Err(e) => {
-                        ctx.cancellation_token.store(true, Ordering::Release);
-                        eprintln!(
-                            "\nCritical error: Output verification failed for '{}': {}",
-                            ctx.part_name, e
+                        if let Some(audit) = ctx.audit {
+                            let _ = audit.partition(
+                                &ctx.part_name,
+                                &ctx.out_path,
+                                ctx.partition_len,
+                                None,
+                                false,
+                                Some(format!("{e:#}")),
+                            );
+                        }
+                        return self.critical(
+                            ctx,
+                            FailureKind::Verification,
+                            format!("Output verification failed for '{}': {}", ctx.part_name, e),
                         );
-                        return;
                     }
                 }
-            } else if self.cmd.strict {
-                ctx.cancellation_token.store(true, Ordering::Release);
-                eprintln!(
-                    "\nCritical error: Strict mode: missing partition hash for '{}'",
-                    ctx.part_name
+            } else if self.cmd.strict || self.cmd.verify_chain {
+                return self.critical(
+                    ctx,
+                    FailureKind::Verification,
+                    format!(
+                        "Strict mode: missing partition hash for '{}'",
+                        ctx.part_name
+                    ),
                 );
-                return;
             }
         }
 
-        if is_cancelled() {
+        if ctx.is_cancelled() {
             return;
         }
 
         if self.cmd.sanity && is_all_zero_with_simd(simd, final_slice) {
-            ctx.cancellation_token.store(true, Ordering::Release);
-            eprintln!(
-                "\nCritical error: Sanity check failed for '{}'",
-                ctx.part_name
+            return self.critical(
+                ctx,
+                FailureKind::Verification,
+                format!("Sanity check failed for '{}'", ctx.part_name),
             );
-            return;
         }
 
-        if is_cancelled() {
+        if let Some(sender) = ctx.sanity_sender.as_ref() {
+            let profile = entropy::Profile::scan(simd, final_slice);
+            let mut findings: Vec<String> = magic::check(&ctx.part_name, final_slice)
+                .into_iter()
+                .collect();
+            findings.extend(profile.iter().flat_map(|p| p.findings()));
+            if !findings.is_empty() {
+                let _ = sender.send(SanityRec {
+                    order: part_index,
+                    name: ctx.part_name.to_string(),
+                    findings,
+                    profile: profile.map(|p| p.render()),
+                });
+            }
+        }
+
+        if let Some(sender) = ctx.verity_sender.as_ref() {
+            let _ = sender.send(VerityRec {
+                order: part_index,
+                name: ctx.part_name.to_string(),
+                verity: Verity::scan(final_slice),
+            });
+        }
+
+        if ctx.is_cancelled() {
             return;
         }
 
-        if let Some(sender) = ctx.hash_sender.as_ref() {
-            let digest = if let Some(d) = computed_digest_opt {
-                d
-            } else {
-                let d = digest(&SHA256, final_slice);
-                let mut arr = [0u8; 32];
-                arr.copy_from_slice(d.as_ref());
-                arr
-            };
+        // The audit log records the image hash even when it was not verified against the manifest.
+        let verified = computed_digest_opt.is_some();
+        if verified {
+            log::info!("{}: SHA-256 verified", ctx.part_name);
+        } else {
+            log::debug!("{}: not verified against the manifest", ctx.part_name);
+        }
+        let report_sha256 =
+            ctx.hash_sender.is_some() && self.cmd.hash_algo.contains(&HashAlgo::Sha256);
+        if (report_sha256 || ctx.audit.is_some()) && computed_digest_opt.is_none() {
+            computed_digest_opt = Some(crypto::sha256(final_slice));
+        }
+
+        if let Some(audit) = ctx.audit
+            && let Err(e) = audit.partition(
+                &ctx.part_name,
+                &ctx.out_path,
+                ctx.partition_len,
+                computed_digest_opt,
+                verified,
+                None,
+            )
+        {
+            return self.critical(ctx, FailureKind::of(&e), format!("{e:#}"));
+        }
 
-            let hexstr = hex::encode(digest);
+        if let Some(sender) = ctx.hash_sender.as_ref() {
+            let digests = self
+                .cmd
+                .hash_algo
+                .iter()
+                .map(|&algo| match (algo, computed_digest_opt) {
+                    (HashAlgo::Sha256, Some(digest)) => (algo, hex::encode(digest)),
+                    _ => (algo, algo.digest(final_slice)),
+                })
+                .collect();
             let _ = sender.send(HashRec {
                 order: part_index,
                 name: ctx.part_name.to_string(),
-                hex: hexstr,
+                digests,
+                file: ctx.out_path.clone(),
             });
         }
 
         if let (Some(start), Some(sender)) = (part_start, ctx.stats_sender.as_ref()) {
             let elapsed = start.elapsed();
             let _ = sender.send(Stat {
+                order: part_index,
                 name: ctx.part_name.to_string(),
                 bytes: ctx.partition_len as u64,
                 ms: elapsed.as_millis(),
@@ -938,89 +2066,330 @@ impl<'a> Extractor<'a> {
         }
     }
 
-    /// # Safety
-    /// This function is the core of otaripper's high-performance extraction.
-    /// It is sound because:
-    /// 1.`base_ptr` is guaranteed to be valid for `partition_len` as long as the
-    ///   `partition_file` Mmap is alive.
-    /// 2. Scoped threads (`rayon::scope`) ensure that worker threads cannot outlive
-    ///   the `Mmap` lifetime.
-    /// 3. `validate_non_overlapping_extents` proves that no two threads can receive
-    ///   the same memory range, preventing data races and mutable aliasing UB.
+    /// Runs one operation, recording it in the `--audit` log when one is open. Returns the
+    /// number of image bytes written, for progress.
     #[allow(clippy::too_many_arguments)]
     #[inline(always)]
-    fn run_op_raw(
+    fn run_op(
         &self,
         ctx: &WorkerContext,
+        index: usize,
         op: &InstallOperation,
         payload: &Payload,
-        base_ptr: PartitionPtr,
+        regions: Option<&mut [&mut [u8]]>,
         partition_len: usize,
         block_size: usize,
         partition_name: &str,
         simd: CpuSimd,
     ) -> Result<usize> {
-        let op_type = Type::try_from(op.r#type)?;
-        let raw_extents =
-            self.extract_dst_extents_raw(op, base_ptr.0, partition_len, block_size)?;
-
-        // SAFETY: Reconstitute pointer inside the thread.
-        // Sound because extents are non-overlapping and threads are scoped to the Mmap lifetime.
-        let mut dst_extents = Vec::with_capacity(raw_extents.len());
-
-        for (ptr, len) in raw_extents {
-            dst_extents.push(unsafe { slice::from_raw_parts_mut(ptr, len) });
+        if let Some(readahead) = ctx.readahead {
+            readahead.started(op.data_length.unwrap_or(0));
         }
-
-        let total_dst_size: usize = dst_extents.iter().map(|e| e.len()).sum();
-
-        match op_type {
-            Type::Replace => {
-                let data = self.extract_data(op, payload)?;
-                self.run_op_replace_slice(
-                    data,
-                    &mut dst_extents,
+        let logged = log::log_enabled!(log::Level::Debug);
+        if ctx.audit.is_none() && !logged {
+            return self
+                .run_op_raw(
+                    ctx,
+                    op,
+                    payload,
+                    regions,
+                    partition_len,
                     block_size,
-                    total_dst_size,
+                    partition_name,
                     simd,
-                )?;
-                Ok(total_dst_size)
-            }
+                )
+                .map(|out| out.written);
+        }
 
-            Type::ReplaceBz => {
-                let data = self.extract_data(op, payload)?;
-                let mut decoder = BzDecoder::new(data);
-                self.run_op_replace(&mut decoder, &mut dst_extents, block_size, simd)?;
-                Ok(total_dst_size)
-            }
-            Type::ReplaceXz => {
-                let data = self.extract_data(op, payload)?;
-                let mut decoder = liblzma::read::XzDecoder::new(data);
-                self.run_op_replace(&mut decoder, &mut dst_extents, block_size, simd)?;
-                Ok(total_dst_size)
+        let started_us = ctx.audit.map(|audit| audit.elapsed_us());
+        let started = Instant::now();
+        let result = self.run_op_raw(
+            ctx,
+            op,
+            payload,
+            regions,
+            partition_len,
+            block_size,
+            partition_name,
+            simd,
+        );
+        let elapsed = started.elapsed();
+        if logged {
+            Self::log_op(partition_name, index, op, elapsed, &result);
+        }
+        if let (Some(audit), Some(started_us)) = (ctx.audit, started_us) {
+            audit.operation(
+                partition_name,
+                index,
+                op,
+                payload,
+                started_us,
+                elapsed,
+                &result,
+            )?;
+        }
+        result.map(|out| out.written)
+    }
+
+    /// `-vv`: one line per operation, with its payload blob and destination extents.
+    fn log_op(
+        partition_name: &str,
+        index: usize,
+        op: &InstallOperation,
+        elapsed: Duration,
+        result: &Result<OpOutput>,
+    ) {
+        let op_type = Type::try_from(op.r#type).map_or("UNKNOWN", |t| t.as_str_name());
+        let extents = op
+            .dst_extents
+            .iter()
+            .map(|e| {
+                format!(
+                    "{}+{}",
+                    e.start_block.unwrap_or(0),
+                    e.num_blocks.unwrap_or(0)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        match result {
+            Ok(out) => log::debug!(
+                "{partition_name}#{index} {op_type} data {}+{} -> [{extents}]: {} bytes in {:.3} ms",
+                op.data_offset.unwrap_or(0),
+                op.data_length.unwrap_or(0),
+                out.written,
+                elapsed.as_secs_f64() * 1000.0
+            ),
+            Err(e) => log::warn!("{partition_name}#{index} {op_type} -> [{extents}] failed: {e:#}"),
+        }
+    }
+
+    /// The core of otaripper's extraction. `regions` are the op's destination slices in the
+    /// output mapping, one per extent, from [`op_regions`](Self::op_regions).
+    ///
+    /// When `regions` is `None` the partition is written in [`IoMode::Pwrite`] mode: the op is
+    /// decoded into a scratch buffer which is then written to `ctx.out_file` extent by extent.
+    #[allow(clippy::too_many_arguments)]
+    #[inline(always)]
+    fn run_op_raw(
+        &self,
+        ctx: &WorkerContext,
+        op: &InstallOperation,
+        payload: &Payload,
+        regions: Option<&mut [&mut [u8]]>,
+        partition_len: usize,
+        block_size: usize,
+        partition_name: &str,
+        simd: CpuSimd,
+    ) -> Result<OpOutput> {
+        let op_type =
+            Type::try_from(op.r#type).map_err(|e| FailureKind::Unsupported.tag(e.into()))?;
+        let extents = self.extract_dst_extents(op, partition_len, block_size)?;
+        let total_dst_size: usize = extents.iter().map(|&(_, len)| len).sum();
+
+        // Held until the operation is done, so large ones take turns under --max-memory. While
+        // it is held, copies stay on this thread: waiting on a parallel copy, it could pick up
+        // another operation that then waits for this reservation.
+        let reserved = ctx.memory.map_or(0, |_| {
+            self.op_memory(op_type, regions.is_none(), total_dst_size)
+        });
+        let _reservation = ctx.memory.map(|budget| budget.reserve(reserved));
+        let parallel = reserved == 0;
+
+        let gathered;
+        let data = match op_type {
+            Type::Replace | Type::ReplaceBz | Type::ReplaceXz => self.extract_data(op, payload)?,
+            Type::SourceCopy if let Some(image) = ctx.source => {
+                gathered = self.source_copy_data(op, image, block_size)?;
+                &gathered[..]
             }
-            Type::Zero | Type::Discard => {
-                if ctx.zero_ops_are_noops {
-                    Ok(0) // no work done
-                } else {
-                    for extent in dst_extents.iter_mut() {
-                        extent.fill(0);
+            Type::Zero | Type::Discard if ctx.zero_ops_are_noops => {
+                // Nothing to write. The region of a fresh image is usually a hole already, but
+                // punching makes sure it takes no space, whatever the filesystem preallocated.
+                if ctx.punch_holes {
+                    for &(offset, len) in &extents {
+                        let _ = holes::punch(&ctx.out_file, offset as u64, len as u64);
                     }
-                    Ok(total_dst_size) // actual zeroing happened
                 }
+                return Ok(OpOutput {
+                    decoded: 0,
+                    written: 0,
+                });
             }
+            Type::Zero | Type::Discard => &[],
 
             // Catch-all for incremental types (Bsdiff, Brotli, etc.) or unknown future types
             _ => {
                 let type_name = format!("{:?}", op_type);
 
-                bail!(
+                return Err(FailureKind::Unsupported.tag(anyhow!(
                     "Operation type {} is not supported for full extraction in partition '{}'.",
                     type_name,
                     partition_name
-                )
+                )));
+            }
+        };
+
+        if op_type == Type::Replace
+            && let Some((input, data_base)) = ctx.input_file
+            && let [(dst_off, _)] = extents[..]
+            && data.len() >= 1024 * 1024
+            && data.len().div_ceil(block_size) * block_size == total_dst_size
+        {
+            // Kernel-side copy straight from the payload file; anything it couldn't move
+            // is finished by a regular copy.
+            let src_off = data_base + op.data_offset.unwrap_or(0);
+            let copied =
+                fastcopy::copy_range(input, src_off, &ctx.out_file, dst_off as u64, data.len());
+            if copied > 0 {
+                let rest = &data[copied..];
+                match regions {
+                    // One extent, so one region.
+                    Some(regions) => {
+                        simd_copy_large(simd, rest, &mut regions[0][copied..data.len()])
+                    }
+                    None => sink::write_all_at(&ctx.out_file, rest, (dst_off + copied) as u64)
+                        .context("failed to write to output file")?,
+                }
+                return Ok(OpOutput {
+                    decoded: data.len(),
+                    written: total_dst_size,
+                });
             }
         }
+
+        let Some(dst_extents) = regions else {
+            // BUFFERED PATH: decode into scratch memory, then flush each extent positionally
+            let mut scratch = vec![0u8; total_dst_size];
+            let mut dst_extents = Vec::with_capacity(extents.len());
+            let mut rest = scratch.as_mut_slice();
+            for &(_, len) in &extents {
+                let (head, tail) = rest.split_at_mut(len);
+                dst_extents.push(head);
+                rest = tail;
+            }
+            let decoded = self.write_op(
+                op_type,
+                data,
+                &mut dst_extents,
+                block_size,
+                total_dst_size,
+                simd,
+                parallel,
+            )?;
+
+            if let Some((queue, target)) = &ctx.write_behind {
+                // Written while this worker moves on; write_partition waits for it.
+                queue.submit(target, scratch, extents);
+            } else {
+                let mut pos = 0;
+                for &(offset, len) in &extents {
+                    sink::write_all_at(&ctx.out_file, &scratch[pos..pos + len], offset as u64)
+                        .context("failed to write to output file")?;
+                    pos += len;
+                }
+            }
+            return Ok(OpOutput {
+                decoded,
+                written: total_dst_size,
+            });
+        };
+
+        let decoded = self.write_op(
+            op_type,
+            data,
+            dst_extents,
+            block_size,
+            total_dst_size,
+            simd,
+            parallel,
+        )?;
+        Ok(OpOutput {
+            decoded,
+            written: total_dst_size,
+        })
+    }
+
+    /// Memory the multithreaded xz decoder may use: [`XZ_MT_MEMLIMIT`], or a quarter of the
+    /// operations' share of `--max-memory` if that is less.
+    fn xz_mt_memlimit(&self) -> u64 {
+        self.cmd.max_memory.map_or(XZ_MT_MEMLIMIT, |max| {
+            XZ_MT_MEMLIMIT.min(Shares::of(max).operations / 4)
+        })
+    }
+
+    /// Heap an operation allocates while it runs, for `--max-memory`: a scratch buffer the size
+    /// of its destination when it is `buffered` (pwrite mode) or gathers SOURCE_COPY blocks, and
+    /// the multithreaded xz decoder's state. Decoding into the output mapping allocates nothing;
+    /// those pages belong to the page cache, which the kernel writes back under pressure.
+    fn op_memory(&self, op_type: Type, buffered: bool, total_dst_size: usize) -> u64 {
+        let mut bytes = 0;
+        if buffered {
+            bytes += total_dst_size as u64;
+        }
+        match op_type {
+            Type::SourceCopy => bytes += total_dst_size as u64,
+            Type::ReplaceXz if total_dst_size >= XZ_MT_MIN_SIZE => bytes += self.xz_mt_memlimit(),
+            _ => {}
+        }
+        bytes
+    }
+
+    /// Applies a full-payload operation to already-validated destination extents. Returns the
+    /// number of bytes the op data decoded to. Large copies are split across the rayon pool only
+    /// when `parallel` is set.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn write_op(
+        &self,
+        op_type: Type,
+        data: &[u8],
+        dst_extents: &mut [&mut [u8]],
+        block_size: usize,
+        total_dst_size: usize,
+        simd: CpuSimd,
+        parallel: bool,
+    ) -> Result<usize> {
+        match op_type {
+            // SOURCE_COPY's data is the source blocks, already gathered.
+            Type::Replace | Type::SourceCopy => self.run_op_replace_slice(
+                data,
+                dst_extents,
+                block_size,
+                total_dst_size,
+                simd,
+                parallel,
+            ),
+            Type::ReplaceBz => {
+                let mut decoder = BzDecoder::new(data);
+                self.run_op_replace(&mut decoder, dst_extents, block_size, simd)
+            }
+            Type::ReplaceXz if total_dst_size >= XZ_MT_MIN_SIZE => {
+                // Streams written in several blocks with their sizes recorded (xz -T) are decoded
+                // in parallel; liblzma decodes anything else on one thread as usual.
+                let stream = liblzma::stream::MtStreamBuilder::new()
+                    .threads(rayon::current_num_threads().try_into().unwrap_or(u32::MAX))
+                    .memlimit_threading(self.xz_mt_memlimit())
+                    .memlimit_stop(u64::MAX)
+                    .decoder()
+                    .context("failed to set up multithreaded xz decoder")?;
+                let mut decoder = liblzma::read::XzDecoder::new_stream(data, stream);
+                self.run_op_replace(&mut decoder, dst_extents, block_size, simd)
+            }
+            Type::ReplaceXz => {
+                let mut decoder = liblzma::read::XzDecoder::new(data);
+                self.run_op_replace(&mut decoder, dst_extents, block_size, simd)
+            }
+            Type::Zero | Type::Discard => {
+                for extent in dst_extents.iter_mut() {
+                    extent.fill(0);
+                }
+                Ok(0)
+            }
+            _ => Err(FailureKind::Unsupported.tag(anyhow!(
+                "Operation type {:?} cannot be written directly",
+                op_type
+            ))),
+        }
     }
 
     fn run_op_replace(
@@ -1029,7 +2398,7 @@ impl<'a> Extractor<'a> {
         dst_extents: &mut [&mut [u8]],
         block_size: usize,
         simd: CpuSimd,
-    ) -> Result<()> {
+    ) -> Result<usize> {
         let dst_len = dst_extents.iter().map(|e| e.len()).sum::<usize>();
 
         // FAST PATH: Single extent zero-copy decompressive read directly into memory-mapped file
@@ -1060,7 +2429,7 @@ impl<'a> Extractor<'a> {
                 extra_bytes == 0,
                 "stream contained more data than extent capacity"
             );
-            return Ok(());
+            return Ok(total_read);
         }
 
         // BUFFERED PATH: Multi-extent using thread local 1MB buffer for optimal SIMD triggers
@@ -1090,7 +2459,7 @@ impl<'a> Extractor<'a> {
             bytes_read_aligned == dst_len,
             "more dst blocks than data, even with padding"
         );
-        Ok(())
+        Ok(total_read)
     }
 
     fn run_op_replace_slice(
@@ -1100,7 +2469,8 @@ impl<'a> Extractor<'a> {
         block_size: usize,
         total_dst_size: usize,
         simd: CpuSimd,
-    ) -> Result<()> {
+        parallel: bool,
+    ) -> Result<usize> {
         let bytes_read = data.len();
 
         let bytes_read_aligned = bytes_read.div_ceil(block_size) * block_size;
@@ -1116,13 +2486,31 @@ impl<'a> Extractor<'a> {
             let target = &mut dst[..bytes_read];
 
             // Large write-once buffers: avoid cache pollution
-            if bytes_read >= 1024 * 1024 {
-                simd_copy_large(simd, data, target);
-            } else {
+            if bytes_read < 1024 * 1024 {
                 target.copy_from_slice(data);
+            } else if parallel {
+                simd_copy_parallel(simd, data, target);
+            } else {
+                simd_copy_large(simd, data, target);
             }
 
-            return Ok(());
+            return Ok(bytes_read);
+        }
+
+        // GIANT OPS: every extent's share of the data is known up front, so the extents are
+        // copied in parallel, and large ones in parallel pieces.
+        if bytes_read >= PARALLEL_COPY_MIN && parallel {
+            let mut rest = data;
+            let mut copies = Vec::with_capacity(dst_extents.len());
+            for dst in dst_extents.iter_mut() {
+                let (src, tail) = rest.split_at(dst.len().min(rest.len()));
+                copies.push((src, &mut dst[..src.len()]));
+                rest = tail;
+            }
+            copies
+                .into_par_iter()
+                .for_each(|(src, dst)| simd_copy_parallel(simd, src, dst));
+            return Ok(bytes_read);
         }
 
         // GENERIC PATH: multiple extents
@@ -1135,13 +2523,113 @@ impl<'a> Extractor<'a> {
             "failed to write all data to destination extents"
         );
 
-        Ok(())
+        Ok(bytes_read)
+    }
+
+    /// Parses the payload once it is fully inflated and drops trailing data the manifest doesn't
+    /// reference, noting it the first time.
+    fn parse_payload<'s>(
+        &self,
+        bytes: &'s PayloadBytes,
+        manifest: &DeltaArchiveManifest,
+    ) -> Result<(&'s PayloadSource, Payload<'s>)> {
+        let source = bytes.wait()?;
+        // Because PayloadSource implements Deref, this call works seamlessly.
+        let mut payload = Payload::parse(source).map_err(|e| FailureKind::Parse.tag(e))?;
+        let trailing = payload.trim_trailing(manifest);
+        if trailing > 0 && bytes.first_parse() && self.cmd.chatty() {
+            eprintln!(
+                "Note: ignoring {} of trailing data after the last byte the manifest references.",
+                indicatif::HumanBytes(trailing as u64)
+            );
+        }
+        Ok((source, payload))
+    }
+
+    /// File names, without `.img`, of the selected partitions' images under `--rename-slot`.
+    /// Fails if two partitions would be written under the same name.
+    pub(super) fn output_stems<'m>(
+        &self,
+        manifest: &'m DeltaArchiveManifest,
+    ) -> Result<Vec<(&'m [u8], Vec<u8>)>> {
+        let mut owners: HashMap<Vec<u8>, &[u8]> = HashMap::new();
+        let mut stems = Vec::new();
+        for update in manifest.partitions.iter().filter(|u| self.is_selected(u)) {
+            let name = update.partition_name.as_slice();
+            let stem = self.cmd.rename_slot.apply(name);
+            if let Some(other) = owners.insert(stem.clone(), name)
+                && other != name
+            {
+                return Err(FailureKind::Usage.tag(anyhow!(
+                    "Both {} and {} would be written as {}.img with --rename-slot.\n\
+                     Hint: select one slot, e.g. --partitions boot --slot a, or name partitions \
+                     with their suffix.",
+                    String::from_utf8_lossy(other),
+                    update.name(),
+                    String::from_utf8_lossy(&stem),
+                )));
+            }
+            stems.push((name, stem));
+        }
+        Ok(stems)
+    }
+
+    /// Decodes a manifest, giving major version 1 (Chrome OS) ones the partition list of later
+    /// versions.
+    pub(super) fn decode_manifest(bytes: &[u8]) -> Result<DeltaArchiveManifest> {
+        let mut manifest =
+            DeltaArchiveManifest::decode(bytes).context("unable to parse manifest")?;
+        chromeos::upgrade_manifest(&mut manifest);
+        Ok(manifest)
+    }
+
+    /// The manifest's block size, checked to be one extraction can work with.
+    pub(super) fn block_size(manifest: &DeltaArchiveManifest) -> Result<usize> {
+        let block_size = manifest.block_size.context(
+            "The update file is missing critical metadata (block_size). It is likely corrupted.",
+        )? as usize;
+        ensure!(
+            (MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size),
+            "The update file has an invalid internal structure (block size {} is unsupported). It may be corrupted.",
+            block_size,
+        );
+        ensure!(
+            block_size.is_power_of_two(),
+            "The update file is malformed: block size {} is not a power of two.",
+            block_size,
+        );
+        Ok(block_size)
+    }
+
+    /// Reads just the payload header and manifest: nothing is mapped or spilled to a temp file,
+    /// and a compressed payload.bin is only inflated as far as the manifest.
+    pub(super) fn read_payload_header(path: &Path) -> Result<PayloadHeader> {
+        let mut file = File::open(path)
+            .with_context(|| format!("unable to open file for reading: {path:?}"))?;
+        let mut magic = [0u8; 4];
+        let is_zip = file.read_exact(&mut magic).is_ok() && &magic == b"PK\x03\x04";
+        file.seek(std::io::SeekFrom::Start(0))?;
+
+        // Past opening the file, anything that goes wrong means the input is not a payload.
+        if is_zip {
+            let mut archive = ZipArchive::new(&file)
+                .context("File has ZIP magic but is not a valid ZIP archive")
+                .map_err(|e| FailureKind::Parse.tag(e))?;
+            if let Ok(entry) = archive.by_name("payload.bin") {
+                return Payload::parse_header(io::BufReader::new(entry))
+                    .map_err(|e| FailureKind::Parse.tag(e));
+            }
+        }
+        Payload::parse_header(io::BufReader::new(&file)).map_err(|e| FailureKind::Parse.tag(e))
     }
 
-    fn open_payload_file(&self, path: &Path) -> Result<PayloadSource> {
-        use sysinfo::System;
-        use tempfile::NamedTempFile;
+    pub(super) fn open_payload_file(&self, path: &Path) -> Result<PayloadSource> {
+        self.start_payload(path)?.into_source()
+    }
 
+    /// Opens the payload. A raw payload.bin, or one stored uncompressed in the zip, is mapped in
+    /// place; a deflated one starts inflating on a thread of its own.
+    pub(super) fn start_payload(&self, path: &Path) -> Result<PayloadBytes> {
         // 1. Open the file and peek magic bytes to identify format
         let mut file = File::open(path)
             .with_context(|| format!("unable to open file for reading: {path:?}"))?;
@@ -1170,88 +2658,88 @@ impl<'a> Extractor<'a> {
             let mut archive = ZipArchive::new(&file)
                 .context("File has ZIP magic but is not a valid ZIP archive")?;
 
-            if let Ok(mut zipfile) = archive.by_name("payload.bin") {
-                let payload_size = zipfile.size();
-
-                // LIGHTWEIGHT RAM CHECK: Only refresh memory stats to minimize overhead
-                let mut sys = System::new_with_specifics(
-                    RefreshKind::nothing().with_memory(MemoryRefreshKind::nothing().with_ram()),
-                );
-                sys.refresh_memory();
-                let available_ram = sys.available_memory();
-
-                // HEURISTIC: Use temp file if payload > 50% available RAM to avoid OOM or Swap lag
-                if payload_size > available_ram / 2 {
-                    eprintln!(
-                        "⚠️ Large payload detected ({}). Available RAM: {}. Using localized temp file for safety.",
-                        indicatif::HumanBytes(payload_size),
-                        indicatif::HumanBytes(available_ram)
-                    );
-
-                    // LOCALIZED TEMP: Create in output dir to prevent cross-partition copy performance hits
-                    let temp_file = if let Some(ref out_dir) = self.cmd.output_dir {
-                        fs::create_dir_all(out_dir)?;
-                        NamedTempFile::new_in(out_dir)
-                    } else {
-                        NamedTempFile::new()
-                    }
-                    .context("Failed to create temporary file for payload extraction")?;
-
-                    // Stream directly from ZIP to Disk
-                    io::copy(&mut zipfile, &mut temp_file.as_file())
-                        .context("Failed to stream payload.bin from ZIP to disk")?;
-
-                    // SYNC: Ensure data is physically committed before mapping for correctness
-                    temp_file.as_file().sync_all()?;
-
-                    let mmap = unsafe { Mmap::map(temp_file.as_file()) }
-                        .context("Failed to mmap streamed payload")?;
-
-                    return Ok(PayloadSource::Temp(mmap, temp_file));
+            let deflated = match archive.by_name("payload.bin") {
+                // STORED PATH: Uncompressed entries are mapped in place, no copy at all
+                Ok(zipfile)
+                    if zipfile.compression() == zip::CompressionMethod::Stored
+                        && !zipfile.encrypted()
+                        && let Some(start) = zipfile.data_start() =>
+                {
+                    let len = usize::try_from(zipfile.size())
+                        .context("payload.bin is too large to map on this system")?;
+                    let mmap = unsafe { MmapOptions::new().offset(start).len(len).map(&file) }
+                        .with_context(|| format!("failed to mmap payload.bin inside {path:?}"))?;
+                    pagecache::sequential(&mmap);
+                    let source = PayloadSource::Mapped(mmap, file.try_clone()?, start);
+                    return Ok(PayloadBytes::ready(source));
                 }
-
-                // RAM PATH: Small enough to fit comfortably in memory
-                let mut buffer = Vec::with_capacity(payload_size as usize);
-                zipfile
-                    .read_to_end(&mut buffer)
-                    .context("Failed to read payload.bin from ZIP into RAM")?;
-                return Ok(PayloadSource::Owned(buffer));
+                Ok(_) => true,
+                Err(_) => false,
+            };
+            drop(archive);
+
+            if deflated {
+                // --temp-dir wins. Otherwise a network share is no place for a spilled payload;
+                // every read would cross the wire.
+                let spill_dir = self.cmd.temp_dir.clone().or_else(|| {
+                    self.cmd
+                        .output_dir
+                        .clone()
+                        .filter(|dir| !fsprobe::is_remote(dir))
+                });
+                return Ok(PayloadBytes::inflate(
+                    file,
+                    spill_dir,
+                    self.cmd.low_memory,
+                    self.cmd
+                        .max_memory
+                        .map(|max| Shares::of(max).in_ram_payload),
+                    self.interrupt.clone(),
+                ));
             }
         }
 
         // 3. CASE: Raw payload.bin (Zero-copy mapping)
         let mmap = unsafe { Mmap::map(&file) }
             .with_context(|| format!("failed to mmap raw payload file: {path:?}"))?;
+        pagecache::sequential(&mmap);
 
-        Ok(PayloadSource::Mapped(mmap))
+        Ok(PayloadBytes::ready(PayloadSource::Mapped(mmap, file, 0)))
     }
 
     fn open_partition_file(
         &self,
         update: &PartitionUpdate,
         partition_dir: impl AsRef<Path>,
-    ) -> Result<(Arc<MmapMut>, usize, PathBuf)> {
+        file_name: &OsStr,
+        io_mode: IoMode,
+    ) -> Result<(Option<MmapMut>, File, usize, PathBuf)> {
         let partition_len = update
             .new_partition_info
             .as_ref()
             .and_then(|info| info.size)
             .context("unable to determine output file size")?;
-
-        let filename = Path::new(&update.partition_name).with_extension("img");
-        let path: PathBuf = partition_dir.as_ref().join(filename);
-
+        let len = usize::try_from(partition_len)
+            .context("partition is too large to extract on this platform")?;
+
+        let path: PathBuf = partition_dir.as_ref().join(file_name);
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .with_context(|| format!("unable to open file for writing: {path:?}"))?;
+        // Before sizing it, so ZERO/DISCARD regions stay holes on Windows too. Filesystems
+        // without holes refuse, and the image is then allocated in full as before.
+        holes::make_sparse(&file);
+        file.set_len(partition_len)?;
+        if io_mode == IoMode::Pwrite {
+            return Ok((None, file, len, path));
+        }
         #[cfg_attr(not(target_os = "linux"), allow(unused_mut))]
-        let mut mmap = {
-            let file = OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create_new(true)
-                .open(&path)
-                .with_context(|| format!("unable to open file for writing: {path:?}"))?;
-            file.set_len(partition_len)?;
-            unsafe { MmapMut::map_mut(&file) }
-                .with_context(|| format!("failed to mmap file: {path:?}"))?
-        };
+        let mut mmap = unsafe { MmapMut::map_mut(&file) }
+            .with_context(|| format!("failed to mmap file: {path:?}"))?;
         // Linux-only sequential access hint for mmap writes
         #[cfg(target_os = "linux")]
         {
@@ -1265,26 +2753,16 @@ impl<'a> Extractor<'a> {
             }
         }
 
-        let partition = Arc::new(mmap);
-        Ok((partition, partition_len as usize, path))
+        Ok((Some(mmap), file, len, path))
     }
 
-    fn extract_data<'b>(&self, op: &InstallOperation, payload: &'b Payload) -> Result<&'b [u8]> {
-        let data_len = op.data_length.context("data_length not defined")? as usize;
-        let offset = op.data_offset.context("data_offset not defined")? as usize;
-
-        let end_offset = offset
-            .checked_add(data_len)
-            .context("data_offset + data_length overflows")?;
-        ensure!(
-            end_offset <= payload.data.len(),
-            "data range {}..{} exceeds payload size {}",
-            offset,
-            end_offset,
-            payload.data.len()
-        );
-
-        let data = &payload.data[offset..end_offset];
+    pub(super) fn extract_data<'b>(
+        &self,
+        op: &InstallOperation,
+        payload: &'b Payload,
+    ) -> Result<&'b [u8]> {
+        // Data past the end of the file means a truncated download, not a bug.
+        let data = payload.op_data(op).map_err(|e| FailureKind::Parse.tag(e))?;
 
         if !self.cmd.no_verify
             && let Some(hash) = &op.data_sha256_hash
@@ -1295,53 +2773,73 @@ impl<'a> Extractor<'a> {
         Ok(data)
     }
 
-    /// Extracts destination extents as (pointer, length) pairs — safe for concurrent use.
-    fn extract_dst_extents_raw(
+    /// Splits `image` into the destination slices of every operation, one per extent in the
+    /// operation's extent order. Each operation gets slices no other operation can reach, so
+    /// they can all be written at once.
+    fn op_regions<'i>(
         &self,
-        op: &InstallOperation,
-        base_ptr: *mut u8,
-        partition_len: usize,
+        ops: &[InstallOperation],
+        image: &'i mut [u8],
         block_size: usize,
-    ) -> Result<Vec<(*mut u8, usize)>> {
-        let mut out = Vec::with_capacity(op.dst_extents.len());
-        for extent in &op.dst_extents {
-            let start_block = extent.start_block.context("missing start_block")? as usize;
-            let num_blocks = extent.num_blocks.context("missing num_blocks")? as usize;
-
-            let start = start_block
-                .checked_mul(block_size)
-                .context("start_block * block_size overflows")?;
-            let len = num_blocks
-                .checked_mul(block_size)
-                .context("num_blocks * block_size overflows")?;
-
-            ensure!(len != 0, "extent length cannot be zero");
-
+    ) -> Result<Vec<Vec<&'i mut [u8]>>> {
+        // (byte offset, length, op index), in op and extent order
+        let mut extents = Vec::with_capacity(ops.len());
+        for (index, op) in ops.iter().enumerate() {
+            let ranges = self
+                .extract_dst_extents(op, image.len(), block_size)
+                .with_context(|| format!("operation #{index}"))?;
+            extents.extend(ranges.into_iter().map(|(offset, len)| (offset, len, index)));
+        }
+        let mut by_offset: Vec<usize> = (0..extents.len()).collect();
+        by_offset.sort_unstable_by_key(|&i| extents[i].0);
+
+        // Cut the extents off the front of the image in offset order.
+        let mut pieces = Vec::with_capacity(extents.len());
+        let mut rest = image;
+        let mut consumed = 0;
+        for i in by_offset {
+            let (offset, len, index) = extents[i];
             ensure!(
-                start + len <= partition_len,
-                "extent {}..{} exceeds partition size {}",
-                start,
-                start + len,
-                partition_len
+                offset >= consumed,
+                "operation #{index} writes blocks another operation also writes"
             );
+            let (_, tail) = std::mem::take(&mut rest).split_at_mut(offset - consumed);
+            let (piece, tail) = tail.split_at_mut(len);
+            rest = tail;
+            consumed = offset + len;
+            pieces.push((i, piece));
+        }
 
-            let ptr = unsafe { base_ptr.add(start) };
-            out.push((ptr, len));
+        pieces.sort_unstable_by_key(|&(i, _)| i);
+        let mut regions: Vec<Vec<&mut [u8]>> = ops.iter().map(|_| Vec::new()).collect();
+        for (i, piece) in pieces {
+            regions[extents[i].2].push(piece);
         }
-        Ok(out)
+        Ok(regions)
+    }
+
+    /// Extracts destination extents as (byte offset, length) pairs, validated against the
+    /// partition size, with adjacent extents merged.
+    pub(super) fn extract_dst_extents(
+        &self,
+        op: &InstallOperation,
+        partition_len: usize,
+        block_size: usize,
+    ) -> Result<Vec<(usize, usize)>> {
+        payload::extent_ranges(&op.dst_extents, block_size, partition_len)
+            .map(payload::coalesce_extents)
     }
     // Same as verify_sha256, but returns the computed digest on success so it can be reused.
     fn verify_sha256_returning(&self, data: &[u8], exp_hash: &[u8]) -> Result<[u8; 32]> {
-        let got = digest(&SHA256, data);
-        ensure!(
-            got.as_ref() == exp_hash,
-            "hash mismatch: expected {}, got {}",
-            hex::encode(exp_hash),
-            hex::encode(got.as_ref())
-        );
-        let mut out = [0u8; 32];
-        out.copy_from_slice(got.as_ref());
-        Ok(out)
+        let got = crypto::sha256(data);
+        if got.as_slice() != exp_hash {
+            return Err(FailureKind::Verification.tag(anyhow!(
+                "hash mismatch: expected {}, got {}",
+                hex::encode(exp_hash),
+                hex::encode(got)
+            )));
+        }
+        Ok(got)
     }
 
     fn verify_sha256(&self, data: &[u8], exp_hash: &[u8]) -> Result<()> {
@@ -1352,10 +2850,14 @@ impl<'a> Extractor<'a> {
     /// Validates that all dst_extents across all InstallOperations are non-overlapping.
     /// Implementation uses an O(n log n) sorted interval sweep.
     /// This is acceptable because extents per partition are typically small.
-    fn validate_non_overlapping_extents(&self, operations: &[InstallOperation]) -> Result<()> {
-        let mut extents: Vec<(u64, u64)> = Vec::with_capacity(operations.len() * 2);
+    pub(super) fn validate_non_overlapping_extents(
+        &self,
+        operations: &[InstallOperation],
+    ) -> Result<()> {
+        // (start block, end block, op index)
+        let mut extents: Vec<(u64, u64, usize)> = Vec::with_capacity(operations.len() * 2);
 
-        for op in operations {
+        for (index, op) in operations.iter().enumerate() {
             for e in &op.dst_extents {
                 let start = e.start_block.context("missing start_block")?;
                 let num = e.num_blocks.context("missing num_blocks")?;
@@ -1367,7 +2869,7 @@ impl<'a> Extractor<'a> {
                     continue;
                 }
 
-                extents.push((start, end));
+                extents.push((start, end, index));
             }
         }
 
@@ -1379,31 +2881,54 @@ impl<'a> Extractor<'a> {
         // -------------------------
         // O(N log N) INTERVAL CHECK
         // -------------------------
-        extents.sort_unstable_by_key(|(s, _)| *s);
+        extents.sort_unstable_by_key(|&(s, _, _)| s);
 
         for w in extents.windows(2) {
             let prev = w[0];
             let curr = w[1];
 
-            ensure!(
-                curr.0 >= prev.1,
-                "Overlapping destination extents detected: {} < {}",
-                curr.0,
-                prev.1
-            );
+            if curr.0 < prev.1 {
+                let what = if prev.2 == curr.2 {
+                    format!("operation #{} writes", curr.2)
+                } else {
+                    format!(
+                        "operations #{} and #{} both write",
+                        prev.2.min(curr.2),
+                        prev.2.max(curr.2)
+                    )
+                };
+                return Err(FailureKind::Parse.tag(anyhow!(
+                    "{what} blocks {}..{}. Overlapping destination extents are refused: \
+                     the result would depend on which thread finishes last. \
+                     The payload is malformed or has been tampered with.",
+                    curr.0,
+                    prev.1.min(curr.1)
+                )));
+            }
         }
 
         Ok(())
     }
 
-    fn create_partition_dir(&self) -> Result<(PathBuf, bool)> {
-        let dir = match &self.cmd.output_dir {
-            Some(output_base) => {
+    /// Creates the output directory: `default_dir` (an output map's `default`) or `--resume`'s
+    /// folder as given, else --output-dir itself with `--flat`, else a timestamped folder under
+    /// --output-dir or the current directory.
+    pub(super) fn create_partition_dir(
+        &self,
+        default_dir: Option<&Path>,
+    ) -> Result<(PathBuf, bool)> {
+        let dir = match (
+            default_dir.or(self.cmd.resume.as_deref()),
+            &self.cmd.output_dir,
+        ) {
+            (Some(dir), _) => dir.to_path_buf(),
+            (None, Some(output_base)) if self.cmd.flat => output_base.clone(),
+            (None, Some(output_base)) => {
                 let now = Local::now();
                 let timestamp_folder = format!("{}", now.format("extracted_%Y-%m-%d_%H-%M-%S"));
                 output_base.join(timestamp_folder)
             }
-            None => {
+            (None, None) => {
                 let now = Local::now();
                 let current_dir = env::current_dir().with_context(|| {
                     "Failed to determine current directory. Please specify --output-dir explicitly."
@@ -1418,7 +2943,16 @@ impl<'a> Extractor<'a> {
         Ok((dir, !existed))
     }
 
-    fn get_threadpool(&self) -> Result<ThreadPool> {
+    /// The pool written images are hashed and checked on while `threadpool` goes on decoding:
+    /// a quarter of its size, at least one thread.
+    fn get_verify_pool(&self, threadpool: &ThreadPool) -> Result<ThreadPool> {
+        ThreadPoolBuilder::new()
+            .num_threads((threadpool.current_num_threads() / 4).max(1))
+            .build()
+            .context("unable to start verification threadpool")
+    }
+
+    pub(super) fn get_threadpool(&self) -> Result<ThreadPool> {
         let mut builder = ThreadPoolBuilder::new();
         if let Some(t) = self.cmd.threads
             && t > 0
@@ -1429,23 +2963,27 @@ impl<'a> Extractor<'a> {
     }
 
     /// Calculate and display the total size of the extracted folder
-    fn display_extracted_folder_size(&self, partition_dir: impl AsRef<Path>) -> Result<()> {
+    pub(super) fn display_extracted_folder_size(&self, partition_dir: impl AsRef<Path>) -> Result<()> {
         let dir_path = partition_dir.as_ref();
 
         // Calculate total size recursively
         let total_size = self.calculate_directory_size(dir_path)?;
 
         // Display the result
-        println!("\nExtraction completed successfully!");
-        println!("Output directory: {}", dir_path.display());
+        println!("\n{}", Msg::Completed.text());
+        println!(
+            "{}",
+            Msg::OutputDirectory.fill(&[&fsprobe::display_path(dir_path).display()])
+        );
         println!(
-            "Total extracted size: {}",
-            indicatif::HumanBytes(total_size)
+            "{}",
+            Msg::TotalSize.fill(&[&indicatif::HumanBytes(total_size)])
         );
         let bold_bright_blue = Style::new().bold().blue();
         println!(
-            "Tool Source: {}",
-            bold_bright_blue.apply_to("https://github.com/syedinsaf/otaripper")
+            "{}",
+            Msg::ToolSource
+                .fill(&[&bold_bright_blue.apply_to("https://github.com/syedinsaf/otaripper")])
         );
         Ok(())
     }
@@ -1481,21 +3019,26 @@ impl<'a> Extractor<'a> {
     }
 
     /// Automatically open the extracted folder in the default file manager
-    fn open_extracted_folder(&self, partition_dir: impl AsRef<Path>) -> Result<()> {
+    pub(super) fn open_extracted_folder(&self, partition_dir: impl AsRef<Path>) -> Result<()> {
         let dir_path = partition_dir.as_ref();
 
         // Only attempt to open if the directory exists
         if !dir_path.exists() {
-            eprintln!("Warning: Output directory does not exist, cannot open folder");
+            eprintln!("{}", Msg::OutputDirMissing.text());
             return Ok(());
         }
 
+        if let Some(command) = &self.config.open_with {
+            return run_open_with(command, &fsprobe::display_path(dir_path));
+        }
+
         // Cross-platform folder opening
         cfg_select! {
             target_os = "windows" => {
                 use std::process::Command;
+                // explorer.exe does not understand the \\?\ verbatim prefix
                 let _ = Command::new("explorer")
-                    .arg(dir_path)
+                    .arg(fsprobe::display_path(dir_path))
                     .spawn()
                     .map_err(|e| eprintln!("Warning: Failed to open folder: {}", e));
             }
@@ -1527,18 +3070,530 @@ impl<'a> Extractor<'a> {
 
         Ok(())
     }
-    #[inline]
-    fn is_incremental_partition(p: &PartitionUpdate) -> bool {
-        p.operations.iter().any(|op| {
-            matches!(
-                Type::try_from(op.r#type),
-                Ok(Type::SourceCopy
-                    | Type::SourceBsdiff
-                    | Type::BrotliBsdiff
-                    | Type::Lz4diffBsdiff
-                    | Type::Puffdiff
-                    | Type::Zucchini)
+
+    /// Keys from the package certificate (`META-INF/com/android/otacert`) of the OTA zip at `path`.
+    /// `failed` and `option` name the check that needs them and how to pass a key instead.
+    fn package_keys(&self, path: &Path, failed: &str, option: &str) -> Result<Vec<PublicKey>> {
+        let cert = Self::read_zip_entry(path, sigverify::OTACERT_PATH)?.with_context(|| {
+            format!(
+                "{failed}: no signing certificate to verify against. \
+                 Only OTA zips carry {}; use {option} to verify a raw payload.bin.",
+                sigverify::OTACERT_PATH
             )
-        })
+        })?;
+        PublicKey::parse_all(&cert, sigverify::OTACERT_PATH)
+            .context("unable to read the package certificate")
+    }
+
+    /// Checks the signed half of the `--verify-chain` trust chain (metadata and payload
+    /// signatures) and returns the key that signed both blobs.
+    fn verify_signature_chain(
+        &self,
+        keys: &[PublicKey],
+        anchor: &str,
+        bytes: &[u8],
+        payload: &Payload,
+        manifest: &DeltaArchiveManifest,
+    ) -> Result<PublicKey> {
+        let broken = "Chain of trust verification failed";
+
+        let metadata_signer = self.verify_metadata_signature(keys, anchor, payload, broken)?;
+        let payload_signer =
+            self.verify_payload_signature(keys, anchor, bytes, payload, manifest, broken)?;
+        ensure!(
+            metadata_signer.fingerprint() == payload_signer.fingerprint(),
+            "{broken}: metadata and payload are signed with different keys"
+        );
+        Ok(payload_signer)
+    }
+
+    /// Checks the metadata signature, which covers the header and manifest, and returns the key
+    /// that made it.
+    fn verify_metadata_signature(
+        &self,
+        keys: &[PublicKey],
+        anchor: &str,
+        payload: &Payload,
+        failed: &str,
+    ) -> Result<PublicKey> {
+        let metadata_sig = payload.metadata_signature().with_context(|| {
+            format!("{failed}: the payload has no metadata signature (format version 1)")
+        })?;
+        let signer = sigverify::find_signer(keys, payload.metadata(), metadata_sig)
+            .with_context(|| format!("{failed}: unreadable metadata signature"))?
+            .with_context(|| format!("{failed}: the metadata signature does not match {anchor}"))?;
+        Ok(signer.clone())
+    }
+
+    /// Checks the payload signature, which covers everything in front of the signature blob, and
+    /// returns the key that made it.
+    fn verify_payload_signature(
+        &self,
+        keys: &[PublicKey],
+        anchor: &str,
+        bytes: &[u8],
+        payload: &Payload,
+        manifest: &DeltaArchiveManifest,
+        failed: &str,
+    ) -> Result<PublicKey> {
+        let (signed, sig_blob) = sigverify::payload_signature(bytes, payload, manifest)
+            .with_context(|| format!("{failed}: malformed payload signature"))?
+            .with_context(|| {
+                format!("{failed}: the payload is not signed (manifest has no signatures_offset)")
+            })?;
+        let signer = sigverify::find_signer(keys, signed, sig_blob)
+            .with_context(|| format!("{failed}: unreadable payload signature"))?
+            .with_context(|| format!("{failed}: the payload signature does not match {anchor}"))?;
+        Ok(signer.clone())
+    }
+
+    /// Reads a small file from the OTA zip at `path`. Returns `None` when `path` is not a zip or
+    /// has no such entry.
+    pub(super) fn read_zip_entry(path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+        let file = File::open(path).with_context(|| format!("unable to open file: {path:?}"))?;
+        let Ok(mut archive) = ZipArchive::new(file) else {
+            return Ok(None);
+        };
+        let Ok(mut entry) = archive.by_name(name) else {
+            return Ok(None);
+        };
+        let mut buf = Vec::with_capacity(entry.size() as usize);
+        entry
+            .read_to_end(&mut buf)
+            .with_context(|| format!("failed to read {name} from {path:?}"))?;
+        Ok(Some(buf))
+    }
+
+    /// Whether `update` was picked with `--partitions` (everything is picked when none were given)
+    /// and not left out with `--skip-partitions`.
+    pub(super) fn is_selected(&self, update: &PartitionUpdate) -> bool {
+        let selection = self.selection();
+        (selection.is_empty() || selection.iter().any(|p| self.name_matches(update, p)))
+            && !self.skipped().iter().any(|p| self.name_matches(update, p))
+            && self
+                .unchanged
+                .get()
+                .is_none_or(|unchanged| !unchanged.contains(&*update.name()))
+    }
+
+    /// Whether `update` is selected and still to be extracted, i.e. not finished by an earlier
+    /// `--resume` run.
+    fn is_pending(&self, update: &PartitionUpdate) -> bool {
+        self.is_selected(update)
+            && self
+                .finished
+                .get()
+                .is_none_or(|finished| !finished.contains(&*update.name()))
+    }
+
+    /// Resolves `--partitions` and `--skip-partitions` against `manifest` for the rest of the
+    /// run; only the first call takes effect. False when `--changed-since` leaves nothing to
+    /// extract, which is not an error.
+    pub(super) fn select_partitions(&self, manifest: &DeltaArchiveManifest) -> Result<bool> {
+        let usage = |e| FailureKind::Usage.tag(e);
+        let selection = self
+            .resolve_selection(manifest, &self.cmd.partitions)
+            .map_err(usage)?;
+        let skipped = self
+            .resolve_selection(manifest, &self.cmd.skip_partitions)
+            .map_err(usage)?;
+        let _ = self.selection.set(selection);
+        let _ = self.skipped.set(skipped);
+        if !self.cmd.skip_partitions.is_empty()
+            && !manifest.partitions.iter().any(|u| self.is_selected(u))
+        {
+            return Err(usage(anyhow!(
+                "--skip-partitions leaves no partition to extract"
+            )));
+        }
+        if let Some(reference) = &self.cmd.changed_since
+            && self.unchanged.get().is_none()
+        {
+            let unchanged = Self::unchanged_since(reference, manifest)?;
+            let skipped: Vec<_> = manifest
+                .partitions
+                .iter()
+                .filter(|u| self.is_selected(u) && unchanged.contains(&*u.name()))
+                .map(|u| u.name())
+                .collect();
+            if !skipped.is_empty() && self.cmd.chatty() {
+                eprintln!(
+                    "Note: unchanged since {}, so not extracted: {}",
+                    reference.display(),
+                    skipped.join(", ")
+                );
+            }
+            let _ = self.unchanged.set(unchanged);
+            if !manifest.partitions.iter().any(|u| self.is_selected(u)) {
+                if self.cmd.chatty() {
+                    eprintln!(
+                        "No selected partition changed since {}; nothing to extract.",
+                        reference.display()
+                    );
+                }
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// `--changed-since`: the partitions of `manifest` whose image has the same size and
+    /// SHA-256 in `reference`'s manifest. Only `reference`'s manifest is read. A partition
+    /// either manifest gives no hash for counts as changed.
+    fn unchanged_since(
+        reference: &Path,
+        manifest: &DeltaArchiveManifest,
+    ) -> Result<HashSet<String>> {
+        let header = Self::read_payload_header(reference).with_context(|| {
+            format!(
+                "unable to read the --changed-since payload {}",
+                reference.display()
+            )
+        })?;
+        let reference_manifest = Self::decode_manifest(header.manifest())?;
+        let images: HashMap<&[u8], &PartitionInfo> = reference_manifest
+            .partitions
+            .iter()
+            .filter_map(|u| Some((u.partition_name.as_slice(), u.new_partition_info.as_ref()?)))
+            .collect();
+        Ok(manifest
+            .partitions
+            .iter()
+            .filter(|u| {
+                let (Some(new), Some(old)) = (
+                    u.new_partition_info.as_ref(),
+                    images.get(u.partition_name.as_slice()),
+                ) else {
+                    return false;
+                };
+                new.hash.is_some() && new.hash == old.hash && new.size == old.size
+            })
+            .map(|u| u.name().into_owned())
+            .collect())
+    }
+
+    /// The names picked with `--partitions`, aliases expanded once the manifest has been read.
+    fn selection(&self) -> &[String] {
+        self.selection.get().unwrap_or(&self.cmd.partitions)
+    }
+
+    /// The names left out with `--skip-partitions`, aliases expanded likewise.
+    fn skipped(&self) -> &[String] {
+        self.skipped.get().unwrap_or(&self.cmd.skip_partitions)
+    }
+
+    /// Checks every name in `partitions` against the manifest. A name no partition answers to
+    /// is expanded when it is an alias, to those of its targets the payload has; anything
+    /// still unmatched is an error with suggestions.
+    fn resolve_selection(
+        &self,
+        manifest: &DeltaArchiveManifest,
+        partitions: &[String],
+    ) -> Result<Vec<String>> {
+        let aliases = Aliases::new(&self.cmd.aliases)?;
+        let present = |name: &str| {
+            manifest
+                .partitions
+                .iter()
+                .any(|p| self.name_matches(p, name))
+        };
+
+        let mut selection: Vec<String> = Vec::new();
+        for partition in partitions {
+            if present(partition) {
+                selection.push(partition.clone());
+                continue;
+            }
+            let targets: Vec<&String> = aliases
+                .get(partition)
+                .unwrap_or_default()
+                .iter()
+                .filter(|t| present(t))
+                .collect();
+            if !targets.is_empty() {
+                if self.cmd.chatty() {
+                    let names: Vec<&str> = targets.iter().map(|t| t.as_str()).collect();
+                    eprintln!(
+                        "Note: no partition is named \"{partition}\"; selecting {} instead.",
+                        names.join(", ")
+                    );
+                }
+                selection.extend(targets.into_iter().cloned());
+                continue;
+            }
+
+            let names: Vec<_> = manifest.partitions.iter().map(|p| p.name()).collect();
+            let names: Vec<&str> = names.iter().map(|n| n.as_ref()).collect();
+            let mut available = names.clone();
+            available.sort_unstable();
+            ensure!(
+                !is_pattern(partition),
+                "pattern \"{}\" matches no partition in the manifest\nAvailable partitions: {}",
+                partition,
+                available.join(", ")
+            );
+            let hint = match suggest::similar(partition, &names)[..] {
+                [] => String::new(),
+                [one] => format!("\nDid you mean \"{one}\"?"),
+                ref many => format!("\nDid you mean one of: {}?", many.join(", ")),
+            };
+            bail!(
+                "partition \"{}\" not found in manifest{hint}\nAvailable partitions: {}",
+                partition,
+                available.join(", ")
+            );
+        }
+        selection.dedup();
+        Ok(selection)
+    }
+
+    /// Matches a user-supplied name against the raw manifest name, or against its lossy display
+    /// form so names that aren't valid UTF-8 can still be selected as `-l` prints them. Slot
+    /// suffixes are optional on both sides: `boot` matches `boot_a`/`boot_b` as limited by
+    /// `--slot`, and `boot_a` matches a payload that just has `boot`. A name with `*` or `?` is
+    /// a pattern, matched the same way: `*_dlkm` picks `vendor_dlkm_a` as limited by `--slot`.
+    pub(super) fn name_matches(&self, update: &PartitionUpdate, name: &str) -> bool {
+        if is_pattern(name) {
+            let full = update.name();
+            return match Slot::All
+                .suffixes()
+                .iter()
+                .find_map(|suffix| Some((full.strip_suffix(suffix)?, suffix)))
+            {
+                Some((base, suffix)) => {
+                    let slot_ok = self.cmd.slot.suffixes().contains(suffix);
+                    (glob_match(name, &full) && (slot_ok || name.ends_with(suffix)))
+                        || (slot_ok && glob_match(name, base))
+                }
+                None => glob_match(name, &full),
+            };
+        }
+        let exact = |n: &str| update.partition_name == n.as_bytes() || update.name() == n;
+        exact(name)
+            || self
+                .cmd
+                .slot
+                .suffixes()
+                .iter()
+                .any(|suffix| exact(&format!("{name}{suffix}")))
+            || Slot::All
+                .suffixes()
+                .iter()
+                .any(|suffix| name.strip_suffix(suffix).is_some_and(exact))
+    }
+
+    /// Whether every block of the image is written exactly once by a `REPLACE`, `REPLACE_BZ`
+    /// or `REPLACE_XZ` operation carrying a data hash. Once those hashes check out, the image
+    /// holds nothing the partition hash would verify that they didn't already.
+    fn op_hashes_cover_image(update: &PartitionUpdate, block_size: usize) -> bool {
+        let Some(size) = update.new_partition_info.as_ref().and_then(|i| i.size) else {
+            return false;
+        };
+        let mut extents = Vec::new();
+        for op in &update.operations {
+            let hashed_replace = matches!(
+                Type::try_from(op.r#type),
+                Ok(Type::Replace | Type::ReplaceBz | Type::ReplaceXz)
+            ) && op.data_sha256_hash.is_some();
+            if !hashed_replace {
+                return false;
+            }
+            extents.extend(
+                op.dst_extents
+                    .iter()
+                    .map(|e| (e.start_block.unwrap_or(0), e.num_blocks.unwrap_or(0))),
+            );
+        }
+        extents.sort_unstable();
+        let mut next_block = 0u64;
+        for (start, num_blocks) in extents {
+            if start != next_block {
+                return false;
+            }
+            next_block = start.saturating_add(num_blocks);
+        }
+        next_block.checked_mul(block_size as u64) == Some(size)
+    }
+
+    /// Total bytes covered by ZERO/DISCARD operations in a partition.
+    fn zero_extent_bytes(update: &PartitionUpdate, block_size: usize) -> u64 {
+        update
+            .operations
+            .iter()
+            .filter(|op| matches!(Type::try_from(op.r#type), Ok(Type::Zero | Type::Discard)))
+            .flat_map(|op| &op.dst_extents)
+            .map(|e| e.num_blocks.unwrap_or(0).saturating_mul(block_size as u64))
+            .fold(0, u64::saturating_add)
+    }
+
+    #[inline]
+    pub(super) fn is_incremental_partition(p: &PartitionUpdate) -> bool {
+        p.operations.iter().any(|op| Self::is_source_op(op.r#type))
+    }
+
+    /// Whether SOURCE_COPY is the only operation in `p` that reads from the old partition image,
+    /// so `--source-dir` can rebuild it.
+    fn is_source_copy_only(p: &PartitionUpdate) -> bool {
+        p.operations
+            .iter()
+            .all(|op| op.r#type == Type::SourceCopy as i32 || !Self::is_source_op(op.r#type))
+    }
+
+    /// The patch operation types in `parts` that otaripper can't apply even with a base image,
+    /// in manifest order.
+    fn diff_op_names(parts: &[&PartitionUpdate]) -> Vec<&'static str> {
+        let mut out = Vec::new();
+        for op in parts.iter().flat_map(|u| &u.operations) {
+            if let Ok(op_type) = Type::try_from(op.r#type)
+                && op_type != Type::SourceCopy
+                && Self::is_source_op(op.r#type)
+                && !out.contains(&op_type.as_str_name())
+            {
+                out.push(op_type.as_str_name());
+            }
+        }
+        out
+    }
+
+    /// Whether an operation of this type reads from the old partition image.
+    fn is_source_op(op_type: i32) -> bool {
+        matches!(
+            Type::try_from(op_type),
+            Ok(Type::Move
+                | Type::Bsdiff
+                | Type::SourceCopy
+                | Type::SourceBsdiff
+                | Type::BrotliBsdiff
+                | Type::Lz4diffBsdiff
+                | Type::Puffdiff
+                | Type::Zucchini)
+        )
+    }
+}
+
+/// Hash records gathered during extraction, in partition order.
+fn drain_hashes(receiver: Option<&crossbeam_channel::Receiver<HashRec>>) -> Vec<HashRec> {
+    let mut hashes: Vec<HashRec> = receiver.into_iter().flat_map(|r| r.try_iter()).collect();
+    hashes.sort_by_key(|r| r.order);
+    hashes
+}
+
+/// Digests as printed by --print-hash: `sha256=... md5=...`.
+pub(super) fn format_digests(digests: &[(HashAlgo, String)]) -> String {
+    digests
+        .iter()
+        .map(|(algo, hex)| format!("{}={hex}", algo.name()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Writes each of `algos`' checksum file (`sha256sums.txt`, ...) to `dir` in the format
+/// `sha256sum -c` and its siblings read, naming images relative to `dir` (output map paths
+/// elsewhere stay absolute). Earlier ones are replaced.
+fn write_hash_files(dir: &Path, algos: &[HashAlgo], hashes: &[HashRec]) -> Result<()> {
+    for (i, algo) in algos.iter().enumerate() {
+        let mut sums = String::new();
+        for r in hashes {
+            let file = r.file.strip_prefix(dir).unwrap_or(&r.file);
+            sums.push_str(&format!("{}  {}\n", r.digests[i].1, file.display()));
+        }
+        let path = dir.join(algo.sums_file());
+        fs::write(&path, sums).with_context(|| format!("could not write {path:?}"))?;
+    }
+    Ok(())
+}
+
+/// Whether a `--partitions` name is a pattern rather than a partition name.
+fn is_pattern(name: &str) -> bool {
+    name.contains(['*', '?'])
+}
+
+/// Matches `name` against a `--partitions` pattern, where `*` stands for any run of
+/// characters and `?` for any one.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // The last `*` seen and where in `name` its match currently ends.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the `*` take one more character and try again from there.
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Runs the `--open-with` command on `dir` and waits for it, so a script's output isn't cut
+/// off by otaripper exiting. A failing command is reported, not treated as an extraction error.
+fn run_open_with(command: &str, dir: &Path) -> Result<()> {
+    let mut args = split_command(command);
+    ensure!(!args.is_empty(), "--open-with: empty command");
+    let dir = dir.to_string_lossy();
+    if args.iter().any(|a| a.contains("{dir}")) {
+        for arg in &mut args {
+            *arg = arg.replace("{dir}", &dir);
+        }
+    } else {
+        args.push(dir.into_owned());
+    }
+
+    match std::process::Command::new(&args[0])
+        .args(&args[1..])
+        .status()
+    {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("Warning: '{}' exited with {status}", args[0]),
+        Err(e) => eprintln!("Warning: Failed to run '{}': {e}", args[0]),
+    }
+    Ok(())
+}
+
+/// Splits a command line into words on whitespace, honoring single and double quotes. No
+/// other shell syntax is interpreted.
+fn split_command(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
     }
+    words
 }
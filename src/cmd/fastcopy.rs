@@ -0,0 +1,64 @@
+use std::fs::File;
+
+/// Copies up to `len` bytes from `src` at `src_off` into `dst` at `dst_off` without bouncing the
+/// data through userspace. On filesystems with shared extents (btrfs, XFS) the kernel may reflink
+/// the range instead of copying it.
+///
+/// Returns how many bytes were moved, which can be anything from 0 to `len`; the caller is
+/// responsible for copying the remainder the regular way.
+#[cfg(target_os = "linux")]
+pub(crate) fn copy_range(src: &File, src_off: u64, dst: &File, dst_off: u64, len: usize) -> usize {
+    use std::os::unix::io::AsRawFd;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Set once the kernel or filesystem pair rejects copy_file_range, so later ops skip the syscall.
+    static UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
+    if UNSUPPORTED.load(Ordering::Relaxed) {
+        return 0;
+    }
+
+    let mut done = 0usize;
+    while done < len {
+        let mut s = (src_off + done as u64) as libc::loff_t;
+        let mut d = (dst_off + done as u64) as libc::loff_t;
+        let n = unsafe {
+            libc::copy_file_range(
+                src.as_raw_fd(),
+                &mut s,
+                dst.as_raw_fd(),
+                &mut d,
+                len - done,
+                0,
+            )
+        };
+
+        if n > 0 {
+            done += n as usize;
+            continue;
+        }
+        if n == 0 {
+            break;
+        }
+        match std::io::Error::last_os_error().raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::ENOSYS | libc::EXDEV | libc::EINVAL | libc::EOPNOTSUPP | libc::EPERM) => {
+                UNSUPPORTED.store(true, Ordering::Relaxed);
+                break;
+            }
+            _ => break,
+        }
+    }
+    done
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn copy_range(
+    _src: &File,
+    _src_off: u64,
+    _dst: &File,
+    _dst_off: u64,
+    _len: usize,
+) -> usize {
+    0
+}
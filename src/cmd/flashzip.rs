@@ -0,0 +1,189 @@
+use crate::cmd::extractor::Extractor;
+use crate::cmd::i18n::Msg;
+use crate::proto::chromeos_update_engine::{DeltaArchiveManifest, PartitionUpdate};
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Written next to the images by `--make-recovery-zip`.
+const ZIP_NAME: &str = "recovery_flashable.zip";
+
+const UPDATE_BINARY: &str = "META-INF/com/google/android/update-binary";
+const UPDATER_SCRIPT: &str = "META-INF/com/google/android/updater-script";
+
+/// Shell `update-binary` run by TWRP/OrangeFox as `update-binary <api> <outfd> <zip>`. Every
+/// image is checked against its block device before the first one is written. An unsuffixed
+/// name goes to the active slot on A/B devices.
+const SCRIPT_HEAD: &str = r#"#!/sbin/sh
+# Generated by otaripper: writes the images in this zip to their partitions.
+OUTFD=/proc/self/fd/$2
+ZIPFILE="$3"
+
+ui_print() { echo "ui_print $1" > "$OUTFD"; echo "ui_print" > "$OUTFD"; }
+abort() { ui_print "$1"; exit 1; }
+
+SLOT=$(getprop ro.boot.slot_suffix)
+
+find_block() {
+  for name in "$1$SLOT" "$1"; do
+    for dir in /dev/block/by-name /dev/block/bootdevice/by-name /dev/block/platform/*/by-name; do
+      [ -b "$dir/$name" ] && { echo "$dir/$name"; return 0; }
+    done
+  done
+  return 1
+}
+
+check() {
+  block=$(find_block "$2") || abort "! No block device for $2"
+  size=$(blockdev --getsize64 "$block" 2>/dev/null)
+  [ -n "$size" ] && [ "$size" -lt "$3" ] && abort "! $1 ($3 bytes) does not fit $block ($size bytes)"
+  return 0
+}
+
+flash() {
+  block=$(find_block "$2")
+  ui_print "- Flashing $1 to $block"
+  unzip -p "$ZIPFILE" "$1" | dd of="$block" bs=1048576 2>/dev/null || abort "! Writing $1 failed"
+}
+
+ui_print "Images extracted by otaripper"
+"#;
+
+const SCRIPT_TAIL: &str = r#"sync
+ui_print "- Done"
+exit 0
+"#;
+
+impl Extractor<'_> {
+    /// `--make-recovery-zip`: packs the extracted images with an `update-binary` that writes each
+    /// to its by-name block device. Partitions inside `super` have no block device of their own
+    /// and are left out.
+    pub(super) fn make_recovery_zip(
+        &self,
+        manifest: &DeltaArchiveManifest,
+        file_names: &HashMap<&[u8], OsString>,
+        dir: &Path,
+    ) -> Result<()> {
+        let mut images = Vec::new();
+        let mut dynamic = Vec::new();
+        for update in manifest.partitions.iter().filter(|u| self.is_selected(u)) {
+            let name = update.name();
+            if is_dynamic(manifest, update) {
+                dynamic.push(name);
+                continue;
+            }
+            let path = dir.join(&file_names[update.partition_name.as_slice()]);
+            let entry = path
+                .file_name()
+                .context("output image has no file name")?
+                .to_string_lossy()
+                .into_owned();
+            if !script_safe(&name) || !script_safe(&entry) {
+                if self.cmd.chatty() {
+                    eprintln!("Note: not adding '{name}' to the recovery zip: unusual name.");
+                }
+                continue;
+            }
+            images.push((entry, name, path));
+        }
+
+        if self.cmd.chatty() && !dynamic.is_empty() {
+            eprintln!(
+                "Note: {} live inside super and are not added to the recovery zip.",
+                dynamic.join(", ")
+            );
+        }
+        if images.is_empty() {
+            if self.cmd.chatty() {
+                eprintln!("Note: no partitions to put in a recovery zip.");
+            }
+            return Ok(());
+        }
+
+        let mut script = String::from(SCRIPT_HEAD);
+        let mut sizes = Vec::with_capacity(images.len());
+        for (entry, name, path) in &images {
+            let size = path
+                .metadata()
+                .with_context(|| format!("could not read {path:?}"))?
+                .len();
+            let _ = writeln!(script, "check \"{entry}\" \"{name}\" {size}");
+            sizes.push(size);
+        }
+        for (entry, name, _) in &images {
+            let _ = writeln!(script, "flash \"{entry}\" \"{name}\"");
+        }
+        script.push_str(SCRIPT_TAIL);
+
+        let zip_path = dir.join(ZIP_NAME);
+        if let Err(e) = write_zip(&zip_path, &script, &images, &sizes) {
+            // Leave no half-written zip behind.
+            let _ = fs::remove_file(&zip_path);
+            return Err(e.context(format!("could not write {zip_path:?}")));
+        }
+
+        if self.cmd.chatty() {
+            let names = images
+                .iter()
+                .map(|(_, name, _)| &**name)
+                .collect::<Vec<&str>>()
+                .join(", ");
+            println!("{}", Msg::SavedRecoveryZip.fill(&[&ZIP_NAME, &names]));
+        }
+        Ok(())
+    }
+}
+
+fn write_zip(
+    path: &Path,
+    script: &str,
+    images: &[(String, Cow<str>, PathBuf)],
+    sizes: &[u64],
+) -> Result<()> {
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(UPDATE_BINARY, options.unix_permissions(0o755))?;
+    zip.write_all(script.as_bytes())?;
+    zip.start_file(UPDATER_SCRIPT, options)?;
+    zip.write_all(b"# Dummy file; the work is done by update-binary.\n")?;
+    for ((entry, _, image), &size) in images.iter().zip(sizes) {
+        zip.start_file(entry.as_str(), options.large_file(size >= u32::MAX as u64))?;
+        let mut image = File::open(image).with_context(|| format!("could not read {image:?}"))?;
+        io::copy(&mut image, &mut zip).with_context(|| format!("could not add {entry}"))?;
+    }
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+/// Whether the partition is a logical one inside `super`, per the manifest's dynamic partition
+/// groups. Groups list names without slot suffixes.
+fn is_dynamic(manifest: &DeltaArchiveManifest, update: &PartitionUpdate) -> bool {
+    let name = update.partition_name.as_slice();
+    let base = [b"_a".as_slice(), b"_b"]
+        .into_iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .unwrap_or(name);
+    manifest
+        .dynamic_partition_metadata
+        .iter()
+        .flat_map(|metadata| &metadata.groups)
+        .flat_map(|group| &group.partition_names)
+        .any(|member| member == name || member == base)
+}
+
+/// Whether `name` can go into the `update-binary` script as is: quoted, it is one word to the
+/// shell and a literal file name, not an option or pattern, to `unzip`. Payloads stick to these
+/// names anyway.
+fn script_safe(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"_-.".contains(&b))
+}
@@ -0,0 +1,81 @@
+use std::fs::File;
+use std::sync::{Mutex, PoisonError};
+
+/// `--flush-every`: hands finished parts of one output image to the kernel for writeback once
+/// enough of them have piled up, rather than leaving gigabytes of dirty pages for the end.
+pub(crate) struct Flusher {
+    every: u64,
+    pending: Mutex<Pending>,
+}
+
+#[derive(Default)]
+struct Pending {
+    /// Byte ranges of the image written since the last flush.
+    ranges: Vec<(usize, usize)>,
+    bytes: u64,
+}
+
+impl Flusher {
+    pub(crate) fn new(every: u64) -> Self {
+        Self {
+            every,
+            pending: Mutex::default(),
+        }
+    }
+
+    /// Records ranges an operation has finished writing, flushing everything pending once at
+    /// least the configured amount has built up.
+    pub(crate) fn written(&self, file: &File, ranges: impl IntoIterator<Item = (usize, usize)>) {
+        let batch = {
+            let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+            for (offset, len) in ranges {
+                pending.ranges.push((offset, len));
+                pending.bytes += len as u64;
+            }
+            if pending.bytes < self.every {
+                return;
+            }
+            std::mem::take(&mut *pending)
+        };
+        write_back(file, batch.ranges);
+    }
+
+    /// Flushes whatever is still pending; the image is complete.
+    pub(crate) fn finish(&self, file: &File) {
+        let batch =
+            std::mem::take(&mut *self.pending.lock().unwrap_or_else(PoisonError::into_inner));
+        if !batch.ranges.is_empty() {
+            write_back(file, batch.ranges);
+        }
+    }
+}
+
+cfg_select! {
+    target_os = "linux" => {
+        /// Starts writeback of `ranges` and returns without waiting for it. Works the same for
+        /// pages dirtied through the mapping and by `pwrite`, as both live in the page cache.
+        fn write_back(file: &File, mut ranges: Vec<(usize, usize)>) {
+            use crate::payload;
+            use std::os::unix::io::AsRawFd;
+
+            ranges.sort_unstable();
+            for (offset, len) in payload::coalesce_extents(ranges) {
+                unsafe {
+                    libc::sync_file_range(
+                        file.as_raw_fd(),
+                        offset as libc::off64_t,
+                        len as libc::off64_t,
+                        libc::SYNC_FILE_RANGE_WRITE,
+                    );
+                }
+            }
+        }
+    }
+    _ => {
+        /// Without a ranged, non-blocking writeback call, syncs the file's data. The worker that
+        /// crosses the threshold waits for it, which paces extraction to the disk.
+        fn write_back(file: &File, _ranges: Vec<(usize, usize)>) {
+            let _ = file.sync_data();
+        }
+    }
+}
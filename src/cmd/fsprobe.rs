@@ -0,0 +1,412 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf, Prefix};
+
+/// Broad classification of the filesystem backing the output directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FsKind {
+    Fat,
+    Exfat,
+    Ntfs,
+    Other,
+}
+
+/// Where the filesystem's data actually lives, as far as write-back behaviour is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Backing {
+    Local,
+    /// SMB/CIFS, NFS, 9P, AFS, Ceph or a UNC path.
+    Network,
+    /// A userspace (FUSE) filesystem: sshfs, rclone, ntfs-3g, ...
+    Fuse,
+}
+
+impl Backing {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Backing::Local => "a local disk",
+            Backing::Network => "a network share",
+            Backing::Fuse => "a FUSE filesystem",
+        }
+    }
+}
+
+/// Constraints of the output filesystem that affect how images are written.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FsTraits {
+    pub kind: FsKind,
+    /// `boot.img` and `BOOT.img` refer to the same file.
+    pub case_insensitive: bool,
+    /// Windows-style naming rules apply (no `<>:"|?*`, no trailing dots/spaces, no `CON`/`NUL`...).
+    pub restricted_names: bool,
+    /// Extending a file with `set_len` leaves holes instead of allocating zero-filled blocks.
+    pub sparse_files: bool,
+    /// Network and FUSE filesystems handle mmap write-back poorly (slow, or errors that surface
+    /// as SIGBUS), so output there goes through positional writes instead.
+    pub backing: Backing,
+}
+
+impl FsKind {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            FsKind::Fat => "FAT",
+            FsKind::Exfat => "exFAT",
+            FsKind::Ntfs => "NTFS",
+            FsKind::Other => "the output filesystem",
+        }
+    }
+}
+
+impl FsTraits {
+    /// Probes the filesystem that holds `dir`. `dir` must already exist.
+    pub(crate) fn probe(dir: &Path) -> Self {
+        let (kind, backing) = detect(dir);
+        let probe_file = tempfile::Builder::new()
+            .prefix(".otaripper-probe-")
+            .tempfile_in(dir)
+            .ok();
+
+        let case_insensitive = probe_file
+            .as_ref()
+            .and_then(|f| probe_case_insensitive(dir, f.path()))
+            .unwrap_or(matches!(kind, FsKind::Fat | FsKind::Exfat | FsKind::Ntfs));
+        let restricted_names =
+            cfg!(windows) || matches!(kind, FsKind::Fat | FsKind::Exfat | FsKind::Ntfs);
+        // FAT and exFAT have no notion of holes; trust the kind over the probe for those.
+        let sparse_files = !matches!(kind, FsKind::Fat | FsKind::Exfat)
+            && probe_file
+                .as_ref()
+                .and_then(|f| probe_sparse(f.as_file()))
+                .unwrap_or(false);
+
+        Self {
+            kind,
+            case_insensitive,
+            restricted_names,
+            sparse_files,
+            backing,
+        }
+    }
+}
+
+/// Cheap check (no files created) for whether `dir`, or its closest existing ancestor, is on a
+/// network share.
+pub(crate) fn is_remote(dir: &Path) -> bool {
+    let existing = dir.ancestors().find(|p| p.exists()).unwrap_or(dir);
+    detect(existing).1 == Backing::Network
+}
+
+/// Strips the `\\?\` verbatim prefix Windows APIs sometimes hand back, so paths shown to the user
+/// or passed to Explorer look like `\\server\share\...` or `C:\...` again. Other paths are
+/// returned unchanged.
+pub(crate) fn display_path(path: &Path) -> PathBuf {
+    let mut components = path.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return path.to_path_buf();
+    };
+    let root = match prefix.kind() {
+        Prefix::VerbatimUNC(server, share) => {
+            let mut root = OsString::from(r"\\");
+            root.push(server);
+            root.push(r"\");
+            root.push(share);
+            PathBuf::from(root)
+        }
+        Prefix::VerbatimDisk(letter) => PathBuf::from(format!("{}:", letter as char)),
+        _ => return path.to_path_buf(),
+    };
+    let mut out = root;
+    out.extend(components);
+    out
+}
+
+// Checks whether the case-swapped name of an existing probe file resolves to it.
+fn probe_case_insensitive(dir: &Path, probe: &Path) -> Option<bool> {
+    let name = probe.file_name()?.to_str()?;
+    let swapped: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_lowercase() {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            }
+        })
+        .collect();
+    Some(dir.join(swapped).exists())
+}
+
+// Extends the probe file and checks whether the filesystem actually allocated the new range.
+#[cfg(unix)]
+fn probe_sparse(file: &std::fs::File) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    const PROBE_LEN: u64 = 16 * 1024 * 1024;
+    file.set_len(PROBE_LEN).ok()?;
+    let allocated = file.metadata().ok()?.blocks() * 512;
+    let _ = file.set_len(0);
+    Some(allocated < PROBE_LEN)
+}
+
+// Windows only creates sparse files on explicit request (FSCTL_SET_SPARSE), which the
+// filesystem refuses when it has no holes; images are marked the same way.
+#[cfg(not(unix))]
+fn probe_sparse(file: &std::fs::File) -> Option<bool> {
+    Some(crate::cmd::holes::make_sparse(file))
+}
+
+#[cfg(target_os = "linux")]
+fn detect(dir: &Path) -> (FsKind, Backing) {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    const MSDOS_SUPER_MAGIC: i64 = 0x4d44;
+    const EXFAT_SUPER_MAGIC: i64 = 0x2011_bab0;
+    const NTFS_SB_MAGIC: i64 = 0x5346_544e;
+    const NFS_SUPER_MAGIC: i64 = 0x6969;
+    const SMB_SUPER_MAGIC: i64 = 0x517b;
+    const CIFS_MAGIC_NUMBER: i64 = 0xff53_4d42;
+    const SMB2_MAGIC_NUMBER: i64 = 0xfe53_4d42;
+    const V9FS_MAGIC: i64 = 0x0102_1997;
+    const AFS_SUPER_MAGIC: i64 = 0x5346_414f;
+    const CEPH_SUPER_MAGIC: i64 = 0x00c3_6400;
+    const FUSE_SUPER_MAGIC: i64 = 0x6573_5546;
+
+    let Ok(c_path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return (FsKind::Other, Backing::Local);
+    };
+    let mut st: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut st) } != 0 {
+        return (FsKind::Other, Backing::Local);
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    match st.f_type as i64 {
+        MSDOS_SUPER_MAGIC => (FsKind::Fat, Backing::Local),
+        EXFAT_SUPER_MAGIC => (FsKind::Exfat, Backing::Local),
+        NTFS_SB_MAGIC => (FsKind::Ntfs, Backing::Local),
+        NFS_SUPER_MAGIC | SMB_SUPER_MAGIC | CIFS_MAGIC_NUMBER | SMB2_MAGIC_NUMBER | V9FS_MAGIC
+        | AFS_SUPER_MAGIC | CEPH_SUPER_MAGIC => (FsKind::Other, Backing::Network),
+        // Also covers ntfs-3g (fuseblk); its naming rules can't be told apart from here.
+        FUSE_SUPER_MAGIC => (FsKind::Other, Backing::Fuse),
+        _ => (FsKind::Other, Backing::Local),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn detect(dir: &Path) -> (FsKind, Backing) {
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return (FsKind::Other, Backing::Local);
+    };
+    let mut st: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut st) } != 0 {
+        return (FsKind::Other, Backing::Local);
+    }
+    let name = unsafe { CStr::from_ptr(st.f_fstypename.as_ptr()) };
+
+    match name.to_bytes() {
+        b"msdos" => (FsKind::Fat, Backing::Local),
+        b"exfat" => (FsKind::Exfat, Backing::Local),
+        b"ntfs" => (FsKind::Ntfs, Backing::Local),
+        b"smbfs" | b"nfs" | b"afpfs" | b"webdav" => (FsKind::Other, Backing::Network),
+        b"macfuse" | b"osxfuse" | b"fusefs" => (FsKind::Other, Backing::Fuse),
+        _ => (FsKind::Other, Backing::Local),
+    }
+}
+
+#[cfg(windows)]
+fn detect(dir: &Path) -> (FsKind, Backing) {
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+    const DRIVE_REMOTE: u32 = 4;
+    const MAX_PATH: usize = 260;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetVolumePathNameW(file_name: *const u16, volume_path: *mut u16, len: u32) -> i32;
+        fn GetDriveTypeW(root: *const u16) -> u32;
+        fn GetVolumeInformationW(
+            root: *const u16,
+            volume_name: *mut u16,
+            volume_name_len: u32,
+            serial: *mut u32,
+            max_component_len: *mut u32,
+            flags: *mut u32,
+            fs_name: *mut u16,
+            fs_name_len: u32,
+        ) -> i32;
+    }
+
+    // UNC paths are remote by definition, whatever the server's filesystem is.
+    let unc = matches!(
+        dir.components().next(),
+        Some(Component::Prefix(p)) if matches!(p.kind(), Prefix::UNC(..) | Prefix::VerbatimUNC(..))
+    );
+
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut root = vec![0u16; wide.len().max(MAX_PATH) + 1];
+    let network = if unc {
+        Backing::Network
+    } else {
+        Backing::Local
+    };
+    if unsafe { GetVolumePathNameW(wide.as_ptr(), root.as_mut_ptr(), root.len() as u32) } == 0 {
+        return (FsKind::Other, network);
+    }
+    let backing = if unc || unsafe { GetDriveTypeW(root.as_ptr()) } == DRIVE_REMOTE {
+        Backing::Network
+    } else {
+        Backing::Local
+    };
+
+    let mut fs_name = [0u16; MAX_PATH + 1];
+    let ok = unsafe {
+        GetVolumeInformationW(
+            root.as_ptr(),
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            fs_name.as_mut_ptr(),
+            fs_name.len() as u32,
+        )
+    };
+    if ok == 0 {
+        return (FsKind::Other, backing);
+    }
+    let len = fs_name
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(fs_name.len());
+    let fs_name = std::ffi::OsString::from_wide(&fs_name[..len]);
+
+    let kind = match fs_name.to_str() {
+        Some("FAT" | "FAT32") => FsKind::Fat,
+        Some("exFAT") => FsKind::Exfat,
+        Some("NTFS") => FsKind::Ntfs,
+        _ => FsKind::Other,
+    };
+    // WinFsp-based filesystems (sshfs-win, rclone mount) report themselves as "FUSE-<name>".
+    let backing = match fs_name.to_str() {
+        Some(name) if backing == Backing::Local && name.starts_with("FUSE") => Backing::Fuse,
+        _ => backing,
+    };
+    (kind, backing)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn detect(_dir: &Path) -> (FsKind, Backing) {
+    (FsKind::Other, Backing::Local)
+}
+
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Turns a partition name into a file stem that is valid on the output filesystem.
+/// Path separators are always replaced so a manifest can never escape the output directory.
+///
+/// Names are raw manifest bytes. Invalid UTF-8 is kept verbatim where the platform allows
+/// arbitrary byte names (Unix, unrestricted filesystems) and replaced with `_` elsewhere.
+fn sanitize_stem(name: &[u8], traits: FsTraits) -> OsString {
+    let keep_raw = cfg!(unix) && !traits.restricted_names;
+
+    let mut stem = Vec::with_capacity(name.len());
+    for chunk in name.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            let c = match c {
+                '/' | '\\' | '\0' => '_',
+                '<' | '>' | ':' | '"' | '|' | '?' | '*' if traits.restricted_names => '_',
+                c if c.is_control() && traits.restricted_names => '_',
+                c => c,
+            };
+            stem.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+        if keep_raw {
+            stem.extend_from_slice(chunk.invalid());
+        } else if !chunk.invalid().is_empty() {
+            stem.push(b'_');
+        }
+    }
+
+    if traits.restricted_names {
+        let trimmed = stem
+            .iter()
+            .rposition(|&b| b != b'.' && b != b' ')
+            .map_or(0, |i| i + 1);
+        if trimmed != stem.len() {
+            stem.truncate(trimmed);
+            stem.push(b'_');
+        }
+        if WINDOWS_RESERVED
+            .iter()
+            .any(|r| r.as_bytes().eq_ignore_ascii_case(&stem))
+        {
+            stem.push(b'_');
+        }
+    }
+
+    if stem.is_empty() || stem == b"." || stem == b".." {
+        stem.iter_mut().for_each(|b| *b = b'_');
+        stem.push(b'_');
+    }
+    bytes_to_os_string(stem)
+}
+
+fn bytes_to_os_string(bytes: Vec<u8>) -> OsString {
+    cfg_select! {
+        unix => {
+            use std::os::unix::ffi::OsStringExt;
+            OsString::from_vec(bytes)
+        }
+        _ => String::from_utf8_lossy(&bytes).into_owned().into(),
+    }
+}
+
+/// Assigns every partition an output file name (`<stem>.img`) that is valid and unique on the
+/// output filesystem.
+///
+/// Names are processed in sorted order so the result never depends on manifest ordering: the
+/// first partition keeps its (sanitized) name and later ones that would clash receive a numeric
+/// suffix (`boot_2.img`).
+pub(crate) fn output_file_names<'n>(
+    names: impl IntoIterator<Item = &'n [u8]>,
+    traits: FsTraits,
+) -> HashMap<&'n [u8], OsString> {
+    let mut names: Vec<&[u8]> = names.into_iter().collect();
+    names.sort_unstable();
+    names.dedup();
+
+    let fold = |s: &OsString| {
+        if traits.case_insensitive {
+            s.to_string_lossy().to_lowercase().into_bytes()
+        } else {
+            s.as_encoded_bytes().to_vec()
+        }
+    };
+
+    let mut taken = HashSet::<Vec<u8>>::new();
+    let mut out = HashMap::with_capacity(names.len());
+
+    for name in names {
+        let stem = sanitize_stem(name, traits);
+        let with_suffix = |suffix: &str| {
+            let mut file = stem.clone();
+            file.push(suffix);
+            file
+        };
+        let mut candidate = with_suffix(".img");
+        let mut n = 2;
+        while !taken.insert(fold(&candidate)) {
+            candidate = with_suffix(&format!("_{n}.img"));
+            n += 1;
+        }
+        out.insert(name, candidate);
+    }
+    out
+}
@@ -0,0 +1,145 @@
+use crate::cmd::crypto;
+use md5::Md5;
+use sha1::{Digest, Sha1};
+use std::io::{self, Write};
+use xxhash_rust::xxh3::Xxh3;
+
+/// A digest `--hash-algo` can report. Manifest verification is always SHA-256; these only
+/// change what `--print-hash` and `--hash-file` show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HashAlgo {
+    Sha256,
+    Sha1,
+    Md5,
+    Blake3,
+    Xxh3,
+}
+
+impl HashAlgo {
+    /// Parses one `--hash-algo` name.
+    pub(crate) fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sha256" | "sha-256" => Ok(Self::Sha256),
+            "sha1" | "sha-1" => Ok(Self::Sha1),
+            "md5" => Ok(Self::Md5),
+            "blake3" | "b3" => Ok(Self::Blake3),
+            "xxh3" => Ok(Self::Xxh3),
+            _ => Err(format!(
+                "unknown hash '{value}'; use sha256, sha1, md5, blake3 or xxh3"
+            )),
+        }
+    }
+
+    /// Name shown next to the digest, as in `sha256=...`.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha1 => "sha1",
+            Self::Md5 => "md5",
+            Self::Blake3 => "blake3",
+            Self::Xxh3 => "xxh3",
+        }
+    }
+
+    /// File `--hash-file` writes, named as the tool that checks it (`sha1sum -c`, `b3sum -c`,
+    /// ...) expects.
+    pub(crate) fn sums_file(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256sums.txt",
+            Self::Sha1 => "sha1sums.txt",
+            Self::Md5 => "md5sums.txt",
+            Self::Blake3 => "b3sums.txt",
+            Self::Xxh3 => "xxh3sums.txt",
+        }
+    }
+
+    /// Hex digest of `data`.
+    pub(crate) fn digest(self, data: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    pub(crate) fn hasher(self) -> Hasher {
+        match self {
+            Self::Sha256 => Hasher::Sha256(crypto::Sha256::new()),
+            Self::Sha1 => Hasher::Sha1(Sha1::new()),
+            Self::Md5 => Hasher::Md5(Md5::new()),
+            Self::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            Self::Xxh3 => Hasher::Xxh3(Box::new(Xxh3::new())),
+        }
+    }
+}
+
+/// Incremental hash for one of the [`HashAlgo`]s.
+pub(crate) enum Hasher {
+    Sha256(crypto::Sha256),
+    Sha1(Sha1),
+    Md5(Md5),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<Xxh3>),
+}
+
+impl Hasher {
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+            Hasher::Xxh3(h) => h.update(data),
+        }
+    }
+
+    /// The digest, hex-encoded.
+    pub(crate) fn finish(self) -> String {
+        match self {
+            Hasher::Sha256(h) => hex::encode(h.finish()),
+            Hasher::Sha1(h) => hex::encode(h.finalize()),
+            Hasher::Md5(h) => hex::encode(h.finalize()),
+            Hasher::Blake3(h) => h.finalize().to_hex().to_string(),
+            Hasher::Xxh3(h) => format!("{:016x}", h.digest()),
+        }
+    }
+}
+
+/// Hashes everything written through it with several algorithms before passing it on.
+pub(crate) struct HashingWriter<W: Write> {
+    hashers: Vec<(HashAlgo, Hasher)>,
+    inner: W,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub(crate) fn new(algos: &[HashAlgo], inner: W) -> Self {
+        Self {
+            hashers: algos.iter().map(|&algo| (algo, algo.hasher())).collect(),
+            inner,
+        }
+    }
+
+    /// The underlying writer and the hex digests of everything written.
+    pub(crate) fn finish(self) -> (W, Vec<(HashAlgo, String)>) {
+        let digests = self
+            .hashers
+            .into_iter()
+            .map(|(algo, hasher)| (algo, hasher.finish()))
+            .collect();
+        (self.inner, digests)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        for (_, hasher) in &mut self.hashers {
+            hasher.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
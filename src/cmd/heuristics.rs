@@ -0,0 +1,176 @@
+use crate::payload::Payload;
+use crate::proto::chromeos_update_engine::DeltaArchiveManifest;
+use crate::proto::chromeos_update_engine::install_operation::Type;
+use std::collections::HashMap;
+
+/// Decompressed-to-compressed ratio above which a compressed op is reported. Real images rarely
+/// pass a few hundred to one; zero-heavy blocks are emitted as ZERO ops by the AOSP tooling.
+const MAX_PLAUSIBLE_RATIO: u64 = 10_000;
+
+/// Stop listing individual findings of one kind after this many.
+const MAX_REPORTS_PER_CHECK: usize = 5;
+
+/// Structural red flags that payloads produced by the AOSP tooling never have. None of them is
+/// proof of tampering, but each deserves a closer look before the output is trusted.
+pub(crate) fn analyze(payload: &Payload, manifest: &DeltaArchiveManifest) -> Vec<String> {
+    let mut findings = Findings::default();
+    let block_size = manifest.block_size.unwrap_or(4096) as u64;
+
+    // Duplicate partitions: the second copy silently overwrites (or is hidden by) the first.
+    let mut seen = HashMap::new();
+    for p in &manifest.partitions {
+        *seen.entry(p.partition_name.as_slice()).or_insert(0usize) += 1;
+    }
+    let mut dupes: Vec<_> = seen.into_iter().filter(|&(_, n)| n > 1).collect();
+    dupes.sort();
+    for (name, n) in dupes {
+        findings.push(
+            "duplicate",
+            format!(
+                "partition '{}' appears {n} times in the manifest",
+                String::from_utf8_lossy(name)
+            ),
+        );
+    }
+
+    // Data ranges, to check them against each other and the signature blob afterwards.
+    let mut data_ranges: Vec<(u64, u64, String)> = Vec::new();
+
+    for p in &manifest.partitions {
+        let name = p.name();
+        let size = p.new_partition_info.as_ref().and_then(|i| i.size);
+        match size {
+            None => findings.push("size", format!("'{name}' declares no partition size")),
+            Some(size) if size % block_size != 0 => findings.push(
+                "size",
+                format!("'{name}' size {size} is not a multiple of the block size {block_size}"),
+            ),
+            _ => {}
+        }
+
+        for (i, op) in p.operations.iter().enumerate() {
+            let op_type = Type::try_from(op.r#type).ok();
+            let mut dst_bytes = 0u64;
+
+            for e in &op.dst_extents {
+                let start = e.start_block.unwrap_or(0).saturating_mul(block_size);
+                let len = e.num_blocks.unwrap_or(0).saturating_mul(block_size);
+                dst_bytes = dst_bytes.saturating_add(len);
+                if let Some(size) = size
+                    && start.saturating_add(len) > size
+                {
+                    findings.push(
+                        "extent",
+                        format!(
+                            "'{name}' op #{i} writes {}..{} past the partition end ({size})",
+                            start,
+                            start.saturating_add(len)
+                        ),
+                    );
+                }
+            }
+
+            let data_len = op.data_length.unwrap_or(0);
+            if data_len == 0 {
+                continue;
+            }
+            let offset = op.data_offset.unwrap_or(0);
+            let end = offset.saturating_add(data_len);
+            if end > payload.data().len() as u64 {
+                findings.push(
+                    "data",
+                    format!(
+                        "'{name}' op #{i} data {offset}..{end} lies outside the data section ({} bytes)",
+                        payload.data().len()
+                    ),
+                );
+            }
+            data_ranges.push((offset, end, format!("'{name}' op #{i}")));
+
+            match op_type {
+                Some(Type::ReplaceBz | Type::ReplaceXz)
+                    if dst_bytes / data_len > MAX_PLAUSIBLE_RATIO =>
+                {
+                    findings.push(
+                        "ratio",
+                        format!(
+                            "'{name}' op #{i} expands {data_len} bytes into {dst_bytes} ({}:1)",
+                            dst_bytes / data_len
+                        ),
+                    );
+                }
+                Some(Type::Replace) if data_len > dst_bytes => findings.push(
+                    "ratio",
+                    format!(
+                        "'{name}' op #{i} carries {data_len} bytes for a {dst_bytes} byte destination"
+                    ),
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    // Op data overlapping other op data, or the payload signature blob.
+    if let (Some(offset), Some(size)) = (manifest.signatures_offset, manifest.signatures_size) {
+        data_ranges.push((
+            offset,
+            offset.saturating_add(size),
+            "the payload signature".to_string(),
+        ));
+    }
+    data_ranges.sort_unstable_by_key(|&(start, end, _)| (start, end));
+    let mut furthest: Option<&(u64, u64, String)> = None;
+    for range in &data_ranges {
+        if let Some(prev) = furthest
+            && range.0 < prev.1
+        {
+            findings.push(
+                "overlap",
+                format!(
+                    "{} and {} share payload data ({}..{})",
+                    prev.2,
+                    range.2,
+                    range.0,
+                    prev.1.min(range.1)
+                ),
+            );
+        }
+        if furthest.is_none_or(|prev| range.1 > prev.1) {
+            furthest = Some(range);
+        }
+    }
+
+    findings.finish()
+}
+
+// Collects findings, capping how many of one kind are listed individually.
+#[derive(Default)]
+struct Findings {
+    out: Vec<String>,
+    per_check: HashMap<&'static str, usize>,
+}
+
+impl Findings {
+    fn push(&mut self, check: &'static str, message: String) {
+        let n = self.per_check.entry(check).or_insert(0);
+        *n += 1;
+        if *n <= MAX_REPORTS_PER_CHECK {
+            self.out.push(message);
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        let mut extra: Vec<_> = self
+            .per_check
+            .iter()
+            .filter(|&(_, &n)| n > MAX_REPORTS_PER_CHECK)
+            .map(|(check, n)| (*check, n - MAX_REPORTS_PER_CHECK))
+            .collect();
+        extra.sort();
+        for (check, n) in extra {
+            self.out
+                .push(format!("... and {n} more '{check}' findings"));
+        }
+        self.out
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs::File;
+use std::io;
+
+cfg_select! {
+    windows => {
+        use std::os::windows::io::AsRawHandle;
+
+        const FSCTL_SET_SPARSE: u32 = 0x0009_00c4;
+        const FSCTL_SET_ZERO_DATA: u32 = 0x0009_80c8;
+
+        #[repr(C)]
+        struct FileZeroDataInformation {
+            file_offset: i64,
+            beyond_final_zero: i64,
+        }
+
+        #[link(name = "kernel32")]
+        unsafe extern "system" {
+            fn DeviceIoControl(
+                device: *mut std::ffi::c_void,
+                code: u32,
+                in_buffer: *const std::ffi::c_void,
+                in_len: u32,
+                out_buffer: *mut std::ffi::c_void,
+                out_len: u32,
+                returned: *mut u32,
+                overlapped: *mut std::ffi::c_void,
+            ) -> i32;
+        }
+
+        fn control(
+            file: &File,
+            code: u32,
+            input: Option<&FileZeroDataInformation>,
+        ) -> io::Result<()> {
+            let (in_buffer, in_len) = match input {
+                Some(info) => (
+                    info as *const FileZeroDataInformation as *const std::ffi::c_void,
+                    size_of::<FileZeroDataInformation>() as u32,
+                ),
+                None => (std::ptr::null(), 0),
+            };
+            let mut returned = 0u32;
+            let ok = unsafe {
+                DeviceIoControl(
+                    file.as_raw_handle(),
+                    code,
+                    in_buffer,
+                    in_len,
+                    std::ptr::null_mut(),
+                    0,
+                    &mut returned,
+                    std::ptr::null_mut(),
+                )
+            };
+            if ok == 0 { Err(io::Error::last_os_error()) } else { Ok(()) }
+        }
+
+        /// Marks `file` sparse, so extending it and zeroing ranges of it leave holes. Windows
+        /// allocates every byte of a file that is not.
+        pub(crate) fn make_sparse(file: &File) -> bool {
+            control(file, FSCTL_SET_SPARSE, None).is_ok()
+        }
+
+        /// Deallocates `len` bytes at `offset`, which then read as zeros. The file must have
+        /// been [`make_sparse`]d first.
+        pub(crate) fn punch(file: &File, offset: u64, len: u64) -> io::Result<()> {
+            let info = FileZeroDataInformation {
+                file_offset: offset as i64,
+                beyond_final_zero: (offset + len) as i64,
+            };
+            control(file, FSCTL_SET_ZERO_DATA, Some(&info))
+        }
+    }
+    any(target_os = "linux", target_os = "android") => {
+        use std::os::unix::io::AsRawFd;
+
+        /// Holes need no setup here.
+        pub(crate) fn make_sparse(_file: &File) -> bool {
+            true
+        }
+
+        /// Deallocates `len` bytes at `offset`, which then read as zeros; the file keeps its size.
+        pub(crate) fn punch(file: &File, offset: u64, len: u64) -> io::Result<()> {
+            let result = unsafe {
+                libc::fallocate(
+                    file.as_raw_fd(),
+                    libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                    offset as libc::off_t,
+                    len as libc::off_t,
+                )
+            };
+            if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+        }
+    }
+    target_vendor = "apple" => {
+        use std::os::unix::io::AsRawFd;
+
+        /// Holes need no setup here.
+        pub(crate) fn make_sparse(_file: &File) -> bool {
+            true
+        }
+
+        /// Deallocates `len` bytes at `offset`, which then read as zeros. APFS wants both
+        /// block-aligned, which operation extents always are.
+        pub(crate) fn punch(file: &File, offset: u64, len: u64) -> io::Result<()> {
+            let args = libc::fpunchhole_t {
+                fp_flags: 0,
+                reserved: 0,
+                fp_offset: offset as libc::off_t,
+                fp_length: len as libc::off_t,
+            };
+            let result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_PUNCHHOLE, &args) };
+            if result == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+        }
+    }
+    _ => {
+        /// Holes need no setup here.
+        pub(crate) fn make_sparse(_file: &File) -> bool {
+            true
+        }
+
+        /// No way to deallocate part of a file on this platform.
+        pub(crate) fn punch(_file: &File, _offset: u64, _len: u64) -> io::Result<()> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+}
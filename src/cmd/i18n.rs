@@ -0,0 +1,241 @@
+//! The message catalog for user-facing output. Every message is written in English first; each
+//! translation is a `match` over [`Msg`], so adding a message fails to build until every language
+//! has it. Messages not in the catalog yet (most errors) stay in English.
+
+use crate::cmd::Lang;
+use std::env;
+use std::fmt::{Display, Write};
+use std::sync::OnceLock;
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Sets the language for the rest of the process. The first call wins.
+pub(crate) fn init(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+/// The language messages are printed in: the one passed to [`init`], or the system locale when
+/// otaripper failed before getting that far.
+fn current() -> Lang {
+    *LANG.get_or_init(|| system_lang().unwrap_or(Lang::En))
+}
+
+/// The language of the POSIX locale (`LC_ALL`, `LC_MESSAGES`, then `LANG`), if it is one with a
+/// translation. `es_MX.UTF-8` and `pt_PT` fall back to `es` and `pt-br`.
+pub(crate) fn system_lang() -> Option<Lang> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(env::var_os)
+        .find(|value| !value.is_empty())?;
+    let locale = locale.to_str()?;
+    let language = locale.split(['_', '.', '@', '-']).next()?;
+    match language.to_ascii_lowercase().as_str() {
+        "en" => Some(Lang::En),
+        "es" => Some(Lang::Es),
+        "pt" => Some(Lang::PtBr),
+        _ => None,
+    }
+}
+
+/// Whether `answer` to a yes/no prompt means yes, in English or the current language.
+pub(crate) fn is_yes(answer: &str) -> bool {
+    let answer = answer.trim().to_lowercase();
+    let local: &[&str] = match current() {
+        Lang::En => &[],
+        Lang::Es => &["s", "si", "sí"],
+        Lang::PtBr => &["s", "sim"],
+    };
+    ["y", "yes"].contains(&answer.as_str()) || local.contains(&answer.as_str())
+}
+
+/// A user-facing message. `{}` in the text marks where [`Msg::fill`] puts its arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    Interrupted,
+    ExitingNow,
+    HandlerFailed,
+    Error,
+    Completed,
+    OutputDirectory,
+    TotalSize,
+    ToolSource,
+    Cancelled,
+    Failed,
+    CancelledBefore,
+    OutputDirMissing,
+    ScanningFolders,
+    NoExtractedFolders,
+    FoldersToRemove,
+    ProceedPrompt,
+    Aborted,
+    Removed,
+    CleanupComplete,
+    HashesHeading,
+    HashesPartialHeading,
+    SanityHeading,
+    VerityHeading,
+    StatsHeading,
+    SavedMetadata,
+    SavedEdl,
+    SavedRecoveryZip,
+    ConvertedSparse,
+    WriteJsonPrompt,
+    JsonWritten,
+    DebugBundleSaved,
+}
+
+impl Msg {
+    /// The message in the current language.
+    pub fn text(self) -> &'static str {
+        match current() {
+            Lang::En => english(self),
+            Lang::Es => spanish(self),
+            Lang::PtBr => portuguese(self),
+        }
+    }
+
+    /// The message with each `{}` replaced by the next of `args`.
+    pub fn fill(self, args: &[&dyn Display]) -> String {
+        let mut parts = self.text().split("{}");
+        let mut out = String::from(parts.next().unwrap_or_default());
+        let mut args = args.iter();
+        for part in parts {
+            if let Some(arg) = args.next() {
+                let _ = write!(out, "{arg}");
+            }
+            out.push_str(part);
+        }
+        out
+    }
+}
+
+fn english(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Interrupted => {
+            "Received interrupt signal (Ctrl+C). Cleaning up and exiting... \
+             (press Ctrl+C again to quit immediately)"
+        }
+        Msg::ExitingNow => "Exiting immediately; partial files may remain.",
+        Msg::HandlerFailed => "Failed to set up Ctrl+C handler: {}",
+        Msg::Error => "ERROR",
+        Msg::Completed => "Extraction completed successfully!",
+        Msg::OutputDirectory => "Output directory: {}",
+        Msg::TotalSize => "Total extracted size: {}",
+        Msg::ToolSource => "Tool Source: {}",
+        Msg::Cancelled => "Extraction cancelled. All partial files have been cleaned up.",
+        Msg::Failed => {
+            "❌ Extraction failed due to errors (see above). All partial files have been cleaned up."
+        }
+        Msg::CancelledBefore => "Extraction cancelled before processing '{}'",
+        Msg::OutputDirMissing => "Warning: Output directory does not exist, cannot open folder",
+        Msg::ScanningFolders => "Scanning for extracted folders in:",
+        Msg::NoExtractedFolders => "No extracted folders found.",
+        Msg::FoldersToRemove => "The following folders will be removed:",
+        Msg::ProceedPrompt => "Proceed? [Y/n]",
+        Msg::Aborted => "Aborted.",
+        Msg::Removed => "Removed {}",
+        Msg::CleanupComplete => "Cleanup complete.",
+        Msg::HashesHeading => "Partition hashes (SHA-256):",
+        Msg::HashesPartialHeading => {
+            "Partition hashes (SHA-256) of images finished before extraction stopped:"
+        }
+        Msg::SanityHeading => "Sanity warnings:",
+        Msg::VerityHeading => "AVB verification data:",
+        Msg::StatsHeading => "Extraction statistics:",
+        Msg::SavedMetadata => "Saved OTA metadata: {}",
+        Msg::SavedEdl => "Saved EDL programming files: {}",
+        Msg::SavedRecoveryZip => "Saved recovery-flashable zip: {} ({})",
+        Msg::ConvertedSparse => "Converted {} images to Android sparse format ({} → {})",
+        Msg::WriteJsonPrompt => "Write JSON output? [y/N]: ",
+        Msg::JsonWritten => "✔ JSON written: {}",
+        Msg::DebugBundleSaved => "Debug information for a bug report saved to {}",
+    }
+}
+
+fn spanish(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Interrupted => {
+            "Se recibió la señal de interrupción (Ctrl+C). Limpiando y saliendo... \
+             (pulsa Ctrl+C otra vez para salir de inmediato)"
+        }
+        Msg::ExitingNow => "Saliendo de inmediato; pueden quedar archivos parciales.",
+        Msg::HandlerFailed => "No se pudo instalar el manejador de Ctrl+C: {}",
+        Msg::Error => "ERROR",
+        Msg::Completed => "¡Extracción completada con éxito!",
+        Msg::OutputDirectory => "Carpeta de salida: {}",
+        Msg::TotalSize => "Tamaño total extraído: {}",
+        Msg::ToolSource => "Código fuente: {}",
+        Msg::Cancelled => "Extracción cancelada. Se eliminaron todos los archivos parciales.",
+        Msg::Failed => {
+            "❌ La extracción falló por errores (ver arriba). Se eliminaron todos los archivos \
+             parciales."
+        }
+        Msg::CancelledBefore => "Extracción cancelada antes de procesar '{}'",
+        Msg::OutputDirMissing => "Aviso: la carpeta de salida no existe, no se puede abrir",
+        Msg::ScanningFolders => "Buscando carpetas extraídas en:",
+        Msg::NoExtractedFolders => "No se encontraron carpetas extraídas.",
+        Msg::FoldersToRemove => "Se eliminarán las siguientes carpetas:",
+        Msg::ProceedPrompt => "¿Continuar? [S/n]",
+        Msg::Aborted => "Cancelado.",
+        Msg::Removed => "Eliminada {}",
+        Msg::CleanupComplete => "Limpieza completada.",
+        Msg::HashesHeading => "Hashes de las particiones (SHA-256):",
+        Msg::HashesPartialHeading => {
+            "Hashes (SHA-256) de las imágenes terminadas antes de que se detuviera la extracción:"
+        }
+        Msg::SanityHeading => "Avisos de comprobación:",
+        Msg::VerityHeading => "Datos de verificación AVB:",
+        Msg::StatsHeading => "Estadísticas de extracción:",
+        Msg::SavedMetadata => "Metadatos de la OTA guardados: {}",
+        Msg::SavedEdl => "Archivos de programación EDL guardados: {}",
+        Msg::SavedRecoveryZip => "Zip flasheable desde recovery guardado: {} ({})",
+        Msg::ConvertedSparse => "{} imágenes convertidas al formato sparse de Android ({} → {})",
+        Msg::WriteJsonPrompt => "¿Escribir la salida en JSON? [s/N]: ",
+        Msg::JsonWritten => "✔ JSON escrito: {}",
+        Msg::DebugBundleSaved => "Información de depuración para reportar el error guardada en {}",
+    }
+}
+
+fn portuguese(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Interrupted => {
+            "Sinal de interrupção recebido (Ctrl+C). Limpando e saindo... \
+             (pressione Ctrl+C de novo para sair imediatamente)"
+        }
+        Msg::ExitingNow => "Saindo imediatamente; arquivos parciais podem ter ficado.",
+        Msg::HandlerFailed => "Não foi possível configurar o tratamento de Ctrl+C: {}",
+        Msg::Error => "ERRO",
+        Msg::Completed => "Extração concluída com sucesso!",
+        Msg::OutputDirectory => "Pasta de saída: {}",
+        Msg::TotalSize => "Tamanho total extraído: {}",
+        Msg::ToolSource => "Código-fonte: {}",
+        Msg::Cancelled => "Extração cancelada. Todos os arquivos parciais foram removidos.",
+        Msg::Failed => {
+            "❌ A extração falhou devido a erros (veja acima). Todos os arquivos parciais foram \
+             removidos."
+        }
+        Msg::CancelledBefore => "Extração cancelada antes de processar '{}'",
+        Msg::OutputDirMissing => "Aviso: a pasta de saída não existe, não é possível abri-la",
+        Msg::ScanningFolders => "Procurando pastas extraídas em:",
+        Msg::NoExtractedFolders => "Nenhuma pasta extraída encontrada.",
+        Msg::FoldersToRemove => "As seguintes pastas serão removidas:",
+        Msg::ProceedPrompt => "Continuar? [S/n]",
+        Msg::Aborted => "Cancelado.",
+        Msg::Removed => "Removida {}",
+        Msg::CleanupComplete => "Limpeza concluída.",
+        Msg::HashesHeading => "Hashes das partições (SHA-256):",
+        Msg::HashesPartialHeading => {
+            "Hashes (SHA-256) das imagens concluídas antes de a extração parar:"
+        }
+        Msg::SanityHeading => "Avisos de verificação:",
+        Msg::VerityHeading => "Dados de verificação AVB:",
+        Msg::StatsHeading => "Estatísticas da extração:",
+        Msg::SavedMetadata => "Metadados da OTA salvos: {}",
+        Msg::SavedEdl => "Arquivos de programação EDL salvos: {}",
+        Msg::SavedRecoveryZip => "Zip para flashar pelo recovery salvo: {} ({})",
+        Msg::ConvertedSparse => "{} imagens convertidas para o formato sparse do Android ({} → {})",
+        Msg::WriteJsonPrompt => "Gravar a saída em JSON? [s/N]: ",
+        Msg::JsonWritten => "✔ JSON gravado: {}",
+        Msg::DebugBundleSaved => "Informações de depuração para relatar o erro salvas em {}",
+    }
+}
@@ -0,0 +1,201 @@
+use crate::cmd::extractor::PayloadSource;
+use crate::cmd::pagecache;
+use anyhow::{Context, Result, bail};
+use memmap2::Mmap;
+use std::cell::{Cell, OnceCell};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use sysinfo::{MemoryRefreshKind, RefreshKind, System};
+use tempfile::NamedTempFile;
+use zip::ZipArchive;
+
+/// The payload's bytes, which may still be inflating out of the zip. Extraction reads the
+/// manifest from the start of the entry and gets ready meanwhile, and only
+/// [`wait`](Self::wait)s where it needs the data.
+pub(crate) struct PayloadBytes {
+    source: OnceCell<PayloadSource>,
+    inflation: Cell<Option<Inflation>>,
+    parsed: Cell<bool>,
+}
+
+impl PayloadBytes {
+    pub(crate) fn ready(source: PayloadSource) -> Self {
+        Self {
+            source: OnceCell::from(source),
+            inflation: Cell::new(None),
+            parsed: Cell::new(false),
+        }
+    }
+
+    /// Starts inflating the zip's `payload.bin` entry on its own thread. `spill_dir` is where a
+    /// payload too large for RAM (or than `ram_limit`), or any payload with `low_memory`, goes;
+    /// the system temp directory if `None`. Inflation stops early once `interrupt` is set.
+    pub(crate) fn inflate(
+        zip: File,
+        spill_dir: Option<PathBuf>,
+        low_memory: bool,
+        ram_limit: Option<u64>,
+        interrupt: Arc<AtomicBool>,
+    ) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let stop = [cancel.clone(), interrupt];
+        let thread = std::thread::spawn(move || {
+            inflate_entry(&zip, spill_dir, low_memory, ram_limit, &stop)
+        });
+        Self {
+            source: OnceCell::new(),
+            inflation: Cell::new(Some(Inflation {
+                thread: Some(thread),
+                cancel,
+            })),
+            parsed: Cell::new(false),
+        }
+    }
+
+    /// The payload, if it needs no more inflating.
+    pub(crate) fn get(&self) -> Option<&PayloadSource> {
+        self.source.get()
+    }
+
+    /// The payload, once it is fully inflated.
+    pub(crate) fn wait(&self) -> Result<&PayloadSource> {
+        if let Some(source) = self.source.get() {
+            return Ok(source);
+        }
+        let inflation = self
+            .inflation
+            .take()
+            .context("payload.bin could not be inflated")?;
+        let source = inflation.finish()?;
+        Ok(self.source.get_or_init(|| source))
+    }
+
+    /// Like [`wait`](Self::wait), but hands over the payload.
+    pub(crate) fn into_source(self) -> Result<PayloadSource> {
+        self.wait()?;
+        self.source
+            .into_inner()
+            .context("payload.bin could not be inflated")
+    }
+
+    /// True the first time it is called, so notes about the payload are printed once however
+    /// many steps parse it.
+    pub(crate) fn first_parse(&self) -> bool {
+        !self.parsed.replace(true)
+    }
+}
+
+/// The inflating thread. Dropping it before it finishes stops the thread and waits for it, so a
+/// run that fails early doesn't leave it writing a temp file.
+struct Inflation {
+    thread: Option<JoinHandle<Result<PayloadSource>>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Inflation {
+    fn finish(mut self) -> Result<PayloadSource> {
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            None => bail!("payload.bin could not be inflated"),
+        }
+    }
+}
+
+impl Drop for Inflation {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.cancel.store(true, Ordering::Release);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Reads through to the zip entry until one of the flags is set.
+struct Stoppable<'s, R> {
+    inner: R,
+    stop: &'s [Arc<AtomicBool>],
+}
+
+impl<R: Read> Read for Stoppable<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.stop.iter().any(|flag| flag.load(Ordering::Acquire)) {
+            return Err(io::Error::other("cancelled while inflating payload.bin"));
+        }
+        self.inner.read(buf)
+    }
+}
+
+/// Inflates `payload.bin` from `zip` into RAM, or into a temp file when it would take more than
+/// half the available memory or `ram_limit` (`--max-memory`'s share), or `low_memory` asks for
+/// it.
+fn inflate_entry(
+    zip: &File,
+    spill_dir: Option<PathBuf>,
+    low_memory: bool,
+    ram_limit: Option<u64>,
+    stop: &[Arc<AtomicBool>],
+) -> Result<PayloadSource> {
+    let mut archive =
+        ZipArchive::new(zip).context("File has ZIP magic but is not a valid ZIP archive")?;
+    let zipfile = archive
+        .by_name("payload.bin")
+        .context("payload.bin not found in the zip")?;
+    let payload_size = zipfile.size();
+    let mut zipfile = Stoppable {
+        inner: zipfile,
+        stop,
+    };
+
+    // LIGHTWEIGHT RAM CHECK: Only refresh memory stats to minimize overhead
+    let mut sys = System::new_with_specifics(
+        RefreshKind::nothing().with_memory(MemoryRefreshKind::nothing().with_ram()),
+    );
+    sys.refresh_memory();
+    let available_ram = sys.available_memory();
+
+    // HEURISTIC: Use temp file if payload > 50% available RAM to avoid OOM or Swap lag
+    let over_limit = ram_limit.is_some_and(|limit| payload_size > limit);
+    if low_memory || over_limit || payload_size > available_ram / 2 {
+        if !low_memory && !over_limit {
+            eprintln!(
+                "⚠️ Large payload detected ({}). Available RAM: {}. Using localized temp file for safety.",
+                indicatif::HumanBytes(payload_size),
+                indicatif::HumanBytes(available_ram)
+            );
+        }
+
+        // LOCALIZED TEMP: Create in output dir to prevent cross-partition copy performance hits.
+        let temp_file = if let Some(out_dir) = spill_dir {
+            fs::create_dir_all(&out_dir)?;
+            NamedTempFile::new_in(out_dir)
+        } else {
+            NamedTempFile::new()
+        }
+        .context("Failed to create temporary file for payload extraction")?;
+
+        // Stream directly from ZIP to Disk
+        io::copy(&mut zipfile, &mut temp_file.as_file())
+            .context("Failed to stream payload.bin from ZIP to disk")?;
+
+        // SYNC: Ensure data is physically committed before mapping for correctness
+        temp_file.as_file().sync_all()?;
+
+        let mmap =
+            unsafe { Mmap::map(temp_file.as_file()) }.context("Failed to mmap streamed payload")?;
+        pagecache::sequential(&mmap);
+
+        return Ok(PayloadSource::TempFile(mmap, temp_file));
+    }
+
+    // RAM PATH: Small enough to fit comfortably in memory
+    let mut buffer = Vec::with_capacity(payload_size as usize);
+    zipfile
+        .read_to_end(&mut buffer)
+        .context("Failed to read payload.bin from ZIP into RAM")?;
+    Ok(PayloadSource::Owned(buffer))
+}
@@ -0,0 +1,190 @@
+use crate::cmd::chromeos;
+use crate::cmd::crypto;
+use crate::cmd::extractor::Extractor;
+use crate::cmd::heuristics;
+use crate::cmd::sigverify::{self, PublicKey};
+use crate::payload::{Payload, Signature, Signatures};
+use anyhow::{Context, Result};
+use base64::Engine;
+use console::Style;
+use std::path::Path;
+
+impl Extractor<'_> {
+    /// `info` subcommand: prints header, manifest and signature details of a payload.
+    pub(super) fn run_info(&self, path: &Path) -> Result<()> {
+        let source = self.open_payload_file(path)?;
+        let bytes: &[u8] = &source;
+        let mut payload = Payload::parse(bytes)?;
+        let manifest = Self::decode_manifest(payload.manifest())?;
+        let trailing = payload.trim_trailing(&manifest);
+
+        let heading = Style::new().bold().cyan();
+        let row = |key: &str, value: &dyn std::fmt::Display| println!("  {key:<22}{value}");
+
+        println!("{}", heading.apply_to("Payload"));
+        row(
+            "Format version",
+            &format!(
+                "{} ({})",
+                payload.version(),
+                chromeos::major_version_name(payload.version())
+            ),
+        );
+        row("Header size", &format!("{} bytes", payload.header_size()));
+        row(
+            "Manifest size",
+            &indicatif::HumanBytes(payload.manifest_size()),
+        );
+        row(
+            "Metadata signature",
+            &payload
+                .metadata_signature()
+                .map_or("none".to_string(), |s| format!("{} bytes", s.len())),
+        );
+        row("Data offset", &format!("{} bytes", payload.data_offset()));
+        row(
+            "Data size",
+            &indicatif::HumanBytes(payload.data().len() as u64),
+        );
+        if trailing > 0 {
+            row(
+                "Trailing bytes",
+                &format!("{trailing} (not referenced by the manifest; ignored)"),
+            );
+        }
+        let metadata_hash = payload.metadata_hash();
+        row("Metadata hash", &hex::encode(metadata_hash));
+        row(
+            "",
+            &format!(
+                "METADATA_HASH={}",
+                base64::engine::general_purpose::STANDARD.encode(metadata_hash)
+            ),
+        );
+        row("Crypto backend", &crypto::BACKEND);
+
+        println!("\n{}", heading.apply_to("Manifest"));
+        row("Block size", &manifest.block_size.unwrap_or(4096));
+        let minor = manifest.minor_version.unwrap_or(0);
+        row(
+            "Minor version",
+            &format!("{minor} ({})", chromeos::minor_version_name(minor)),
+        );
+        if chromeos::is_chromeos(&manifest) {
+            row("Platform", &"Chrome OS");
+        }
+        if let Some(ts) = manifest.max_timestamp {
+            let when = chrono::DateTime::from_timestamp(ts, 0).map_or(ts.to_string(), |t| {
+                t.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+            });
+            row("Max timestamp", &when);
+        }
+        if let Some(spl) = &manifest.security_patch_level {
+            row("Security patch level", spl);
+        }
+        if manifest.partial_update == Some(true) {
+            row("Partial update", &"yes");
+        }
+        let total: u64 = manifest
+            .partitions
+            .iter()
+            .filter_map(|p| p.new_partition_info.as_ref()?.size)
+            .fold(0, u64::saturating_add);
+        row(
+            "Partitions",
+            &format!(
+                "{} ({})",
+                manifest.partitions.len(),
+                indicatif::HumanBytes(total)
+            ),
+        );
+
+        println!("\n{}", heading.apply_to("Structure checks"));
+        let findings = heuristics::analyze(&payload, &manifest);
+        if findings.is_empty() {
+            println!("  no red flags");
+        }
+        for finding in &findings {
+            println!("  {} {finding}", Style::new().bold().yellow().apply_to("⚠"));
+        }
+
+        // Signatures are matched against --keyring, or else the package certificate.
+        let keys = match &self.cmd.keyring {
+            Some(keyring) => {
+                Some(sigverify::load_keyring(keyring).context("unable to load keyring")?)
+            }
+            None => match Self::read_zip_entry(path, sigverify::OTACERT_PATH)? {
+                Some(cert) => match PublicKey::parse_all(&cert, sigverify::OTACERT_PATH) {
+                    Ok(keys) => Some(keys),
+                    Err(e) => {
+                        eprintln!("Warning: unable to read {}: {e:#}", sigverify::OTACERT_PATH);
+                        None
+                    }
+                },
+                None => None,
+            },
+        };
+
+        println!("\n{}", heading.apply_to("Metadata signature"));
+        match payload.metadata_signature() {
+            Some(blob) => print_signatures(blob, payload.metadata(), keys.as_deref()),
+            None => println!("  none"),
+        }
+
+        println!("\n{}", heading.apply_to("Payload signature"));
+        match sigverify::payload_signature(bytes, &payload, &manifest) {
+            Ok(Some((signed, blob))) => print_signatures(blob, signed, keys.as_deref()),
+            Ok(None) => println!("  none"),
+            Err(e) => println!("  invalid: {e:#}"),
+        }
+        Ok(())
+    }
+}
+
+fn print_signatures(blob: &[u8], signed: &[u8], keys: Option<&[PublicKey]>) {
+    let entries = match Signatures::decode(blob) {
+        Ok(entries) if !entries.is_empty() => entries,
+        Ok(_) => {
+            println!("  empty signature blob");
+            return;
+        }
+        Err(e) => {
+            println!("  invalid: {e:#}");
+            return;
+        }
+    };
+
+    for (i, entry) in entries.iter().enumerate() {
+        print_signature(i + 1, entry, signed, keys);
+    }
+}
+
+fn print_signature(index: usize, entry: &Signature, signed: &[u8], keys: Option<&[PublicKey]>) {
+    let signer = keys.and_then(|keys| keys.iter().find(|k| k.verify(signed, &entry.data)));
+
+    println!("  Signature #{index}");
+    let algorithm = match signer {
+        Some(key) => format!("{} (verified)", key.describe()),
+        None => entry.algorithm_hint(),
+    };
+    println!("    {:<20}{}", "Algorithm", algorithm);
+    println!(
+        "    {:<20}{}",
+        "Key version",
+        entry
+            .version
+            .map_or("not recorded".to_string(), |v| v.to_string())
+    );
+    println!(
+        "    {:<20}{} bytes ({} padded)",
+        "Size",
+        entry.data.len(),
+        entry.padded_len
+    );
+    let fingerprint = match (signer, keys) {
+        (Some(key), _) => format!("{} ({})", key.fingerprint(), key.label),
+        (None, Some(_)) => "unknown (matches no trusted key)".to_string(),
+        (None, None) => "unknown (no certificate to match against)".to_string(),
+    };
+    println!("    {:<20}{}", "Key SHA-256", fingerprint);
+}
@@ -0,0 +1,175 @@
+use crate::cmd::ProgressMode;
+use indicatif::{HumanBytes, ProgressBar};
+use serde_json::json;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How often `--progress plain` prints a line for each partition that moved.
+const INTERVAL: Duration = Duration::from_secs(5);
+/// How often `--progress json` reports; frontends redraw from it, so it needs to be snappier.
+const JSON_INTERVAL: Duration = Duration::from_millis(500);
+
+/// `--progress plain`: periodic one-line updates (`boot: 45% 120.00 MiB/s`) in place of the
+/// redrawn bars, for CI logs, serial consoles and screen readers. `--progress json`: the same
+/// as newline-delimited JSON events on stderr, for GUI frontends and CI dashboards.
+///
+/// The bars still count bytes, drawn nowhere; one thread runs [`run`](Self::run), reading their
+/// positions every [`INTERVAL`] and printing a line for each that changed, until stopped.
+pub(crate) struct LineProgress {
+    bars: Vec<(String, ProgressBar)>,
+    json: bool,
+    stopped: Mutex<bool>,
+    wake: Condvar,
+}
+
+/// What was last printed for a bar.
+struct Last {
+    position: u64,
+    at: Instant,
+    done: bool,
+    /// The bar's message, naming a phase such as "verifying".
+    phase: String,
+    /// A `start` event went out (JSON only).
+    started: bool,
+}
+
+impl LineProgress {
+    /// `bars` are named in the order their lines are printed in; `mode` is `plain` or `json`.
+    pub(crate) fn new(bars: Vec<(String, ProgressBar)>, mode: ProgressMode) -> Self {
+        Self {
+            bars,
+            json: mode == ProgressMode::Json,
+            stopped: Mutex::new(false),
+            wake: Condvar::new(),
+        }
+    }
+
+    /// Ends [`run`](Self::run), after a last round of lines, once the returned guard is dropped.
+    pub(crate) fn stop_on_drop(&self) -> StopOnDrop<'_> {
+        StopOnDrop(self)
+    }
+
+    pub(crate) fn run(&self) {
+        let start = Instant::now();
+        let mut last: Vec<Last> = self
+            .bars
+            .iter()
+            .map(|_| Last {
+                position: 0,
+                at: start,
+                done: false,
+                phase: String::new(),
+                started: false,
+            })
+            .collect();
+        let interval = if self.json { JSON_INTERVAL } else { INTERVAL };
+        loop {
+            let (stopped, _) = self
+                .wake
+                .wait_timeout_while(self.lock(), interval, |stopped| !*stopped)
+                .unwrap_or_else(PoisonError::into_inner);
+            let stopped = *stopped;
+            if self.json {
+                self.report_json(&mut last);
+            } else {
+                self.report(&mut last);
+            }
+            if stopped {
+                return;
+            }
+        }
+    }
+
+    fn report(&self, last: &mut [Last]) {
+        let now = Instant::now();
+        for ((name, bar), last) in self.bars.iter().zip(last) {
+            let phase = bar.message();
+            if !last.done && !phase.is_empty() && phase != last.phase {
+                eprintln!("{name}: {phase}");
+            }
+            last.phase = phase;
+            let position = bar.position();
+            if last.done || position == last.position {
+                continue;
+            }
+            let len = bar.length().unwrap_or(0);
+            if position >= len {
+                eprintln!("{name}: 100% done");
+                last.done = true;
+            } else {
+                let secs = now.duration_since(last.at).as_secs_f64();
+                let rate = (position - last.position) as f64 / secs.max(f64::EPSILON);
+                eprintln!(
+                    "{name}: {}% {}/s",
+                    position * 100 / len.max(1),
+                    HumanBytes(rate as u64)
+                );
+            }
+            last.position = position;
+            last.at = now;
+        }
+    }
+
+    /// One event per change: `start` when a partition begins, `phase` when it enters one such
+    /// as "verifying", `progress` with bytes written, `written` once every byte is, and `done`
+    /// when the image is complete (and verified, unless `--no-verify`).
+    fn report_json(&self, last: &mut [Last]) {
+        let now = Instant::now();
+        for ((name, bar), last) in self.bars.iter().zip(last) {
+            if last.done {
+                continue;
+            }
+            let position = bar.position();
+            let len = bar.length().unwrap_or(0);
+            let phase = bar.message();
+            let finished = bar.is_finished();
+            if !last.started && (position > 0 || !phase.is_empty() || finished) {
+                event(json!({ "event": "start", "partition": name, "total": len }));
+                last.started = true;
+            }
+            if !phase.is_empty() && phase != last.phase {
+                event(json!({ "event": "phase", "partition": name, "phase": phase }));
+            }
+            last.phase = phase;
+            if position != last.position {
+                let secs = now.duration_since(last.at).as_secs_f64();
+                let rate = (position - last.position) as f64 / secs.max(f64::EPSILON);
+                event(json!({
+                    "event": "progress",
+                    "partition": name,
+                    "bytes": position,
+                    "total": len,
+                    "percent": position * 100 / len.max(1),
+                    "bytes_per_sec": rate as u64,
+                }));
+                if position >= len {
+                    event(json!({ "event": "written", "partition": name, "bytes": position }));
+                }
+                last.position = position;
+                last.at = now;
+            }
+            if finished {
+                event(json!({ "event": "done", "partition": name, "bytes": position }));
+                last.done = true;
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, bool> {
+        self.stopped.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub(crate) struct StopOnDrop<'p>(&'p LineProgress);
+
+impl Drop for StopOnDrop<'_> {
+    fn drop(&mut self) {
+        *self.0.lock() = true;
+        self.0.wake.notify_one();
+    }
+}
+
+/// Prints one `--progress json` event as a line on stderr.
+pub(crate) fn event(value: serde_json::Value) {
+    eprintln!("{value}");
+}
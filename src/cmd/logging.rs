@@ -0,0 +1,100 @@
+use anyhow::{Context, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// `-v`/`--log-file`: diagnostics through the `log` facade. The console gets records up to the
+/// `-v` level, on stderr; the log file gets every record down to per-operation traces, each
+/// stamped with the seconds since extraction started.
+struct Logger {
+    console: LevelFilter,
+    file: Option<Mutex<BufWriter<File>>>,
+    start: Instant,
+}
+
+/// Console level for a `-v` count: warnings and errors go to the console anyway, so one `-v`
+/// adds progress through the run, `-vv` each operation and `-vvv` everything.
+fn console_level(verbose: u8) -> LevelFilter {
+    match verbose {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Installs the logger for `-v` and `--log-file`. Does nothing when neither is given, so the
+/// library never takes over its caller's logger.
+pub(crate) fn init(verbose: u8, log_file: Option<&Path>) -> Result<()> {
+    if verbose == 0 && log_file.is_none() {
+        return Ok(());
+    }
+    // Batch mode runs one extraction per payload; the logger the first installs serves them all.
+    static INSTALLED: AtomicBool = AtomicBool::new(false);
+    if INSTALLED.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+    let file = match log_file {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("could not create log file {path:?}"))?;
+            Some(Mutex::new(BufWriter::new(file)))
+        }
+        None => None,
+    };
+    let console = console_level(verbose);
+    let max = if file.is_some() {
+        LevelFilter::Trace
+    } else {
+        console
+    };
+    let logger = Logger {
+        console,
+        file,
+        start: Instant::now(),
+    };
+    // A logger installed earlier in the process, by an embedding program, stays in charge.
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(max);
+    }
+    Ok(())
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.console || self.file.is_some()
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= self.console {
+            let tag = match record.level() {
+                Level::Error => "error",
+                Level::Warn => "warning",
+                Level::Info => "info",
+                Level::Debug => "debug",
+                Level::Trace => "trace",
+            };
+            eprintln!("[{tag}] {}", record.args());
+        }
+        if let Some(file) = &self.file {
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = writeln!(
+                file,
+                "{:>10.3} {:<5} {}",
+                self.start.elapsed().as_secs_f64(),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap_or_else(|e| e.into_inner()).flush();
+        }
+    }
+}
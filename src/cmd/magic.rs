@@ -0,0 +1,102 @@
+/// What an image's leading bytes say it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Ext4,
+    Erofs,
+    F2fs,
+    Squashfs,
+    AndroidBoot,
+    VendorBoot,
+    Vbmeta,
+    Dtbo,
+    Sparse,
+}
+
+impl Kind {
+    fn label(self) -> &'static str {
+        match self {
+            Kind::Ext4 => "an ext4 filesystem",
+            Kind::Erofs => "an EROFS filesystem",
+            Kind::F2fs => "an F2FS filesystem",
+            Kind::Squashfs => "a SquashFS filesystem",
+            Kind::AndroidBoot => "an Android boot image",
+            Kind::VendorBoot => "a vendor boot image",
+            Kind::Vbmeta => "an AVB vbmeta image",
+            Kind::Dtbo => "a DTBO image",
+            Kind::Sparse => "an Android sparse image",
+        }
+    }
+}
+
+const FILESYSTEMS: &[Kind] = &[Kind::Ext4, Kind::Erofs, Kind::F2fs, Kind::Squashfs];
+
+/// Partitions whose contents are predictable from their name, without the `_a`/`_b` suffix.
+fn expected(name: &str) -> Option<&'static [Kind]> {
+    let base = name
+        .strip_suffix("_a")
+        .or_else(|| name.strip_suffix("_b"))
+        .unwrap_or(name);
+    match base {
+        "system" | "system_ext" | "product" | "vendor" | "odm" | "oem" | "system_dlkm"
+        | "vendor_dlkm" | "odm_dlkm" => Some(FILESYSTEMS),
+        "boot" | "init_boot" | "recovery" => Some(&[Kind::AndroidBoot]),
+        "vendor_boot" | "vendor_kernel_boot" => Some(&[Kind::VendorBoot]),
+        "dtbo" => Some(&[Kind::Dtbo]),
+        b if b.starts_with("vbmeta") => Some(&[Kind::Vbmeta]),
+        _ => None,
+    }
+}
+
+fn detect(image: &[u8]) -> Option<Kind> {
+    let at = |offset: usize, magic: &[u8]| image.get(offset..offset + magic.len()) == Some(magic);
+
+    // Whole-image containers and headers at offset 0 first, then superblocks at 1 KiB.
+    if at(0, &0xED26_FF3Au32.to_le_bytes()) {
+        Some(Kind::Sparse)
+    } else if at(0, b"ANDROID!") {
+        Some(Kind::AndroidBoot)
+    } else if at(0, b"VNDRBOOT") {
+        Some(Kind::VendorBoot)
+    } else if at(0, b"AVB0") {
+        Some(Kind::Vbmeta)
+    } else if at(0, &0xD7B7_AB1Eu32.to_be_bytes()) {
+        Some(Kind::Dtbo)
+    } else if at(0, b"hsqs") {
+        Some(Kind::Squashfs)
+    } else if at(1024 + 0x38, &0xEF53u16.to_le_bytes()) {
+        Some(Kind::Ext4)
+    } else if at(1024, &0xE0F5_E1E2u32.to_le_bytes()) {
+        Some(Kind::Erofs)
+    } else if at(1024, &0xF2F5_2010u32.to_le_bytes()) {
+        Some(Kind::F2fs)
+    } else {
+        None
+    }
+}
+
+/// Warns when an image doesn't start with the magic its partition name implies, or when it is
+/// still in Android sparse format, which no partition holds once flashed.
+pub(crate) fn check(name: &str, image: &[u8]) -> Option<String> {
+    let found = detect(image);
+    if found == Some(Kind::Sparse) {
+        return Some(format!(
+            "the image is {}, not a raw partition image",
+            Kind::Sparse.label()
+        ));
+    }
+    let expected = expected(name)?;
+    if found.is_some_and(|k| expected.contains(&k)) {
+        return None;
+    }
+    let wanted = match expected {
+        [kind] => kind.label(),
+        _ => "a filesystem (ext4, EROFS, F2FS or SquashFS)",
+    };
+    Some(match found {
+        Some(kind) => format!(
+            "expected {wanted}, but the image looks like {}",
+            kind.label()
+        ),
+        None => format!("expected {wanted}, but the image has no recognizable magic"),
+    })
+}
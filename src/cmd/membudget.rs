@@ -0,0 +1,101 @@
+use crate::cmd::split;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// Smallest `--max-memory` accepted; below it a single large operation can't get anywhere.
+const MIN_MAX_MEMORY: u64 = 64 * 1024 * 1024;
+
+/// Parses `--max-memory`, e.g. `2G` or `512M`.
+pub(crate) fn parse_max_memory(value: &str) -> Result<u64, String> {
+    let size = split::parse_bytes(value)?;
+    if size < MIN_MAX_MEMORY {
+        return Err(format!(
+            "'{}' is too small; allow at least 64M",
+            value.trim()
+        ));
+    }
+    Ok(size)
+}
+
+/// How `--max-memory` is shared out: a payload inflated into RAM, the buffers of operations being
+/// decoded, and the write-behind queue each get part of it, so together they stay under it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Shares {
+    /// Largest deflated payload.bin inflated into RAM; larger ones go to a temp file.
+    pub in_ram_payload: u64,
+    /// Scratch buffers and decoder state of the operations running at once.
+    pub operations: u64,
+    /// Decoded data waiting in the write-behind queue.
+    pub write_behind: u64,
+}
+
+impl Shares {
+    pub(crate) fn of(max_memory: u64) -> Self {
+        Self {
+            in_ram_payload: max_memory / 4,
+            operations: max_memory / 2,
+            write_behind: max_memory / 4,
+        }
+    }
+}
+
+/// Bytes of memory operations may hold at once. A worker [`reserve`](Self::reserve)s what an
+/// operation is about to allocate and waits while that would go over the limit, so large
+/// operations take turns instead of all decoding at once.
+pub(crate) struct MemoryBudget {
+    limit: u64,
+    used: Mutex<u64>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits until `bytes` fit in the budget and holds them until the returned reservation is
+    /// dropped. A reservation larger than the whole budget only waits for everything else to be
+    /// released, so it still runs, alone. Zero bytes never wait.
+    pub(crate) fn reserve(&self, bytes: u64) -> Reservation<'_> {
+        if bytes == 0 {
+            return Reservation {
+                budget: self,
+                bytes,
+            };
+        }
+        let mut used = self.lock();
+        while *used > 0 && *used + bytes > self.limit {
+            used = self
+                .released
+                .wait(used)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *used += bytes;
+        Reservation {
+            budget: self,
+            bytes,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, u64> {
+        self.used.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub(crate) struct Reservation<'b> {
+    budget: &'b MemoryBudget,
+    bytes: u64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if self.bytes == 0 {
+            return;
+        }
+        *self.budget.lock() -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
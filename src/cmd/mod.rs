@@ -1,13 +1,66 @@
 pub mod extractor;
 pub mod simd;
 pub mod arbscan;
+pub mod fsprobe;
+pub mod fastcopy;
+pub mod sink;
+pub mod awake;
+pub mod crypto;
+pub mod sigverify;
+pub mod info;
+pub mod heuristics;
+pub mod audit;
+pub mod dumpops;
+pub mod entropy;
+pub mod magic;
+pub mod suggest;
+pub mod verifyonly;
+pub mod sidecar;
+pub mod alias;
+pub mod outmap;
+pub mod cleanup;
+pub mod readahead;
+pub mod flush;
+pub mod writebehind;
+pub mod inflate;
+pub mod config;
+pub mod chromeos;
+pub mod edl;
+pub mod flashzip;
+pub mod avb;
+pub mod i18n;
+pub mod lineprogress;
+pub mod debugbundle;
+pub mod source;
+pub mod remote;
+pub mod tostdout;
+pub mod dumpmanifest;
+pub mod simg;
+pub mod compress;
+pub mod split;
+pub mod streamout;
+pub mod resume;
+pub mod repack;
+pub mod hashalgo;
+pub mod pagecache;
+pub mod holes;
+pub mod membudget;
+pub mod logging;
+pub mod exitcode;
+pub mod batch;
 
-use crate::cmd::extractor::Extractor;
+use crate::cmd::compress::Compression;
+use crate::cmd::config::Config;
+use crate::cmd::extractor::{Extractor, ProgressHook};
+use crate::cmd::hashalgo::HashAlgo;
+use crate::payload::PayloadHeader;
 use anyhow::Result;
 use clap::{Parser, ValueHint};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
-#[derive(Debug, clap::Subcommand)]
+#[derive(Debug, Clone, clap::Subcommand)]
 pub enum SubCmd {
     /// Remove extracted_* folders
     #[clap(aliases = &["c"])]
@@ -32,9 +85,62 @@ pub enum SubCmd {
         #[clap(value_hint = clap::ValueHint::FilePath, value_name = "PATH")]
         image: PathBuf,
     },
+    /// Show payload header, manifest and signature details without extracting
+    Info {
+        /// Path to the OTA zip or payload.bin
+        #[clap(value_hint = clap::ValueHint::FilePath, value_name = "PATH")]
+        payload: PathBuf,
+    },
+    /// Check every operation and image hash without writing anything
+    #[clap(
+        long_about = "Check a payload's integrity without writing anything, for validating OTA artifacts in CI: the manifest's structure (duplicate partitions, extents past the partition end, overlapping data ranges, ...), then for each partition its destination extents and every operation's data hash. Partitions stored in full are also rebuilt in memory and checked against their image hash; incremental ones can't be without the base build. Prints a line per partition and exits with an error if any of them failed."
+    )]
+    Verify {
+        /// Path to the OTA zip or payload.bin
+        #[clap(value_hint = clap::ValueHint::FilePath, value_name = "PATH")]
+        payload: PathBuf,
+    },
+    /// Build a payload.bin from a folder of extracted images
+    #[clap(
+        long_about = "Build a full payload.bin from a folder of raw images, the inverse of extracting: each .img file becomes the partition named after it (boot.img → boot). Images are cut into 2 MiB operations, compressed with xz in parallel (REPLACE_XZ; REPLACE where xz doesn't make them smaller, ZERO for blocks of zeros), and the manifest carries every operation and partition hash, so otaripper and other tools can extract and verify it. The payload is not signed and has no dynamic partition metadata, so devices won't install it as is; sign it with AOSP's tooling for that. Sparse images must be converted with simg2img first. An existing output file is never overwritten."
+    )]
+    Repack {
+        /// Where to write the payload
+        #[clap(
+            short = 'o',
+            long,
+            value_name = "FILE",
+            default_value = "payload.bin",
+            value_hint = clap::ValueHint::FilePath
+        )]
+        output: PathBuf,
+
+        /// xz preset for operation data, 1-9; 0 stores it uncompressed
+        #[clap(
+            long,
+            value_name = "LEVEL",
+            default_value_t = 6,
+            value_parser = clap::value_parser!(u32).range(0..=9)
+        )]
+        level: u32,
+
+        /// Folder of .img files to pack
+        #[clap(value_hint = clap::ValueHint::DirPath, value_name = "DIR")]
+        images: PathBuf,
+    },
+    /// Print the payload's whole manifest as JSON or textproto
+    DumpManifest {
+        /// Output format
+        #[clap(long, value_enum, default_value_t = ManifestFormat::Json, value_name = "FORMAT")]
+        format: ManifestFormat,
+
+        /// Path to the OTA zip or payload.bin
+        #[clap(value_hint = clap::ValueHint::FilePath, value_name = "PATH")]
+        payload: PathBuf,
+    },
 }
 
-#[derive(Debug, Parser)]
+#[derive(Debug, Clone, Parser)]
 #[clap(
     about,
     author,
@@ -50,6 +156,7 @@ pub struct Cmd {
         conflicts_with = "threads",
         conflicts_with = "output_dir",
         conflicts_with = "partitions",
+        conflicts_with = "skip_partitions",
         conflicts_with = "no_verify",
         long,
         short
@@ -60,14 +167,153 @@ pub struct Cmd {
     #[clap(long, short, value_name = "NUMBER")]
     pub(super) threads: Option<usize>,
 
+    /// With several payloads, how many to extract at once
+    #[clap(
+        long,
+        short = 'j',
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..),
+        requires = "more_payloads",
+        help = "With several payloads, extract N of them at once instead of one after another. Each extraction still uses every thread (see --threads), so this pays off mostly for small payloads or slow storage. Progress bars are left out when N is above 1."
+    )]
+    pub(super) jobs: u16,
+
+    /// Start writing each image back to disk every MIB mebibytes
+    #[clap(
+        long,
+        value_name = "MIB",
+        value_parser = clap::value_parser!(u64).range(1..=1 << 20),
+        help = "Hand finished parts of each image to the OS for writing to disk every MIB mebibytes, instead of letting dirty pages pile up until the end. Smooths out I/O on multi-GB images, keeps progress closer to what is on disk and shortens the wait when extraction finishes. On Linux writeback starts in the background; elsewhere the image's data is synced, pausing one worker each time."
+    )]
+    pub(super) flush_every: Option<u64>,
+
+    /// How images are written: memory mapping or positional writes
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = IoModeChoice::Auto,
+        value_name = "MODE",
+        help = "How images are written: `mmap` decodes straight into a memory mapping of each image; `pwrite` decodes into buffers written with positional writes, reporting disk errors where they happen. `auto` uses pwrite on network shares and FUSE filesystems, and elsewhere times both on a small scratch file in the output directory, keeping mmap unless it takes over twice as long (slow page faults, some antivirus scanners)."
+    )]
+    pub(super) io_mode: IoModeChoice,
+
+    /// Inflate a compressed payload.bin to a temporary file instead of RAM
+    #[clap(
+        long,
+        help = "Never hold payload.bin in memory. A payload deflated inside the zip is inflated a chunk at a time into a temporary file (in --output-dir when given, else the system temp directory) and operations read it back through a memory mapping the system can page out. Without this, that happens only when the payload is larger than half the available RAM. A raw payload.bin, or one stored uncompressed in the zip, is always read in place."
+    )]
+    pub(super) low_memory: bool,
+
+    /// Keep otaripper's own memory use under SIZE
+    #[clap(
+        long,
+        value_name = "SIZE",
+        value_parser = membudget::parse_max_memory,
+        help = "Keep otaripper's own memory use under about SIZE, e.g. --max-memory 2G on a phone or small VPS; K, M, G and T are powers of 1024. A deflated payload.bin is inflated to a temporary file unless it fits in a quarter of SIZE, large operations wait their turn instead of all decoding at once, and the multithreaded xz decoder and the pwrite queue are held to their share. Images written through memory mappings live in the page cache, which the OS writes back under pressure, and don't count."
+    )]
+    pub(super) max_memory: Option<u64>,
+
+    /// Directory for large temporary files
+    #[clap(
+        long,
+        value_hint = ValueHint::DirPath,
+        value_name = "DIR",
+        help = "Put large temporary files in DIR: a deflated payload.bin inflated to disk (when larger than half the available RAM, or always with --low-memory) and the local copy of a payload read from a URL. By default the inflated payload goes in --output-dir unless that is a network share, and everything else in the system temp directory."
+    )]
+    pub(super) temp_dir: Option<PathBuf>,
+
     /// Set output directory
     #[clap(long, short, value_hint = ValueHint::DirPath, value_name = "PATH")]
     pub(super) output_dir: Option<PathBuf>,
 
+    /// Write images straight into --output-dir, without a timestamped folder
+    #[clap(
+        long,
+        alias = "no-subdir",
+        requires = "output_dir",
+        help = "Write the images straight into --output-dir instead of a new extracted_<date>_<time> folder inside it, for scripts that expect them at a fixed path. The folder is created if needed. Unless --force is given, images already there are never overwritten: extraction stops before writing anything if one of them exists. On failure only the images this run created are removed."
+    )]
+    pub(super) flat: bool,
+
+    /// Replace output images that already exist
+    #[clap(
+        long,
+        conflicts_with = "resume",
+        help = "Replace output images that already exist instead of stopping, so a run can be repeated into the same --flat folder or output map. An existing file is deleted and written anew, never modified in place, so hard links to it keep the old contents. With --split-size, every numbered piece of an image goes, not only those written again. If extraction fails, the replaced images are removed along with the rest of this run's output."
+    )]
+    pub(super) force: bool,
+
+    /// Extract into DIR, skipping images an earlier interrupted run already finished
+    #[clap(
+        long,
+        value_hint = ValueHint::DirPath,
+        value_name = "DIR",
+        conflicts_with_all = [
+            "list", "dump_ops", "verify_only", "stdout", "output_dir", "output_map", "compress",
+            "split_size", "sparse",
+        ],
+        help = "Extract into DIR and keep a journal there (.otaripper-state) of every image that has been written and verified. If the run is stopped with Ctrl+C or fails, finished images are kept instead of deleted, and running the same command again extracts only what is missing. Unfinished images are started over. A folder is only resumed with the payload that started it; the journal is removed once everything is extracted."
+    )]
+    pub(super) resume: Option<PathBuf>,
+
     /// Dump only selected partitions (comma-separated)
-    #[clap(short = 'p', long, value_delimiter = ',', value_name = "PARTITIONS")]
+    #[clap(
+        short = 'p',
+        long,
+        value_delimiter = ',',
+        value_name = "PARTITIONS",
+        help = "Extract only these partitions (comma-separated). A name with * (any run of characters) or ? (any one character) is a pattern, e.g. -p 'vendor*,*_dlkm'; quote patterns so the shell leaves them alone. Every name and pattern must match a partition in the manifest."
+    )]
     pub(super) partitions: Vec<String>,
 
+    /// Leave these partitions out (comma-separated)
+    #[clap(
+        long,
+        value_delimiter = ',',
+        value_name = "PARTITIONS",
+        help = "Extract everything except these partitions (comma-separated), e.g. --skip-partitions system,product. Combines with --partitions, leaving the named ones out of its selection. Names are checked against the manifest like --partitions names, aliases included."
+    )]
+    pub(super) skip_partitions: Vec<String>,
+
+    /// Extract only partitions that differ from those of another OTA
+    #[clap(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "OTA",
+        conflicts_with = "list",
+        help = "Extract only the partitions whose image differs from the one in OTA, e.g. last month's zip or payload.bin: those whose size or SHA-256 in the manifest (new_partition_info) is not the same as OTA's, and those OTA doesn't have. Only OTA's header and manifest are read. Partitions without a hash in either manifest are always extracted. Combines with --partitions and --skip-partitions. If nothing changed, nothing is written and the exit code is 0."
+    )]
+    pub(super) changed_since: Option<PathBuf>,
+
+    /// Slot(s) a suffix-less partition name picks on A/B payloads
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = Slot::All,
+        value_name = "SLOT",
+        help = "Slot(s) a suffix-less --partitions name picks when the payload names partitions per slot: `boot` selects boot_a and/or boot_b. A suffixed name (boot_a) also matches an unsuffixed entry (boot)."
+    )]
+    pub(super) slot: Slot,
+
+    /// How slot suffixes appear in output file names
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = RenameSlot::Keep,
+        value_name = "MODE",
+        help = "How slot suffixes appear in output file names: `keep` writes boot_a as boot_a.img; `strip` writes it as boot.img; `force-a` names every image for slot A, so boot and boot_b are written as boot_a.img too. Extraction stops before writing anything if two selected partitions would get the same name; pick one with --slot or --partitions."
+    )]
+    pub(super) rename_slot: RenameSlot,
+
+    /// Define a partition alias for --partitions
+    #[clap(
+        long = "alias",
+        value_name = "NAME=PARTITIONS",
+        help = "Define an alias usable with --partitions, as NAME=PART[+PART...] (e.g. --alias radio=modem+dsp); repeatable. Built in: recovery (vendor_boot), kernel (boot) and bootloader (abl, xbl, xbl_config, tz, ...). An alias only applies when the payload has no partition by that name, and selects whichever of its partitions the payload has."
+    )]
+    pub(super) aliases: Vec<String>,
+
     /// Skip file verification (dangerous!)
     #[clap(long, conflicts_with = "strict")]
     pub(super) no_verify: bool,
@@ -86,13 +332,246 @@ pub struct Cmd {
     )]
     pub(super) print_hash: bool,
 
+    /// Write a sha256sums.txt of the extracted images to the output directory
+    #[clap(
+        long,
+        conflicts_with_all = ["list", "dump_ops", "verify_only", "stdout", "compress", "split_size", "sparse", "resume"],
+        help = "Write sha256sums.txt next to the images, listing the SHA-256 of each one in the format `sha256sum -c sha256sums.txt` checks, so the files can be verified later without otaripper. With --hash-algo, one such file is written per algorithm. Images written elsewhere by --output-map are listed by their full path. Like --print-hash, this may add one pass over an image whose manifest has no hash."
+    )]
+    pub(super) hash_file: bool,
+
+    /// Digests --print-hash and --hash-file report (comma-separated)
+    #[clap(
+        long,
+        value_delimiter = ',',
+        value_name = "ALGOS",
+        value_parser = HashAlgo::parse,
+        default_value = "sha256",
+        conflicts_with = "verify_only",
+        help = "Digests --print-hash and --hash-file report, comma-separated from sha256, sha1, md5, blake3 and xxh3, e.g. --hash-algo sha256,md5. --hash-file writes one file per algorithm (md5sums.txt, b3sums.txt, ...). blake3 and xxh3 take a fraction of the time SHA-256 does on large images; with --trust-op-hashes and no sha256 here, the SHA-256 pass is skipped altogether. Images are always verified against the manifest with SHA-256."
+    )]
+    pub(super) hash_algo: Vec<HashAlgo>,
+
+    /// Skip the image hash pass when operation hashes cover the whole image
+    #[clap(
+        long,
+        conflicts_with_all = ["no_verify", "strict", "verify_chain", "verify_only"],
+        help = "Skip a partition's final SHA-256 pass when every block of it is written exactly once by REPLACE/REPLACE_BZ/REPLACE_XZ operations whose data hashes verified; the image then holds only verified bytes. The pass still runs for partitions with ZERO/DISCARD or unhashed operations, and for all partitions with --print-hash or --audit, which need the image digest."
+    )]
+    pub(super) trust_op_hashes: bool,
+
+    /// Verify the full chain of trust before and during extraction
+    #[clap(
+        long,
+        conflicts_with = "no_verify",
+        help = "Verify the full chain of trust: payload signature, metadata signature, every operation hash and every partition hash, starting from keys you trust given with --keyring or --pubkey (one is required). The certificate inside the zip is not used: whoever re-signs a zip replaces it too. Fails on the first missing or mismatching link."
+    )]
+    pub(super) verify_chain: bool,
+
+    /// Trusted public keys for signature verification
+    #[clap(
+        long,
+        global = true,
+        value_hint = ValueHint::AnyPath,
+        value_name = "PATH",
+        help = "Trusted public keys (a PEM/DER certificate or key, a PEM bundle, or a directory of them). The payload signature must match one of them before extraction starts; `info` reports which key matched. The trust anchor for --verify-chain."
+    )]
+    pub(super) keyring: Option<PathBuf>,
+
+    /// Public key the payload signature is checked against
+    #[clap(
+        long,
+        conflicts_with = "keyring",
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        help = "Check the payload signature against this public key or certificate (PEM or DER) before extracting. A missing or mismatching signature is reported as a warning and extraction goes ahead; with --strict (or --verify-chain, where it is the trust anchor) extraction is refused."
+    )]
+    pub(super) pubkey: Option<PathBuf>,
+
+    /// Check the metadata signature before extracting
+    #[clap(
+        long,
+        help = "Check the metadata signature, which covers the payload header and manifest, before extracting; refuses payloads without one (format version 1) or where it doesn't match. Checked against --cert; without it, only against the certificate shipped in the OTA zip, which shows the zip is self-consistent but not who signed it. Implied by --verify-chain."
+    )]
+    pub(super) verify_metadata: bool,
+
+    /// Certificate or public key for --verify-metadata
+    #[clap(
+        long,
+        requires = "verify_metadata",
+        conflicts_with = "verify_chain",
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        help = "Certificate or public key (PEM or DER) that --verify-metadata checks the metadata signature against, e.g. a vendor's release certificate. Needed for a raw payload.bin, which carries no package certificate."
+    )]
+    pub(super) cert: Option<PathBuf>,
+
+    /// Write a JSON Lines record of every operation applied
+    #[clap(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        help = "Write a forensic audit log (JSON Lines): payload details, then one record per operation with its type, data offsets, length and SHA-256, destination extents, decompressed size and timing, then each image's SHA-256."
+    )]
+    pub(super) audit: Option<PathBuf>,
+
+    /// Write each operation's raw data blob to a directory instead of extracting
+    #[clap(
+        long,
+        value_hint = ValueHint::DirPath,
+        value_name = "DIR",
+        conflicts_with = "list",
+        help = "Instead of extracting, write every operation's raw data (still compressed or diffed) to numbered files under DIR/<partition>/, plus DIR/index.json describing each op. Honors --partitions and works on incremental OTAs."
+    )]
+    pub(super) dump_ops: Option<PathBuf>,
+
+    /// Directory of the base build's images, for incremental OTAs
+    #[clap(
+        long,
+        visible_alias = "old",
+        value_hint = ValueHint::DirPath,
+        value_name = "DIR",
+        conflicts_with_all = ["list", "dump_ops", "verify_only"],
+        help = "Directory holding the base build's partition images (<partition>.img, as otaripper extracts them) for an incremental OTA. Partitions patched only with SOURCE_COPY operations are rebuilt by copying their blocks from the old image, checked against the operation's source hash (or the old image's hash when operations carry none). Partitions using binary diffs (SOURCE_BSDIFF, PUFFDIFF, ...) still can't be extracted."
+    )]
+    pub(super) source_dir: Option<PathBuf>,
+
+    /// Check every hash by rebuilding images in memory, without writing anything
+    #[clap(
+        long,
+        conflicts_with_all = ["list", "no_verify", "output_dir", "audit", "dump_ops", "sanity"],
+        help = "Verify the OTA end to end without writing anything: every operation is decoded and its data hash checked, and every image is rebuilt in memory and checked against its manifest hash. Images written front to back are hashed as they stream by; others are rebuilt in an anonymous memory mapping. Honors --partitions, --keyring, --pubkey and --verify-chain."
+    )]
+    pub(super) verify_only: bool,
+
+    /// Write the one selected partition's image to standard output
+    #[clap(
+        long,
+        requires = "partitions",
+        conflicts_with_all = [
+            "list", "output_dir", "print_hash", "audit", "dump_ops", "source_dir", "verify_only",
+            "verify_chain", "extract_metadata", "output_map", "edl_xml", "make_recovery_zip",
+            "sanity", "verity", "stats",
+        ],
+        help = "Write the image of the one partition selected with --partitions to standard output instead of a file, e.g. `otaripper ota.zip -p boot --stdout | magiskboot unpack -`. Operation hashes are checked before data is written; the image hash is checked once the whole image has gone out, and a mismatch then exits with an error. Images whose operations write out of order are assembled in memory first."
+    )]
+    pub(super) stdout: bool,
+
+    /// Also save the OTA package's metadata files next to the images
+    #[clap(
+        long,
+        conflicts_with_all = ["list", "dump_ops", "verify_only"],
+        help = "Also save the OTA package's metadata next to the images: META-INF/com/android/metadata (and metadata.pb), apex_info.pb, care_map.pb and payload_properties.txt. payload_properties.txt is generated from the payload when the package has none, e.g. for a bare payload.bin."
+    )]
+    pub(super) extract_metadata: bool,
+
+    /// Route partitions to specific paths with a TOML map file
+    #[clap(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        conflicts_with_all = ["list", "dump_ops", "verify_only"],
+        help = "Write partitions where a TOML map file says: `[partitions]` entries map a name to a file (boot = \"magisk_work/boot.img\") or, ending in /, a directory; `default = \"images/\"` sets where the rest go instead of a timestamped folder (not combinable with --output-dir). Names match like --partitions names. Relative paths are taken from the map file's directory. Existing files are never overwritten."
+    )]
+    pub(super) output_map: Option<PathBuf>,
+
+    /// Write QFIL/edl rawprogram and patch XML for the extracted images
+    #[clap(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        conflicts_with_all = ["list", "dump_ops", "verify_only"],
+        help = "Also write rawprogram<LUN>.xml and patch<LUN>.xml for flashing the extracted images with QFIL or edl. The TOML map FILE gives `sector-size` (default 4096; 512 on eMMC), `default-lun` for partitions without an entry, and `[partitions]` entries like boot = { lun = 4, start = 131078 }. Partitions without a `start` are laid out one after another from `first-sector` (default 6) on their LUN, sized from the manifest. Names match like --partitions names. The patch files are empty: the partition table is not changed."
+    )]
+    pub(super) edl_xml: Option<PathBuf>,
+
+    /// Also pack the extracted images into a recovery-flashable zip
+    #[clap(
+        long,
+        conflicts_with_all = ["list", "dump_ops", "verify_only"],
+        help = "Also write recovery_flashable.zip next to the images: a TWRP/OrangeFox-flashable zip whose update-binary writes each selected image to its /dev/block/by-name partition, on the active slot for unsuffixed names. Every image is checked against its partition before the first is written. Partitions inside super (system, vendor, ...) are left out."
+    )]
+    pub(super) make_recovery_zip: bool,
+
+    /// Write compressed images: zstd, gzip or xz, with an optional :LEVEL
+    #[clap(
+        long,
+        value_name = "CODEC[:LEVEL]",
+        value_parser = Compression::parse,
+        conflicts_with_all = [
+            "list", "dump_ops", "verify_only", "stdout", "audit", "source_dir", "extract_metadata",
+            "output_map", "edl_xml", "make_recovery_zip", "sanity", "verity", "stats", "sparse",
+        ],
+        help = "Write each image compressed while extracting, as boot.img.zst, boot.img.gz or boot.img.xz, instead of compressing the raw images afterwards. CODEC is zstd (levels 1-22, default 3), gzip (1-9, default 6) or xz (1-9, default 6), e.g. --compress xz:9. Partitions are rebuilt one after another and streamed through the compressor; those whose operations write out of order are assembled in memory first. Each image's hash is checked once it is written."
+    )]
+    pub(super) compress: Option<Compression>,
+
+    /// Cut each image into pieces of at most SIZE bytes
+    #[clap(
+        long,
+        value_name = "SIZE",
+        value_parser = split::parse_size,
+        conflicts_with_all = [
+            "list", "dump_ops", "verify_only", "stdout", "audit", "source_dir", "extract_metadata",
+            "output_map", "edl_xml", "make_recovery_zip", "sanity", "verity", "stats", "sparse",
+        ],
+        help = "Write images larger than SIZE as numbered pieces of at most SIZE bytes (super.img.000, super.img.001, ...), e.g. --split-size 3800M to fit FAT32 drives; K, M, G and T are powers of 1024. Smaller images keep their plain name. Join the pieces with `cat super.img.* > super.img` or `copy /b super.img.000+super.img.001 super.img`. Images are streamed out one after another as with --compress, which this combines with (super.img.zst.000, ...)."
+    )]
+    pub(super) split_size: Option<u64>,
+
+    /// Write images in Android sparse format
+    #[clap(
+        long,
+        conflicts_with_all = ["list", "dump_ops", "verify_only", "stdout", "edl_xml", "make_recovery_zip"],
+        help = "Write the images in Android sparse format (as produced by img2simg), which fastboot flashes directly. Blocks written only by ZERO/DISCARD operations, or by none, become DONT_CARE chunks and take no space in the file. Each image is extracted and verified as usual, then rewritten in place; --print-hash still reports the hash of the raw image."
+    )]
+    pub(super) sparse: bool,
+
     /// Run lightweight sanity checks on output images (e.g., detect all-zero images)
     #[clap(
         long,
-        help = "Run quick sanity checks on output images and fail on obviously invalid content (e.g., all zeros)."
+        help = "Run quick sanity checks on output images and fail on obviously invalid content (e.g., all zeros). Also warns when an image doesn't start with the magic its name implies (ext4/EROFS for system, ANDROID! for boot, AVB0 for vbmeta, ...) or its entropy profile looks implausible: a zero tail covering half the image or more, a near-constant fill, or random-looking data."
     )]
     pub(super) sanity: bool,
 
+    /// Report AVB verification data found in each extracted image
+    #[clap(
+        long,
+        conflicts_with_all = ["list", "dump_ops", "verify_only"],
+        help = "After extraction, list for each image whether it carries Android Verified Boot data that must be regenerated after modifying it: a dm-verity hash tree (with its offset and salt, and whether FEC follows), a hash descriptor (boot, dtbo, ...) or none. Read from the AVB footer at the end of each image."
+    )]
+    pub(super) verity: bool,
+
+    /// How progress is shown: redrawn bars or plain lines
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = ProgressMode::Bars,
+        value_name = "MODE",
+        help = "How progress is shown: `bars` redraws a bar per partition; `plain` prints a line such as \"boot: 45% 120.00 MiB/s\" every 5 seconds for each partition that moved, without colors or cursor movement, for CI logs, serial consoles and screen readers; `json` prints newline-delimited JSON events on stderr twice a second for GUI frontends and dashboards: start, phase, progress (bytes, total, percent, bytes_per_sec), written and done per partition, and error if the run fails. Other messages on stderr are plain text lines, so read only lines starting with `{`."
+    )]
+    pub(super) progress: ProgressMode,
+
+    /// Order of the progress bars
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = ProgressOrder::Size,
+        value_name = "ORDER",
+        help = "Order of the progress bars: `size` (largest first, the order partitions are started in), `name` (alphabetical) or `selection` (the order given to --partitions, else the payload's own order)."
+    )]
+    pub(super) progress_order: ProgressOrder,
+
+    /// Print only errors and the output asked for
+    #[clap(
+        long = "quiet",
+        short = 'q',
+        visible_alias = "no-progress",
+        conflicts_with = "progress",
+        help = "Print only errors and the output asked for, such as --print-hash: no progress bars or lines, no \"do NOT close this window\" banner, no notes along the way and no closing summary. The extracted folder is not opened. For cron jobs and build scripts."
+    )]
+    pub(super) quiet_flag: bool,
+
     /// Print per-partition and total timing/throughput statistics after extraction
     #[clap(
         long,
@@ -108,19 +587,225 @@ pub struct Cmd {
     )]
     pub(super) no_open: bool,
 
-    /// Positional argument for the payload file
+    /// Run a command on the output directory instead of opening the file manager
+    #[clap(
+        long,
+        env = "OTARIPPER_OPEN_WITH",
+        value_name = "COMMAND",
+        help = "Run COMMAND on completion instead of opening the file manager, e.g. --open-with \"code {dir}\". {dir} is replaced by the output directory, which is appended when COMMAND doesn't mention it. Quote arguments containing spaces. Can also be set with the OTARIPPER_OPEN_WITH environment variable or open-with in the config file; --no-open still wins."
+    )]
+    pub(super) open_with: Option<String>,
+
+    /// Let the system sleep during extraction
+    #[clap(
+        long,
+        help = "Let the system suspend while extracting. By default otaripper keeps the machine awake until extraction finishes."
+    )]
+    pub(super) allow_sleep: bool,
+
+    /// Read settings from this file instead of the default config file
+    #[clap(
+        long,
+        env = "OTARIPPER_CONFIG",
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "Read settings from this TOML file instead of otaripper/config.toml in the user's config directory (%APPDATA% on Windows, $XDG_CONFIG_HOME or ~/.config elsewhere). Keys: debug-cpu, open-with, lang. Command-line options and environment variables take precedence over the file. Can also be set with the OTARIPPER_CONFIG environment variable."
+    )]
+    pub(super) config: Option<PathBuf>,
+
+    /// Print the detected CPU features and the selected SIMD path
+    #[clap(
+        long,
+        help = "Print the detected CPU features and the selected SIMD path. Can also be set with OTARIPPER_DEBUG_CPU=1 or debug-cpu = true in the config file."
+    )]
+    pub(super) debug_cpu: bool,
+
+    /// Write diagnostics for a bug report to this file
+    #[clap(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        help = "Write diagnostics for a bug report to FILE, whether or not extraction succeeds: the command line, OS, CPU and SIMD features, payload header fields, each partition's size and operation type counts, and the full error chain. No payload data is included. An extraction that fails unexpectedly (exit code 1) writes one to the temp directory even without this option."
+    )]
+    pub(super) debug_bundle: Option<PathBuf>,
+
+    /// Log what extraction is doing; repeat for more detail
+    #[clap(
+        long,
+        short,
+        action = clap::ArgAction::Count,
+        help = "Log what extraction is doing to stderr: -v the steps of the run and each partition's verification, -vv every operation (type, data offset and length, destination extents, timing), -vvv everything."
+    )]
+    pub(super) verbose: u8,
+
+    /// Write a detailed log of the run to this file
+    #[clap(
+        long,
+        value_hint = ValueHint::FilePath,
+        value_name = "FILE",
+        help = "Write a timestamped log of the run to FILE at full detail, whatever -v is: payload and manifest fields, the chosen write mode, every operation with its offsets, extents and timing, and each verification result. For post-mortem debugging of bad payloads; --audit is the machine-readable counterpart."
+    )]
+    pub(super) log_file: Option<PathBuf>,
+
+    /// Language for messages
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        env = "OTARIPPER_LANG",
+        value_name = "LANG",
+        help = "Language for progress messages, prompts and summaries: en, es (Spanish) or pt-br (Brazilian Portuguese). Defaults to lang in the config file, then the system locale (LC_ALL, LC_MESSAGES or LANG), then English. Can also be set with the OTARIPPER_LANG environment variable. Most error details are still in English."
+    )]
+    pub(super) lang: Option<Lang>,
+
+    /// Positional argument for the payload file, or an http(s) URL of one
     #[clap(value_hint = ValueHint::FilePath)]
     #[clap(index = 1, value_name = "PATH")]
     pub(super) positional_payload: Option<PathBuf>,
 
-    /// Internal flag to suppress output
+    /// More payloads to extract, each into its own folder
+    #[clap(
+        index = 2,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = [
+            "resume", "stdout", "output_map", "audit", "dump_ops", "debug_bundle",
+        ],
+        help = "More payloads to extract after the first, e.g. `otaripper a.zip b.zip c/payload.bin`. Each goes into its own folder under --output-dir (or the current directory): extracted_<name>_<date>_<time>, or just <name> with --flat, where <name> is the file name without extension, or the folder name for a payload.bin. All other options apply to every payload. A failed payload doesn't stop the others; one summary follows at the end, and the exit code is non-zero if any failed."
+    )]
+    pub(super) more_payloads: Vec<PathBuf>,
+
+    /// Set by the library, which reports through its caller: suppresses all output.
     #[clap(skip)]
-    pub(super) quiet: bool,
+    pub(super) machine_output: bool,
+}
+
+/// A/B slot selection for `--slot`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum Slot {
+    A,
+    B,
+    All,
+}
+
+impl Slot {
+    /// Partition name suffixes this selection accepts.
+    pub(crate) fn suffixes(self) -> &'static [&'static str] {
+        match self {
+            Slot::A => &["_a"],
+            Slot::B => &["_b"],
+            Slot::All => &["_a", "_b"],
+        }
+    }
+}
+
+/// Output file naming for `--rename-slot`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum RenameSlot {
+    Keep,
+    Strip,
+    ForceA,
+}
+
+impl RenameSlot {
+    /// The name `partition`'s image is written under, before the `.img` extension.
+    pub(crate) fn apply(self, partition: &[u8]) -> Vec<u8> {
+        let base = [b"_a".as_slice(), b"_b"]
+            .into_iter()
+            .find_map(|suffix| partition.strip_suffix(suffix))
+            .unwrap_or(partition);
+        match self {
+            RenameSlot::Keep => partition.to_vec(),
+            RenameSlot::Strip => base.to_vec(),
+            RenameSlot::ForceA => [base, b"_a"].concat(),
+        }
+    }
+}
+
+/// Output format of `dump-manifest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ManifestFormat {
+    Json,
+    Textproto,
+}
+
+/// Message language for `--lang`; see [`i18n`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum Lang {
+    En,
+    Es,
+    #[value(alias = "pt")]
+    PtBr,
+}
+
+/// Output backend selection for `--io-mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum IoModeChoice {
+    Auto,
+    Mmap,
+    Pwrite,
+}
+
+/// Progress display for `--progress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum ProgressMode {
+    Bars,
+    Plain,
+    Json,
+}
+
+/// Progress bar ordering for `--progress-order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum ProgressOrder {
+    Size,
+    Name,
+    Selection,
 }
 
 impl Cmd {
+    /// Runs the command. No signal handlers are installed; to stop an extraction early, use
+    /// [`run_cancellable`](Self::run_cancellable).
     pub fn run(&self) -> Result<()> {
-        Extractor { cmd: self }.run()
+        if !self.more_payloads.is_empty() {
+            return batch::run(self, Arc::default());
+        }
+        Extractor::new(self, Config::resolve(self)?).run()
+    }
+
+    /// Like [`run`](Self::run), but an extraction stops between operations once `cancel` is set,
+    /// removes the files it started and returns an error.
+    pub fn run_cancellable(&self, cancel: Arc<AtomicBool>) -> Result<()> {
+        if !self.more_payloads.is_empty() {
+            return batch::run(self, cancel);
+        }
+        Extractor::new(self, Config::resolve(self)?)
+            .interruptible(cancel)
+            .run()
+    }
+
+    /// Runs with `config` as given rather than resolved from the environment and config file,
+    /// for the library's [`crate::Extractor`].
+    pub(crate) fn run_with_config(
+        &self,
+        config: Config,
+        cancel: Arc<AtomicBool>,
+        progress: Option<ProgressHook>,
+    ) -> Result<()> {
+        Extractor::new(self, config)
+            .interruptible(cancel)
+            .reporting(progress)
+            .run()
+    }
+
+    /// Whether progress, banners and notes are printed: not with --quiet, nor for the library,
+    /// which prints nothing.
+    pub(crate) fn chatty(&self) -> bool {
+        !self.machine_output && !self.quiet_flag
+    }
+
+    /// Reads the header and manifest of an OTA zip or payload.bin without extracting anything.
+    pub(crate) fn read_payload_header(path: &Path) -> Result<PayloadHeader> {
+        Extractor::read_payload_header(path)
     }
 }
 
@@ -139,6 +824,7 @@ const FRIENDLY_HELP: &str = color_print::cstr!(
   • <bold>Extract specific</bold>:                           otaripper update.zip -p boot,init_boot,vendor_boot
   • <bold>Disable auto-open folder after extraction: </bold> otaripper update.zip -n
   • <bold>Scan bootloader for ARB metadata: </bold>          otaripper arbscan xbl_config.img
+  • <bold>Show header & signature details: </bold>          otaripper info update.zip
 
 <bold>CLEANUP</bold>
     • <bold>Remove extracted folders</bold>:                 otaripper clean
@@ -0,0 +1,123 @@
+use crate::cmd::extractor::Extractor;
+use crate::cmd::fsprobe::{self, FsTraits};
+use crate::proto::chromeos_update_engine::PartitionUpdate;
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The `--output-map` file as written:
+///
+/// ```toml
+/// default = "images/"                  # everything else (optional)
+///
+/// [partitions]
+/// boot = "magisk_work/boot.img"        # exact file
+/// vendor_boot = "magisk_work/"         # directory: magisk_work/vendor_boot.img
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MapFile {
+    default: Option<PathBuf>,
+    #[serde(default)]
+    partitions: BTreeMap<String, String>,
+}
+
+/// Where `--output-map` sends partitions. Relative paths are taken from the map file's directory,
+/// so a map kept next to a script works from anywhere.
+pub(crate) struct OutputMap {
+    /// Directory for partitions without an entry, used as-is instead of a timestamped folder.
+    pub(crate) default: Option<PathBuf>,
+    entries: Vec<(String, Destination)>,
+}
+
+enum Destination {
+    File(PathBuf),
+    /// Written as `<partition>.img` inside the directory, slot suffix as `--rename-slot` says.
+    Dir(PathBuf),
+}
+
+impl OutputMap {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("could not read output map {path:?}"))?;
+        let map: MapFile =
+            toml::from_str(&text).with_context(|| format!("invalid output map {path:?}"))?;
+        let base = std::path::absolute(path)?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let entries = map
+            .partitions
+            .into_iter()
+            .map(|(name, target)| {
+                let is_dir = target.ends_with(['/', '\\']) || base.join(&target).is_dir();
+                let target = base.join(target);
+                let destination = if is_dir {
+                    Destination::Dir(target)
+                } else {
+                    Destination::File(target)
+                };
+                (name, destination)
+            })
+            .collect();
+        Ok(Self {
+            default: map.default.map(|dir| base.join(dir)),
+            entries,
+        })
+    }
+}
+
+impl Extractor<'_> {
+    /// Output paths for the selected partitions the map has an entry for, in selection order.
+    /// Entries are matched like `--partitions` names, so `boot` also routes `boot_a`/`boot_b`
+    /// unless they have entries of their own. File names in a directory entry are sanitized as
+    /// every other output name is. Two partitions may not land on the same file.
+    pub(super) fn mapped_output_paths<'m>(
+        &self,
+        map: &OutputMap,
+        selected: &[&'m PartitionUpdate],
+    ) -> Result<Vec<(&'m [u8], PathBuf)>> {
+        let mut paths = Vec::new();
+        let mut owners: HashMap<PathBuf, String> = HashMap::new();
+        for update in selected {
+            let name = update.name();
+            let entry = map
+                .entries
+                .iter()
+                .find(|(entry, _)| *entry == name)
+                .or_else(|| {
+                    map.entries
+                        .iter()
+                        .find(|(entry, _)| self.name_matches(update, entry))
+                });
+            let Some((_, destination)) = entry else {
+                continue;
+            };
+            let path = match destination {
+                Destination::File(file) => file.clone(),
+                Destination::Dir(dir) => {
+                    let stem = self.cmd.rename_slot.apply(&update.partition_name);
+                    let mut files =
+                        fsprobe::output_file_names([stem.as_slice()], FsTraits::probe(dir));
+                    dir.join(files.remove(stem.as_slice()).unwrap_or_default())
+                }
+            };
+            if let Some(other) = owners.insert(path.clone(), name.to_string()) {
+                bail!("output map sends both {other} and {name} to {path:?}");
+            }
+            paths.push((update.partition_name.as_slice(), path));
+        }
+
+        if self.cmd.chatty() {
+            for (entry, _) in &map.entries {
+                if !selected.iter().any(|u| self.name_matches(u, entry)) {
+                    eprintln!("Note: output map entry \"{entry}\" matches no selected partition.");
+                }
+            }
+        }
+        Ok(paths)
+    }
+}
@@ -0,0 +1,48 @@
+use std::fs::File;
+
+cfg_select! {
+    target_os = "linux" => {
+        use std::os::unix::io::AsRawFd;
+
+        /// Tells the kernel `map`, a payload mapping, is read mostly front to back, so it reads
+        /// ahead further and frees pages behind the reads sooner.
+        pub(crate) fn sequential(map: &[u8]) {
+            advise(map, libc::MADV_SEQUENTIAL);
+        }
+
+        /// Drops a finished, verified image from the page cache: `map`, its mapping if it was
+        /// written through one, lets go of its pages, and the file's clean pages are evicted
+        /// while dirty ones are queued for writeback. Without this, extracting a 12 GB OTA pushes
+        /// everything else out of the cache for images nothing is going to read again soon.
+        pub(crate) fn release(map: Option<&[u8]>, file: &File) {
+            if let Some(map) = map {
+                advise(map, libc::MADV_DONTNEED);
+            }
+            unsafe {
+                libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+            }
+        }
+
+        fn advise(map: &[u8], advice: libc::c_int) {
+            // madvise wants a page-aligned start. Mappings start on a page boundary even when
+            // the payload inside them does not, so rounding down stays inside them.
+            let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as usize;
+            let start = map.as_ptr() as usize;
+            let aligned = start & !(page - 1);
+            unsafe {
+                libc::madvise(
+                    aligned as *mut libc::c_void,
+                    map.len() + (start - aligned),
+                    advice,
+                );
+            }
+        }
+    }
+    _ => {
+        /// Access hints are Linux-only.
+        pub(crate) fn sequential(_map: &[u8]) {}
+
+        /// Page-cache hints are Linux-only.
+        pub(crate) fn release(_map: Option<&[u8]>, _file: &File) {}
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// How far the readahead thread stays ahead of the operations the workers have started.
+const WINDOW: u64 = 64 * 1024 * 1024;
+/// Large blobs are paged in a piece at a time so the window holds within a single operation.
+const STEP: usize = 8 * 1024 * 1024;
+
+/// Pages in the compressed data of upcoming operations before a worker gets to them, so
+/// decompression reads warm pages instead of stalling on faults into the payload mapping.
+///
+/// One thread runs [`run`](Self::run) over the operations' data in the order they are scheduled
+/// while workers report each operation they start; the thread waits whenever it is more than
+/// [`WINDOW`] bytes ahead, so readahead never pushes data out of the page cache before it is
+/// used.
+pub(crate) struct Readahead {
+    state: Mutex<State>,
+    progress: Condvar,
+}
+
+struct State {
+    /// Data bytes of the operations workers have started.
+    started: u64,
+    stopped: bool,
+}
+
+impl Readahead {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                started: 0,
+                stopped: false,
+            }),
+            progress: Condvar::new(),
+        }
+    }
+
+    /// A worker is about to read `bytes` of operation data.
+    pub(crate) fn started(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        self.lock().started += bytes;
+        self.progress.notify_one();
+    }
+
+    /// Ends [`run`](Self::run) at its next step once the returned guard is dropped, which also
+    /// happens while unwinding, so a panicking extraction isn't left waiting on the thread.
+    pub(crate) fn stop_on_drop(&self) -> StopOnDrop<'_> {
+        StopOnDrop(self)
+    }
+
+    /// Pages in `upcoming`, slices of the payload mapping in scheduling order, until they are
+    /// all paged in or it is stopped.
+    pub(crate) fn run<'a>(&self, upcoming: impl IntoIterator<Item = &'a [u8]>) {
+        let mut issued = 0u64;
+        for step in upcoming.into_iter().flat_map(|data| data.chunks(STEP)) {
+            let mut state = self.lock();
+            while !state.stopped && issued > state.started + WINDOW {
+                state = self
+                    .progress
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+            if state.stopped {
+                return;
+            }
+            drop(state);
+            will_need(step);
+            issued += step.len() as u64;
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub(crate) struct StopOnDrop<'r>(&'r Readahead);
+
+impl Drop for StopOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.lock().stopped = true;
+        self.0.progress.notify_one();
+    }
+}
+
+cfg_select! {
+    unix => {
+        /// Asks the kernel to start reading `data` in; returns without waiting for the I/O.
+        fn will_need(data: &[u8]) {
+            // madvise wants a page-aligned start. The payload mapping itself starts on a page
+            // boundary, so rounding down stays inside it.
+            let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as usize;
+            let start = data.as_ptr() as usize;
+            let aligned = start & !(page - 1);
+            unsafe {
+                libc::madvise(
+                    aligned as *mut libc::c_void,
+                    data.len() + (start - aligned),
+                    libc::MADV_WILLNEED,
+                );
+            }
+        }
+    }
+    _ => {
+        /// Faults `data` in by touching one byte per page.
+        fn will_need(data: &[u8]) {
+            for byte in data.iter().step_by(4096) {
+                std::hint::black_box(*byte);
+            }
+        }
+    }
+}
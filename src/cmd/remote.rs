@@ -0,0 +1,452 @@
+use crate::cmd::ProgressMode;
+use crate::cmd::exitcode::FailureKind;
+use crate::cmd::extractor::Extractor;
+use crate::cmd::sink;
+use crate::payload::Payload;
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use tempfile::NamedTempFile;
+
+/// Bytes fetched first: the payload header and, for all but the largest manifests, the
+/// manifest and metadata signature too.
+const HEAD_LEN: u64 = 1 << 20;
+
+/// Wanted ranges closer together than this are fetched in one request: a few hundred KiB
+/// that aren't needed cost less than another round trip.
+const MERGE_GAP: u64 = 256 * 1024;
+
+/// Whether the payload argument is an `http://` or `https://` URL rather than a path.
+pub(crate) fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|s| {
+        let s = s.to_ascii_lowercase();
+        s.starts_with("https://") || s.starts_with("http://")
+    })
+}
+
+impl Extractor<'_> {
+    /// Fetches what the run needs from the payload.bin at `url` with HTTP range requests, into
+    /// a sparse local copy of it that stands in for the payload from then on: the header and
+    /// manifest, the payload signature and, unless `metadata_only`, the operation data of the
+    /// selected partitions. The rest of the copy is never written.
+    ///
+    /// An OTA zip's central directory is read first, to find where its payload.bin is stored;
+    /// a deflated one would have to be downloaded whole.
+    ///
+    /// Requests are made by `curl`, which handles TLS, proxies and redirects the same way on
+    /// every platform otaripper runs on.
+    pub(super) fn fetch_remote(&self, url: &str, metadata_only: bool) -> Result<NamedTempFile> {
+        if self.cmd.keyring.is_some() || self.cmd.pubkey.is_some() || self.cmd.verify_chain {
+            return Err(FailureKind::Usage.tag(anyhow!(
+                "The payload signature covers the whole payload, which is not downloaded from a \
+                 URL; download the file to use --keyring, --pubkey or --verify-chain."
+            )));
+        }
+        if self.cmd.extract_metadata {
+            return Err(FailureKind::Usage.tag(anyhow!(
+                "--extract-metadata needs the whole payload; download the file to use it."
+            )));
+        }
+
+        let (head, url_len) = fetch(url, 0, HEAD_LEN)?;
+        let (base, total, head) = if head.starts_with(b"PK\x03\x04") {
+            let (base, total) = zip_payload_entry(url, url_len)?;
+            let (head, _) = fetch(url, base, HEAD_LEN.min(total))?;
+            (base, total, head)
+        } else {
+            (0, url_len, head)
+        };
+        let data_offset = metadata_len(&head)
+            .with_context(|| format!("{url} does not look like a payload.bin or OTA zip"))?;
+        ensure!(
+            data_offset <= total,
+            "the payload at {url} is truncated: its metadata ends past {total} bytes"
+        );
+
+        let mut local = tempfile::Builder::new();
+        local.prefix("otaripper-remote-");
+        let local = match &self.cmd.temp_dir {
+            Some(dir) => local.tempfile_in(dir),
+            None => local.tempfile(),
+        }
+        .context("failed to create a local copy of the remote payload")?;
+        let file = local.as_file();
+        // Sparse where the filesystem supports it: only fetched ranges take up space.
+        file.set_len(total)
+            .context("failed to size the local copy of the remote payload")?;
+        let metadata = if head.len() as u64 >= data_offset {
+            head[..data_offset as usize].to_vec()
+        } else {
+            let fetched = head.len() as u64;
+            let (rest, _) = fetch(url, base + fetched, data_offset - fetched)?;
+            [head, rest].concat()
+        };
+        sink::write_all_at(file, &metadata, 0)?;
+
+        let header = Payload::parse_header(&metadata[..])?;
+        let manifest = Self::decode_manifest(header.manifest())?;
+        // Offsets come from the manifest; any that overflow can't be in the payload.
+        let past_end = || anyhow!("the manifest of {url} points past the end of the payload");
+        let mut wanted = Vec::new();
+        if let (Some(offset), Some(size)) = (manifest.signatures_offset, manifest.signatures_size) {
+            wanted.push((data_offset.checked_add(offset).ok_or_else(past_end)?, size));
+        }
+        if !metadata_only {
+            self.select_partitions(&manifest)?;
+            for update in manifest.partitions.iter().filter(|u| self.is_selected(u)) {
+                for op in &update.operations {
+                    if let (Some(offset), Some(len)) = (op.data_offset, op.data_length)
+                        && len > 0
+                    {
+                        wanted.push((data_offset.checked_add(offset).ok_or_else(past_end)?, len));
+                    }
+                }
+            }
+        }
+        let ranges = merge_ranges(wanted, total);
+
+        let needed: u64 = ranges.iter().map(|&(_, len)| len).sum();
+        if self.cmd.chatty() {
+            eprintln!(
+                "Fetching {} of {} from {url}",
+                HumanBytes(needed),
+                HumanBytes(total)
+            );
+        }
+        let progress_bar = if !self.cmd.chatty() || self.cmd.progress != ProgressMode::Bars {
+            ProgressBar::hidden()
+        } else {
+            ProgressBar::new(needed).with_style(
+                ProgressStyle::with_template(
+                    "{prefix:>24!.green.bold} [{wide_bar:.white.dim}] {bytes}/{total_bytes}",
+                )
+                .context("unable to build progress bar template")?
+                .progress_chars("=> "),
+            )
+        };
+        progress_bar.set_prefix("download");
+        for (start, len) in ranges {
+            ensure!(!self.interrupted(), "Download cancelled.");
+            fetch_into(url, base, start, len, file, &progress_bar)?;
+        }
+        progress_bar.finish_and_clear();
+        Ok(local)
+    }
+}
+
+/// Finds the payload.bin entry of the zip at `url` from its central directory, returning where
+/// its data starts and its length.
+fn zip_payload_entry(url: &str, url_len: u64) -> Result<(u64, u64)> {
+    const MAX_COMMENT: u64 = u16::MAX as u64;
+    const EOCD_LEN: u64 = 22;
+    const LOCATOR_LEN: u64 = 20;
+
+    // Every offset and length below is read from the server's bytes, so each range is checked
+    // against the zip's length before it is requested.
+    let within = |offset: u64, len: u64| offset.checked_add(len).is_some_and(|end| end <= url_len);
+    let tail_start = url_len.saturating_sub(EOCD_LEN + MAX_COMMENT + LOCATOR_LEN);
+    let (tail, _) = fetch(url, tail_start, url_len - tail_start)?;
+    let eocd = tail
+        .windows(4)
+        .rposition(|w| w == b"PK\x05\x06")
+        .context("the zip's end of central directory record is missing")?;
+    let truncated = || anyhow::anyhow!("the zip's central directory is truncated");
+    let mut cd_len = u64::from(le32(&tail, eocd + 12).ok_or_else(truncated)?);
+    let mut cd_offset = u64::from(le32(&tail, eocd + 16).ok_or_else(truncated)?);
+    if (cd_len == u64::from(u32::MAX) || cd_offset == u64::from(u32::MAX))
+        && let Some(locator) = eocd.checked_sub(LOCATOR_LEN as usize)
+        && tail[locator..].starts_with(b"PK\x06\x07")
+    {
+        // ZIP64: the real sizes are in a record the locator points at.
+        let eocd64_offset = le64(&tail, locator + 8).ok_or_else(truncated)?;
+        ensure!(
+            within(eocd64_offset, 56),
+            "the zip's ZIP64 end of central directory record is past the end of the zip"
+        );
+        let (eocd64, _) = fetch(url, eocd64_offset, 56)?;
+        ensure!(
+            eocd64.starts_with(b"PK\x06\x06"),
+            "the zip's ZIP64 end of central directory record is missing"
+        );
+        cd_len = le64(&eocd64, 40).ok_or_else(truncated)?;
+        cd_offset = le64(&eocd64, 48).ok_or_else(truncated)?;
+    }
+    ensure!(
+        within(cd_offset, cd_len) && cd_len < 64 << 20,
+        "the zip's central directory is malformed"
+    );
+
+    let (cd, _) = fetch(url, cd_offset, cd_len)?;
+    let mut pos = 0;
+    while cd
+        .get(pos..)
+        .is_some_and(|rest| rest.starts_with(b"PK\x01\x02"))
+    {
+        let field16 = |at| le16(&cd, pos + at).map(usize::from).ok_or_else(truncated);
+        let field32 = |at| le32(&cd, pos + at).ok_or_else(truncated);
+        let (name_len, extra_len) = (field16(28)?, field16(30)?);
+        let entry_len = 46 + name_len + extra_len + field16(32)?;
+        let name = cd
+            .get(pos + 46..pos + 46 + name_len)
+            .ok_or_else(truncated)?;
+        if name != b"payload.bin" {
+            pos += entry_len;
+            continue;
+        }
+
+        ensure!(field16(8)? & 1 == 0, "payload.bin in {url} is encrypted");
+        ensure!(
+            field16(10)? == 0,
+            "payload.bin in {url} is compressed, so it can't be read in pieces; download the \
+             file instead"
+        );
+        let mut size = u64::from(field32(24)?);
+        let mut local_offset = u64::from(field32(42)?);
+        let extra = cd
+            .get(pos + 46 + name_len..pos + 46 + name_len + extra_len)
+            .ok_or_else(truncated)?;
+        if let Some(zip64) = zip64_extra(extra) {
+            // Present in this order, each only when its 32-bit field is saturated.
+            let mut values = zip64.chunks_exact(8).map(|v| le64(v, 0).unwrap_or(0));
+            if size == u64::from(u32::MAX) {
+                size = values.next().context("ZIP64 field missing")?;
+            }
+            if u64::from(field32(20)?) == u64::from(u32::MAX) {
+                values.next();
+            }
+            if local_offset == u64::from(u32::MAX) {
+                local_offset = values.next().context("ZIP64 field missing")?;
+            }
+        }
+
+        ensure!(
+            within(local_offset, 30),
+            "payload.bin's local header in {url} is past the end of the zip"
+        );
+        let (local, _) = fetch(url, local_offset, 30)?;
+        ensure!(
+            local.starts_with(b"PK\x03\x04"),
+            "payload.bin's local header in {url} is missing"
+        );
+        let header_len = 30
+            + u64::from(le16(&local, 26).ok_or_else(truncated)?)
+            + u64::from(le16(&local, 28).ok_or_else(truncated)?);
+        let start = local_offset
+            .checked_add(header_len)
+            .filter(|&start| within(start, size))
+            .with_context(|| format!("payload.bin in {url} extends past the end of the zip"))?;
+        return Ok((start, size));
+    }
+    bail!("{url} has no payload.bin")
+}
+
+/// The data of the ZIP64 extended information field (id 1) in an entry's extra field.
+fn zip64_extra(mut extra: &[u8]) -> Option<&[u8]> {
+    while extra.len() >= 4 {
+        let id = le16(extra, 0)?;
+        let len = usize::from(le16(extra, 2)?);
+        let data = extra.get(4..4 + len)?;
+        if id == 1 {
+            return Some(data);
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+fn le16(buf: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(buf.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le32(buf: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(buf.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le64(buf: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(buf.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Length of the header, manifest and metadata signature, from the fixed header fields.
+fn metadata_len(head: &[u8]) -> Option<u64> {
+    let field = |at: usize| Some(u64::from_be_bytes(head.get(at..at + 8)?.try_into().ok()?));
+    if !head.starts_with(b"CrAU") {
+        return None;
+    }
+    let manifest_len = field(12)?;
+    match field(4)? {
+        1 => manifest_len.checked_add(20),
+        2 => {
+            let sig_len = u32::from_be_bytes(head.get(20..24)?.try_into().ok()?);
+            manifest_len.checked_add(24 + u64::from(sig_len))
+        }
+        _ => None,
+    }
+}
+
+/// Sorts and merges `(offset, len)` ranges, joining those less than [`MERGE_GAP`] apart and
+/// dropping any part past `total`.
+fn merge_ranges(mut ranges: Vec<(u64, u64)>, total: u64) -> Vec<(u64, u64)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, len) in ranges {
+        let end = start.saturating_add(len).min(total);
+        if start >= end {
+            continue;
+        }
+        match merged.last_mut() {
+            Some((prev_start, prev_len))
+                if start <= (*prev_start + *prev_len).saturating_add(MERGE_GAP) =>
+            {
+                *prev_len = (*prev_start + *prev_len).max(end) - *prev_start;
+            }
+            _ => merged.push((start, end - start)),
+        }
+    }
+    merged
+}
+
+/// Reads `len` bytes at `start` of `url` into memory, returning them with the resource's total
+/// length.
+fn fetch(url: &str, start: u64, len: u64) -> Result<(Vec<u8>, u64)> {
+    let mut child = spawn_curl(url, start, len)?;
+    let mut body = BufReader::new(child.stdout.take().context("curl has no stdout")?);
+    let total = read_headers(&mut body, url)?;
+    let mut data = Vec::new();
+    (&mut body)
+        .take(len)
+        .read_to_end(&mut data)
+        .context("failed to read from curl")?;
+    drop(body);
+    finish_curl(child, url)?;
+    Ok((data, total))
+}
+
+/// Streams `len` bytes at `base + start` of `url` into `file` at `start`.
+fn fetch_into(
+    url: &str,
+    base: u64,
+    start: u64,
+    len: u64,
+    file: &File,
+    progress_bar: &ProgressBar,
+) -> Result<()> {
+    let at = base
+        .checked_add(start)
+        .with_context(|| format!("offset {start} is out of range for {url}"))?;
+    let mut child = spawn_curl(url, at, len)?;
+    let mut body = BufReader::new(child.stdout.take().context("curl has no stdout")?);
+    read_headers(&mut body, url)?;
+    let mut buf = vec![0u8; 1 << 20];
+    let mut done = 0u64;
+    while done < len {
+        let want = buf.len().min((len - done) as usize);
+        let n = match body.read(&mut buf[..want]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).context("failed to read from curl"),
+        };
+        sink::write_all_at(file, &buf[..n], start + done)
+            .context("failed to write to the local copy of the remote payload")?;
+        done += n as u64;
+        progress_bar.inc(n as u64);
+    }
+    drop(body);
+    finish_curl(child, url)?;
+    ensure!(
+        done == len,
+        "{url} ended early: got {done} of {len} bytes at offset {start}"
+    );
+    Ok(())
+}
+
+fn spawn_curl(url: &str, start: u64, len: u64) -> Result<Child> {
+    let end = start
+        .checked_add(len.max(1) - 1)
+        .with_context(|| format!("{len} bytes at offset {start} are out of range for {url}"))?;
+    let range = format!("{start}-{end}");
+    Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--location",
+            "--dump-header",
+            "-",
+        ])
+        .args(["--range", &range, "--", url])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                anyhow::anyhow!(
+                    "Remote payloads are fetched with curl, which was not found on PATH."
+                )
+            }
+            _ => anyhow::Error::new(e).context("failed to run curl"),
+        })
+}
+
+fn finish_curl(mut child: Child, url: &str) -> Result<()> {
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    let status = child.wait().context("failed to wait for curl")?;
+    if !status.success() {
+        bail!("failed to fetch {url}: {}", stderr.trim());
+    }
+    Ok(())
+}
+
+/// Skips the response headers curl writes ahead of the body, one block per redirect, and
+/// returns the total length from the final `Content-Range`. A server that answers a range
+/// request with the whole file is an error.
+fn read_headers(reader: &mut impl BufRead, url: &str) -> Result<u64> {
+    loop {
+        let mut status_line = String::new();
+        reader
+            .read_line(&mut status_line)
+            .context("failed to read from curl")?;
+        let status: u16 = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .with_context(|| format!("unexpected response from {url}: {status_line:?}"))?;
+
+        let mut total = None;
+        loop {
+            let mut line = String::new();
+            if reader
+                .read_line(&mut line)
+                .context("failed to read from curl")?
+                == 0
+            {
+                break;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-range")
+            {
+                total = value.rsplit('/').next().and_then(|t| t.trim().parse().ok());
+            }
+        }
+
+        match status {
+            100..=199 | 300..=399 => continue,
+            206 => {
+                return total.with_context(|| format!("{url} did not report its total length"));
+            }
+            200 => bail!("{url} does not support range requests; download the file instead"),
+            _ => bail!("failed to fetch {url}: {}", status_line.trim()),
+        }
+    }
+}
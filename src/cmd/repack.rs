@@ -0,0 +1,244 @@
+use crate::cmd::cleanup::CleanupGuard;
+use crate::cmd::crypto;
+use crate::cmd::extractor::Extractor;
+use crate::cmd::i18n::Msg;
+use crate::cmd::simd::{CpuSimd, is_all_zero_with_simd};
+use crate::cmd::verifyonly::OPS_PER_THREAD;
+use crate::proto::chromeos_update_engine::install_operation::Type;
+use crate::proto::chromeos_update_engine::{
+    DeltaArchiveManifest, Extent, InstallOperation, PartitionInfo, PartitionUpdate,
+};
+use anyhow::{Context, Result, bail, ensure};
+use liblzma::write::XzEncoder;
+use memmap2::Mmap;
+use prost::Message;
+use rayon::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+const BLOCK_SIZE: usize = 4096;
+
+/// How an Android sparse image starts, which would be packed as if it were the raw one.
+const SPARSE_MAGIC: [u8; 4] = 0xed26_ff3a_u32.to_le_bytes();
+
+/// Blocks each operation writes: 2 MiB, as in the full payloads update_engine generates.
+const OP_BLOCKS: usize = 512;
+
+/// An operation's data once compressed, and its hash.
+struct Encoded {
+    kind: Type,
+    blob: Option<(Vec<u8>, [u8; 32])>,
+}
+
+impl Extractor<'_> {
+    /// `repack` subcommand: builds a full, unsigned payload.bin from a folder of raw images,
+    /// one partition per `.img` file. Each image is cut into 2 MiB REPLACE_XZ operations
+    /// (REPLACE where xz doesn't help, ZERO for blocks of zeros) compressed in parallel, with
+    /// operation and partition hashes, so the payload extracts and verifies like any other.
+    pub(super) fn run_repack(&self, dir: &Path, output: &Path, level: u32) -> Result<()> {
+        let images = list_images(dir)?;
+        let out_dir = match output.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let cleanup = CleanupGuard::new(out_dir.to_path_buf(), false);
+        let out_file =
+            File::create_new(output).with_context(|| format!("could not create {output:?}"))?;
+        cleanup.track(output.to_path_buf());
+
+        // Data goes to a temp file first: the manifest before it isn't known until the end.
+        let data = NamedTempFile::new_in(out_dir).context("could not create a temporary file")?;
+        let mut data_out = BufWriter::with_capacity(1 << 20, data.as_file());
+        let mut data_len = 0u64;
+
+        let threadpool = self.get_threadpool()?;
+        // Operations compressed at a time; bounds the compressed data held in memory.
+        let window = threadpool.current_num_threads() * OPS_PER_THREAD;
+        let simd = CpuSimd::get();
+        let mut partitions = Vec::with_capacity(images.len());
+        for (name, path) in &images {
+            if self.interrupted() {
+                bail!("{}", Msg::Cancelled.text());
+            }
+            let update = threadpool
+                .install(|| {
+                    self.encode_image(
+                        name,
+                        path,
+                        level,
+                        simd,
+                        window,
+                        &mut data_out,
+                        &mut data_len,
+                    )
+                })
+                .with_context(|| format!("could not repack {path:?}"))?;
+            partitions.push(update);
+        }
+        data_out.flush()?;
+        drop(data_out);
+
+        let manifest = DeltaArchiveManifest {
+            block_size: Some(BLOCK_SIZE as u32),
+            minor_version: Some(0),
+            partitions,
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let mut out = BufWriter::with_capacity(1 << 20, out_file);
+        out.write_all(b"CrAU")?;
+        out.write_all(&2u64.to_be_bytes())?;
+        out.write_all(&(manifest.len() as u64).to_be_bytes())?;
+        out.write_all(&0u32.to_be_bytes())?; // no metadata signature
+        out.write_all(&manifest)?;
+        let mut data_in = data.as_file();
+        data_in.seek(SeekFrom::Start(0))?;
+        io::copy(&mut data_in, &mut out).with_context(|| format!("could not write {output:?}"))?;
+        out.into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()
+            .with_context(|| format!("could not write {output:?}"))?;
+
+        cleanup.keep();
+        if self.cmd.chatty() {
+            let size = fs::metadata(output)?.len();
+            println!(
+                "Wrote {} ({}, {} partitions). The payload is not signed.",
+                output.display(),
+                indicatif::HumanBytes(size),
+                images.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Encodes one image's operations, appending their data to `data` from offset `data_len`
+    /// on, `window` operations at a time.
+    #[allow(clippy::too_many_arguments)]
+    fn encode_image(
+        &self,
+        name: &str,
+        path: &Path,
+        level: u32,
+        simd: CpuSimd,
+        window: usize,
+        data: &mut impl Write,
+        data_len: &mut u64,
+    ) -> Result<PartitionUpdate> {
+        let file = File::open(path).with_context(|| format!("could not open {path:?}"))?;
+        let len = file.metadata()?.len();
+        ensure!(
+            len.is_multiple_of(BLOCK_SIZE as u64),
+            "its {len} bytes are not a whole number of {BLOCK_SIZE}-byte blocks"
+        );
+        // Mapping an empty file fails on some systems.
+        let map = match len {
+            0 => None,
+            _ => Some(unsafe { Mmap::map(&file) }.context("could not map the image")?),
+        };
+        let image: &[u8] = map.as_deref().unwrap_or_default();
+        ensure!(
+            !image.starts_with(&SPARSE_MAGIC),
+            "it is an Android sparse image; convert it with simg2img first"
+        );
+
+        let mut update = PartitionUpdate {
+            partition_name: name.as_bytes().to_vec(),
+            new_partition_info: Some(PartitionInfo {
+                size: Some(len),
+                hash: None,
+            }),
+            ..Default::default()
+        };
+        let progress_bar = self.create_progress_bar(&update)?;
+
+        let chunks: Vec<&[u8]> = image.chunks(OP_BLOCKS * BLOCK_SIZE).collect();
+        for (batch_index, batch) in chunks.chunks(window).enumerate() {
+            if self.interrupted() {
+                bail!("{}", Msg::Cancelled.text());
+            }
+            let encoded = batch
+                .par_iter()
+                .map(|chunk| {
+                    let encoded = encode_chunk(chunk, level, simd);
+                    progress_bar.inc(chunk.len() as u64);
+                    encoded
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            for (i, (chunk, encoded)) in batch.iter().zip(encoded).enumerate() {
+                let start_block = ((batch_index * window + i) * OP_BLOCKS) as u64;
+                let mut op = InstallOperation {
+                    r#type: encoded.kind as i32,
+                    dst_extents: vec![Extent {
+                        start_block: Some(start_block),
+                        num_blocks: Some((chunk.len() / BLOCK_SIZE) as u64),
+                    }],
+                    ..Default::default()
+                };
+                if let Some((blob, hash)) = encoded.blob {
+                    op.data_offset = Some(*data_len);
+                    op.data_length = Some(blob.len() as u64);
+                    op.data_sha256_hash = Some(hash.to_vec());
+                    data.write_all(&blob)?;
+                    *data_len += blob.len() as u64;
+                }
+                update.operations.push(op);
+            }
+        }
+        progress_bar.finish();
+
+        if let Some(info) = update.new_partition_info.as_mut() {
+            info.hash = Some(crypto::sha256(image).to_vec());
+        }
+        Ok(update)
+    }
+}
+
+/// The `.img` files in `dir`, sorted, each with the partition name it stands for.
+fn list_images(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut images = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("could not read {dir:?}"))? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "img") {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .with_context(|| format!("{path:?} is not a usable partition name"))?
+            .to_owned();
+        images.push((name, path));
+    }
+    ensure!(!images.is_empty(), "no .img files in {dir:?}");
+    images.sort();
+    Ok(images)
+}
+
+/// A ZERO operation for a chunk of zeros, otherwise REPLACE_XZ, or REPLACE when compressing
+/// doesn't make it smaller or `level` is 0.
+fn encode_chunk(chunk: &[u8], level: u32, simd: CpuSimd) -> io::Result<Encoded> {
+    if is_all_zero_with_simd(simd, chunk) {
+        return Ok(Encoded {
+            kind: Type::Zero,
+            blob: None,
+        });
+    }
+    if level > 0 {
+        let mut xz = XzEncoder::new(Vec::with_capacity(chunk.len() / 2), level);
+        xz.write_all(chunk)?;
+        let packed = xz.finish()?;
+        if packed.len() < chunk.len() {
+            let hash = crypto::sha256(&packed);
+            return Ok(Encoded {
+                kind: Type::ReplaceXz,
+                blob: Some((packed, hash)),
+            });
+        }
+    }
+    Ok(Encoded {
+        kind: Type::Replace,
+        blob: Some((chunk.to_vec(), crypto::sha256(chunk))),
+    })
+}
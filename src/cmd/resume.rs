@@ -0,0 +1,128 @@
+use anyhow::{Context, Result, ensure};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// The `--resume` journal's name in the output directory.
+pub(crate) const STATE_FILE: &str = ".otaripper-state";
+
+#[derive(Serialize, Deserialize)]
+struct State {
+    /// SHA-256 of the payload's header and manifest, so a folder is only ever resumed with the
+    /// payload that started it.
+    payload: String,
+    /// Images written and verified so far, by partition name.
+    done: BTreeMap<String, Done>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Done {
+    /// File name in the output directory.
+    file: PathBuf,
+    size: u64,
+}
+
+/// `--resume` journal: records each image once it is written, verified and synced to disk, so
+/// a run that was stopped only extracts what is missing when started again.
+pub(crate) struct Journal {
+    dir: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Journal {
+    /// Opens the journal in `dir`, or starts an empty one. A journal left by another payload
+    /// is refused rather than mixing images from two builds.
+    pub(crate) fn open(dir: &Path, payload_id: &str) -> Result<Self> {
+        let path = dir.join(STATE_FILE);
+        let state = match fs::read(&path) {
+            Ok(bytes) => {
+                let state: State = serde_json::from_slice(&bytes).with_context(|| {
+                    format!(
+                        "{path:?} is damaged; delete it to extract into this folder from scratch"
+                    )
+                })?;
+                ensure!(
+                    state.payload == payload_id,
+                    "{} was extracted from a different payload; resume it with the one that \
+                     started it, or pick another folder",
+                    dir.display()
+                );
+                state
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => State {
+                payload: payload_id.to_owned(),
+                done: BTreeMap::new(),
+            },
+            Err(e) => return Err(e).with_context(|| format!("could not read {path:?}")),
+        };
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Whether an earlier run finished `partition` as `file`, and the image is still there at
+    /// its full size.
+    pub(crate) fn is_done(&self, partition: &str, file: &Path) -> bool {
+        let state = self.lock();
+        state.done.get(partition).is_some_and(|done| {
+            Some(done.file.as_os_str()) == file.file_name()
+                && self
+                    .dir
+                    .join(&done.file)
+                    .metadata()
+                    .is_ok_and(|m| m.len() == done.size)
+        })
+    }
+
+    /// Records `partition` as finished, its image written to `file`. The journal is replaced
+    /// atomically, so a crash leaves either the old or the new one.
+    pub(crate) fn record(&self, partition: &str, file: &Path, size: u64) -> Result<()> {
+        let mut state = self.lock();
+        let name = file.file_name().context("output file has no name")?;
+        state.done.insert(
+            partition.to_owned(),
+            Done {
+                file: PathBuf::from(name),
+                size,
+            },
+        );
+        let json = serde_json::to_vec_pretty(&*state)?;
+        let path = self.dir.join(STATE_FILE);
+        let mut tmp_name = OsString::from(STATE_FILE);
+        tmp_name.push(".tmp");
+        let tmp = self.dir.join(tmp_name);
+        let mut out = File::create(&tmp).with_context(|| format!("could not write {tmp:?}"))?;
+        out.write_all(&json)?;
+        out.sync_all()?;
+        fs::rename(&tmp, &path).with_context(|| format!("could not write {path:?}"))
+    }
+
+    /// Paths of every image recorded so far.
+    pub(crate) fn finished(&self) -> Vec<PathBuf> {
+        self.lock()
+            .done
+            .values()
+            .map(|done| self.dir.join(&done.file))
+            .collect()
+    }
+
+    /// Everything is extracted: the folder no longer needs resuming.
+    pub(crate) fn finish(self) -> Result<()> {
+        let path = self.dir.join(STATE_FILE);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("could not remove {path:?}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
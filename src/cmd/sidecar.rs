@@ -0,0 +1,85 @@
+use crate::cmd::crypto;
+use crate::cmd::extractor::Extractor;
+use crate::cmd::i18n::Msg;
+use crate::payload::Payload;
+use anyhow::{Context, Result};
+use base64::Engine;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Build fingerprints and OTA properties, one `key=value` per line.
+const METADATA_ENTRY: &str = "META-INF/com/android/metadata";
+
+/// OTA zip entries copied by `--extract-metadata`, saved under their base names.
+const SIDECAR_FILES: &[&str] = &[
+    METADATA_ENTRY,
+    "META-INF/com/android/metadata.pb",
+    "apex_info.pb",
+    "care_map.pb",
+    "payload_properties.txt",
+];
+
+const PROPERTIES_FILE: &str = "payload_properties.txt";
+
+impl Extractor<'_> {
+    /// `--extract-metadata`: copies the OTA package's metadata files next to the images, and
+    /// writes a `payload_properties.txt` computed from the payload when the package lacks one
+    /// (or the input is a bare payload.bin).
+    pub(super) fn extract_metadata(
+        &self,
+        source: &Path,
+        payload_bytes: &[u8],
+        payload: &Payload,
+        dir: &Path,
+    ) -> Result<()> {
+        let mut saved = Vec::new();
+        for entry in SIDECAR_FILES {
+            let Some(contents) = Self::read_zip_entry(source, entry)? else {
+                continue;
+            };
+            let name = entry.rsplit('/').next().unwrap_or(entry);
+            let path = dir.join(name);
+            fs::write(&path, contents).with_context(|| format!("could not write {path:?}"))?;
+            saved.push(name.to_string());
+        }
+
+        if !saved.iter().any(|name| name == PROPERTIES_FILE) {
+            let path = dir.join(PROPERTIES_FILE);
+            fs::write(&path, payload_properties(payload_bytes, payload))
+                .with_context(|| format!("could not write {path:?}"))?;
+            saved.push(format!("{PROPERTIES_FILE} (generated)"));
+        }
+
+        if self.cmd.chatty() {
+            println!("{}", Msg::SavedMetadata.fill(&[&saved.join(", ")]));
+        }
+        Ok(())
+    }
+}
+
+/// `payload_properties.txt` as AOSP's `brillo_update_payload properties` writes it: hashes are
+/// base64 SHA-256, the metadata being the header plus manifest.
+fn payload_properties(payload_bytes: &[u8], payload: &Payload) -> String {
+    let b64 = base64::engine::general_purpose::STANDARD;
+    format!(
+        "FILE_HASH={}\nFILE_SIZE={}\nMETADATA_HASH={}\nMETADATA_SIZE={}\n",
+        b64.encode(crypto::sha256(payload_bytes)),
+        payload_bytes.len(),
+        b64.encode(payload.metadata_hash()),
+        payload.metadata_size()
+    )
+}
+
+/// `key=value` pairs of the OTA package's `META-INF/com/android/metadata` (`pre-build`,
+/// `post-build`, `ota-type`, ...). Empty for a bare payload.bin or a package without one.
+pub(crate) fn package_metadata(source: &Path) -> Result<HashMap<String, String>> {
+    let Some(contents) = Extractor::read_zip_entry(source, METADATA_ENTRY)? else {
+        return Ok(HashMap::new());
+    };
+    Ok(String::from_utf8_lossy(&contents)
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect())
+}
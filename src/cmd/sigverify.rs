@@ -0,0 +1,287 @@
+use crate::cmd::crypto;
+use crate::payload::{Payload, Signatures};
+use crate::proto::chromeos_update_engine::DeltaArchiveManifest;
+use anyhow::{Context, Result, bail, ensure};
+use base64::Engine;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Package signing certificate inside an OTA zip. AOSP signs the payload with the same key.
+pub(crate) const OTACERT_PATH: &str = "META-INF/com/android/otacert";
+
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyAlgorithm {
+    Rsa,
+    EcP256,
+}
+
+/// A public key that payload signatures can be checked against.
+#[derive(Debug, Clone)]
+pub(crate) struct PublicKey {
+    pub algorithm: KeyAlgorithm,
+    /// Where the key came from (file name, with `#n` for the n-th key of a bundle).
+    pub label: String,
+    /// PKCS#1 `RSAPublicKey` for RSA, the uncompressed point for EC.
+    key: Vec<u8>,
+}
+
+impl PublicKey {
+    /// Parses every key in `bytes`: a PEM bundle (`CERTIFICATE`, `PUBLIC KEY`, `RSA PUBLIC KEY`
+    /// blocks) or a single DER certificate / SubjectPublicKeyInfo. Keys are labelled `label`.
+    pub(crate) fn parse_all(bytes: &[u8], label: &str) -> Result<Vec<Self>> {
+        let mut keys = Self::parse_unlabelled(bytes)?;
+        let bundle = keys.len() > 1;
+        for (i, key) in keys.iter_mut().enumerate() {
+            key.label = if bundle {
+                format!("{label} #{}", i + 1)
+            } else {
+                label.to_string()
+            };
+        }
+        Ok(keys)
+    }
+
+    fn parse_unlabelled(bytes: &[u8]) -> Result<Vec<Self>> {
+        let Ok(text) = std::str::from_utf8(bytes) else {
+            return Ok(vec![Self::from_der(bytes)?]);
+        };
+        if !text.contains("-----BEGIN ") {
+            return Ok(vec![Self::from_der(bytes)?]);
+        }
+
+        let mut keys = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("-----BEGIN ") {
+            let after = &rest[start + "-----BEGIN ".len()..];
+            let label_end = after.find("-----").context("malformed PEM header")?;
+            let label = &after[..label_end];
+            let body = &after[label_end + "-----".len()..];
+            let end_marker = format!("-----END {label}-----");
+            let body_end = body
+                .find(&end_marker)
+                .with_context(|| format!("PEM block '{label}' is not terminated"))?;
+
+            let b64: String = body[..body_end]
+                .lines()
+                .filter(|l| !l.contains(':')) // skip RFC 1421 headers
+                .flat_map(|l| l.chars())
+                .filter(|c| !c.is_whitespace())
+                .collect();
+            let der = base64::engine::general_purpose::STANDARD
+                .decode(b64)
+                .with_context(|| format!("PEM block '{label}' is not valid base64"))?;
+
+            match label {
+                "CERTIFICATE" | "PUBLIC KEY" => keys.push(Self::from_der(&der)?),
+                "RSA PUBLIC KEY" => keys.push(Self {
+                    algorithm: KeyAlgorithm::Rsa,
+                    label: String::new(),
+                    key: der,
+                }),
+                _ => {} // private keys, parameters, ... are not ours to read
+            }
+            rest = &body[body_end + end_marker.len()..];
+        }
+        ensure!(!keys.is_empty(), "no certificate or public key found");
+        Ok(keys)
+    }
+
+    /// Parses a DER X.509 certificate or SubjectPublicKeyInfo.
+    fn from_der(der: &[u8]) -> Result<Self> {
+        let (outer, _) = der_expect(der, 0x30).context("not a DER certificate or public key")?;
+        let (first, _) = der_next(outer)?;
+
+        // SubjectPublicKeyInfo starts with the AlgorithmIdentifier SEQUENCE, a certificate with
+        // the tbsCertificate SEQUENCE whose own first element is a version tag or serial number.
+        let spki = if first.0 == 0x30 && matches!(first.1.first(), Some(0xa0 | 0x02)) {
+            let mut tbs = first.1;
+            if tbs.first() == Some(&0xa0) {
+                tbs = der_next(tbs)?.1; // version
+            }
+            for _ in 0..5 {
+                tbs = der_next(tbs)?.1; // serial, signature, issuer, validity, subject
+            }
+            der_expect(tbs, 0x30)?.0
+        } else {
+            outer
+        };
+        Self::from_spki(spki)
+    }
+
+    fn from_spki(spki: &[u8]) -> Result<Self> {
+        let (alg, rest) = der_expect(spki, 0x30).context("malformed SubjectPublicKeyInfo")?;
+        let (bits, _) = der_expect(rest, 0x03).context("malformed SubjectPublicKeyInfo")?;
+        let (oid, params) = der_expect(alg, 0x06)?;
+        ensure!(
+            bits.first() == Some(&0),
+            "public key bit string has unused bits"
+        );
+        let key = bits[1..].to_vec();
+
+        let algorithm = if oid == OID_RSA_ENCRYPTION {
+            KeyAlgorithm::Rsa
+        } else if oid == OID_EC_PUBLIC_KEY {
+            let (curve, _) = der_expect(params, 0x06)?;
+            ensure!(
+                curve == OID_PRIME256V1,
+                "only P-256 EC keys are used for payload signing"
+            );
+            KeyAlgorithm::EcP256
+        } else {
+            bail!(
+                "unsupported public key algorithm (OID {})",
+                hex::encode(oid)
+            );
+        };
+        Ok(Self {
+            algorithm,
+            label: String::new(),
+            key,
+        })
+    }
+
+    /// Human-readable algorithm and size, e.g. `RSA-2048` or `ECDSA P-256`.
+    pub(crate) fn describe(&self) -> String {
+        match self.algorithm {
+            KeyAlgorithm::Rsa => {
+                // RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+                let bits = der_expect(&self.key, 0x30)
+                    .and_then(|(seq, _)| der_expect(seq, 0x02))
+                    .map(|(modulus, _)| {
+                        let leading = modulus.iter().take_while(|&&b| b == 0).count();
+                        (modulus.len() - leading) * 8
+                    });
+                match bits {
+                    Ok(bits) => format!("RSA-{bits}"),
+                    Err(_) => "RSA".to_string(),
+                }
+            }
+            KeyAlgorithm::EcP256 => "ECDSA P-256".to_string(),
+        }
+    }
+
+    /// SHA-256 of the raw public key, identical whether the key came from a certificate or a
+    /// bare public key file.
+    pub(crate) fn fingerprint(&self) -> String {
+        hex::encode(crypto::sha256(&self.key))
+    }
+
+    /// Checks an update_engine signature: PKCS#1 v1.5 (RSA) or ASN.1 ECDSA (P-256) over the
+    /// SHA-256 of `message`.
+    pub(crate) fn verify(&self, message: &[u8], sig: &[u8]) -> bool {
+        crypto::verify(self.algorithm, &self.key, message, sig)
+    }
+}
+
+/// Loads `--keyring`: a single PEM/DER file (possibly a bundle) or a directory of them.
+pub(crate) fn load_keyring(path: &Path) -> Result<Vec<PublicKey>> {
+    let read = |file: &Path| {
+        let label = file.file_name().map_or_else(
+            || file.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        );
+        let bytes =
+            fs::read(file).with_context(|| format!("unable to read keyring file {file:?}"))?;
+        PublicKey::parse_all(&bytes, &label)
+            .with_context(|| format!("no usable public key in {file:?}"))
+    };
+
+    if !path.is_dir() {
+        return read(path);
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(path)
+        .with_context(|| format!("unable to read keyring directory {path:?}"))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+
+    let mut keys = Vec::new();
+    for file in files {
+        match read(&file) {
+            Ok(found) => keys.extend(found),
+            Err(e) => eprintln!("Warning: skipping keyring file: {e:#}"),
+        }
+    }
+    ensure!(
+        !keys.is_empty(),
+        "keyring {path:?} contains no usable public keys"
+    );
+    Ok(keys)
+}
+
+/// Loads `--pubkey`: one PEM/DER certificate or public key file.
+pub(crate) fn load_pubkey(path: &Path) -> Result<Vec<PublicKey>> {
+    ensure!(
+        !path.is_dir(),
+        "{path:?} is a directory; use --keyring for a directory of keys"
+    );
+    load_keyring(path)
+}
+
+/// Locates the payload signature blob. Returns the bytes it covers (everything in front of it)
+/// and the blob itself, or `None` for unsigned payloads.
+pub(crate) fn payload_signature<'b>(
+    bytes: &'b [u8],
+    payload: &Payload<'b>,
+    manifest: &DeltaArchiveManifest,
+) -> Result<Option<(&'b [u8], &'b [u8])>> {
+    let Some(blob) = payload.payload_signature(manifest)? else {
+        return Ok(None);
+    };
+    // `payload_signature` has checked the offset against the data section.
+    let start = manifest.signatures_offset.unwrap_or(0) as usize;
+    let signed_end = payload.data_offset() + start;
+    Ok(Some((&bytes[..signed_end], blob)))
+}
+
+/// Returns the first key that produced any of the signatures in `blob` over `message`.
+pub(crate) fn find_signer<'k>(
+    keys: &'k [PublicKey],
+    message: &[u8],
+    blob: &[u8],
+) -> Result<Option<&'k PublicKey>> {
+    let sigs = Signatures::decode(blob)?;
+    ensure!(!sigs.is_empty(), "signature blob holds no signatures");
+    Ok(keys
+        .iter()
+        .find(|key| sigs.iter().any(|sig| key.verify(message, &sig.data))))
+}
+
+/// A DER element as (tag, contents).
+type Tlv<'a> = (u8, &'a [u8]);
+
+// Reads one DER TLV, returning it and the remaining input.
+fn der_next(input: &[u8]) -> Result<(Tlv<'_>, &[u8])> {
+    let [tag, first, rest @ ..] = input else {
+        bail!("truncated DER element");
+    };
+    let (len, rest) = match *first {
+        n if n < 0x80 => (n as usize, rest),
+        n @ 0x81..=0x84 => {
+            let width = (n & 0x7f) as usize;
+            ensure!(rest.len() >= width, "truncated DER length");
+            let len = rest[..width]
+                .iter()
+                .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+            (len, &rest[width..])
+        }
+        _ => bail!("unsupported DER length encoding"),
+    };
+    ensure!(rest.len() >= len, "truncated DER element");
+    Ok(((*tag, &rest[..len]), &rest[len..]))
+}
+
+fn der_expect(input: &[u8], tag: u8) -> Result<(&[u8], &[u8])> {
+    let ((got, contents), rest) = der_next(input)?;
+    ensure!(
+        got == tag,
+        "unexpected DER tag {got:#04x}, expected {tag:#04x}"
+    );
+    Ok((contents, rest))
+}
@@ -1,10 +1,19 @@
+use rayon::prelude::*;
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
-use std::io::{self};
+use std::io::{self, SeekFrom};
 
 pub(crate) const SIMD_THRESHOLD: usize = 4096;
 
-/// Writes sequential data across multiple extents with SIMD acceleration.
+/// Copies at least this large are split across the rayon pool by [`simd_copy_parallel`].
+pub(crate) const PARALLEL_COPY_MIN: usize = 64 * 1024 * 1024;
+/// Piece size for [`simd_copy_parallel`]: big enough to stay on the streaming-store path.
+const PARALLEL_COPY_CHUNK: usize = 8 * 1024 * 1024;
+
+/// Writes data across multiple extents with SIMD acceleration. The extents form one logical
+/// buffer: writes continue from the current position, which `Seek` can move anywhere in it.
 pub struct ExtentsWriter<'a, 'b> {
     extents: &'a mut [&'b mut [u8]],
     idx: usize,
@@ -13,7 +22,7 @@ pub struct ExtentsWriter<'a, 'b> {
 }
 impl<'a, 'b> ExtentsWriter<'a, 'b> {
     /// Create a new ExtentsWriter for writing to the given extents.
-    pub(crate) fn new(extents: &'a mut [&'b mut [u8]], simd: CpuSimd) -> Self {
+    pub fn new(extents: &'a mut [&'b mut [u8]], simd: CpuSimd) -> Self {
         Self {
             extents,
             idx: 0,
@@ -60,6 +69,48 @@ impl<'a, 'b> ExtentsWriter<'a, 'b> {
         }
         to_copy
     }
+
+    /// Total length of all extents.
+    pub fn len(&self) -> u64 {
+        self.extents.iter().map(|e| e.len() as u64).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.extents.iter().all(|e| e.is_empty())
+    }
+
+    fn position(&self) -> u64 {
+        let before: u64 = self.extents[..self.idx.min(self.extents.len())]
+            .iter()
+            .map(|e| e.len() as u64)
+            .sum();
+        before + self.off as u64
+    }
+
+    /// Moves the cursor to logical offset `pos`, clamped to the end, where writes store nothing.
+    fn set_position(&mut self, mut pos: u64) {
+        self.idx = 0;
+        self.off = 0;
+        while let Some(extent) = self.extents.get(self.idx) {
+            let len = extent.len() as u64;
+            if pos < len {
+                self.off = pos as usize;
+                return;
+            }
+            pos -= len;
+            self.idx += 1;
+        }
+    }
+
+    /// Writes `buf` at logical offset `offset` without moving the cursor, like
+    /// `FileExt::write_at`. Returns how much fit before the end of the last extent.
+    pub fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<usize> {
+        let (idx, off) = (self.idx, self.off);
+        self.set_position(offset);
+        let written = io::Write::write(self, buf);
+        (self.idx, self.off) = (idx, off);
+        written
+    }
 }
 
 impl<'a, 'b> io::Write for ExtentsWriter<'a, 'b> {
@@ -84,78 +135,182 @@ impl<'a, 'b> io::Write for ExtentsWriter<'a, 'b> {
     }
 }
 
+impl<'a, 'b> io::Seek for ExtentsWriter<'a, 'b> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len().checked_add_signed(n),
+            SeekFrom::Current(n) => self.position().checked_add_signed(n),
+        };
+        let Some(target) = target else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ));
+        };
+        self.set_position(target);
+        Ok(self.position())
+    }
+}
+
 // Runtime CPU feature detection for SIMD acceleration.
-// Cached via OnceLock; enable debug output with OTARIPPER_DEBUG_CPU=1.
+// Cached via OnceLock; `print_detection` reports it for --debug-cpu.
+// Variants can't be constructed outside this crate: a level the CPU lacks would execute
+// illegal instructions. Use `get` or `available`.
 #[cfg(target_arch = "x86_64")]
 #[derive(Debug, Clone, Copy)]
-pub(crate) enum CpuSimd {
+pub enum CpuSimd {
+    #[non_exhaustive]
     None,
+    #[non_exhaustive]
     Sse2,
+    #[non_exhaustive]
     Avx2,
+    #[non_exhaustive]
     Avx512,
 }
 
 #[cfg(target_arch = "x86_64")]
 impl CpuSimd {
     fn detect() -> Self {
-        let avx512f = is_x86_feature_detected!("avx512f");
-        let avx512bw = is_x86_feature_detected!("avx512bw");
-        let avx2 = is_x86_feature_detected!("avx2");
-        let sse2 = is_x86_feature_detected!("sse2");
-
-        let selected = if avx512f && avx512bw {
+        if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
             CpuSimd::Avx512
-        } else if avx2 {
+        } else if is_x86_feature_detected!("avx2") {
             CpuSimd::Avx2
-        } else if sse2 {
+        } else if is_x86_feature_detected!("sse2") {
             CpuSimd::Sse2
         } else {
             CpuSimd::None
-        };
-
-        if std::env::var("OTARIPPER_DEBUG_CPU").is_ok() {
-            eprintln!("CPU Feature Detection:");
-            eprintln!("  AVX512F: {}", avx512f);
-            eprintln!("  AVX512BW: {}", avx512bw);
-            eprintln!("  AVX2: {}", avx2);
-            eprintln!("  SSE2: {}", sse2);
-            eprintln!("  Selected: {:?}", selected);
         }
-
-        selected
     }
 
-    pub(crate) fn get() -> Self {
+    pub fn get() -> Self {
         use std::sync::OnceLock;
         static DETECTED: OnceLock<CpuSimd> = OnceLock::new();
         *DETECTED.get_or_init(CpuSimd::detect)
     }
+
+    /// Prints the CPU features behind [`get`](Self::get)'s choice, for `--debug-cpu`.
+    pub fn print_detection() {
+        eprint!("{}", Self::detection());
+    }
+
+    /// [`print_detection`](Self::print_detection)'s report, also kept in debug bundles.
+    pub fn detection() -> String {
+        format!(
+            "CPU Feature Detection:\n  AVX512F: {}\n  AVX512BW: {}\n  AVX2: {}\n  SSE2: {}\n  \
+             Selected: {:?}\n",
+            is_x86_feature_detected!("avx512f"),
+            is_x86_feature_detected!("avx512bw"),
+            is_x86_feature_detected!("avx2"),
+            is_x86_feature_detected!("sse2"),
+            CpuSimd::get()
+        )
+    }
+
+    /// Every level this CPU can run, scalar first and `get()`'s choice last.
+    pub fn available() -> Vec<Self> {
+        let levels = [CpuSimd::None, CpuSimd::Sse2, CpuSimd::Avx2, CpuSimd::Avx512];
+        let best = CpuSimd::get() as usize;
+        levels.into_iter().take(best + 1).collect()
+    }
 }
 
-// For non-x86_64 targets, we use a simple fallback enum
-#[cfg(not(target_arch = "x86_64"))]
+#[cfg(target_arch = "aarch64")]
 #[derive(Debug, Clone, Copy)]
-pub(crate) enum CpuSimd {
+pub enum CpuSimd {
+    #[non_exhaustive]
     None,
+    #[non_exhaustive]
+    Neon,
 }
 
-#[cfg(not(target_arch = "x86_64"))]
+#[cfg(target_arch = "aarch64")]
 impl CpuSimd {
-    pub(crate) fn get() -> Self {
-        if std::env::var("OTARIPPER_DEBUG_CPU").is_ok() {
-            eprintln!("CPU Feature Detection: ARM64/Other architecture - using scalar operations");
+    fn detect() -> Self {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            CpuSimd::Neon
+        } else {
+            CpuSimd::None
         }
+    }
+
+    pub fn get() -> Self {
+        use std::sync::OnceLock;
+        static DETECTED: OnceLock<CpuSimd> = OnceLock::new();
+        *DETECTED.get_or_init(CpuSimd::detect)
+    }
+
+    /// Prints the CPU features behind [`get`](Self::get)'s choice, for `--debug-cpu`.
+    pub fn print_detection() {
+        eprint!("{}", Self::detection());
+    }
+
+    /// [`print_detection`](Self::print_detection)'s report, also kept in debug bundles.
+    pub fn detection() -> String {
+        format!(
+            "CPU Feature Detection:\n  NEON: {}\n  Selected: {:?}\n",
+            std::arch::is_aarch64_feature_detected!("neon"),
+            CpuSimd::get()
+        )
+    }
+
+    /// Every level this CPU can run, scalar first and `get()`'s choice last.
+    pub fn available() -> Vec<Self> {
+        let levels = [CpuSimd::None, CpuSimd::Neon];
+        let best = CpuSimd::get() as usize;
+        levels.into_iter().take(best + 1).collect()
+    }
+}
+
+// For other targets, we use a simple fallback enum
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[derive(Debug, Clone, Copy)]
+pub enum CpuSimd {
+    #[non_exhaustive]
+    None,
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl CpuSimd {
+    pub fn get() -> Self {
         CpuSimd::None
     }
+
+    pub fn print_detection() {
+        eprint!("{}", Self::detection());
+    }
+
+    pub fn detection() -> String {
+        "CPU Feature Detection: no SIMD paths for this architecture - using scalar operations\n"
+            .to_string()
+    }
+
+    pub fn available() -> Vec<Self> {
+        vec![CpuSimd::None]
+    }
 }
 
-/// SIMD-optimized large data copying
+/// SIMD-optimized large data copying. Panics if `src` and `dst` differ in length.
 #[inline]
-pub(crate) fn simd_copy_large(simd: CpuSimd, src: &[u8], dst: &mut [u8]) {
-    debug_assert_eq!(src.len(), dst.len());
+pub fn simd_copy_large(simd: CpuSimd, src: &[u8], dst: &mut [u8]) {
+    assert_eq!(src.len(), dst.len(), "simd_copy_large: length mismatch");
     simd_copy_chunk(simd, src, dst);
 }
 
+/// [`simd_copy_large`] for copies of `PARALLEL_COPY_MIN` (64 MiB) or more: the copy is cut into
+/// pieces that idle workers of the current rayon pool pick up while the caller works through its
+/// own. Panics if `src` and `dst` differ in length.
+pub fn simd_copy_parallel(simd: CpuSimd, src: &[u8], dst: &mut [u8]) {
+    if src.len() < PARALLEL_COPY_MIN || rayon::current_num_threads() < 2 {
+        return simd_copy_large(simd, src, dst);
+    }
+    assert_eq!(src.len(), dst.len(), "simd_copy_parallel: length mismatch");
+    src.par_chunks(PARALLEL_COPY_CHUNK)
+        .zip(dst.par_chunks_mut(PARALLEL_COPY_CHUNK))
+        .for_each(|(src, dst)| simd_copy_chunk(simd, src, dst));
+}
+
 #[cfg(target_arch = "x86_64")]
 #[inline(always)]
 fn simd_copy_chunk(simd: CpuSimd, src: &[u8], dst: &mut [u8]) {
@@ -179,14 +334,23 @@ fn simd_copy_chunk(simd: CpuSimd, src: &[u8], dst: &mut [u8]) {
     }
 }
 
-#[cfg(not(target_arch = "x86_64"))]
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+fn simd_copy_chunk(simd: CpuSimd, src: &[u8], dst: &mut [u8]) {
+    match simd {
+        CpuSimd::Neon => unsafe { simd_copy_neon(src, dst) },
+        CpuSimd::None => dst.copy_from_slice(src),
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
 #[inline(always)]
 fn simd_copy_chunk(_simd: CpuSimd, src: &[u8], dst: &mut [u8]) {
     dst.copy_from_slice(src);
 }
 
 #[inline(always)]
-pub(crate) fn is_all_zero_with_simd(simd: CpuSimd, data: &[u8]) -> bool {
+pub fn is_all_zero_with_simd(simd: CpuSimd, data: &[u8]) -> bool {
     cfg_select! {
         target_arch = "x86_64" => {
             match simd {
@@ -196,8 +360,14 @@ pub(crate) fn is_all_zero_with_simd(simd: CpuSimd, data: &[u8]) -> bool {
                 CpuSimd::None => data.iter().all(|&b| b == 0),
             }
         }
+        target_arch = "aarch64" => {
+            match simd {
+                CpuSimd::Neon => unsafe { is_all_zero_neon(data) },
+                CpuSimd::None => data.iter().all(|&b| b == 0),
+            }
+        }
         _ => {
-            // Non-x86 always scalar (auto-vectorized by LLVM)
+            // Other targets always scalar (auto-vectorized by LLVM)
             let _ = simd;
             data.iter().all(|&b| b == 0)
         }
@@ -239,12 +409,14 @@ unsafe fn simd_copy_avx512_stream(src: &[u8], dst: &mut [u8]) {
         }
     }
 
+    // Streaming stores need an aligned destination; copy up to the first 64-byte boundary.
+    let mut i = dst.as_ptr().align_offset(64).min(src.len());
+    dst[..i].copy_from_slice(&src[..i]);
     let src_ptr = src.as_ptr();
     let dst_ptr = dst.as_mut_ptr();
-    let mut i = 0;
 
     // Work in 64-byte blocks
-    let simd_end = src.len() & !63;
+    let simd_end = i + ((src.len() - i) & !63);
     while i < simd_end {
         unsafe {
             let data = _mm512_loadu_si512(src_ptr.add(i) as *const __m512i);
@@ -296,12 +468,14 @@ unsafe fn simd_copy_avx2_stream(src: &[u8], dst: &mut [u8]) {
         }
     }
 
+    // Streaming stores need an aligned destination; copy up to the first 32-byte boundary.
+    let mut i = dst.as_ptr().align_offset(32).min(src.len());
+    dst[..i].copy_from_slice(&src[..i]);
     let src_ptr = src.as_ptr();
     let dst_ptr = dst.as_mut_ptr();
-    let mut i = 0;
 
     // Work in 32-byte blocks
-    let simd_end = src.len() & !31;
+    let simd_end = i + ((src.len() - i) & !31);
     while i < simd_end {
         unsafe {
             let data = _mm256_loadu_si256(src_ptr.add(i) as *const __m256i);
@@ -407,3 +581,46 @@ unsafe fn is_all_zero_sse2(data: &[u8]) -> bool {
     data[i..].iter().all(|&b| b == 0)
 }
 
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+#[inline]
+unsafe fn simd_copy_neon(src: &[u8], dst: &mut [u8]) {
+    let src_ptr = src.as_ptr();
+    let dst_ptr = dst.as_mut_ptr();
+    let mut i = 0;
+    let simd_end = src.len().saturating_sub(63);
+
+    // Four 128-bit registers per step; ARM has no non-temporal store worth using here.
+    while i < simd_end {
+        unsafe {
+            let data = vld1q_u8_x4(src_ptr.add(i));
+            vst1q_u8_x4(dst_ptr.add(i), data);
+        }
+        i += 64;
+    }
+
+    if i < src.len() {
+        dst[i..].copy_from_slice(&src[i..]);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+#[inline]
+unsafe fn is_all_zero_neon(data: &[u8]) -> bool {
+    let ptr = data.as_ptr();
+    let mut i = 0;
+    let simd_end = data.len().saturating_sub(63);
+
+    while i < simd_end {
+        unsafe {
+            let chunk = vld1q_u8_x4(ptr.add(i));
+            let any = vorrq_u8(vorrq_u8(chunk.0, chunk.1), vorrq_u8(chunk.2, chunk.3));
+            if vmaxvq_u8(any) != 0 {
+                return false;
+            }
+        }
+        i += 64;
+    }
+    data[i..].iter().all(|&b| b == 0)
+}
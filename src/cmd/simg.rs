@@ -0,0 +1,183 @@
+use crate::cmd::extractor::Extractor;
+use crate::cmd::i18n::Msg;
+use crate::proto::chromeos_update_engine::install_operation::Type;
+use crate::proto::chromeos_update_engine::{DeltaArchiveManifest, PartitionUpdate};
+use anyhow::{Context, Result, ensure};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+const SPARSE_MAGIC: u32 = 0xed26_ff3a;
+const FILE_HEADER_LEN: u16 = 28;
+const CHUNK_HEADER_LEN: u16 = 12;
+const CHUNK_RAW: u16 = 0xcac1;
+const CHUNK_DONT_CARE: u16 = 0xcac3;
+
+/// Largest RAW chunk written, so `total_sz` always fits its 32-bit field.
+const MAX_RAW_CHUNK: u64 = 1 << 30;
+
+/// A run of blocks in the sparse image: written from the raw image, or left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Chunk {
+    Raw { start: u64, blocks: u64 },
+    DontCare { blocks: u64 },
+}
+
+impl Extractor<'_> {
+    /// `--sparse`: rewrites every extracted image in Android sparse format, in place. Blocks
+    /// only ZERO/DISCARD operations (or none) write become DONT_CARE chunks, the rest RAW.
+    pub(super) fn write_sparse_images(
+        &self,
+        manifest: &DeltaArchiveManifest,
+        file_names: &HashMap<&[u8], OsString>,
+        dir: &Path,
+        block_size: usize,
+    ) -> Result<()> {
+        let (mut count, mut raw_total, mut sparse_total) = (0usize, 0u64, 0u64);
+        for update in manifest.partitions.iter().filter(|u| self.is_selected(u)) {
+            let path = dir.join(&file_names[update.partition_name.as_slice()]);
+            let raw_len = path
+                .metadata()
+                .with_context(|| format!("could not read {path:?}"))?
+                .len();
+            let sparse_len =
+                convert(&path, update, block_size as u64, raw_len).with_context(|| {
+                    format!("could not write '{}' as a sparse image", update.name())
+                })?;
+            count += 1;
+            raw_total += raw_len;
+            sparse_total += sparse_len;
+        }
+
+        if self.cmd.chatty() && count > 0 {
+            println!(
+                "{}",
+                Msg::ConvertedSparse.fill(&[
+                    &count,
+                    &indicatif::HumanBytes(raw_total),
+                    &indicatif::HumanBytes(sparse_total),
+                ])
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the raw image at `path` with its sparse form and returns the new file's size. A
+/// partition that doesn't end on a block boundary is padded with zeros to a whole block.
+fn convert(path: &Path, update: &PartitionUpdate, block_size: u64, raw_len: u64) -> Result<u64> {
+    let total_blocks = raw_len.div_ceil(block_size);
+    ensure!(
+        u32::try_from(total_blocks).is_ok(),
+        "{total_blocks} blocks is more than a sparse image can describe"
+    );
+    let chunks = chunks(update, block_size, total_blocks);
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let tmp = NamedTempFile::new_in(dir).context("could not create a temporary file")?;
+    let mut input = File::open(path).with_context(|| format!("could not read {path:?}"))?;
+    let mut out = BufWriter::new(tmp.as_file());
+
+    out.write_all(&SPARSE_MAGIC.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?; // major version
+    out.write_all(&0u16.to_le_bytes())?; // minor version
+    out.write_all(&FILE_HEADER_LEN.to_le_bytes())?;
+    out.write_all(&CHUNK_HEADER_LEN.to_le_bytes())?;
+    out.write_all(&(block_size as u32).to_le_bytes())?;
+    out.write_all(&(total_blocks as u32).to_le_bytes())?;
+    out.write_all(&(chunks.len() as u32).to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?; // image checksum, unused
+
+    for chunk in &chunks {
+        match *chunk {
+            Chunk::Raw { start, blocks } => {
+                let len = blocks * block_size;
+                write_chunk_header(&mut out, CHUNK_RAW, blocks, CHUNK_HEADER_LEN as u64 + len)?;
+                let offset = start * block_size;
+                input.seek(SeekFrom::Start(offset))?;
+                let available = raw_len.saturating_sub(offset).min(len);
+                let copied = io::copy(&mut Read::by_ref(&mut input).take(available), &mut out)?;
+                ensure!(copied == available, "image is shorter than expected");
+                io::copy(&mut io::repeat(0).take(len - available), &mut out)?;
+            }
+            Chunk::DontCare { blocks } => {
+                write_chunk_header(&mut out, CHUNK_DONT_CARE, blocks, CHUNK_HEADER_LEN as u64)?;
+            }
+        }
+    }
+    out.flush()?;
+    drop(out);
+    drop(input);
+
+    let len = tmp.as_file().metadata()?.len();
+    tmp.persist(path)
+        .with_context(|| format!("could not replace {path:?}"))?;
+    Ok(len)
+}
+
+fn write_chunk_header(out: &mut impl Write, kind: u16, blocks: u64, total: u64) -> io::Result<()> {
+    out.write_all(&kind.to_le_bytes())?;
+    out.write_all(&0u16.to_le_bytes())?; // reserved
+    out.write_all(&(blocks as u32).to_le_bytes())?;
+    out.write_all(&(total as u32).to_le_bytes())
+}
+
+/// Splits the image into RAW runs over every block an operation writes data to, and DONT_CARE
+/// runs over the rest: ZERO/DISCARD extents and blocks nothing writes.
+fn chunks(update: &PartitionUpdate, block_size: u64, total_blocks: u64) -> Vec<Chunk> {
+    let mut written: Vec<(u64, u64)> = update
+        .operations
+        .iter()
+        .filter(|op| !matches!(Type::try_from(op.r#type), Ok(Type::Zero | Type::Discard)))
+        .flat_map(|op| &op.dst_extents)
+        .filter_map(|e| {
+            let start = e.start_block?.min(total_blocks);
+            let end = start.saturating_add(e.num_blocks?).min(total_blocks);
+            (end > start).then_some((start, end))
+        })
+        .collect();
+    written.sort_unstable();
+
+    let max_raw = (MAX_RAW_CHUNK / block_size).max(1);
+    let mut chunks = Vec::new();
+    let mut pos = 0;
+    let mut run: Option<(u64, u64)> = None;
+    for (start, end) in written {
+        match &mut run {
+            Some((_, run_end)) if start <= *run_end => *run_end = (*run_end).max(end),
+            _ => {
+                if let Some((run_start, run_end)) = run.replace((start, end)) {
+                    push_raw(&mut chunks, run_start, run_end, max_raw);
+                    pos = run_end;
+                }
+                if start > pos {
+                    chunks.push(Chunk::DontCare {
+                        blocks: start - pos,
+                    });
+                }
+            }
+        }
+    }
+    if let Some((run_start, run_end)) = run {
+        push_raw(&mut chunks, run_start, run_end, max_raw);
+        pos = run_end;
+    }
+    if total_blocks > pos {
+        chunks.push(Chunk::DontCare {
+            blocks: total_blocks - pos,
+        });
+    }
+    chunks
+}
+
+/// Adds RAW chunks covering blocks `start..end`, at most `max` blocks each.
+fn push_raw(chunks: &mut Vec<Chunk>, mut start: u64, end: u64, max: u64) {
+    while start < end {
+        let blocks = (end - start).min(max);
+        chunks.push(Chunk::Raw { start, blocks });
+        start += blocks;
+    }
+}
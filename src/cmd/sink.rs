@@ -0,0 +1,85 @@
+use memmap2::MmapMut;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Bytes each backend writes during [`probe`].
+const PROBE_SIZE: usize = 16 * 1024 * 1024;
+
+/// How partition images are written to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IoMode {
+    /// Operations write straight into a shared writable mapping of the image.
+    Mmap,
+    /// Operations decode into scratch buffers that are flushed with positional writes.
+    /// Slower on local disks, but well-behaved where mmap write-back is not (network shares).
+    Pwrite,
+}
+
+/// Writes all of `buf` to `file` at `offset` without touching the file cursor, so several
+/// threads can write disjoint ranges of the same file concurrently.
+pub(crate) fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    cfg_select! {
+        unix => {
+            use std::os::unix::fs::FileExt;
+            file.write_all_at(buf, offset)
+        }
+        windows => {
+            use std::os::windows::fs::FileExt;
+            let mut written = 0;
+            while written < buf.len() {
+                match file.seek_write(&buf[written..], offset + written as u64) {
+                    Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                    Ok(n) => written += n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+        _ => {
+            let _ = (file, buf, offset);
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+}
+
+/// Times writing [`PROBE_SIZE`] bytes into scratch files in `dir` through a mapping and through
+/// positional writes, and picks pwrite only when mapping takes over twice as long: page faults
+/// that allocate blocks one page at a time, or an antivirus scanner hooking them. Mapping wins
+/// close calls, as it skips the scratch buffer and its copy. `None` if the scratch files can't
+/// be written.
+pub(crate) fn probe(dir: &Path) -> Option<IoMode> {
+    let chunk = vec![0x5au8; 1024 * 1024];
+
+    let mapped = timed(dir, |file| {
+        file.set_len(PROBE_SIZE as u64)?;
+        let mut map = unsafe { MmapMut::map_mut(file)? };
+        for block in map.chunks_mut(chunk.len()) {
+            block.copy_from_slice(&chunk[..block.len()]);
+        }
+        Ok(())
+    })?;
+    let written = timed(dir, |file| {
+        file.set_len(PROBE_SIZE as u64)?;
+        for offset in (0..PROBE_SIZE).step_by(chunk.len()) {
+            write_all_at(file, &chunk, offset as u64)?;
+        }
+        Ok(())
+    })?;
+
+    Some(if mapped > written * 2 {
+        IoMode::Pwrite
+    } else {
+        IoMode::Mmap
+    })
+}
+
+/// How long `write` takes on a fresh scratch file in `dir`, which is removed afterwards.
+fn timed(dir: &Path, write: impl FnOnce(&File) -> io::Result<()>) -> Option<Duration> {
+    let scratch = tempfile::NamedTempFile::new_in(dir).ok()?;
+    let start = Instant::now();
+    write(scratch.as_file()).ok()?;
+    Some(start.elapsed())
+}
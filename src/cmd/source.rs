@@ -0,0 +1,143 @@
+use crate::cmd::crypto;
+use crate::cmd::extractor::Extractor;
+use crate::payload;
+use crate::proto::chromeos_update_engine::install_operation::Type;
+use crate::proto::chromeos_update_engine::{InstallOperation, PartitionUpdate};
+use anyhow::{Context, Result, bail, ensure};
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// A base build's image of one partition, mapped read-only for SOURCE_COPY.
+pub(crate) struct SourceImage {
+    mmap: Mmap,
+    /// The old partition size from the manifest, or the file's length when it has none. A raw
+    /// dump of the block device may be longer than the image it holds.
+    len: usize,
+}
+
+impl SourceImage {
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.mmap[..self.len]
+    }
+}
+
+impl Extractor<'_> {
+    /// `--source-dir`: maps the base build's image of each of `updates` from `dir`, named
+    /// `<partition>.img` or, for a slotted partition, the name without its suffix.
+    ///
+    /// Each SOURCE_COPY operation's own source hash is checked as it is applied. When some lack
+    /// one (payload minor version 2), the whole image is checked against the manifest's old
+    /// partition hash here instead.
+    pub(super) fn map_source_images(
+        &self,
+        dir: &Path,
+        updates: &[&PartitionUpdate],
+    ) -> Result<HashMap<String, SourceImage>> {
+        let mut images = HashMap::with_capacity(updates.len());
+        for update in updates {
+            let name = update.name();
+            let path = source_path(dir, &name).with_context(|| {
+                format!(
+                    "--source-dir: no base image for '{name}' in {} (looked for {name}.img)",
+                    dir.display()
+                )
+            })?;
+            let file =
+                File::open(&path).with_context(|| format!("failed to open base image {path:?}"))?;
+            let mmap = unsafe { Mmap::map(&file) }
+                .with_context(|| format!("failed to mmap base image {path:?}"))?;
+
+            let old_info = update.old_partition_info.as_ref();
+            let len = match old_info.and_then(|info| info.size) {
+                Some(size) => {
+                    let size = usize::try_from(size)
+                        .context("base partition is too large for this platform")?;
+                    ensure!(
+                        mmap.len() >= size,
+                        "base image {path:?} is {} bytes, but the update expects {size}",
+                        mmap.len()
+                    );
+                    size
+                }
+                None => mmap.len(),
+            };
+            let image = SourceImage { mmap, len };
+
+            let unhashed = update
+                .operations
+                .iter()
+                .any(|op| op.r#type == Type::SourceCopy as i32 && op.src_sha256_hash.is_none());
+            if unhashed && !self.cmd.no_verify {
+                match old_info.and_then(|info| info.hash.as_deref()) {
+                    Some(expected) => {
+                        let got = crypto::sha256(image.bytes());
+                        ensure!(
+                            got.as_slice() == expected,
+                            "base image {path:?} is not the one '{name}' was updated from: \
+                             expected SHA-256 {}, got {}",
+                            hex::encode(expected),
+                            hex::encode(got)
+                        );
+                    }
+                    None if self.cmd.strict => bail!(
+                        "--strict: '{name}' has SOURCE_COPY operations without a source hash \
+                         and no old partition hash to check {path:?} against"
+                    ),
+                    None => {}
+                }
+            }
+            images.insert(name.to_string(), image);
+        }
+        Ok(images)
+    }
+
+    /// The bytes a SOURCE_COPY operation copies: its source extents of the base image, in order,
+    /// checked against the operation's source hash.
+    pub(super) fn source_copy_data<'s>(
+        &self,
+        op: &InstallOperation,
+        image: &'s [u8],
+        block_size: usize,
+    ) -> Result<Cow<'s, [u8]>> {
+        let ranges = payload::extent_ranges(&op.src_extents, block_size, image.len())
+            .context("source extents do not fit the base image")?;
+        let data = match payload::coalesce_extents(ranges)[..] {
+            [(start, len)] => Cow::Borrowed(&image[start..start + len]),
+            ref ranges => {
+                let total = ranges.iter().map(|&(_, len)| len).sum();
+                let mut data = Vec::with_capacity(total);
+                for &(start, len) in ranges {
+                    data.extend_from_slice(&image[start..start + len]);
+                }
+                Cow::Owned(data)
+            }
+        };
+
+        if !self.cmd.no_verify
+            && let Some(hash) = &op.src_sha256_hash
+        {
+            let got = crypto::sha256(&data);
+            ensure!(
+                got.as_slice() == hash.as_slice(),
+                "base image data does not match the update: expected SHA-256 {}, got {}",
+                hex::encode(hash),
+                hex::encode(got)
+            );
+        }
+        Ok(data)
+    }
+}
+
+fn source_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    let unslotted = ["_a", "_b"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix));
+    [Some(name), unslotted]
+        .into_iter()
+        .flatten()
+        .map(|name| dir.join(format!("{name}.img")))
+        .find(|path| path.is_file())
+}
@@ -0,0 +1,200 @@
+use crate::cmd::cleanup::CleanupGuard;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Smallest `--split-size` accepted; smaller pieces would only multiply files.
+const MIN_SPLIT: u64 = 1 << 20;
+
+/// Parses a `--split-size` value, e.g. `3800M` or `4G`.
+pub(crate) fn parse_size(value: &str) -> Result<u64, String> {
+    let size = parse_bytes(value)?;
+    if size < MIN_SPLIT {
+        return Err(format!(
+            "'{}' is too small; pieces must be at least 1M",
+            value.trim()
+        ));
+    }
+    Ok(size)
+}
+
+/// Parses a byte count with an optional K, M, G or T suffix (powers of 1024).
+pub(crate) fn parse_bytes(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(digits);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{value}' is not a size such as 3800M or 4G"))?;
+    let shift = match unit
+        .to_ascii_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(format!("unknown size unit in '{value}'; use K, M, G or T")),
+    };
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("'{value}' is too large"))
+}
+
+/// Files an earlier run left that writing `base` would replace: `base` itself and, when `split`,
+/// its numbered pieces (`base.000`, `base.001`, ...), sorted by name.
+pub(crate) fn existing_outputs(base: &Path, split: bool) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    if base.exists() {
+        found.push(base.to_path_buf());
+    }
+    let Some(name) = base.file_name().filter(|_| split) else {
+        return Ok(found);
+    };
+    let dir = base
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let entries = match fs::read_dir(dir) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(found),
+        entries => entries?,
+    };
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let is_piece = file_name
+            .as_encoded_bytes()
+            .strip_prefix(name.as_encoded_bytes())
+            .and_then(|rest| rest.strip_prefix(b"."))
+            .is_some_and(|index| index.len() >= 3 && index.iter().all(u8::is_ascii_digit));
+        if is_piece {
+            found.push(entry.path());
+        }
+    }
+    found.sort_unstable();
+    Ok(found)
+}
+
+/// Writes a stream to `base`, or with a size limit to `base.000`, `base.001`, ... of at most
+/// `limit` bytes each, which `cat`/`copy /b` join back together. A stream that fits one piece
+/// keeps the plain name. Every file created is tracked by `cleanup`. Existing files are only
+/// replaced when `replace` is set, and then all of them go before the first piece is written.
+pub(crate) struct SplitWriter<'c> {
+    base: PathBuf,
+    limit: u64,
+    replace: bool,
+    /// Pieces opened so far.
+    parts: usize,
+    /// Bytes in the current piece.
+    written: u64,
+    current: Option<BufWriter<File>>,
+    cleanup: &'c CleanupGuard,
+}
+
+impl<'c> SplitWriter<'c> {
+    pub(crate) fn new(
+        base: PathBuf,
+        limit: Option<u64>,
+        replace: bool,
+        cleanup: &'c CleanupGuard,
+    ) -> Self {
+        Self {
+            base,
+            limit: limit.unwrap_or(u64::MAX),
+            replace,
+            parts: 0,
+            written: 0,
+            current: None,
+            cleanup,
+        }
+    }
+
+    fn part_path(&self, index: usize) -> PathBuf {
+        let mut name = OsString::from(self.base.as_os_str());
+        name.push(format!(".{index:03}"));
+        PathBuf::from(name)
+    }
+
+    /// Closes the current piece and opens the next. The first piece is renamed to `.000` once
+    /// a second one is needed.
+    fn next_part(&mut self) -> io::Result<()> {
+        if let Some(mut current) = self.current.take() {
+            current.flush()?;
+        }
+        let path = match self.parts {
+            0 => {
+                self.remove_existing()?;
+                self.base.clone()
+            }
+            1 => {
+                let first = self.part_path(0);
+                self.cleanup.track(first.clone());
+                fs::rename(&self.base, &first)?;
+                self.part_path(1)
+            }
+            n => self.part_path(n),
+        };
+        let file = File::create_new(&path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("could not create {}: {e}", path.display()),
+            )
+        })?;
+        self.cleanup.track(path);
+        self.current = Some(BufWriter::with_capacity(1 << 20, file));
+        self.parts += 1;
+        self.written = 0;
+        Ok(())
+    }
+
+    /// With `replace`, deletes what an earlier run left at `base`, every piece included, so
+    /// none outlives a stream that now needs fewer.
+    fn remove_existing(&self) -> io::Result<()> {
+        if !self.replace {
+            return Ok(());
+        }
+        for path in existing_outputs(&self.base, self.limit != u64::MAX)? {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes the last piece. An empty stream still leaves an empty file behind.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        if self.current.is_none() {
+            self.next_part()?;
+        }
+        self.flush()
+    }
+}
+
+impl Write for SplitWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.current.is_none() || self.written == self.limit {
+            self.next_part()?;
+        }
+        let room = usize::try_from(self.limit - self.written).unwrap_or(usize::MAX);
+        let current = self.current.as_mut().expect("next_part opens a piece");
+        let n = current.write(&buf[..buf.len().min(room)])?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current.as_mut() {
+            Some(current) => current.flush(),
+            None => Ok(()),
+        }
+    }
+}
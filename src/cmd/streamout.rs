@@ -0,0 +1,147 @@
+use crate::cmd::cleanup::CleanupGuard;
+use crate::cmd::compress;
+use crate::cmd::exitcode::FailureKind;
+use crate::cmd::extractor::{Extractor, format_digests};
+use crate::cmd::fsprobe::{self, FsTraits};
+use crate::cmd::hashalgo::{HashAlgo, HashingWriter};
+use crate::cmd::i18n::Msg;
+use crate::cmd::simd::CpuSimd;
+use crate::cmd::split::{self, SplitWriter};
+use crate::payload::Payload;
+use crate::proto::chromeos_update_engine::DeltaArchiveManifest;
+use anyhow::{Context, Result, anyhow, bail};
+
+impl Extractor<'_> {
+    /// `--compress` and `--split-size`: writes each selected partition as a stream instead of
+    /// into a file sized up front, compressed (`boot.img.zst`) and/or cut into pieces
+    /// (`super.img.000`, `super.img.001`, ...). Images are rebuilt one after another as
+    /// `--stdout` does: streamed out as they are decoded when their operations write front to
+    /// back, otherwise assembled in memory first. Each image's hash is checked once it has been
+    /// written; a mismatch removes everything written so far, as with a failed extraction.
+    pub(super) fn extract_streamed(
+        &self,
+        payload: &Payload,
+        manifest: &DeltaArchiveManifest,
+        block_size: usize,
+        simd: CpuSimd,
+    ) -> Result<()> {
+        let compression = self.cmd.compress;
+        let stems = self.output_stems(manifest)?;
+        let (dir, created_new_dir) = self.create_partition_dir(None)?;
+        let cleanup = CleanupGuard::new(dir.clone(), created_new_dir);
+        let files = fsprobe::output_file_names(
+            stems.iter().map(|(_, stem)| stem.as_slice()),
+            FsTraits::probe(&dir),
+        );
+        let output_path = |stem: &[u8]| {
+            let mut file_name = files[stem].clone();
+            if let Some(compression) = compression {
+                file_name.push(".");
+                file_name.push(compression.extension());
+            }
+            dir.join(file_name)
+        };
+
+        // As with mapped output, say which images are in the way before anything is written.
+        if !self.cmd.force {
+            let mut existing = Vec::new();
+            for (_, stem) in &stems {
+                let path = output_path(stem);
+                let found = split::existing_outputs(&path, self.cmd.split_size.is_some())
+                    .with_context(|| format!("unable to look for {}", path.display()))?;
+                existing.extend(found.iter().map(|path| path.display().to_string()));
+            }
+            existing.sort_unstable();
+            if !existing.is_empty() {
+                return Err(FailureKind::Usage.tag(anyhow!(
+                    "{} already exist: {}\nRemove them, extract somewhere else, or pass --force \
+                     to replace them.",
+                    existing.len(),
+                    existing.join(", ")
+                )));
+            }
+        }
+
+        let threadpool = self.get_threadpool()?;
+        if self.cmd.chatty() {
+            let how = match compression {
+                Some(compression) => format!("Compressing with {}", compression.extension()),
+                None => "Streaming".to_string(),
+            };
+            eprintln!(
+                "{how} {} partitions using {} threads...\n",
+                stems.len(),
+                threadpool.current_num_threads()
+            );
+        }
+
+        // SHA-256 comes from verification; any other --hash-algo hashes the stream on its way out.
+        let extra_algos: Vec<HashAlgo> = if self.cmd.print_hash {
+            let algos = self.cmd.hash_algo.iter().copied();
+            algos.filter(|&algo| algo != HashAlgo::Sha256).collect()
+        } else {
+            Vec::new()
+        };
+        let mut hashes = Vec::with_capacity(stems.len());
+        for (name, stem) in &stems {
+            if self.interrupted() {
+                bail!("{}", Msg::Cancelled.text());
+            }
+            let update = manifest
+                .partitions
+                .iter()
+                .find(|u| u.partition_name.as_slice() == *name)
+                .context("selected partition is not in the manifest")?;
+            let sink = SplitWriter::new(
+                output_path(stem),
+                self.cmd.split_size,
+                self.cmd.force,
+                &cleanup,
+            );
+            let mut out = HashingWriter::new(&extra_algos, compress::encoder(compression, sink)?);
+
+            let result = self.verify_with_progress(
+                &threadpool,
+                update,
+                payload,
+                block_size,
+                simd,
+                Some(&mut out),
+            )?;
+            let (digest, extra) = result
+                .and_then(|(digest, _)| {
+                    let (encoder, extra) = out.finish();
+                    encoder.finish()?.finish()?;
+                    Ok((digest, extra))
+                })
+                .with_context(|| format!("Error in partition '{}'", update.name()))?;
+            if self.cmd.print_hash {
+                let digests = self
+                    .cmd
+                    .hash_algo
+                    .iter()
+                    .filter_map(|&algo| match algo {
+                        HashAlgo::Sha256 => Some((algo, hex::encode(digest))),
+                        _ => extra.iter().find(|(hashed, _)| *hashed == algo).cloned(),
+                    })
+                    .collect::<Vec<_>>();
+                hashes.push((update.name(), digests));
+            }
+        }
+
+        cleanup.keep();
+        if self.cmd.print_hash {
+            println!("{}", Msg::HashesHeading.text());
+            for (name, digests) in &hashes {
+                println!("{name}: {}", format_digests(digests));
+            }
+        }
+        if self.cmd.chatty() {
+            self.display_extracted_folder_size(&dir)?;
+        }
+        if !self.cmd.no_open && self.cmd.chatty() {
+            self.open_extracted_folder(&dir)?;
+        }
+        Ok(())
+    }
+}
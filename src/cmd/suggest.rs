@@ -0,0 +1,53 @@
+/// A/B slot suffixes partition names may carry in a payload.
+const SLOT_SUFFIXES: &[&str] = &["_a", "_b"];
+
+/// Names from `available` the user may have meant by `wanted`: slot-suffix variants
+/// (`boot` ↔ `boot_a`) first, then names within a small edit distance, closest first.
+pub(crate) fn similar<'n>(wanted: &str, available: &[&'n str]) -> Vec<&'n str> {
+    let wanted_lower = wanted.to_ascii_lowercase();
+    let base = strip_slot(&wanted_lower);
+
+    let mut slot_variants: Vec<&str> = Vec::new();
+    let mut close: Vec<(usize, &str)> = Vec::new();
+    for &name in available {
+        let lower = name.to_ascii_lowercase();
+        if lower == wanted_lower || strip_slot(&lower) == base {
+            slot_variants.push(name);
+            continue;
+        }
+        // Allow roughly one typo per three characters, and always at least one.
+        let limit = (wanted.chars().count() / 3).max(1);
+        let distance =
+            edit_distance(&wanted_lower, &lower).min(edit_distance(base, strip_slot(&lower)));
+        if distance <= limit {
+            close.push((distance, name));
+        }
+    }
+
+    close.sort();
+    slot_variants.extend(close.into_iter().map(|(_, name)| name));
+    slot_variants
+}
+
+fn strip_slot(name: &str) -> &str {
+    SLOT_SUFFIXES
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .unwrap_or(name)
+}
+
+/// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = substitute.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
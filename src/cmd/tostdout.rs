@@ -0,0 +1,81 @@
+use crate::cmd::exitcode::FailureKind;
+use crate::cmd::extractor::Extractor;
+use crate::cmd::simd::CpuSimd;
+use crate::payload::Payload;
+use crate::proto::chromeos_update_engine::DeltaArchiveManifest;
+use anyhow::{Context, Result, anyhow};
+use indicatif::HumanBytes;
+use std::io::{self, BufWriter, IsTerminal, Write};
+
+impl Extractor<'_> {
+    /// `--stdout`: writes the one selected partition's image to standard output, for piping
+    /// into another tool. The image is rebuilt as `--verify-only` does: streamed out as it is
+    /// decoded when its operations write front to back, otherwise assembled in memory first.
+    ///
+    /// Operation data is checked before it is written, but the image hash only once the last
+    /// byte has gone out; a mismatch is then an error, and a non-zero exit status.
+    pub(super) fn extract_to_stdout(
+        &self,
+        payload: &Payload,
+        manifest: &DeltaArchiveManifest,
+        block_size: usize,
+        simd: CpuSimd,
+    ) -> Result<()> {
+        let selected: Vec<_> = manifest
+            .partitions
+            .iter()
+            .filter(|u| self.is_selected(u))
+            .collect();
+        let [update] = selected[..] else {
+            let names: Vec<_> = selected.iter().map(|u| u.name()).collect();
+            return Err(FailureKind::Usage.tag(anyhow!(
+                "--stdout writes a single image, but --partitions selects {} ({}); name exactly \
+                 one, or pick a slot with --slot.",
+                selected.len(),
+                names.join(", ")
+            )));
+        };
+        let stdout = io::stdout();
+        if stdout.is_terminal() {
+            return Err(FailureKind::Usage.tag(anyhow!(
+                "--stdout writes a binary image; pipe it into another tool or redirect it to a file."
+            )));
+        }
+
+        let threadpool = self.get_threadpool()?;
+        let mut out = BufWriter::with_capacity(1 << 20, stdout);
+        let result = self
+            .verify_with_progress(
+                &threadpool,
+                update,
+                payload,
+                block_size,
+                simd,
+                Some(&mut out),
+            )?
+            .and_then(|_| Ok(out.flush()?));
+        // The reader stopped reading (`| head -c`); whatever it wanted, it has.
+        if let Err(e) = &result
+            && e.chain()
+                .filter_map(|cause| cause.downcast_ref::<io::Error>())
+                .any(|e| e.kind() == io::ErrorKind::BrokenPipe)
+        {
+            return Ok(());
+        }
+        result.with_context(|| format!("Error in partition '{}'", update.name()))?;
+
+        if self.cmd.chatty() {
+            let size = update
+                .new_partition_info
+                .as_ref()
+                .and_then(|info| info.size)
+                .unwrap_or(0);
+            eprintln!(
+                "Wrote {} ({}) to standard output.",
+                update.name(),
+                HumanBytes(size)
+            );
+        }
+        Ok(())
+    }
+}
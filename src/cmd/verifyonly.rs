@@ -0,0 +1,470 @@
+use crate::cmd::ProgressMode;
+use crate::cmd::crypto;
+use crate::cmd::exitcode::FailureKind;
+use crate::cmd::extractor::Extractor;
+use crate::cmd::heuristics;
+use crate::cmd::lineprogress::LineProgress;
+use crate::cmd::simd::CpuSimd;
+use crate::payload::Payload;
+use crate::proto::chromeos_update_engine::install_operation::Type;
+use crate::proto::chromeos_update_engine::{
+    DeltaArchiveManifest, InstallOperation, PartitionUpdate,
+};
+use anyhow::{Context, Result, anyhow, bail};
+use console::Style;
+use memmap2::MmapMut;
+use rayon::ThreadPool;
+use rayon::prelude::*;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Operations decoded at once per worker thread; bounds how much decoded data is held in memory.
+pub(crate) const OPS_PER_THREAD: usize = 4;
+
+static ZEROS: [u8; 64 * 1024] = [0; 64 * 1024];
+
+/// What an operation puts at its destination extents, in extent order.
+enum Output {
+    Data(Vec<u8>),
+    Zero,
+}
+
+/// Where decoded operations go: straight into a running hash while the destination extents
+/// only move forward (gaps read as zeros, like the sparse output file would), otherwise into
+/// an anonymous mapping that is hashed once complete. With `--stdout` the image is also
+/// written out as it streams by, or from the mapping once complete.
+enum Image {
+    Stream {
+        hasher: Box<crypto::Sha256>,
+        pos: usize,
+    },
+    Mapped(MmapMut),
+}
+
+impl Image {
+    fn new(ops: &[InstallOperation], partition_len: usize) -> Result<Self> {
+        if is_sequential(ops) {
+            return Ok(Image::Stream {
+                hasher: Box::new(crypto::Sha256::new()),
+                pos: 0,
+            });
+        }
+        let map = MmapMut::map_anon(partition_len.max(1))
+            .context("unable to reserve memory to rebuild the image")?;
+        Ok(Image::Mapped(map))
+    }
+
+    fn apply<'w>(
+        &mut self,
+        extents: &[(usize, usize)],
+        output: &Output,
+        mut out: Option<&mut (dyn Write + Send + 'w)>,
+    ) -> Result<()> {
+        let mut at = 0;
+        for &(start, len) in extents {
+            match (&mut *self, output) {
+                (Image::Stream { hasher, pos }, output) => {
+                    feed_zeros(hasher, start - *pos, out.as_deref_mut())?;
+                    match output {
+                        Output::Data(buf) => {
+                            hasher.update(&buf[at..at + len]);
+                            if let Some(out) = out.as_deref_mut() {
+                                out.write_all(&buf[at..at + len])?;
+                            }
+                        }
+                        Output::Zero => feed_zeros(hasher, len, out.as_deref_mut())?,
+                    }
+                    *pos = start + len;
+                }
+                (Image::Mapped(map), Output::Data(buf)) => {
+                    map[start..start + len].copy_from_slice(&buf[at..at + len]);
+                }
+                // Anonymous memory starts out zeroed.
+                (Image::Mapped(_), Output::Zero) => {}
+            }
+            at += len;
+        }
+        Ok(())
+    }
+
+    fn finish<'w>(
+        self,
+        partition_len: usize,
+        out: Option<&mut (dyn Write + Send + 'w)>,
+    ) -> Result<[u8; 32]> {
+        match self {
+            Image::Stream { mut hasher, pos } => {
+                feed_zeros(&mut hasher, partition_len - pos, out)?;
+                Ok(hasher.finish())
+            }
+            Image::Mapped(map) => {
+                if let Some(out) = out {
+                    out.write_all(&map[..partition_len])?;
+                }
+                Ok(crypto::sha256(&map[..partition_len]))
+            }
+        }
+    }
+}
+
+fn feed_zeros<'w>(
+    hasher: &mut crypto::Sha256,
+    mut len: usize,
+    mut out: Option<&mut (dyn Write + Send + 'w)>,
+) -> io::Result<()> {
+    while len > 0 {
+        let n = len.min(ZEROS.len());
+        hasher.update(&ZEROS[..n]);
+        if let Some(out) = out.as_deref_mut() {
+            out.write_all(&ZEROS[..n])?;
+        }
+        len -= n;
+    }
+    Ok(())
+}
+
+/// Whether every destination extent starts at or after the end of the one before it.
+fn is_sequential(ops: &[InstallOperation]) -> bool {
+    let mut end = 0u64;
+    for extent in ops.iter().flat_map(|op| &op.dst_extents) {
+        let start = extent.start_block.unwrap_or(0);
+        if start < end {
+            return false;
+        }
+        end = start.saturating_add(extent.num_blocks.unwrap_or(0));
+    }
+    true
+}
+
+impl Extractor<'_> {
+    /// `--verify-only`: rebuilds every selected partition in memory, checking each operation's
+    /// data hash and each image's hash, without writing a byte to disk.
+    pub(super) fn verify_only(
+        &self,
+        payload: &Payload,
+        manifest: &DeltaArchiveManifest,
+        block_size: usize,
+        simd: CpuSimd,
+    ) -> Result<()> {
+        let threadpool = self.get_threadpool()?;
+        let selected: Vec<_> = manifest
+            .partitions
+            .iter()
+            .filter(|u| self.is_selected(u))
+            .collect();
+
+        if self.cmd.chatty() {
+            eprintln!(
+                "Verifying {} partitions using {} threads; nothing will be written.\n",
+                selected.len(),
+                threadpool.current_num_threads()
+            );
+        }
+
+        let mut results = Vec::with_capacity(selected.len());
+        for update in &selected {
+            let result =
+                self.verify_with_progress(&threadpool, update, payload, block_size, simd, None)?;
+            results.push((update.name(), result));
+        }
+
+        let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+        if self.cmd.chatty() {
+            let ok = Style::new().bold().green();
+            let bad = Style::new().bold().red();
+            let note = Style::new().yellow();
+            println!();
+            for (name, result) in &results {
+                match result {
+                    Ok((_, true)) => println!("  {} {name}", ok.apply_to("✓")),
+                    Ok((_, false)) => println!(
+                        "  {} {name}: no image hash in the manifest; operation data verified",
+                        note.apply_to("-")
+                    ),
+                    Err(e) => println!("  {} {name}: {e:#}", bad.apply_to("✗")),
+                }
+            }
+        }
+        if self.cmd.print_hash {
+            println!("Partition hashes (SHA-256):");
+            for (name, result) in &results {
+                if let Ok((digest, _)) = result {
+                    println!("{name}: sha256={}", hex::encode(digest));
+                }
+            }
+        }
+
+        if failed > 0 {
+            return Err(FailureKind::Verification.tag(anyhow!(
+                "❌ Verification failed for {failed} of {} partitions (see above).",
+                results.len()
+            )));
+        }
+        if self.cmd.chatty() {
+            println!(
+                "\nAll {} partitions verified. Nothing was written to disk.",
+                results.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// `verify` subcommand: checks the manifest's structure and then every partition, without
+    /// writing anything. Partitions in full are rebuilt in memory as with `--verify-only`; those
+    /// patching a base image can't be, so only their operation data hashes and extents are
+    /// checked. Fails if any partition does.
+    pub(super) fn run_verify(&self, path: &Path) -> Result<()> {
+        let source = self.open_payload_file(path)?;
+        let bytes: &[u8] = &source;
+        let payload = Payload::parse(bytes)?;
+        let manifest = Self::decode_manifest(payload.manifest())?;
+        let block_size = Self::block_size(&manifest)?;
+        let simd = CpuSimd::get();
+        let threadpool = self.get_threadpool()?;
+
+        let ok = Style::new().bold().green();
+        let bad = Style::new().bold().red();
+        let note = Style::new().yellow();
+
+        let findings = heuristics::analyze(&payload, &manifest);
+        println!("Structure checks:");
+        if findings.is_empty() {
+            println!("  no red flags");
+        }
+        for finding in &findings {
+            println!("  {} {finding}", note.apply_to("⚠"));
+        }
+        println!(
+            "\nVerifying {} partitions using {} threads; nothing will be written.\n",
+            manifest.partitions.len(),
+            threadpool.current_num_threads()
+        );
+
+        let mut results = Vec::with_capacity(manifest.partitions.len());
+        for update in &manifest.partitions {
+            let incremental = Self::is_incremental_partition(update);
+            let result = self
+                .validate_non_overlapping_extents(&update.operations)
+                .and_then(|()| {
+                    if incremental {
+                        threadpool
+                            .install(|| self.verify_op_data(update, &payload, block_size))
+                            .map(|()| false)
+                    } else {
+                        self.verify_with_progress(
+                            &threadpool,
+                            update,
+                            &payload,
+                            block_size,
+                            simd,
+                            None,
+                        )?
+                        .map(|(_, hashed)| hashed)
+                    }
+                });
+            let unhashed = update
+                .operations
+                .iter()
+                .filter(|op| op.data_length.unwrap_or(0) > 0 && op.data_sha256_hash.is_none())
+                .count();
+            results.push((update.name(), incremental, unhashed, result));
+        }
+
+        println!();
+        for (name, incremental, unhashed, result) in &results {
+            let mut gaps = Vec::new();
+            if *incremental {
+                gaps.push("incremental, image not rebuilt".to_string());
+            } else if matches!(result, Ok(false)) {
+                gaps.push("no image hash in the manifest".to_string());
+            }
+            if *unhashed > 0 {
+                gaps.push(format!("{unhashed} operations carry no data hash"));
+            }
+            match result {
+                Ok(_) if gaps.is_empty() => println!("  {} {name}", ok.apply_to("✓")),
+                Ok(_) => println!("  {} {name}: {}", note.apply_to("-"), gaps.join("; ")),
+                Err(e) => println!("  {} {name}: {e:#}", bad.apply_to("✗")),
+            }
+        }
+
+        let failed = results.iter().filter(|r| r.3.is_err()).count();
+        if failed > 0 {
+            return Err(FailureKind::Verification.tag(anyhow!(
+                "❌ Verification failed for {failed} of {} partitions (see above).",
+                results.len()
+            )));
+        }
+        println!("\nAll {} partitions passed.", results.len());
+        Ok(())
+    }
+
+    /// Checks every operation's destination extents against the partition size and the hash of
+    /// its data, without decoding anything.
+    fn verify_op_data(
+        &self,
+        update: &PartitionUpdate,
+        payload: &Payload,
+        block_size: usize,
+    ) -> Result<()> {
+        let partition_len = update
+            .new_partition_info
+            .as_ref()
+            .and_then(|info| info.size)
+            .context("unable to determine partition size")?;
+        let partition_len = usize::try_from(partition_len)
+            .context("partition is too large to verify on this platform")?;
+        update
+            .operations
+            .par_iter()
+            .enumerate()
+            .try_for_each(|(i, op)| {
+                self.extract_dst_extents(op, partition_len, block_size)
+                    .and_then(|_| {
+                        if op.data_length.unwrap_or(0) > 0 {
+                            self.extract_data(op, payload)?;
+                        }
+                        Ok(())
+                    })
+                    .with_context(|| format!("operation #{i}"))
+            })
+    }
+
+    /// Rebuilds one partition with [`verify_partition`](Self::verify_partition) on `threadpool`,
+    /// showing its progress, and writes the image to `out` when given. The outer error is a
+    /// setup failure, the inner one the verdict.
+    pub(super) fn verify_with_progress(
+        &self,
+        threadpool: &ThreadPool,
+        update: &PartitionUpdate,
+        payload: &Payload,
+        block_size: usize,
+        simd: CpuSimd,
+        out: Option<&mut (dyn Write + Send + '_)>,
+    ) -> Result<Result<([u8; 32], bool)>> {
+        let window = threadpool.current_num_threads() * OPS_PER_THREAD;
+        let progress_bar = self.create_progress_bar(update)?;
+        let lines = (self.cmd.progress != ProgressMode::Bars && self.cmd.chatty()).then(|| {
+            LineProgress::new(
+                vec![(update.name().into_owned(), progress_bar.clone())],
+                self.cmd.progress,
+            )
+        });
+        let result = std::thread::scope(|threads| {
+            if let Some(lines) = &lines {
+                threads.spawn(|| lines.run());
+            }
+            let _stop_lines = lines.as_ref().map(LineProgress::stop_on_drop);
+            threadpool.install(|| {
+                self.verify_partition(
+                    update,
+                    payload,
+                    block_size,
+                    simd,
+                    window,
+                    &progress_bar,
+                    out,
+                )
+            })
+        });
+        progress_bar.finish();
+        Ok(result)
+    }
+
+    /// Rebuilds one partition and returns its SHA-256, and whether the manifest had a hash to
+    /// check it against. The image is also written to `out` when given.
+    #[allow(clippy::too_many_arguments)]
+    fn verify_partition<'w>(
+        &self,
+        update: &PartitionUpdate,
+        payload: &Payload,
+        block_size: usize,
+        simd: CpuSimd,
+        window: usize,
+        progress_bar: &indicatif::ProgressBar,
+        mut out: Option<&mut (dyn Write + Send + 'w)>,
+    ) -> Result<([u8; 32], bool)> {
+        let partition_len = update
+            .new_partition_info
+            .as_ref()
+            .and_then(|info| info.size)
+            .context("unable to determine partition size")?;
+        let partition_len = usize::try_from(partition_len)
+            .context("partition is too large to verify on this platform")?;
+
+        let mut image = Image::new(&update.operations, partition_len)?;
+        for (chunk_index, chunk) in update.operations.chunks(window).enumerate() {
+            let decoded: Vec<_> = chunk
+                .par_iter()
+                .enumerate()
+                .map(|(i, op)| {
+                    self.decode_op(op, payload, partition_len, block_size, simd)
+                        .with_context(|| format!("operation #{}", chunk_index * window + i))
+                })
+                .collect();
+            for result in decoded {
+                let (extents, output) = result?;
+                image
+                    .apply(&extents, &output, out.as_deref_mut())
+                    .context("failed to write the image out")?;
+                progress_bar.inc(extents.iter().map(|&(_, len)| len as u64).sum());
+            }
+        }
+
+        let digest = image
+            .finish(partition_len, out)
+            .context("failed to write the image out")?;
+        let expected = update
+            .new_partition_info
+            .as_ref()
+            .and_then(|info| info.hash.as_deref());
+        if let Some(expected) = expected
+            && digest.as_slice() != expected
+        {
+            bail!(
+                "image hash mismatch: expected {}, got {}",
+                hex::encode(expected),
+                hex::encode(digest)
+            );
+        }
+        Ok((digest, expected.is_some()))
+    }
+
+    /// Checks an operation's data hash and decodes it, returning its destination extents and
+    /// the bytes that go there.
+    fn decode_op(
+        &self,
+        op: &InstallOperation,
+        payload: &Payload,
+        partition_len: usize,
+        block_size: usize,
+        simd: CpuSimd,
+    ) -> Result<(Vec<(usize, usize)>, Output)> {
+        let op_type = Type::try_from(op.r#type)?;
+        let extents = self.extract_dst_extents(op, partition_len, block_size)?;
+        let output = match op_type {
+            Type::Zero | Type::Discard => Output::Zero,
+            Type::Replace | Type::ReplaceBz | Type::ReplaceXz => {
+                let data = self.extract_data(op, payload)?;
+                let total: usize = extents.iter().map(|&(_, len)| len).sum();
+                let mut buf = vec![0u8; total];
+                self.write_op(
+                    op_type,
+                    data,
+                    &mut [&mut buf[..]],
+                    block_size,
+                    total,
+                    simd,
+                    true,
+                )?;
+                Output::Data(buf)
+            }
+            other => {
+                return Err(FailureKind::Unsupported.tag(anyhow!(
+                    "operation type {other:?} is not supported for full extraction"
+                )));
+            }
+        };
+        Ok((extents, output))
+    }
+}
@@ -0,0 +1,175 @@
+use crate::cmd::sink;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+/// Decoded data the queue may hold before workers handing off more have to wait.
+pub(crate) const BUDGET: usize = 256 * 1024 * 1024;
+/// Writer threads draining the queue. More than one keeps a network share busy while a write
+/// waits on a round trip.
+pub(crate) const WRITERS: usize = 2;
+
+/// Write-behind for [`IoMode::Pwrite`](sink::IoMode::Pwrite): workers queue decoded operations
+/// and move on to the next one while writer threads running [`run`](Self::run) write them out.
+/// Queued data is capped at a byte budget; a worker that would go over it waits, so extraction
+/// slows to the disk's pace instead of buffering without limit.
+pub(crate) struct WriteBehind {
+    queue: Mutex<Queue>,
+    /// Signalled when a job is queued, a job is done or the queue is closed.
+    changed: Condvar,
+    budget: usize,
+}
+
+struct Queue {
+    jobs: VecDeque<Job>,
+    bytes: usize,
+    closed: bool,
+}
+
+struct Job {
+    target: Arc<Target>,
+    data: Vec<u8>,
+    /// Where consecutive pieces of `data` go in the file, as (offset, length).
+    extents: Vec<(usize, usize)>,
+}
+
+/// One image being written through the queue.
+pub(crate) struct Target {
+    file: File,
+    state: Mutex<TargetState>,
+    idle: Condvar,
+}
+
+#[derive(Default)]
+struct TargetState {
+    in_flight: usize,
+    /// The first failed write; later jobs for the image are dropped.
+    error: Option<io::Error>,
+}
+
+impl WriteBehind {
+    pub(crate) fn new(budget: usize) -> Self {
+        Self {
+            queue: Mutex::new(Queue {
+                jobs: VecDeque::new(),
+                bytes: 0,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+            budget,
+        }
+    }
+
+    /// Queues `data` to be written to `target` at `extents`, waiting first if the queue is
+    /// over budget. A job larger than the whole budget only waits for the queue to empty.
+    pub(crate) fn submit(&self, target: &Arc<Target>, data: Vec<u8>, extents: Vec<(usize, usize)>) {
+        let mut queue = self.lock();
+        while queue.bytes > 0 && queue.bytes + data.len() > self.budget && !queue.closed {
+            queue = self
+                .changed
+                .wait(queue)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        target.lock().in_flight += 1;
+        queue.bytes += data.len();
+        queue.jobs.push_back(Job {
+            target: target.clone(),
+            data,
+            extents,
+        });
+        drop(queue);
+        self.changed.notify_all();
+    }
+
+    /// Body of a writer thread: writes queued jobs until the queue is closed and empty.
+    pub(crate) fn run(&self) {
+        loop {
+            let mut queue = self.lock();
+            let job = loop {
+                if let Some(job) = queue.jobs.pop_front() {
+                    break job;
+                }
+                if queue.closed {
+                    return;
+                }
+                queue = self
+                    .changed
+                    .wait(queue)
+                    .unwrap_or_else(PoisonError::into_inner);
+            };
+            drop(queue);
+
+            let result = job.write();
+            let mut state = job.target.lock();
+            state.in_flight -= 1;
+            if let Err(e) = result {
+                state.error.get_or_insert(e);
+            }
+            drop(state);
+            job.target.idle.notify_all();
+
+            self.lock().bytes -= job.data.len();
+            self.changed.notify_all();
+        }
+    }
+
+    /// Lets the writer threads exit once the queue is empty when the returned guard is dropped,
+    /// including while unwinding.
+    pub(crate) fn close_on_drop(&self) -> CloseOnDrop<'_> {
+        CloseOnDrop(self)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub(crate) struct CloseOnDrop<'w>(&'w WriteBehind);
+
+impl Drop for CloseOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.lock().closed = true;
+        self.0.changed.notify_all();
+    }
+}
+
+impl Job {
+    fn write(&self) -> io::Result<()> {
+        if self.target.lock().error.is_some() {
+            return Ok(());
+        }
+        let mut pos = 0;
+        for &(offset, len) in &self.extents {
+            sink::write_all_at(&self.target.file, &self.data[pos..pos + len], offset as u64)?;
+            pos += len;
+        }
+        Ok(())
+    }
+}
+
+impl Target {
+    pub(crate) fn new(file: File) -> Self {
+        Self {
+            file,
+            state: Mutex::default(),
+            idle: Condvar::new(),
+        }
+    }
+
+    /// Waits until every job queued for this image is written, returning the first error.
+    pub(crate) fn wait(&self) -> io::Result<()> {
+        let mut state = self.lock();
+        while state.in_flight > 0 {
+            state = self
+                .idle
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        state.error.take().map_or(Ok(()), Err)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TargetState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,181 @@
+//! Extraction as a library call, for tools that embed otaripper instead of running the binary.
+//!
+//! An [`Extractor`] does what `otaripper <source> --flat -o <dir>` does, without printing
+//! anything: images are written straight into the output directory, hashes are checked against
+//! the manifest, and a failed or cancelled run removes what it wrote. The user's config file and
+//! `OTARIPPER_*` variables are not consulted; error messages follow the system locale.
+//!
+//! ```no_run
+//! use otaripper::Extractor;
+//!
+//! Extractor::new("ota.zip")
+//!     .partitions(&["boot", "init_boot"])
+//!     .output_dir("images")
+//!     .run()?;
+//! # anyhow::Ok(())
+//! ```
+
+use crate::cmd::Cmd;
+use crate::cmd::config::Config;
+use crate::cmd::extractor::ProgressHook;
+use crate::proto::chromeos_update_engine::DeltaArchiveManifest;
+use anyhow::{Context, Result};
+use clap::{CommandFactory, FromArgMatches};
+use prost::Message;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+/// Extracts partition images from an OTA zip, a `payload.bin` or an `http(s)` URL to either.
+pub struct Extractor {
+    cmd: Cmd,
+    cancel: Arc<AtomicBool>,
+    progress: Option<ProgressHook>,
+}
+
+/// A partition in the payload, as listed by [`Extractor::list_partitions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    pub name: String,
+    /// Size of its image in bytes.
+    pub size: u64,
+}
+
+impl Extractor {
+    /// Extracts every partition of `source` into the current directory, verified.
+    pub fn new(source: impl Into<PathBuf>) -> Self {
+        // Parsed without the `env =` sources (OTARIPPER_LANG, ...), so the caller's environment
+        // doesn't change what the library does.
+        let matches = Cmd::command()
+            .mut_args(|arg| arg.env(None))
+            .try_get_matches_from(["otaripper"])
+            .expect("defaults always parse");
+        let mut cmd = Cmd::from_arg_matches(&matches).expect("defaults always parse");
+        cmd.positional_payload = Some(source.into());
+        cmd.output_dir = Some(PathBuf::from("."));
+        cmd.flat = true;
+        cmd.no_open = true;
+        cmd.machine_output = true;
+        Self {
+            cmd,
+            cancel: Arc::default(),
+            progress: None,
+        }
+    }
+
+    /// Extracts only these partitions. Names may use aliases and `*`/`?` patterns, as with
+    /// `--partitions`.
+    pub fn partitions(mut self, names: &[impl AsRef<str>]) -> Self {
+        self.cmd.partitions = names.iter().map(|n| n.as_ref().to_owned()).collect();
+        self
+    }
+
+    /// Leaves these partitions out, as with `--skip-partitions`.
+    pub fn skip_partitions(mut self, names: &[impl AsRef<str>]) -> Self {
+        self.cmd.skip_partitions = names.iter().map(|n| n.as_ref().to_owned()).collect();
+        self
+    }
+
+    /// Directory the images are written to; created if needed.
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cmd.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Whether to check images and operation data against the manifest's hashes (the default).
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.cmd.no_verify = !verify;
+        self
+    }
+
+    /// Replace images already in the output directory instead of failing, as with `--force`.
+    pub fn force(mut self, force: bool) -> Self {
+        self.cmd.force = force;
+        self
+    }
+
+    /// Worker threads; 0, the default, uses one per CPU.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.cmd.threads = Some(threads);
+        self
+    }
+
+    /// Keeps a compressed `payload.bin` out of memory, as with `--low-memory`.
+    pub fn low_memory(mut self, low_memory: bool) -> Self {
+        self.cmd.low_memory = low_memory;
+        self
+    }
+
+    /// Stops the run between operations once `cancel` is set; it then removes the images it
+    /// started and returns an error.
+    pub fn cancel_on(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Calls `progress` with the bytes written so far and in total, a few times a second while
+    /// the run lasts and once at the end. The total grows as images are started.
+    pub fn on_progress(mut self, progress: impl Fn(u64, u64) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Lists the partitions of a local source in manifest order, reading only its header and
+    /// manifest.
+    pub fn list_partitions(&self) -> Result<Vec<Partition>> {
+        let source = self
+            .cmd
+            .positional_payload
+            .as_deref()
+            .context("no source given")?;
+        let header = Cmd::read_payload_header(source)?;
+        let manifest =
+            DeltaArchiveManifest::decode(header.manifest()).context("unable to parse manifest")?;
+        Ok(manifest
+            .partitions
+            .iter()
+            .map(|update| Partition {
+                name: update.name().into_owned(),
+                size: update
+                    .new_partition_info
+                    .as_ref()
+                    .and_then(|info| info.size)
+                    .unwrap_or(0),
+            })
+            .collect())
+    }
+
+    /// Runs the extraction.
+    pub fn run(&self) -> Result<()> {
+        self.cmd.run_with_config(
+            Config::default(),
+            Arc::clone(&self.cancel),
+            self.progress.clone(),
+        )
+    }
+
+    /// Runs the extraction on tokio's blocking thread pool, where its memory-mapped I/O belongs.
+    /// Dropping the future, on a timeout or a closed connection say, cancels the run as
+    /// [`cancel_on`](Self::cancel_on) would: it stops between operations and removes what it
+    /// wrote.
+    #[cfg(feature = "async")]
+    pub async fn run_async(self) -> Result<()> {
+        use anyhow::Context;
+
+        /// Sets the flag unless the run got to finish.
+        struct CancelOnDrop(Option<Arc<AtomicBool>>);
+
+        impl Drop for CancelOnDrop {
+            fn drop(&mut self) {
+                if let Some(cancel) = self.0.take() {
+                    cancel.store(true, std::sync::atomic::Ordering::Release);
+                }
+            }
+        }
+
+        let mut guard = CancelOnDrop(Some(Arc::clone(&self.cancel)));
+        let result = tokio::task::spawn_blocking(move || self.run()).await;
+        guard.0 = None;
+        result.context("extraction task failed")?
+    }
+}
@@ -0,0 +1,143 @@
+//! C interface, for GUI wrappers and other languages that would otherwise parse the CLI's
+//! output. `include/otaripper.h` declares it; regenerate that with
+//! `cbindgen --config cbindgen.toml --output include/otaripper.h` after changing this file.
+//!
+//! Strings are UTF-8 and NUL-terminated. A function that fails returns NULL or a non-zero
+//! status and leaves a message that [`otaripper_last_error`] returns on the same thread.
+
+use crate::Extractor;
+use anyhow::{Context, Result, anyhow};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Called during extraction with `user_data`, the bytes written so far and the total.
+pub type OtaripperProgressFn =
+    Option<unsafe extern "C" fn(user_data: *mut c_void, done: u64, total: u64)>;
+
+/// A progress callback and its user data, called from otaripper's own thread.
+struct Callback {
+    progress: unsafe extern "C" fn(*mut c_void, u64, u64),
+    user_data: *mut c_void,
+}
+
+// The caller promises the callback may be called from another thread with its user data.
+unsafe impl Send for Callback {}
+unsafe impl Sync for Callback {}
+
+impl Callback {
+    fn call(&self, done: u64, total: u64) {
+        unsafe { (self.progress)(self.user_data, done, total) }
+    }
+}
+
+/// Runs `f`, turning an error or a panic into the thread's last error.
+fn guard<T>(f: impl FnOnce() -> Result<T>) -> Option<T> {
+    let result = panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(anyhow!("otaripper panicked; this is a bug")));
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            let message = format!("{e:#}").replace('\0', " ");
+            LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+            None
+        }
+    }
+}
+
+/// Reads a path argument.
+///
+/// # Safety
+/// `s` must be NULL or a NUL-terminated string.
+unsafe fn path_arg(s: *const c_char, what: &str) -> Result<PathBuf> {
+    Ok(PathBuf::from(unsafe { str_arg(s, what) }?))
+}
+
+/// # Safety
+/// `s` must be NULL or a NUL-terminated string.
+unsafe fn str_arg<'s>(s: *const c_char, what: &str) -> Result<&'s str> {
+    if s.is_null() {
+        return Err(anyhow!("{what} is NULL"));
+    }
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .with_context(|| format!("{what} is not UTF-8"))
+}
+
+/// Lists the partitions of an OTA zip or payload.bin as a JSON array of
+/// `{"name": "boot", "size": 100663296}` objects, in manifest order. Returns NULL on failure.
+/// Free the result with [`otaripper_free_string`].
+///
+/// # Safety
+/// `source` must be a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn otaripper_list_partitions(source: *const c_char) -> *mut c_char {
+    guard(|| {
+        let source = unsafe { path_arg(source, "source") }?;
+        let partitions = Extractor::new(source).list_partitions()?;
+        let list: Vec<_> = partitions
+            .iter()
+            .map(|p| serde_json::json!({ "name": p.name, "size": p.size }))
+            .collect();
+        Ok(CString::new(serde_json::to_string(&list)?)?.into_raw())
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Extracts one partition of an OTA zip or payload.bin (or an `http(s)` URL to either) into
+/// `output_dir` as `<partition>.img`, verified against the manifest. `progress`, when not NULL,
+/// is called a few times a second from another thread. Returns 0 on success, -1 on failure.
+///
+/// # Safety
+/// `source`, `partition` and `output_dir` must be NUL-terminated strings. `progress` must be
+/// safe to call from another thread with `user_data` until this function returns.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn otaripper_extract_partition(
+    source: *const c_char,
+    partition: *const c_char,
+    output_dir: *const c_char,
+    progress: OtaripperProgressFn,
+    user_data: *mut c_void,
+) -> i32 {
+    guard(|| {
+        let source = unsafe { path_arg(source, "source") }?;
+        let partition = unsafe { str_arg(partition, "partition") }?;
+        let output_dir = unsafe { path_arg(output_dir, "output_dir") }?;
+        let mut extractor = Extractor::new(source)
+            .partitions(&[partition])
+            .output_dir(output_dir);
+        if let Some(progress) = progress {
+            let callback = Callback {
+                progress,
+                user_data,
+            };
+            extractor = extractor.on_progress(move |done, total| callback.call(done, total));
+        }
+        extractor.run()
+    })
+    .map_or(-1, |()| 0)
+}
+
+/// The message of the last failure on this thread, or NULL if nothing failed yet. Valid until
+/// the next failing call on the thread; do not free it.
+#[unsafe(no_mangle)]
+pub extern "C" fn otaripper_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Frees a string returned by otaripper. NULL is ignored.
+///
+/// # Safety
+/// `s` must be NULL or a string otaripper returned that has not been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn otaripper_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
@@ -1,3 +1,19 @@
+//! Payload and manifest parsing ([`payload`], [`proto`]) build for every target, including
+//! wasm32-unknown-unknown for listing partitions in a browser. Extraction and everything that
+//! touches files, memory maps or SIMD is native only.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod builder;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod cmd;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod extract;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ffi;
 pub mod payload;
 pub mod proto;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod reader;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use extract::Extractor;
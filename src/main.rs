@@ -1,15 +1,44 @@
 use clap::Parser;
 use mimalloc::MiMalloc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 // Use MiMalloc for better performance in multi-threaded extraction
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
 use otaripper::cmd::Cmd;
+use otaripper::cmd::exitcode::FailureKind;
+use otaripper::cmd::i18n::Msg;
 
 fn main() {
-    if let Err(e) = Cmd::parse().run() {
-        eprintln!("\nERROR: {:#}", e);
+    let cmd = Cmd::parse();
+
+    // The first Ctrl+C stops extraction and removes partial files; a second one exits at once.
+    let cancel = Arc::new(AtomicBool::new(false));
+    let interrupted = Arc::clone(&cancel);
+    let handler = ctrlc::set_handler(move || {
+        if interrupted.swap(true, Ordering::AcqRel) {
+            eprintln!("\n{}", Msg::ExitingNow.text());
+            std::process::exit(FailureKind::Interrupted.code());
+        }
+        eprintln!("\n\n{}", Msg::Interrupted.text());
+    });
+    if let Err(e) = handler {
+        eprintln!(
+            "\n{}: {}",
+            Msg::Error.text(),
+            Msg::HandlerFailed.fill(&[&e])
+        );
         std::process::exit(1);
     }
+
+    if let Err(e) = cmd.run_cancellable(Arc::clone(&cancel)) {
+        eprintln!("\n{}: {:#}", Msg::Error.text(), e);
+        std::process::exit(if cancel.load(Ordering::Acquire) {
+            FailureKind::Interrupted.code()
+        } else {
+            FailureKind::of(&e).code()
+        });
+    }
 }
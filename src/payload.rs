@@ -1,25 +1,73 @@
-use anyhow::{Context, Result, anyhow, bail};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::cmd::crypto;
+use crate::proto::chromeos_update_engine::{
+    self as proto, DeltaArchiveManifest, Extent, InstallOperation,
+};
+use anyhow::{Context, Result, anyhow, ensure};
+use prost::Message;
+use std::fmt;
+use std::io::{self, Read};
+use std::ops::Range;
+
+#[cfg(target_arch = "wasm32")]
+mod crypto {
+    use sha2::{Digest, Sha256};
+
+    /// Without the native crypto backends, the one hash parsing needs comes from `sha2`.
+    pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+}
 
 const PAYLOAD_MAGIC: &[u8] = b"CrAU";
 const MAX_METADATA_SIG_SIZE: u32 = 64 * 1024 * 1024; // 64 MiB
 const MAX_MANIFEST_SIZE: u64 = 256 * 1024 * 1024; // 256 MiB
 const SUPPORTED_VERSION_MAX: u64 = 2;
 
+/// A parsed `payload.bin`: its header fields and zero-copy views of each section.
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct Payload<'a> {
-    pub file_format_version: u64,
-    pub manifest_size: u64,
-    pub manifest: &'a [u8],
-    pub metadata_signature: Option<&'a [u8]>,
-    pub data: &'a [u8],
+    file_format_version: u64,
+    manifest_size: u64,
+    header_size: usize,
+    /// Header followed by the manifest.
+    metadata: &'a [u8],
+    metadata_signature: Option<&'a [u8]>,
+    data: &'a [u8],
 }
 
-impl<'a> Payload<'a> {
-    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+/// Header, manifest and metadata signature read from a stream by [`Payload::parse_header`]; the
+/// data section is never touched.
+#[derive(Debug)]
+pub struct PayloadHeader {
+    file_format_version: u64,
+    manifest_size: u64,
+    header_size: usize,
+    /// Header followed by the manifest.
+    metadata: Vec<u8>,
+    metadata_signature: Option<Vec<u8>>,
+}
+
+/// Fixed-size header fields, shared by [`Payload::parse`] and [`Payload::parse_header`].
+struct Header {
+    file_format_version: u64,
+    manifest_size: u64,
+    header_size: usize,
+    manifest_len: usize,
+    metadata_sig_size: usize,
+}
+
+impl Header {
+    /// Decodes the 20-byte (v1) or 24-byte (v2) fixed header at the start of `bytes`.
+    fn decode(bytes: &[u8]) -> Result<Self> {
         // ---- Basic Size Check ----
         if bytes.len() < 20 {
-            bail!("This file is too small to be an Android update. Please check your download.");
+            return Err(field_error(
+                "This file is too small to be an Android update. Please check your download.",
+                bytes,
+                "header",
+                0..bytes.len(),
+            ));
         }
 
         // ---- Magic & Vibe Checks ----
@@ -42,11 +90,16 @@ impl<'a> Payload<'a> {
                 _ => "❌ This file isn't a recognized Android update.\n\n",
             };
 
-            bail!(
-                "{hint}\
-                👉 Valid inputs:\n  - A raw 'payload.bin' file\n  - A full OTA .zip (with payload.bin inside)\n\n\
-                💡 Tip: Just drag the correct file onto otaripper! 😎",
-            );
+            return Err(field_error(
+                format!(
+                    "{hint}\
+                    👉 Valid inputs:\n  - A raw 'payload.bin' file\n  - A full OTA .zip (with payload.bin inside)\n\n\
+                    💡 Tip: Just drag the correct file onto otaripper! 😎",
+                ),
+                bytes,
+                "magic (expected \"CrAU\")",
+                0..4,
+            ));
         }
 
         // ---- Version & Size Parsing ----
@@ -56,10 +109,23 @@ impl<'a> Payload<'a> {
                 .map_err(|_| anyhow!("Internal Error: Could not read version"))?,
         );
 
+        if file_format_version == 0 {
+            return Err(field_error(
+                "The update file header is corrupted (format version 0 does not exist; expected 1 or 2).",
+                bytes,
+                "file_format_version",
+                4..12,
+            ));
+        }
         if file_format_version > SUPPORTED_VERSION_MAX {
-            bail!(
-                "This update uses a newer format than this version of otaripper supports. Please check for an app update!"
-            );
+            return Err(field_error(
+                format!(
+                    "This update uses a newer format (version {file_format_version}) than this version of otaripper supports. Please check for an app update!"
+                ),
+                bytes,
+                "file_format_version",
+                4..12,
+            ));
         }
 
         let manifest_size = u64::from_be_bytes(
@@ -68,16 +134,34 @@ impl<'a> Payload<'a> {
                 .map_err(|_| anyhow!("Internal Error: Could not read manifest size"))?,
         );
 
+        if manifest_size == 0 {
+            return Err(field_error(
+                "The update file metadata is missing (the header declares an empty manifest). Please try re-downloading.",
+                bytes,
+                "manifest_size",
+                12..20,
+            ));
+        }
         if manifest_size > MAX_MANIFEST_SIZE {
-            bail!("The update file metadata appears to be corrupted. Please try re-downloading.");
+            return Err(field_error(
+                format!(
+                    "The update file metadata appears to be corrupted (manifest size {manifest_size} exceeds the {MAX_MANIFEST_SIZE}-byte limit). Please try re-downloading."
+                ),
+                bytes,
+                "manifest_size",
+                12..20,
+            ));
         }
 
         // ---- v2 Handling ----
         let (header_size, metadata_sig_size): (usize, usize) = if file_format_version >= 2 {
             if bytes.len() < 24 {
-                bail!(
-                    "The file header is incomplete. This usually happens with a broken download."
-                );
+                return Err(field_error(
+                    "The file header is incomplete. This usually happens with a broken download.",
+                    bytes,
+                    "metadata_signature_size",
+                    20..bytes.len(),
+                ));
             }
             let sig_size = u32::from_be_bytes(
                 bytes[20..24]
@@ -85,36 +169,132 @@ impl<'a> Payload<'a> {
                     .map_err(|_| anyhow!("Internal Error: Could not read signature"))?,
             );
             if sig_size > MAX_METADATA_SIG_SIZE {
-                bail!("The file signature is invalid or corrupted.");
+                return Err(field_error(
+                    format!(
+                        "The file signature is invalid or corrupted (metadata signature size {sig_size} exceeds the {MAX_METADATA_SIG_SIZE}-byte limit)."
+                    ),
+                    bytes,
+                    "metadata_signature_size",
+                    20..24,
+                ));
             }
             (24, sig_size as usize)
         } else {
             (20, 0)
         };
 
-        // ---- Combined Bounds Check with Overflow Protection ----
         let manifest_len: usize = manifest_size
             .try_into()
             .context("This update is too large for your system memory to handle.")?;
 
-        let data_start = header_size
-            .checked_add(manifest_len)
-            .and_then(|sum| sum.checked_add(metadata_sig_size))
-            .ok_or_else(|| anyhow!("Memory overflow: This update file is abnormally large."))?;
+        Ok(Self {
+            file_format_version,
+            manifest_size,
+            header_size,
+            manifest_len,
+            metadata_sig_size,
+        })
+    }
+
+    /// Offset of the data section, i.e. the end of the metadata signature.
+    fn data_start(&self) -> Result<usize> {
+        self.header_size
+            .checked_add(self.manifest_len)
+            .and_then(|sum| sum.checked_add(self.metadata_sig_size))
+            .ok_or_else(|| anyhow!("Memory overflow: This update file is abnormally large."))
+    }
+}
+
+/// The header declares sections that run past the end of the file. `section` covers the bytes
+/// that were expected, `available` is where the input actually ended.
+fn truncated(section: &str, expected: Range<usize>, available: usize) -> anyhow::Error {
+    anyhow!(
+        "❌ Extraction Failed\n\n\
+        The file is missing a large chunk of data at the end. \n\
+        👉 Your download was likely interrupted. Please try downloading the file again!\n\n\
+        Details: the header places the {section} at bytes {}..{}, but the input ends at byte {available}.",
+        expected.start,
+        expected.end
+    )
+}
+
+/// A header parse error naming the field that failed, where it is, and the bytes around it.
+fn field_error(
+    message: impl fmt::Display,
+    bytes: &[u8],
+    field: &str,
+    span: Range<usize>,
+) -> anyhow::Error {
+    anyhow!(
+        "{message}\n\nDetails: {field} at byte offset {} ({} bytes):\n{}",
+        span.start,
+        span.len(),
+        hexdump(bytes, span.clone())
+    )
+}
 
+/// Hexdump of the 16-byte rows around `span`, with the span's bytes marked by `^^`.
+fn hexdump(bytes: &[u8], span: Range<usize>) -> String {
+    const ROW: usize = 16;
+    if bytes.is_empty() {
+        return "  (no data)".to_string();
+    }
+    let first = (span.start / ROW).saturating_sub(1) * ROW;
+    let last = (span.end.div_ceil(ROW) + 1) * ROW;
+    let mut out = String::new();
+    for row in (first..last.min(bytes.len())).step_by(ROW) {
+        let chunk = &bytes[row..(row + ROW).min(bytes.len())];
+        let mut hex = String::new();
+        let mut marks = String::new();
+        for (i, b) in chunk.iter().enumerate() {
+            let sep = if i == 8 { "  " } else { " " };
+            hex.push_str(&format!("{sep}{b:02x}"));
+            let marked = span.contains(&(row + i));
+            marks.push_str(&format!("{sep}{}", if marked { "^^" } else { "  " }));
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+            .collect();
+        out.push_str(&format!("  {row:08x} {hex:<49} |{ascii}|\n"));
+        if marks.contains('^') {
+            out.push_str(&format!("           {}\n", marks.trim_end()));
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+impl<'a> Payload<'a> {
+    /// Splits `bytes` into header, manifest, metadata signature and data section. Any input is
+    /// accepted without panicking: fields that don't fit the input are reported as errors.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        let header = Header::decode(bytes)?;
+
+        // ---- Combined Bounds Check with Overflow Protection ----
+        let data_start = header.data_start()?;
+        let Header {
+            file_format_version,
+            manifest_size,
+            header_size,
+            manifest_len,
+            metadata_sig_size,
+        } = header;
         if data_start > bytes.len() {
-            bail!(
-                "❌ Extraction Failed\n\n\
-                The file is missing a large chunk of data at the end. \n\
-                👉 Your download was likely interrupted. Please try downloading the file again!"
-            );
+            let manifest_end = header_size + manifest_len;
+            return Err(if manifest_end > bytes.len() {
+                truncated("manifest", header_size..manifest_end, bytes.len())
+            } else {
+                truncated("metadata signature", manifest_end..data_start, bytes.len())
+            });
         }
 
         // ---- Final zero-copy slices ----
         Ok(Self {
             file_format_version,
             manifest_size,
-            manifest: &bytes[header_size..header_size + manifest_len],
+            header_size,
+            metadata: &bytes[..header_size + manifest_len],
             metadata_signature: if metadata_sig_size > 0 {
                 Some(&bytes[header_size + manifest_len..data_start])
             } else {
@@ -123,4 +303,421 @@ impl<'a> Payload<'a> {
             data: &bytes[data_start..],
         })
     }
+
+    /// Reads just the fixed header, manifest and metadata signature from `reader`, which must be
+    /// positioned at the start of the payload. Nothing past the metadata signature is read, so
+    /// this is cheap even for multi-gigabyte payloads or slow sources.
+    pub fn parse_header(mut reader: impl Read) -> Result<PayloadHeader> {
+        // v1 headers are 20 bytes; v2 adds the 4-byte metadata signature size. Short reads are
+        // handed to `Header::decode` as they are, so it reports them like `parse` does.
+        let mut fixed = [0u8; 24];
+        let mut fixed_len = read_up_to(&mut reader, &mut fixed[..20])?;
+        if fixed_len == 20
+            && fixed[..4] == *PAYLOAD_MAGIC
+            && u64::from_be_bytes(fixed[4..12].try_into()?) >= 2
+        {
+            fixed_len += read_up_to(&mut reader, &mut fixed[20..])?;
+        }
+        let header = Header::decode(&fixed[..fixed_len])?;
+
+        let mut metadata = fixed[..header.header_size].to_vec();
+        let mut pos = header.header_size;
+        let mut read_section = |section: &str, len: usize| -> Result<Vec<u8>> {
+            let mut buf = Vec::new();
+            (&mut reader)
+                .take(len as u64)
+                .read_to_end(&mut buf)
+                .context("failed to read payload metadata")?;
+            if buf.len() < len {
+                return Err(truncated(section, pos..pos + len, pos + buf.len()));
+            }
+            pos += len;
+            Ok(buf)
+        };
+        metadata.extend(read_section("manifest", header.manifest_len)?);
+        let metadata_signature = match header.metadata_sig_size {
+            0 => None,
+            len => Some(read_section("metadata signature", len)?),
+        };
+
+        Ok(PayloadHeader {
+            file_format_version: header.file_format_version,
+            manifest_size: header.manifest_size,
+            header_size: header.header_size,
+            metadata,
+            metadata_signature,
+        })
+    }
+
+    /// Like [`parse_header`](Self::parse_header), reading from an async source such as a
+    /// `tokio::fs::File` or a request body.
+    #[cfg(feature = "async")]
+    pub async fn parse_header_async(
+        mut reader: impl tokio::io::AsyncRead + Unpin,
+    ) -> Result<PayloadHeader> {
+        use tokio::io::AsyncReadExt;
+
+        // The fixed header says how much metadata follows; once that is read, the blocking
+        // parser checks it all from memory.
+        let mut metadata = Vec::with_capacity(24);
+        (&mut reader)
+            .take(24)
+            .read_to_end(&mut metadata)
+            .await
+            .context("failed to read payload metadata")?;
+        if let Ok(end) = Header::decode(&metadata).and_then(|header| header.data_start())
+            && end > metadata.len()
+        {
+            (&mut reader)
+                .take((end - metadata.len()) as u64)
+                .read_to_end(&mut metadata)
+                .await
+                .context("failed to read payload metadata")?;
+        }
+        Self::parse_header(metadata.as_slice())
+    }
+
+    /// The data blob of `op`: `data_length` bytes at `data_offset` into the data section.
+    /// Fails when either field is missing or the range doesn't fit the data section, so callers
+    /// never slice out of bounds. The blob is not checked against `data_sha256_hash`.
+    pub fn op_data(&self, op: &InstallOperation) -> Result<&'a [u8]> {
+        let len = op.data_length.context("data_length not defined")?;
+        let offset = op.data_offset.context("data_offset not defined")?;
+        let range = usize::try_from(offset)
+            .ok()
+            .and_then(|start| Some(start..start.checked_add(usize::try_from(len).ok()?)?))
+            .context("data_offset + data_length overflows")?;
+        self.data.get(range.clone()).with_context(|| {
+            format!(
+                "data range {}..{} exceeds payload size {}",
+                range.start,
+                range.end,
+                self.data.len()
+            )
+        })
+    }
+
+    /// Drops bytes after the end of everything `manifest` references (operation data and the
+    /// payload signature) from the data section and returns how many there were. Some vendor
+    /// tools pad payload.bin or append their own trailer; update_engine never reads it.
+    pub fn trim_trailing(&mut self, manifest: &DeltaArchiveManifest) -> usize {
+        let ops = manifest
+            .partitions
+            .iter()
+            .flat_map(|p| &p.operations)
+            .filter(|op| op.data_length.unwrap_or(0) > 0)
+            .map(|op| (op.data_offset.unwrap_or(0), op.data_length.unwrap_or(0)));
+        let signature = manifest.signatures_offset.zip(manifest.signatures_size);
+        let end = ops
+            .chain(signature)
+            .map(|(offset, len)| offset.saturating_add(len))
+            .max()
+            .unwrap_or(0);
+
+        // Anything referenced past the end is left for the bounds checks to report.
+        let end = usize::try_from(end).unwrap_or(usize::MAX);
+        if end >= self.data.len() {
+            return 0;
+        }
+        let trailing = self.data.len() - end;
+        self.data = &self.data[..end];
+        trailing
+    }
+
+    /// Payload format version from the header (1 or 2).
+    pub fn version(&self) -> u64 {
+        self.file_format_version
+    }
+
+    /// Size of the fixed header: 20 bytes in version 1, 24 in version 2.
+    pub fn header_size(&self) -> usize {
+        self.header_size
+    }
+
+    /// Manifest size as declared in the header.
+    pub fn manifest_size(&self) -> u64 {
+        self.manifest_size
+    }
+
+    /// The serialized `DeltaArchiveManifest`.
+    pub fn manifest(&self) -> &'a [u8] {
+        &self.metadata[self.header_size..]
+    }
+
+    /// Header plus manifest, the region update_engine calls the payload metadata and the
+    /// metadata signature covers.
+    pub fn metadata(&self) -> &'a [u8] {
+        self.metadata
+    }
+
+    /// Length of [`metadata`](Self::metadata).
+    pub fn metadata_size(&self) -> usize {
+        self.metadata.len()
+    }
+
+    /// SHA-256 of [`metadata`](Self::metadata): update_engine's metadata hash, published as
+    /// `METADATA_HASH` in payload_properties.txt and checked by the device before it trusts
+    /// the manifest.
+    pub fn metadata_hash(&self) -> [u8; 32] {
+        crypto::sha256(self.metadata)
+    }
+
+    /// The serialized `Signatures` blob following the manifest; `None` in version 1 payloads or
+    /// when the size field is zero.
+    pub fn metadata_signature(&self) -> Option<&'a [u8]> {
+        self.metadata_signature
+    }
+
+    /// The metadata signature, decoded.
+    pub fn metadata_signatures(&self) -> Result<Option<Signatures>> {
+        self.metadata_signature.map(Signatures::decode).transpose()
+    }
+
+    /// The payload signature blob `manifest` points at, at the end of the data section, or
+    /// `None` for unsigned payloads. It covers every byte of the payload in front of it.
+    pub fn payload_signature(&self, manifest: &DeltaArchiveManifest) -> Result<Option<&'a [u8]>> {
+        let (Some(offset), Some(size)) = (manifest.signatures_offset, manifest.signatures_size)
+        else {
+            return Ok(None);
+        };
+        let start = usize::try_from(offset)
+            .ok()
+            .filter(|&o| o <= self.data.len())
+            .context("payload signature offset is out of bounds")?;
+        let blob = usize::try_from(size)
+            .ok()
+            .and_then(|len| self.data.get(start..start.checked_add(len)?))
+            .context("payload signature is truncated")?;
+        Ok(Some(blob))
+    }
+
+    /// The payload signature, decoded.
+    pub fn payload_signatures(
+        &self,
+        manifest: &DeltaArchiveManifest,
+    ) -> Result<Option<Signatures>> {
+        self.payload_signature(manifest)?
+            .map(Signatures::decode)
+            .transpose()
+    }
+
+    /// Offset of the data section from the start of the payload. Operation `data_offset`s are
+    /// relative to it.
+    pub fn data_offset(&self) -> usize {
+        self.metadata_size() + self.metadata_signature.map_or(0, <[u8]>::len)
+    }
+
+    /// Everything after the metadata signature: operation data, then the payload signature.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl fmt::Display for Payload<'_> {
+    /// One-line header summary, e.g.
+    /// `payload v2: 24-byte header, 1234-byte manifest, 267-byte metadata signature, 1048576 bytes of data`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "payload v{}: {}-byte header, {}-byte manifest, ",
+            self.file_format_version, self.header_size, self.manifest_size
+        )?;
+        match self.metadata_signature {
+            Some(sig) => write!(f, "{}-byte metadata signature", sig.len())?,
+            None => f.write_str("no metadata signature")?,
+        }
+        write!(f, ", {} bytes of data", self.data.len())
+    }
+}
+
+impl PayloadHeader {
+    /// Payload format version from the header (1 or 2).
+    pub fn version(&self) -> u64 {
+        self.file_format_version
+    }
+
+    /// Size of the fixed header: 20 bytes in version 1, 24 in version 2.
+    pub fn header_size(&self) -> usize {
+        self.header_size
+    }
+
+    /// Manifest size as declared in the header.
+    pub fn manifest_size(&self) -> u64 {
+        self.manifest_size
+    }
+
+    /// The serialized `DeltaArchiveManifest`.
+    pub fn manifest(&self) -> &[u8] {
+        &self.metadata[self.header_size..]
+    }
+
+    /// Header plus manifest, as [`Payload::metadata`].
+    pub fn metadata(&self) -> &[u8] {
+        &self.metadata
+    }
+
+    /// SHA-256 of the header plus manifest, as [`Payload::metadata_hash`].
+    pub fn metadata_hash(&self) -> [u8; 32] {
+        crypto::sha256(&self.metadata)
+    }
+
+    /// The serialized metadata `Signatures` blob, if any.
+    pub fn metadata_signature(&self) -> Option<&[u8]> {
+        self.metadata_signature.as_deref()
+    }
+
+    /// The metadata signature, decoded.
+    pub fn metadata_signatures(&self) -> Result<Option<Signatures>> {
+        self.metadata_signature()
+            .map(Signatures::decode)
+            .transpose()
+    }
+
+    /// Offset of the data section from the start of the payload.
+    pub fn data_offset(&self) -> usize {
+        self.metadata.len() + self.metadata_signature.as_ref().map_or(0, Vec::len)
+    }
+}
+
+/// A decoded `Signatures` blob: the metadata signature after the manifest, or the payload
+/// signature at the end of the data section.
+#[derive(Debug, Clone, Default)]
+pub struct Signatures {
+    pub signatures: Vec<Signature>,
+}
+
+/// One entry of a [`Signatures`] blob.
+#[derive(Debug, Clone)]
+pub struct Signature {
+    /// Key version recorded by the signer (deprecated upstream, but still written).
+    pub version: Option<u32>,
+    /// The signature with padding removed.
+    pub data: Vec<u8>,
+    /// `unpadded_signature_size` as stored; `None` when the signer didn't pad.
+    pub unpadded_len: Option<u32>,
+    /// Size of the signature slot in the blob, padding included.
+    pub padded_len: usize,
+}
+
+impl Signatures {
+    /// Decodes a serialized `Signatures` message, removing the padding from each signature.
+    /// Entries without signature data are skipped.
+    pub fn decode(blob: &[u8]) -> Result<Self> {
+        let sigs = proto::Signatures::decode(blob)
+            .context("signature blob is not a valid Signatures message")?;
+        let mut signatures = Vec::with_capacity(sigs.signatures.len());
+        for sig in sigs.signatures {
+            #[allow(deprecated)]
+            let version = sig.version;
+            let Some(mut data) = sig.data else { continue };
+            let padded_len = data.len();
+            if let Some(unpadded) = sig.unpadded_signature_size {
+                ensure!(
+                    unpadded as usize <= data.len(),
+                    "unpadded signature size {} exceeds signature data ({} bytes)",
+                    unpadded,
+                    data.len()
+                );
+                data.truncate(unpadded as usize);
+            }
+            signatures.push(Signature {
+                version,
+                data,
+                unpadded_len: sig.unpadded_signature_size,
+                padded_len,
+            });
+        }
+        Ok(Self { signatures })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Signature> {
+        self.signatures.iter()
+    }
+}
+
+impl Signature {
+    /// Best guess at the signing algorithm from the signature alone: RSA signatures are exactly
+    /// the modulus size, P-256 ECDSA ones are short DER sequences padded to a fixed slot.
+    pub fn algorithm_hint(&self) -> String {
+        match self.data.as_slice() {
+            [0x30, len, ..] if self.data.len() == *len as usize + 2 && self.data.len() <= 72 => {
+                "ECDSA P-256, SHA-256".to_string()
+            }
+            d if d.len() >= 128 && d.len() % 64 == 0 => {
+                format!("RSA-{} PKCS#1 v1.5, SHA-256", d.len() * 8)
+            }
+            d => format!("unknown ({} byte signature)", d.len()),
+        }
+    }
+}
+
+/// Byte ranges covered by `extents` in a partition of `partition_len` bytes, as `(offset, len)`
+/// pairs. Fails, rather than panicking or wrapping, on missing fields, empty extents, arithmetic
+/// overflow and extents reaching past the end of the partition.
+pub fn extent_ranges(
+    extents: &[Extent],
+    block_size: usize,
+    partition_len: usize,
+) -> Result<Vec<(usize, usize)>> {
+    let mut out = Vec::with_capacity(extents.len());
+    for extent in extents {
+        let start_block = extent.start_block.context("missing start_block")?;
+        let num_blocks = extent.num_blocks.context("missing num_blocks")?;
+
+        let start = usize::try_from(start_block)
+            .ok()
+            .and_then(|b| b.checked_mul(block_size))
+            .context("start_block * block_size overflows")?;
+        let len = usize::try_from(num_blocks)
+            .ok()
+            .and_then(|n| n.checked_mul(block_size))
+            .context("num_blocks * block_size overflows")?;
+        ensure!(len != 0, "extent length cannot be zero");
+
+        start
+            .checked_add(len)
+            .filter(|&end| end <= partition_len)
+            .with_context(|| {
+                format!(
+                    "extent at byte {start} ({len} bytes) exceeds partition size {partition_len}"
+                )
+            })?;
+        out.push((start, len));
+    }
+    Ok(out)
+}
+
+/// Merges ranges that continue exactly where the previous one ends, keeping their order, so
+/// a run of adjacent extents is written as one slice. Ranges are never reordered: the data of
+/// an operation fills its extents in list order.
+pub fn coalesce_extents(mut ranges: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    ranges.dedup_by(|next, prev| {
+        if prev.0.checked_add(prev.1) == Some(next.0)
+            && let Some(len) = prev.1.checked_add(next.1)
+        {
+            prev.1 = len;
+            true
+        } else {
+            false
+        }
+    });
+    ranges
+}
+
+/// Fills as much of `buf` as `reader` can provide, returning how many bytes were read.
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).context("failed to read payload header"),
+        }
+    }
+    Ok(filled)
 }
@@ -254,8 +254,9 @@ pub mod cow_merge_operation {
 pub struct PartitionUpdate {
     /// A platform-specific name to identify the partition set being updated. For
     /// example, in Chrome OS this could be "ROOT" or "KERNEL".
-    #[prost(string, required, tag = "1")]
-    pub partition_name: ::prost::alloc::string::String,
+    /// Kept as raw bytes (`string` on the wire) so names that aren't valid UTF-8 still decode.
+    #[prost(bytes = "vec", required, tag = "1")]
+    pub partition_name: ::prost::alloc::vec::Vec<u8>,
     /// Whether this partition carries a filesystem with post-install program that
     /// must be run to finalize the update process. See also |postinstall_path| and
     /// |filesystem_type|.
@@ -344,8 +345,8 @@ pub struct DynamicPartitionGroup {
     #[prost(uint64, optional, tag = "2")]
     pub size: ::core::option::Option<u64>,
     /// A list of partitions that belong to the group.
-    #[prost(string, repeated, tag = "3")]
-    pub partition_names: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub partition_names: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
 #[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
 pub struct VabcFeatureSet {
@@ -416,6 +417,15 @@ pub struct ApexMetadata {
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct DeltaArchiveManifest {
+    /// Only present in major version = 1. List of install operations for the
+    /// kernel and rootfs partitions. For major version = 2 see the |partitions|
+    /// field.
+    #[deprecated]
+    #[prost(message, repeated, tag = "1")]
+    pub install_operations: ::prost::alloc::vec::Vec<InstallOperation>,
+    #[deprecated]
+    #[prost(message, repeated, tag = "2")]
+    pub kernel_install_operations: ::prost::alloc::vec::Vec<InstallOperation>,
     /// (At time of writing) usually 4096
     #[prost(uint32, optional, tag = "3", default = "4096")]
     pub block_size: ::core::option::Option<u32>,
@@ -428,6 +438,20 @@ pub struct DeltaArchiveManifest {
     pub signatures_offset: ::core::option::Option<u64>,
     #[prost(uint64, optional, tag = "5")]
     pub signatures_size: ::core::option::Option<u64>,
+    /// Only present in major version = 1. Partition metadata used to validate the
+    /// update. For major version = 2 see the |partitions| field.
+    #[deprecated]
+    #[prost(message, optional, tag = "6")]
+    pub old_kernel_info: ::core::option::Option<PartitionInfo>,
+    #[deprecated]
+    #[prost(message, optional, tag = "7")]
+    pub new_kernel_info: ::core::option::Option<PartitionInfo>,
+    #[deprecated]
+    #[prost(message, optional, tag = "8")]
+    pub old_rootfs_info: ::core::option::Option<PartitionInfo>,
+    #[deprecated]
+    #[prost(message, optional, tag = "9")]
+    pub new_rootfs_info: ::core::option::Option<PartitionInfo>,
     /// The minor version, also referred as "delta version", of the payload.
     /// Minor version 0 is full payload, everything else is delta payload.
     #[prost(uint32, optional, tag = "12", default = "0")]
@@ -1 +1,19 @@
 pub mod chromeos_update_engine;
+pub mod summary;
+
+use std::borrow::Cow;
+
+impl chromeos_update_engine::PartitionUpdate {
+    /// Partition name for display. Names that aren't valid UTF-8 are converted lossily; use
+    /// `partition_name` itself wherever the exact bytes matter.
+    pub fn name(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.partition_name)
+    }
+}
+
+impl summary::PartitionSummary {
+    /// Partition name for display, as [`PartitionUpdate::name`](chromeos_update_engine::PartitionUpdate::name).
+    pub fn name(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.partition_name)
+    }
+}
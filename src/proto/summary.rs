@@ -0,0 +1,46 @@
+//! Cut-down views of `DeltaArchiveManifest` for code that only needs the partition table.
+//!
+//! Field tags match `update_metadata.proto`. prost skips every field a message doesn't declare
+//! without allocating, so decoding into these reads past operation extents, hashes and the
+//! other per-op fields instead of materializing them. On payloads with hundreds of thousands
+//! of operations that is most of the cost of a full decode.
+
+use super::chromeos_update_engine::PartitionInfo;
+
+/// The partitions of a `DeltaArchiveManifest`, and nothing else. Major version 1 payloads
+/// describe their two partitions with separate fields instead.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ManifestSummary {
+    #[prost(message, repeated, tag = "13")]
+    pub partitions: ::prost::alloc::vec::Vec<PartitionSummary>,
+    #[prost(message, repeated, tag = "1")]
+    pub install_operations: ::prost::alloc::vec::Vec<OperationSummary>,
+    #[prost(message, repeated, tag = "2")]
+    pub kernel_install_operations: ::prost::alloc::vec::Vec<OperationSummary>,
+    #[prost(message, optional, tag = "7")]
+    pub new_kernel_info: ::core::option::Option<PartitionInfo>,
+    #[prost(message, optional, tag = "9")]
+    pub new_rootfs_info: ::core::option::Option<PartitionInfo>,
+}
+
+/// Name, target size and operation types of a `PartitionUpdate`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PartitionSummary {
+    #[prost(bytes = "vec", required, tag = "1")]
+    pub partition_name: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "7")]
+    pub new_partition_info: ::core::option::Option<PartitionInfo>,
+    #[prost(message, repeated, tag = "8")]
+    pub operations: ::prost::alloc::vec::Vec<OperationSummary>,
+}
+
+/// Just the type of an `InstallOperation`.
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct OperationSummary {
+    #[prost(
+        enumeration = "super::chromeos_update_engine::install_operation::Type",
+        required,
+        tag = "1"
+    )]
+    pub r#type: i32,
+}
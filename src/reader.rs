@@ -0,0 +1,273 @@
+//! Streams a partition image out of a payload as an [`io::Read`], for consumers that store
+//! images somewhere other than files.
+//!
+//! Operations are decoded on demand as reading reaches their destination extents, so only the
+//! operations the read position is currently inside are held in memory. Each operation's data
+//! is checked against its hash before it is decoded, and the whole image against the partition
+//! hash once the last byte has been read; a mismatch is an [`io::ErrorKind::InvalidData`] error.
+//! Only full operations can be streamed: a delta partition needs the source image.
+//!
+//! ```no_run
+//! use otaripper::payload::Payload;
+//! use otaripper::proto::chromeos_update_engine::DeltaArchiveManifest;
+//! use otaripper::reader::PartitionReader;
+//! use prost::Message;
+//!
+//! let bytes = std::fs::read("payload.bin")?;
+//! let payload = Payload::parse(&bytes)?;
+//! let manifest = DeltaArchiveManifest::decode(payload.manifest())?;
+//! let mut boot = PartitionReader::new(&payload, &manifest, "boot")?;
+//! std::io::copy(&mut boot, &mut std::io::sink())?;
+//! # anyhow::Ok(())
+//! ```
+
+use crate::cmd::crypto;
+use crate::payload::Payload;
+use crate::proto::chromeos_update_engine::install_operation::Type;
+use crate::proto::chromeos_update_engine::{DeltaArchiveManifest, InstallOperation};
+use anyhow::{Context, Result, bail, ensure};
+use bzip2::read::BzDecoder;
+use liblzma::read::XzDecoder;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::io::{self, Read};
+
+/// One destination extent of an operation, in bytes.
+struct Piece {
+    start: u64,
+    len: u64,
+    op: usize,
+    /// Offset of this extent's bytes in the operation's decoded output.
+    at: usize,
+    zero: bool,
+}
+
+/// Reads one partition's image from a payload, decoding operations as it goes.
+pub struct PartitionReader<'a> {
+    payload: &'a Payload<'a>,
+    ops: &'a [InstallOperation],
+    block_size: usize,
+    /// Bytes each operation writes.
+    sizes: Vec<usize>,
+    len: u64,
+    pos: u64,
+    /// Destination extents of every operation, sorted by position in the image.
+    pieces: Vec<Piece>,
+    next: usize,
+    /// Pieces of each operation not read to the end yet.
+    remaining: Vec<usize>,
+    /// Decoded output of operations that still have pieces to come.
+    decoded: HashMap<usize, Vec<u8>>,
+    hasher: crypto::Sha256,
+    expected: Option<&'a [u8]>,
+}
+
+impl<'a> PartitionReader<'a> {
+    /// A reader for `partition` in `manifest`, which must be the manifest of `payload`. Fails
+    /// when there is no such partition, its destination extents overlap or reach past its
+    /// size, or it has operations that need a source image.
+    pub fn new(
+        payload: &'a Payload<'a>,
+        manifest: &'a DeltaArchiveManifest,
+        partition: &str,
+    ) -> Result<Self> {
+        let update = manifest
+            .partitions
+            .iter()
+            .find(|u| u.partition_name == partition.as_bytes())
+            .with_context(|| format!("no partition '{partition}' in the payload"))?;
+        let block_size = u64::from(manifest.block_size.unwrap_or(4096));
+        ensure!(block_size > 0, "block size must not be zero");
+        let info = update
+            .new_partition_info
+            .as_ref()
+            .context("unable to determine partition size")?;
+        let len = info.size.context("unable to determine partition size")?;
+
+        let mut pieces = Vec::new();
+        let mut sizes = Vec::with_capacity(update.operations.len());
+        let mut remaining = Vec::with_capacity(update.operations.len());
+        for (i, op) in update.operations.iter().enumerate() {
+            let zero = match Type::try_from(op.r#type)? {
+                Type::Zero | Type::Discard => true,
+                Type::Replace | Type::ReplaceBz | Type::ReplaceXz => false,
+                other => bail!(
+                    "operation #{i} is {other:?}, which needs the source image; only full \
+                     partitions can be streamed"
+                ),
+            };
+            let mut at = 0usize;
+            let mut count = 0;
+            for extent in &op.dst_extents {
+                let (Some(start), Some(num)) = (extent.start_block, extent.num_blocks) else {
+                    bail!("operation #{i} has an incomplete destination extent");
+                };
+                let start = start.checked_mul(block_size);
+                let ext_len = num.checked_mul(block_size);
+                let (Some(start), Some(ext_len)) = (start, ext_len) else {
+                    bail!("operation #{i} has a destination extent out of range");
+                };
+                ensure!(
+                    start.checked_add(ext_len).is_some_and(|end| end <= len),
+                    "operation #{i} writes past the end of the partition"
+                );
+                if ext_len == 0 {
+                    continue;
+                }
+                count += 1;
+                pieces.push(Piece {
+                    start,
+                    len: ext_len,
+                    op: i,
+                    at,
+                    zero,
+                });
+                at += usize::try_from(ext_len).context("operation is too large")?;
+            }
+            sizes.push(at);
+            remaining.push(count);
+        }
+        pieces.sort_by_key(|p| p.start);
+        for pair in pieces.windows(2) {
+            ensure!(
+                pair[0].start + pair[0].len <= pair[1].start,
+                "operations #{} and #{} write overlapping extents",
+                pair[0].op,
+                pair[1].op
+            );
+        }
+
+        Ok(Self {
+            payload,
+            ops: &update.operations,
+            block_size: usize::try_from(block_size).context("block size is too large")?,
+            sizes,
+            len,
+            pos: 0,
+            pieces,
+            next: 0,
+            remaining,
+            decoded: HashMap::new(),
+            hasher: crypto::Sha256::new(),
+            expected: info.hash.as_deref(),
+        })
+    }
+
+    /// Size of the image in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Fills `buf` from the current position, stopping at the end of a gap or extent.
+    fn fill(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let room = buf.len() as u64;
+        let Some(piece) = self.pieces.get(self.next) else {
+            // Blocks no operation writes read as zeros, as in a freshly sized file.
+            let n = room.min(self.len - self.pos) as usize;
+            buf[..n].fill(0);
+            return Ok(n);
+        };
+        if piece.start > self.pos {
+            let n = room.min(piece.start - self.pos) as usize;
+            buf[..n].fill(0);
+            return Ok(n);
+        }
+
+        let offset = self.pos - piece.start;
+        let n = room.min(piece.len - offset) as usize;
+        let (op, at, zero, done) = (
+            piece.op,
+            piece.at,
+            piece.zero,
+            offset + n as u64 == piece.len,
+        );
+        if zero {
+            buf[..n].fill(0);
+        } else {
+            let output = match self.decoded.entry(op) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    decode(self.payload, &self.ops[op], self.sizes[op], self.block_size)
+                        .with_context(|| format!("operation #{op}"))?,
+                ),
+            };
+            let from = at + offset as usize;
+            buf[..n].copy_from_slice(&output[from..from + n]);
+        }
+        if done {
+            self.next += 1;
+            self.remaining[op] -= 1;
+            if self.remaining[op] == 0 {
+                self.decoded.remove(&op);
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Read for PartitionReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos == self.len {
+            return Ok(0);
+        }
+        let n = self
+            .fill(buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{e:#}")))?;
+        self.hasher.update(&buf[..n]);
+        self.pos += n as u64;
+
+        if self.pos == self.len
+            && let Some(expected) = self.expected
+        {
+            let digest = std::mem::take(&mut self.hasher).finish();
+            if digest.as_slice() != expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "image hash mismatch: expected {}, got {}",
+                        hex::encode(expected),
+                        hex::encode(digest)
+                    ),
+                ));
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Checks a REPLACE* operation's data hash and decodes it, padded with zeros to `size`, the
+/// length of its destination.
+fn decode(
+    payload: &Payload,
+    op: &InstallOperation,
+    size: usize,
+    block_size: usize,
+) -> Result<Vec<u8>> {
+    let data = payload.op_data(op)?;
+    if let Some(expected) = op.data_sha256_hash.as_deref() {
+        ensure!(
+            crypto::sha256(data).as_slice() == expected,
+            "operation data hash mismatch"
+        );
+    }
+    let mut out = Vec::with_capacity(size);
+    // One byte past the destination is enough to tell oversized data apart, and takes the
+    // decoders to the end of their stream, where they check its checksum.
+    let limit = size as u64 + 1;
+    match Type::try_from(op.r#type)? {
+        Type::ReplaceBz => BzDecoder::new(data).take(limit).read_to_end(&mut out),
+        Type::ReplaceXz => XzDecoder::new(data).take(limit).read_to_end(&mut out),
+        _ => data.take(limit).read_to_end(&mut out),
+    }
+    .context("failed to decompress the operation's data")?;
+    ensure!(
+        out.len().div_ceil(block_size) * block_size == size,
+        "data does not fill its destination blocks"
+    );
+    out.resize(size, 0);
+    Ok(out)
+}
@@ -0,0 +1,211 @@
+//! Runs the otaripper binary on small payloads from [`otaripper::builder`] and checks how it
+//! exits.
+
+use otaripper::builder::{Corruption, PartitionBuilder, PayloadBuilder};
+use otaripper::proto::chromeos_update_engine::install_operation::Type;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+const BLOCK_SIZE: usize = 4096;
+
+/// A payload with a couple of full partitions, or with `corruption` applied.
+fn full_payload(corruption: Option<Corruption>) -> PayloadBuilder {
+    let boot: Vec<u8> = (0..8 * BLOCK_SIZE).map(|i| (i / 64) as u8).collect();
+    let boot = PartitionBuilder::new("boot", boot)
+        .op(Type::Replace, 0..4)
+        .op(Type::ReplaceXz, 4..8);
+    let vendor = PartitionBuilder::new("vendor", vec![0; 4 * BLOCK_SIZE]).op(Type::Zero, 0..4);
+    let payload = PayloadBuilder::new().partition(boot).partition(vendor);
+    match corruption {
+        Some(corruption) => payload.corrupt(corruption),
+        None => payload,
+    }
+}
+
+/// Writes `payload` to `dir` and returns its path.
+fn write_payload(dir: &Path, name: &str, payload: &PayloadBuilder) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, payload.build().expect("payload builds")).expect("payload is written");
+    path
+}
+
+/// Runs otaripper on `payload` with `args`, writing into a folder under `dir`.
+fn otaripper(dir: &Path, payload: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_otaripper"))
+        .arg(payload)
+        .arg("-o")
+        .arg(dir.join("out"))
+        .args(["-n", "-q"])
+        .args(args)
+        .output()
+        .expect("otaripper runs")
+}
+
+fn exit_code(output: &Output) -> i32 {
+    output.status.code().expect("otaripper exited normally")
+}
+
+#[test]
+fn compress_with_hash_algo_without_print_hash() {
+    let dir = TempDir::new().unwrap();
+    let payload = write_payload(dir.path(), "full.bin", &full_payload(None));
+    let output = otaripper(
+        dir.path(),
+        &payload,
+        &["--compress", "zstd", "--hash-algo", "md5"],
+    );
+    assert_eq!(
+        exit_code(&output),
+        0,
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn compress_prints_every_hash_algo() {
+    let dir = TempDir::new().unwrap();
+    let payload = write_payload(dir.path(), "full.bin", &full_payload(None));
+    let output = otaripper(
+        dir.path(),
+        &payload,
+        &[
+            "--compress",
+            "zstd",
+            "--hash-algo",
+            "md5,sha256",
+            "--print-hash",
+        ],
+    );
+    assert_eq!(
+        exit_code(&output),
+        0,
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("md5=") && stdout.contains("sha256="),
+        "{stdout}"
+    );
+}
+
+/// The exit codes the README lists, one payload per failure class. Only unexpected failures
+/// leave a debug bundle behind.
+#[test]
+fn exit_codes() {
+    let cases: [(&str, Option<Corruption>, &[&str], i32); 8] = [
+        ("full.bin", None, &[], 0),
+        ("bad_magic.bin", Some(Corruption::BadMagic), &[], 2),
+        ("truncated.bin", Some(Corruption::Truncated), &[], 2),
+        ("unknown_name.bin", None, &["-p", "recovery"], 2),
+        ("unanchored_chain.bin", None, &["--verify-chain"], 2),
+        (
+            "two_images.bin",
+            None,
+            &["--stdout", "-p", "boot,vendor"],
+            2,
+        ),
+        (
+            "corrupt_partition_hash.bin",
+            Some(Corruption::PartitionHash),
+            &[],
+            3,
+        ),
+        (
+            "corrupt_op_data.bin",
+            Some(Corruption::OperationData),
+            &[],
+            3,
+        ),
+    ];
+    for (name, corruption, args, expected) in cases {
+        let dir = TempDir::new().unwrap();
+        let payload = write_payload(dir.path(), name, &full_payload(corruption));
+        let output = otaripper(dir.path(), &payload, args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(exit_code(&output), expected, "{name}: {stderr}");
+        assert!(!stderr.contains("otaripper-debug-"), "{name}: {stderr}");
+    }
+}
+
+/// Operations of 64 MiB and more are copied across the thread pool. Under `--max-memory` they
+/// hold a reservation meanwhile, so the worker waiting on the copy must not pick up one of the
+/// small operations queued behind it, which would wait for that reservation in turn.
+#[test]
+fn max_memory_with_giant_copies() {
+    const GIANT: u64 = (64 * 1024 * 1024 / BLOCK_SIZE) as u64;
+    const SMALL: u64 = 256;
+    let dir = TempDir::new().unwrap();
+    let image: Vec<u8> = (0..(GIANT + SMALL) as usize * BLOCK_SIZE)
+        .map(|i| (i / BLOCK_SIZE) as u8)
+        .collect();
+    let source = dir.path().join("source");
+    std::fs::create_dir(&source).unwrap();
+    std::fs::write(source.join("system.img"), &image).unwrap();
+    let mut system = PartitionBuilder::new("system", image.clone()).op(Type::SourceCopy, 0..GIANT);
+    let mut vendor = PartitionBuilder::new("vendor", image).op(Type::Replace, 0..GIANT);
+    for block in GIANT..GIANT + SMALL {
+        system = system.op(Type::SourceCopy, block..block + 1);
+        vendor = vendor.op(Type::Replace, block..block + 1);
+    }
+    let payload = write_payload(
+        dir.path(),
+        "giant.bin",
+        &PayloadBuilder::new()
+            .minor_version(2)
+            .partition(system)
+            .partition(vendor),
+    );
+    for io_mode in ["pwrite", "mmap"] {
+        let output = otaripper(
+            dir.path(),
+            &payload,
+            &[
+                "--max-memory",
+                "64M",
+                "--io-mode",
+                io_mode,
+                "--source-dir",
+                source.to_str().unwrap(),
+                "-t",
+                "8",
+                "--force",
+            ],
+        );
+        assert_eq!(
+            exit_code(&output),
+            0,
+            "{io_mode}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+/// Split output that is already there stops the run unless `--force` is given, which removes
+/// every old piece, not only the ones written again.
+#[test]
+fn split_output_replaced_with_force() {
+    let dir = TempDir::new().unwrap();
+    let payload = write_payload(dir.path(), "full.bin", &full_payload(None));
+    let split = ["--flat", "--split-size", "1M", "-p", "boot"];
+    let out = dir.path().join("out");
+    assert_eq!(exit_code(&otaripper(dir.path(), &payload, &split)), 0);
+
+    let stale = out.join("boot.img.003");
+    std::fs::write(&stale, b"left by an earlier run").unwrap();
+    let output = otaripper(dir.path(), &payload, &split);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(exit_code(&output), 2, "{stderr}");
+    assert!(stderr.contains("boot.img.003"), "{stderr}");
+
+    let output = otaripper(dir.path(), &payload, &[&split[..], &["--force"]].concat());
+    assert_eq!(
+        exit_code(&output),
+        0,
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(out.join("boot.img").exists() && !stale.exists());
+}